@@ -0,0 +1,324 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::process;
+
+use embercore::tme::flatten_layers;
+use embercore::tme::read_map_file;
+use embercore::tme::write_runtime_map;
+use embercore::tme::write_tmx;
+use embercore::tme::Compression;
+use embercore::tme::Encoding;
+use embercore::tme::Layer;
+use embercore::tme::Map;
+use embercore::tme::Severity;
+use embercore::tme::TileLayer;
+use embercore::tme::TilesetContainer;
+use embercore::tme::Validator;
+
+const USAGE: &str = "\
+Usage: embercore-tme <command> [arguments]
+
+Commands:
+    info <map>                                  Print orientation, size, layers,
+                                                tilesets and object counts
+    encode <map> <csv|base64> [zlib|gzip|zstd]  Re-encode the data of all tile
+           [-o <output>]                        layers
+    convert <input> <output>                    Convert between TMX, JSON and the
+                                                runtime format
+    validate <map>                              Report problems of the map, fails
+                                                when errors are found
+    dump <map> <layer>                          Print a tile layer given by id or
+                                                name as a text grid
+
+Maps are read and written as TMX when the file ends with .tmx, in the binary
+runtime format when it ends with .embr and as JSON otherwise. Output goes to stdout unless a file is given.";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(2);
+        }
+    }
+}
+
+/// Runs a command, returns whether it succeeded
+fn run(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["info", path] => {
+            print!("{}", summary(&load(path)?));
+        }
+        ["encode", path, rest @ ..] => {
+            let (output, rest) = match rest {
+                [rest @ .., "-o", output] => (Some(*output), rest),
+                rest => (None, rest),
+            };
+            let (encoding, compression) = match rest {
+                [encoding] => (encoding.parse()?, None),
+                [encoding, compression] => (encoding.parse()?, Some(compression.parse()?)),
+                _ => return Err(USAGE.into()),
+            };
+
+            let mut map = load(path)?;
+            encode(&mut map, encoding, compression)?;
+            save(&map, output.unwrap_or(path), output.is_none())?;
+        }
+        ["convert", input, output] => {
+            save(&load(input)?, output, false)?;
+        }
+        ["validate", path] => {
+            let map = load(path)?;
+            let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+            let diagnostics = Validator::new().with_base_dir(base_dir).validate_map(&map);
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+                .count();
+            println!("{} errors, {} warnings", errors, diagnostics.len() - errors);
+            return Ok(errors == 0);
+        }
+        ["dump", path, layer] => {
+            let map = load(path)?;
+            let tile_layer = find_tile_layer(&map, layer)
+                .ok_or_else(|| format!("No tile layer {} in {}", layer, path))?;
+            print!("{}", dump(tile_layer)?);
+        }
+        ["help"] | ["--help"] | ["-h"] => println!("{}", USAGE),
+        _ => return Err(USAGE.into()),
+    }
+    Ok(true)
+}
+
+fn has_extension(path: &str, expected: &str) -> bool {
+    matches!(Path::new(path).extension(), Some(extension) if extension.eq_ignore_ascii_case(expected))
+}
+
+fn load(path: &str) -> Result<Map, Box<dyn Error>> {
+    Ok(read_map_file(path)?)
+}
+
+/// Writes the map in the format of `path`, to stdout when `stdout` is set
+fn save(map: &Map, path: &str, stdout: bool) -> Result<(), Box<dyn Error>> {
+    let contents = if has_extension(path, "embr") {
+        write_runtime_map(map)?
+    } else if has_extension(path, "tmx") {
+        write_tmx(map).into_bytes()
+    } else {
+        (serde_json::to_string_pretty(map)? + "\n").into_bytes()
+    };
+    if stdout {
+        io::stdout().write_all(&contents)?;
+    } else {
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+fn encode(
+    map: &mut Map,
+    encoding: Encoding,
+    compression: Option<Compression>,
+) -> Result<(), Box<dyn Error>> {
+    let ids: Vec<i32> = flatten_layers(map.layers())
+        .into_iter()
+        .filter(|layer| matches!(layer, Layer::TileLayer(_)))
+        .map(Layer::id)
+        .collect();
+    for id in ids {
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(id) {
+            layer.encode(encoding, compression)?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds a tile layer by id, falling back to its name
+fn find_tile_layer<'a>(map: &'a Map, layer: &str) -> Option<&'a TileLayer> {
+    let by_id = layer.parse().ok().and_then(|id| match map.layer(id) {
+        Some(Layer::TileLayer(tile_layer)) => Some(tile_layer),
+        _ => None,
+    });
+    by_id.or_else(|| map.find_tile_layer(layer))
+}
+
+fn summary(map: &Map) -> String {
+    let mut summary = String::new();
+    let _ = writeln!(
+        summary,
+        "orientation: {}",
+        format!("{:?}", map.orientation()).to_lowercase()
+    );
+    let _ = writeln!(
+        summary,
+        "size: {}x{} tiles of {}x{} px, {}",
+        map.width(),
+        map.height(),
+        map.tile_width(),
+        map.tile_height(),
+        if map.infinite() { "infinite" } else { "finite" }
+    );
+
+    let _ = writeln!(summary, "layers:");
+    layer_summary(&mut summary, map.layers(), 1);
+
+    let _ = writeln!(summary, "tilesets:");
+    for container in map.tile_sets() {
+        let _ = match container {
+            TilesetContainer::Tileset(tileset) => writeln!(
+                summary,
+                "  {} \"{}\" {} tiles",
+                tileset.first_gid.unwrap_or(1),
+                tileset.name,
+                tileset.tile_count
+            ),
+            TilesetContainer::TilesetRef(reference) => writeln!(
+                summary,
+                "  {} {} (external)",
+                reference.first_gid,
+                reference.source.display()
+            ),
+        };
+    }
+
+    let _ = writeln!(summary, "objects: {}", map.objects().len());
+    summary
+}
+
+fn layer_summary(summary: &mut String, layers: &[Layer], depth: usize) {
+    let indent = "  ".repeat(depth);
+    for layer in layers {
+        let _ = match layer {
+            Layer::TileLayer(tile_layer) => writeln!(
+                summary,
+                "{}{} tilelayer \"{}\" {}x{}",
+                indent, tile_layer.id, tile_layer.name, tile_layer.width, tile_layer.height
+            ),
+            Layer::ObjectGroupLayer(group) => writeln!(
+                summary,
+                "{}{} objectgroup \"{}\" {} objects",
+                indent,
+                group.id,
+                group.name,
+                group.objects.len()
+            ),
+            Layer::ImageLayer(image_layer) => writeln!(
+                summary,
+                "{}{} imagelayer \"{}\" {}",
+                indent, image_layer.id, image_layer.name, image_layer.image
+            ),
+            Layer::GroupLayer(group) => {
+                writeln!(summary, "{}{} group \"{}\"", indent, group.id, group.name)
+            }
+        };
+        if let Layer::GroupLayer(group) = layer {
+            layer_summary(summary, &group.layers, depth + 1);
+        }
+    }
+}
+
+/// One row per line with gids stripped of their flip flags, empty cells are
+/// shown as `.`. Layers not starting at the origin get a header with it.
+fn dump(layer: &TileLayer) -> Result<String, Box<dyn Error>> {
+    let grid = layer.decode_grid()?;
+    let cells: Vec<String> = grid
+        .cells()
+        .map(|(_, _, gid)| match gid.is_empty() {
+            true => ".".to_owned(),
+            false => gid.id().to_string(),
+        })
+        .collect();
+    let width = cells.iter().map(String::len).max().unwrap_or(1);
+
+    let mut dump = String::new();
+    if grid.x != 0 || grid.y != 0 {
+        let _ = writeln!(dump, "origin: {}, {}", grid.x, grid.y);
+    }
+    for row in cells.chunks(grid.width.max(1) as usize) {
+        let row: Vec<String> = row
+            .iter()
+            .map(|cell| format!("{:>width$}", cell, width = width))
+            .collect();
+        let _ = writeln!(dump, "{}", row.join(" "));
+    }
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embercore::tme::Gid;
+    use embercore::tme::GroupLayerBuilder;
+    use embercore::tme::MapBuilder;
+    use embercore::tme::ObjectBuilder;
+    use embercore::tme::ObjectLayerBuilder;
+    use embercore::tme::TilesetBuilder;
+
+    fn sample_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(3, 2, 16, 16);
+        builder.add_tileset(
+            TilesetBuilder::new("terrain", 16, 16)
+                .with_image("terrain.png", 64, 64)
+                .build(),
+        );
+        builder.add_tileset_ref("props.tsx", 4);
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(0, 0, Gid(1))
+            .with_tile(2, 1, Gid(12))
+            .build();
+        builder.add_layer(ground);
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::point(1.0, 1.0).build())
+            .build();
+        builder.add_layer(GroupLayerBuilder::new("group").with_layer(objects).build());
+        builder.build()
+    }
+
+    #[test]
+    fn summarizes_map() {
+        assert_eq!(
+            summary(&sample_map()),
+            "orientation: orthogonal\n\
+             size: 3x2 tiles of 16x16 px, finite\n\
+             layers:\n  \
+               1 tilelayer \"ground\" 3x2\n  \
+               2 group \"group\"\n    \
+                 3 objectgroup \"objects\" 1 objects\n\
+             tilesets:\n  \
+               1 \"terrain\" 16 tiles\n  \
+               17 props.tsx (external)\n\
+             objects: 1\n"
+        );
+    }
+
+    #[test]
+    fn dumps_tile_layer() {
+        let map = sample_map();
+        let layer = find_tile_layer(&map, "1").unwrap();
+        assert_eq!(find_tile_layer(&map, "ground"), Some(layer));
+        assert_eq!(find_tile_layer(&map, "2"), None);
+        assert_eq!(dump(layer).unwrap(), " 1  .  .\n .  . 12\n");
+    }
+
+    #[test]
+    fn encodes_all_tile_layers() {
+        let mut map = sample_map();
+        encode(&mut map, Encoding::Base64, Some(Compression::Zlib)).unwrap();
+        let layer = find_tile_layer(&map, "ground").unwrap();
+        assert_eq!(layer.compression, Some(Compression::Zlib));
+        assert_eq!(dump(layer).unwrap(), " 1  .  .\n .  . 12\n");
+    }
+}
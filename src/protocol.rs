@@ -1,7 +1,310 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use crate::tme::editing::PropertyTarget;
+use crate::tme::Gid;
+use crate::tme::Map;
+use crate::tme::Object;
+use crate::tme::Property;
+
+/// Version spoken by this build, bumped whenever a message kind changes in a
+/// way older peers cannot read
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest version this build still understands
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProtocolError {
+    #[error("Unable parse message: {0}")]
+    ParseMessage(#[from] serde_json::Error),
+    #[error("Unsupported protocol version: {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// Envelope of everything sent between client and server.
+///
+/// Compatibility rules:
+/// - adding a message kind or an optional field does not change the version,
+///   older peers read unknown kinds as `MessageKind::Unknown` and skip them,
+///   unknown fields of the envelope and the kinds are ignored. Maps and
+///   objects are read as strictly as from files and reject unknown fields,
+///   changing them bumps the version.
+/// - renaming, removing or changing the meaning of anything bumps
+///   `PROTOCOL_VERSION`, and `MIN_PROTOCOL_VERSION` once the old form is
+///   no longer accepted
+/// - messages of a version below `MIN_PROTOCOL_VERSION` are rejected, newer
+///   ones are read as far as they are understood
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Message {
+    pub id:        Uuid,
+    pub version:   u32,
+    /// Milliseconds since the Unix epoch
     pub timestamp: i64,
-    pub test_text: String,
+    /// Message this one answers, like the ping of a pong
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to:  Option<Uuid>,
+    pub kind:      MessageKind,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageKind {
+    /// First message of each side, naming the versions it speaks
+    Handshake {
+        name:        String,
+        version:     u32,
+        min_version: u32,
+    },
+    MapLoad {
+        name: String,
+        map:  Box<Map>,
+    },
+    TileChange {
+        layer: i32,
+        x:     i32,
+        y:     i32,
+        gid:   Gid,
+    },
+    ObjectUpdate {
+        layer:  i32,
+        object: Box<Object>,
+    },
+    PropertyChange {
+        target:   PropertyTarget,
+        property: Property,
+    },
+    Ping,
+    Pong,
+    Error {
+        code:    ErrorCode,
+        message: String,
+    },
+    /// Kind added by a newer version, to be ignored
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    UnsupportedVersion,
+    InvalidMessage,
+    NotFound,
+    Internal,
+    /// Code added by a newer version
+    #[serde(other)]
+    Unknown,
+}
+
+impl Message {
+    pub fn new(kind: MessageKind) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            version: PROTOCOL_VERSION,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            reply_to: None,
+            kind,
+        }
+    }
+
+    pub fn handshake(name: &str) -> Self {
+        Self::new(MessageKind::Handshake {
+            name:        name.to_owned(),
+            version:     PROTOCOL_VERSION,
+            min_version: MIN_PROTOCOL_VERSION,
+        })
+    }
+
+    /// New message answering this one
+    pub fn reply(&self, kind: MessageKind) -> Self {
+        Self {
+            reply_to: Some(self.id),
+            ..Self::new(kind)
+        }
+    }
+
+    /// Whether the kind is known to this build, unknown ones are skipped
+    pub fn is_known(&self) -> bool {
+        !matches!(self.kind, MessageKind::Unknown)
+    }
+
+    pub fn to_json(&self) -> Result<String, ProtocolError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parses a message, rejecting versions older than `MIN_PROTOCOL_VERSION`
+    pub fn from_json(json: &str) -> Result<Self, ProtocolError> {
+        let message: Message = serde_json::from_str(json)?;
+        if message.version < MIN_PROTOCOL_VERSION {
+            return Err(ProtocolError::UnsupportedVersion(message.version));
+        }
+        Ok(message)
+    }
+}
+
+/// Highest version both sides speak, given the versions of the handshake of
+/// the other side
+pub fn negotiate_version(version: u32, min_version: u32) -> Result<u32, ProtocolError> {
+    let common = version.min(PROTOCOL_VERSION);
+    if common < min_version.max(MIN_PROTOCOL_VERSION) {
+        return Err(ProtocolError::UnsupportedVersion(version));
+    }
+    Ok(common)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::tme::MapBuilder;
+    use crate::tme::ObjectBuilder;
+    use crate::tme::StringProperty;
+
+    fn round_trip(message: &Message) -> Message {
+        Message::from_json(&message.to_json().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_messages() {
+        let map = MapBuilder::orthogonal(2, 2, 16, 16).build();
+        let kinds = vec![
+            Message::handshake("client").kind,
+            MessageKind::MapLoad {
+                name: "town".to_owned(),
+                map:  Box::new(map),
+            },
+            MessageKind::TileChange {
+                layer: 1,
+                x:     -3,
+                y:     4,
+                gid:   Gid(0x8000_0002),
+            },
+            MessageKind::ObjectUpdate {
+                layer:  2,
+                object: Box::new(ObjectBuilder::rectangle(8.0, 8.0, 16.0, 4.0).build()),
+            },
+            MessageKind::PropertyChange {
+                target:   PropertyTarget::Object(7),
+                property: Property::String(StringProperty {
+                    name:  "note".to_owned(),
+                    value: "hello".to_owned(),
+                }),
+            },
+            MessageKind::Ping,
+            MessageKind::Error {
+                code:    ErrorCode::NotFound,
+                message: "no layer 9".to_owned(),
+            },
+        ];
+
+        for kind in kinds {
+            let message = Message::new(kind);
+            let read = round_trip(&message);
+            assert_eq!((read.id, read.version), (message.id, PROTOCOL_VERSION));
+            assert_eq!(
+                serde_json::to_value(&read).unwrap(),
+                serde_json::to_value(&message).unwrap()
+            );
+        }
+
+        let ping = Message::new(MessageKind::Ping);
+        let pong = round_trip(&ping.reply(MessageKind::Pong));
+        assert_eq!(pong.reply_to, Some(ping.id));
+        assert_ne!(pong.id, ping.id);
+    }
+
+    #[test]
+    fn tolerates_newer_messages() {
+        let json = json! {
+            {
+                "id": Uuid::new_v4(),
+                "version": PROTOCOL_VERSION + 1,
+                "timestamp": 0,
+                "kind": { "type": "chat", "text": "hi" }
+            }
+        };
+        let message = Message::from_json(&json.to_string()).unwrap();
+        assert!(!message.is_known());
+
+        let json = json! {
+            {
+                "id": Uuid::new_v4(),
+                "version": PROTOCOL_VERSION,
+                "timestamp": 0,
+                "kind": {
+                    "type": "error",
+                    "code": "rate_limited",
+                    "message": "slow down",
+                    "retry_after": 5
+                }
+            }
+        };
+        let message = Message::from_json(&json.to_string()).unwrap();
+        assert!(matches!(
+            message.kind,
+            MessageKind::Error {
+                code: ErrorCode::Unknown,
+                ..
+            }
+        ));
+
+        let json = json! {
+            {
+                "id": Uuid::new_v4(),
+                "version": PROTOCOL_VERSION,
+                "timestamp": 0,
+                "priority": "high",
+                "kind": {
+                    "type": "property_change",
+                    "target": { "kind": "layer", "id": 3 },
+                    "property": { "name": "note", "type": "string", "value": "hi" }
+                }
+            }
+        };
+        let message = Message::from_json(&json.to_string()).unwrap();
+        assert!(matches!(
+            message.kind,
+            MessageKind::PropertyChange {
+                target: PropertyTarget::Layer(3),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_object_fields() {
+        let message = Message::new(MessageKind::ObjectUpdate {
+            layer:  2,
+            object: Box::new(ObjectBuilder::rectangle(8.0, 8.0, 16.0, 4.0).build()),
+        });
+        let mut json = serde_json::to_value(&message).unwrap();
+        json["kind"]["object"]["glow"] = json!(true);
+        assert!(matches!(
+            Message::from_json(&json.to_string()),
+            Err(ProtocolError::ParseMessage(_))
+        ));
+    }
+
+    #[test]
+    fn checks_versions() {
+        let json = json! {
+            { "id": Uuid::new_v4(), "version": 0, "timestamp": 0, "kind": { "type": "ping" } }
+        };
+        assert!(matches!(
+            Message::from_json(&json.to_string()),
+            Err(ProtocolError::UnsupportedVersion(0))
+        ));
+
+        assert_eq!(
+            negotiate_version(PROTOCOL_VERSION + 3, MIN_PROTOCOL_VERSION).unwrap(),
+            PROTOCOL_VERSION
+        );
+        assert!(matches!(
+            negotiate_version(PROTOCOL_VERSION + 3, PROTOCOL_VERSION + 1),
+            Err(ProtocolError::UnsupportedVersion(_))
+        ));
+    }
 }
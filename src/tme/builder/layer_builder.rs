@@ -0,0 +1,309 @@
+use crate::tme::color::Color;
+use crate::tme::gid::Gid;
+use crate::tme::models::DataSource;
+use crate::tme::models::DrawOrder;
+use crate::tme::models::Encoding;
+use crate::tme::models::GroupLayer;
+use crate::tme::models::ImageLayer;
+use crate::tme::models::Layer;
+use crate::tme::models::Object;
+use crate::tme::models::ObjectGroupLayer;
+use crate::tme::models::Property;
+use crate::tme::models::TileLayer;
+
+/// Settings shared by every kind of layer
+#[derive(Debug)]
+struct LayerSettings {
+    name:       String,
+    offset:     Option<(f64, f64)>,
+    opacity:    f64,
+    visible:    bool,
+    properties: Vec<Property>,
+}
+
+impl LayerSettings {
+    fn new(name: &str) -> Self {
+        Self {
+            name:       name.to_owned(),
+            offset:     None,
+            opacity:    1.0,
+            visible:    true,
+            properties: Vec::new(),
+        }
+    }
+
+    fn take_properties(&mut self) -> Option<Vec<Property>> {
+        Some(std::mem::take(&mut self.properties)).filter(|properties| !properties.is_empty())
+    }
+}
+
+macro_rules! layer_settings {
+    () => {
+        pub fn with_offset(mut self, x: f64, y: f64) -> Self {
+            self.settings.offset = Some((x, y));
+            self
+        }
+
+        pub fn with_opacity(mut self, opacity: f64) -> Self {
+            self.settings.opacity = opacity;
+            self
+        }
+
+        pub fn with_visible(mut self, visible: bool) -> Self {
+            self.settings.visible = visible;
+            self
+        }
+
+        pub fn with_property(mut self, property: Property) -> Self {
+            self.settings.properties.push(property);
+            self
+        }
+    };
+}
+
+/// Finite tile layer with CSV data, built with id `0`
+#[derive(Debug)]
+pub struct TileLayerBuilder {
+    settings: LayerSettings,
+    width:    i32,
+    height:   i32,
+    tiles:    Vec<i32>,
+}
+
+impl TileLayerBuilder {
+    layer_settings!();
+
+    pub fn new(name: &str, width: i32, height: i32) -> Self {
+        Self {
+            settings: LayerSettings::new(name),
+            width,
+            height,
+            tiles: vec![0; (width.max(0) * height.max(0)) as usize],
+        }
+    }
+
+    /// Replaces all tiles in row-major order, missing ones stay empty
+    pub fn with_tiles(mut self, tiles: &[Gid]) -> Self {
+        for (target, gid) in self.tiles.iter_mut().zip(tiles) {
+            *target = (*gid).into();
+        }
+        self
+    }
+
+    /// Tiles outside of the layer are ignored
+    pub fn with_tile(mut self, x: i32, y: i32, gid: Gid) -> Self {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            self.tiles[(y * self.width + x) as usize] = gid.into();
+        }
+        self
+    }
+
+    pub fn build(mut self) -> Layer {
+        let (offset_x, offset_y) = unzip(self.settings.offset);
+        Layer::TileLayer(TileLayer {
+            chunks: None,
+            compression: None,
+            data: DataSource::Raw(self.tiles),
+            encoding: Some(Encoding::Csv),
+            height: self.height,
+            id: 0,
+            name: self.settings.name.clone(),
+            offset_x,
+            offset_y,
+            opacity: self.settings.opacity,
+            properties: self.settings.take_properties(),
+            start_x: None,
+            start_y: None,
+            visible: self.settings.visible,
+            width: self.width,
+            x: 0,
+            y: 0,
+        })
+    }
+}
+
+/// Object layer built with id `0`, objects keep their ids
+#[derive(Debug)]
+pub struct ObjectLayerBuilder {
+    settings:   LayerSettings,
+    draw_order: DrawOrder,
+    objects:    Vec<Object>,
+}
+
+impl ObjectLayerBuilder {
+    layer_settings!();
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            settings:   LayerSettings::new(name),
+            draw_order: DrawOrder::TopDown,
+            objects:    Vec::new(),
+        }
+    }
+
+    pub fn with_draw_order(mut self, draw_order: DrawOrder) -> Self {
+        self.draw_order = draw_order;
+        self
+    }
+
+    pub fn with_object(mut self, object: Object) -> Self {
+        self.objects.push(object);
+        self
+    }
+
+    pub fn build(mut self) -> Layer {
+        let (offset_x, offset_y) = unzip(self.settings.offset);
+        Layer::ObjectGroupLayer(ObjectGroupLayer {
+            draw_order: self.draw_order,
+            id: 0,
+            name: self.settings.name.clone(),
+            objects: self.objects,
+            offset_x,
+            offset_y,
+            opacity: self.settings.opacity,
+            properties: self.settings.take_properties(),
+            start_x: None,
+            start_y: None,
+            visible: self.settings.visible,
+            x: 0,
+            y: 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageLayerBuilder {
+    settings:          LayerSettings,
+    image:             String,
+    transparent_color: Option<Color>,
+}
+
+impl ImageLayerBuilder {
+    layer_settings!();
+
+    pub fn new(name: &str, image: &str) -> Self {
+        Self {
+            settings:          LayerSettings::new(name),
+            image:             image.to_owned(),
+            transparent_color: None,
+        }
+    }
+
+    pub fn with_transparent_color(mut self, color: Color) -> Self {
+        self.transparent_color = Some(color);
+        self
+    }
+
+    pub fn build(mut self) -> Layer {
+        let (offset_x, offset_y) = unzip(self.settings.offset);
+        Layer::ImageLayer(ImageLayer {
+            id: 0,
+            image: self.image,
+            name: self.settings.name.clone(),
+            offset_x,
+            offset_y,
+            opacity: self.settings.opacity,
+            properties: self.settings.take_properties(),
+            start_x: None,
+            start_y: None,
+            transparent_color: self.transparent_color,
+            visible: self.settings.visible,
+            x: 0,
+            y: 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct GroupLayerBuilder {
+    settings: LayerSettings,
+    layers:   Vec<Layer>,
+}
+
+impl GroupLayerBuilder {
+    layer_settings!();
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            settings: LayerSettings::new(name),
+            layers:   Vec::new(),
+        }
+    }
+
+    pub fn with_layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn build(mut self) -> Layer {
+        let (offset_x, offset_y) = unzip(self.settings.offset);
+        Layer::GroupLayer(GroupLayer {
+            id: 0,
+            layers: self.layers,
+            name: self.settings.name.clone(),
+            offset_x,
+            offset_y,
+            opacity: self.settings.opacity,
+            properties: self.settings.take_properties(),
+            start_x: None,
+            start_y: None,
+            visible: self.settings.visible,
+            x: 0,
+            y: 0,
+        })
+    }
+}
+
+fn unzip(offset: Option<(f64, f64)>) -> (Option<f64>, Option<f64>) {
+    match offset {
+        Some((x, y)) => (Some(x), Some(y)),
+        None => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+
+    #[test]
+    fn assigns_ids_to_nested_layers() {
+        let mut builder = MapBuilder::orthogonal(2, 2, 8, 8);
+        let background = ImageLayerBuilder::new("sky", "sky.png")
+            .with_opacity(0.5)
+            .build();
+        builder.add_layer(background);
+
+        let group = GroupLayerBuilder::new("level")
+            .with_offset(4.0, 0.0)
+            .with_layer(
+                TileLayerBuilder::new("floor", 2, 2)
+                    .with_tiles(&[Gid(1), Gid(2)])
+                    .build(),
+            )
+            .with_layer(
+                ObjectLayerBuilder::new("markers")
+                    .with_object(ObjectBuilder::rectangle(0.0, 0.0, 8.0, 8.0).build())
+                    .build(),
+            )
+            .build();
+        assert_eq!(builder.add_layer(group), 2);
+
+        let map = builder.build();
+        let layers = crate::tme::models::flatten_layers(map.layers());
+        let ids: Vec<_> = layers.iter().map(|layer| layer.id()).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        assert_eq!(map.next_object_id(), 2);
+
+        match layers[2] {
+            Layer::TileLayer(layer) => {
+                assert_eq!(layer.data, DataSource::Raw(vec![1, 2, 0, 0]));
+                assert!(layer.visible);
+                assert_eq!(layer.opacity, 1.0);
+            }
+            layer => panic!("unexpected layer {:?}", layer),
+        }
+    }
+}
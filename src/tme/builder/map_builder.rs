@@ -0,0 +1,251 @@
+use std::path::PathBuf;
+
+use super::layer_builder::TileLayerBuilder;
+use super::FORMAT_VERSION;
+use super::TILED_VERSION;
+
+use crate::tme::color::Color;
+use crate::tme::models::map::with_map;
+use crate::tme::models::HexagonalMap;
+use crate::tme::models::IsometricMap;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::MapType;
+use crate::tme::models::OrthogonalMap;
+use crate::tme::models::Property;
+use crate::tme::models::RenderOrder;
+use crate::tme::models::StaggerAxis;
+use crate::tme::models::StaggerIndex;
+use crate::tme::models::StaggeredMap;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+use crate::tme::models::TilesetRef;
+
+/// Builds finite maps, layers and objects added with id `0` get their ids
+/// from the counters of the map
+#[derive(Debug)]
+pub struct MapBuilder {
+    map:      Map,
+    next_gid: i32,
+}
+
+macro_rules! new_map {
+    ($variant:ident, $model:ident, $width:expr, $height:expr, $tile_width:expr, $tile_height:expr $(, $field:ident: $value:expr)*) => {
+        MapBuilder {
+            map:      Map::$variant($model {
+                background_color:  Color::new_transparent(),
+                compression_level: -1,
+                height:            $height,
+                infinite:          false,
+                layers:            Vec::new(),
+                next_layer_id:     1,
+                next_object_id:    1,
+                properties:        None,
+                render_order:      RenderOrder::RightDown,
+                tiled_version:     TILED_VERSION.to_owned(),
+                tile_height:       $tile_height,
+                tile_sets:         Vec::new(),
+                tile_width:        $tile_width,
+                map_type:          MapType::Map,
+                version:           FORMAT_VERSION.to_owned(),
+                width:             $width,
+                $($field: $value,)*
+            }),
+            next_gid: 1,
+        }
+    };
+}
+
+impl MapBuilder {
+    pub fn orthogonal(width: i32, height: i32, tile_width: i32, tile_height: i32) -> Self {
+        new_map!(
+            Orthogonal,
+            OrthogonalMap,
+            width,
+            height,
+            tile_width,
+            tile_height
+        )
+    }
+
+    pub fn isometric(width: i32, height: i32, tile_width: i32, tile_height: i32) -> Self {
+        new_map!(
+            Isometric,
+            IsometricMap,
+            width,
+            height,
+            tile_width,
+            tile_height
+        )
+    }
+
+    pub fn staggered(
+        width: i32,
+        height: i32,
+        tile_width: i32,
+        tile_height: i32,
+        stagger_axis: StaggerAxis,
+        stagger_index: StaggerIndex,
+    ) -> Self {
+        new_map!(
+            Staggered,
+            StaggeredMap,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            stagger_axis: stagger_axis,
+            stagger_index: stagger_index
+        )
+    }
+
+    pub fn hexagonal(
+        width: i32,
+        height: i32,
+        tile_width: i32,
+        tile_height: i32,
+        hex_side_length: i32,
+        stagger_axis: StaggerAxis,
+        stagger_index: StaggerIndex,
+    ) -> Self {
+        new_map!(
+            Hexagonal,
+            HexagonalMap,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            hex_side_length: hex_side_length,
+            stagger_axis: stagger_axis,
+            stagger_index: stagger_index
+        )
+    }
+
+    pub fn with_render_order(mut self, render_order: RenderOrder) -> Self {
+        with_map!(&mut self.map, map => map.render_order = render_order);
+        self
+    }
+
+    pub fn with_background_color(mut self, background_color: Color) -> Self {
+        with_map!(&mut self.map, map => map.background_color = background_color);
+        self
+    }
+
+    pub fn with_compression_level(mut self, compression_level: i32) -> Self {
+        with_map!(&mut self.map, map => map.compression_level = compression_level);
+        self
+    }
+
+    pub fn with_property(mut self, property: Property) -> Self {
+        with_map!(&mut self.map, map => map.properties.get_or_insert_with(Vec::new).push(property));
+        self
+    }
+
+    /// Tile layer covering the whole map, still to be added with `add_layer`
+    pub fn tile_layer(&self, name: &str) -> TileLayerBuilder {
+        TileLayerBuilder::new(name, self.map.width(), self.map.height())
+    }
+
+    /// Returns the id of the layer
+    pub fn add_layer(&mut self, layer: Layer) -> i32 {
+        self.map.add_layer(layer)
+    }
+
+    /// Returns the first gid of the tileset
+    pub fn add_tileset(&mut self, tileset: Tileset) -> i32 {
+        let first_gid = self.next_gid.max(self.map.next_first_gid());
+        let tile_count = tileset.tile_count as i32;
+        self.map.add_tileset(Tileset {
+            first_gid: Some(first_gid),
+            ..tileset
+        });
+        self.next_gid = first_gid + tile_count;
+        first_gid
+    }
+
+    /// References an external tileset holding `tile_count` tiles and returns
+    /// its first gid
+    pub fn add_tileset_ref<P: Into<PathBuf>>(&mut self, source: P, tile_count: usize) -> i32 {
+        let first_gid = self.next_gid.max(self.map.next_first_gid());
+        self.map
+            .tile_sets_mut()
+            .push(TilesetContainer::TilesetRef(TilesetRef {
+                first_gid,
+                source: source.into(),
+            }));
+        self.next_gid = first_gid + tile_count as i32;
+        first_gid
+    }
+
+    pub fn build(self) -> Map {
+        self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::models::IntProperty;
+    use crate::tme::models::Object;
+
+    #[test]
+    fn builds_map_with_ids() {
+        let mut builder =
+            MapBuilder::orthogonal(4, 3, 16, 16).with_property(Property::Int(IntProperty {
+                name:  "level".to_owned(),
+                value: 2,
+            }));
+
+        let terrain = builder.add_tileset(
+            TilesetBuilder::new("terrain", 16, 16)
+                .with_image("terrain.png", 64, 32)
+                .build(),
+        );
+        let props = builder.add_tileset_ref("props.json", 10);
+        let characters = builder.add_tileset(
+            TilesetBuilder::new("characters", 16, 16)
+                .with_image("characters.png", 32, 16)
+                .build(),
+        );
+        assert_eq!((terrain, props, characters), (1, 9, 19));
+
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(1, 2, Gid(terrain as u32 + 3))
+            .build();
+        assert_eq!(builder.add_layer(ground), 1);
+
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::point(8.0, 8.0).with_name("spawn").build())
+            .with_object(ObjectBuilder::tile(Gid(characters as u32), 0.0, 16.0, 16.0, 16.0).build())
+            .build();
+        assert_eq!(builder.add_layer(objects), 2);
+
+        let map = builder.build();
+        assert_eq!((map.next_layer_id(), map.next_object_id()), (3, 3));
+
+        let value = serde_json::to_value(&map).unwrap();
+        assert_eq!(value["orientation"], "orthogonal");
+        assert_eq!(value["type"], "map");
+        assert_eq!(value["tiledversion"], TILED_VERSION);
+        assert_eq!(value["layers"][0]["data"][9], 4);
+        assert_eq!(value["layers"][0]["opacity"], 1.0);
+        assert_eq!(value["layers"][1]["objects"][1]["gid"], 19);
+        assert_eq!(value["tilesets"][1]["firstgid"], 9);
+        assert_eq!(value["tilesets"][2]["firstgid"], 19);
+
+        let map: Map = serde_json::from_value(value).unwrap();
+        match &map.layers()[1] {
+            Layer::ObjectGroupLayer(layer) => {
+                let ids: Vec<_> = layer.objects.iter().map(Object::id).collect();
+                assert_eq!(ids, vec![1, 2]);
+            }
+            layer => panic!("unexpected layer {:?}", layer),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+pub mod layer_builder;
+pub mod map_builder;
+pub mod object_builder;
+pub mod tileset_builder;
+
+pub use layer_builder::*;
+pub use map_builder::*;
+pub use object_builder::*;
+pub use tileset_builder::*;
+
+/// Tiled release whose output the builders mimic
+pub const TILED_VERSION: &str = "1.3.5";
+/// JSON map format version written by `TILED_VERSION`
+pub const FORMAT_VERSION: &str = "1.2";
@@ -0,0 +1,182 @@
+use crate::tme::gid::Gid;
+use crate::tme::models::EllipseObject;
+use crate::tme::models::GeneralObject;
+use crate::tme::models::Object;
+use crate::tme::models::Point;
+use crate::tme::models::PointObject;
+use crate::tme::models::PolygonObject;
+use crate::tme::models::PolylineObject;
+use crate::tme::models::Property;
+use crate::tme::models::RectangleObject;
+use crate::tme::models::Text;
+use crate::tme::models::TextObject;
+
+#[derive(Debug)]
+enum Shape {
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<Point>),
+    Polyline(Vec<Point>),
+    Tile(Gid),
+    Text(Text),
+}
+
+/// Builds objects with id `0`, which gets replaced once the layer holding
+/// the object is added to a map
+#[derive(Debug)]
+pub struct ObjectBuilder {
+    shape:      Shape,
+    name:       String,
+    obj_type:   String,
+    x:          f64,
+    y:          f64,
+    width:      f64,
+    height:     f64,
+    rotation:   f64,
+    visible:    bool,
+    properties: Vec<Property>,
+}
+
+impl ObjectBuilder {
+    fn new(shape: Shape, x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            shape,
+            name: String::new(),
+            obj_type: String::new(),
+            x,
+            y,
+            width,
+            height,
+            rotation: 0.0,
+            visible: true,
+            properties: Vec::new(),
+        }
+    }
+
+    pub fn rectangle(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self::new(Shape::Rectangle, x, y, width, height)
+    }
+
+    pub fn ellipse(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self::new(Shape::Ellipse, x, y, width, height)
+    }
+
+    pub fn point(x: f64, y: f64) -> Self {
+        Self::new(Shape::Point, x, y, 0.0, 0.0)
+    }
+
+    /// Points are relative to `(x, y)`
+    pub fn polygon(x: f64, y: f64, points: &[(f64, f64)]) -> Self {
+        Self::new(Shape::Polygon(to_points(points)), x, y, 0.0, 0.0)
+    }
+
+    /// Points are relative to `(x, y)`
+    pub fn polyline(x: f64, y: f64, points: &[(f64, f64)]) -> Self {
+        Self::new(Shape::Polyline(to_points(points)), x, y, 0.0, 0.0)
+    }
+
+    /// Tile objects are anchored at their bottom-left corner
+    pub fn tile(gid: Gid, x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self::new(Shape::Tile(gid), x, y, width, height)
+    }
+
+    pub fn text(text: Text, x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self::new(Shape::Text(text), x, y, width, height)
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    pub fn with_type(mut self, obj_type: &str) -> Self {
+        self.obj_type = obj_type.to_owned();
+        self
+    }
+
+    /// Clockwise rotation in degrees
+    pub fn with_rotation(mut self, rotation: f64) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn with_property(mut self, property: Property) -> Self {
+        self.properties.push(property);
+        self
+    }
+
+    pub fn build(self) -> Object {
+        let properties = if self.properties.is_empty() {
+            None
+        } else {
+            Some(self.properties)
+        };
+
+        macro_rules! object {
+            ($variant:ident, $model:ident $(, $field:ident = $value:expr)*) => {
+                Object::$variant($model {
+                    height: self.height,
+                    id: 0,
+                    name: self.name,
+                    properties,
+                    rotation: self.rotation,
+                    template: None,
+                    obj_type: self.obj_type,
+                    visible: self.visible,
+                    width: self.width,
+                    x: self.x,
+                    y: self.y,
+                    $($field: $value,)*
+                })
+            };
+        }
+
+        match self.shape {
+            Shape::Rectangle => object!(Rectangle, RectangleObject),
+            Shape::Ellipse => object!(Ellipse, EllipseObject, ellipse = true),
+            Shape::Point => object!(Point, PointObject, point = true),
+            Shape::Polygon(polygon) => object!(Polygon, PolygonObject, polygon = polygon),
+            Shape::Polyline(polyline) => object!(Polyline, PolylineObject, polyline = polyline),
+            Shape::Tile(gid) => object!(General, GeneralObject, gid = gid.0 as i64),
+            Shape::Text(text) => object!(Text, TextObject, text = text),
+        }
+    }
+}
+
+fn to_points(points: &[(f64, f64)]) -> Vec<Point> {
+    points.iter().map(|&(x, y)| Point { x, y }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_objects_that_round_trip() {
+        let objects = vec![
+            ObjectBuilder::rectangle(1.0, 2.0, 3.0, 4.0)
+                .with_name("door")
+                .with_type("trigger")
+                .build(),
+            ObjectBuilder::ellipse(0.0, 0.0, 8.0, 8.0).build(),
+            ObjectBuilder::point(5.0, 5.0).build(),
+            ObjectBuilder::polygon(0.0, 0.0, &[(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)])
+                .with_rotation(90.0)
+                .build(),
+            ObjectBuilder::polyline(0.0, 0.0, &[(0.0, 0.0), (4.0, 4.0)]).build(),
+            ObjectBuilder::tile(Gid::new(3, 0x8000_0000), 0.0, 16.0, 16.0, 16.0).build(),
+        ];
+
+        for object in objects {
+            let value = serde_json::to_value(&object).unwrap();
+            let parsed: Object = serde_json::from_value(value).unwrap();
+            assert_eq!(parsed, object);
+        }
+    }
+}
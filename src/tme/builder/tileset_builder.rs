@@ -0,0 +1,174 @@
+use super::FORMAT_VERSION;
+use super::TILED_VERSION;
+
+use crate::tme::color::Color;
+use crate::tme::models::Property;
+use crate::tme::models::Tile;
+use crate::tme::models::TileOffset;
+use crate::tme::models::Tileset;
+
+/// Builds either an atlas tileset cut from one image or an image collection
+#[derive(Debug)]
+pub struct TilesetBuilder {
+    name:        String,
+    tile_width:  i32,
+    tile_height: i32,
+    image:       Option<(String, i32, i32)>,
+    margin:      i32,
+    spacing:     i32,
+    tile_offset: Option<TileOffset>,
+    tiles:       Vec<Tile>,
+    properties:  Vec<Property>,
+}
+
+impl TilesetBuilder {
+    pub fn new(name: &str, tile_width: i32, tile_height: i32) -> Self {
+        Self {
+            name: name.to_owned(),
+            tile_width,
+            tile_height,
+            image: None,
+            margin: 0,
+            spacing: 0,
+            tile_offset: None,
+            tiles: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// The atlas image, columns and tile count are derived from its size
+    pub fn with_image(mut self, image: &str, image_width: i32, image_height: i32) -> Self {
+        self.image = Some((image.to_owned(), image_width, image_height));
+        self
+    }
+
+    pub fn with_margin(mut self, margin: i32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing: i32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn with_tile_offset(mut self, x: i32, y: i32) -> Self {
+        self.tile_offset = Some(TileOffset { x, y });
+        self
+    }
+
+    /// Per-tile data such as properties or animations, replaces an earlier
+    /// tile with the same id
+    pub fn with_tile(mut self, tile: Tile) -> Self {
+        self.tiles.retain(|existing| existing.id != tile.id);
+        self.tiles.push(tile);
+        self
+    }
+
+    /// Adds a tile of an image collection with the next free id
+    pub fn with_tile_image(self, image: &str, image_width: i32, image_height: i32) -> Self {
+        let id = self.tiles.iter().map(|tile| tile.id + 1).max().unwrap_or(0);
+        self.with_tile(Tile {
+            animation: None,
+            id,
+            image: Some(image.to_owned()),
+            image_height: Some(image_height),
+            image_width: Some(image_width),
+            object_group: None,
+            probability: None,
+            properties: None,
+            terrain: None,
+            tile_type: None,
+        })
+    }
+
+    pub fn with_property(mut self, property: Property) -> Self {
+        self.properties.push(property);
+        self
+    }
+
+    pub fn build(mut self) -> Tileset {
+        let (columns, tile_count) = match &self.image {
+            Some((_, image_width, image_height)) => {
+                let count = |size: i32, tile_size: i32| {
+                    ((size - 2 * self.margin + self.spacing) / (tile_size + self.spacing).max(1))
+                        .max(0) as usize
+                };
+                let columns = count(*image_width, self.tile_width);
+                (columns, columns * count(*image_height, self.tile_height))
+            }
+            None => (
+                0,
+                self.tiles
+                    .iter()
+                    .map(|tile| tile.id as usize + 1)
+                    .max()
+                    .unwrap_or(0),
+            ),
+        };
+        self.tiles.sort_by_key(|tile| tile.id);
+
+        let (image, image_width, image_height) = match self.image {
+            Some((image, width, height)) => (Some(image), Some(width), Some(height)),
+            None => (None, None, None),
+        };
+
+        Tileset {
+            background_color: Color::new_transparent(),
+            columns,
+            first_gid: None,
+            grid: None,
+            image,
+            image_height,
+            image_width,
+            margin: self.margin,
+            name: self.name,
+            properties: Some(self.properties).filter(|properties| !properties.is_empty()),
+            spacing: self.spacing,
+            terrains: None,
+            tile_count,
+            tiled_version: TILED_VERSION.to_owned(),
+            tile_height: self.tile_height,
+            tile_offset: self.tile_offset,
+            tiles: Some(self.tiles).filter(|tiles| !tiles.is_empty()),
+            tile_width: self.tile_width,
+            transparent_color: None,
+            tileset_type: "tileset".to_owned(),
+            version: FORMAT_VERSION.to_owned(),
+            wang_sets: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_atlas_size() {
+        let tileset = TilesetBuilder::new("atlas", 16, 16)
+            .with_image("atlas.png", 70, 36)
+            .with_margin(1)
+            .with_spacing(2)
+            .build();
+        assert_eq!((tileset.columns, tileset.tile_count), (3, 6));
+        assert_eq!(
+            tileset.tile_image(4).map(|image| (image.x, image.y)),
+            Some((19, 19))
+        );
+
+        let collection = TilesetBuilder::new("props", 32, 32)
+            .with_tile_image("barrel.png", 24, 32)
+            .with_tile_image("crate.png", 32, 32)
+            .build();
+        assert_eq!((collection.columns, collection.tile_count), (0, 2));
+        assert_eq!(
+            collection.tile_image(1).map(|image| image.image),
+            Some("crate.png")
+        );
+
+        let value = serde_json::to_value(&collection).unwrap();
+        let parsed: Tileset = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, collection);
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use lru::LruCache;
+
+use super::error::Error;
+use super::gid::Gid;
+use super::models::TileLayer;
+use super::tile_grid::TileGrid;
+
+/// Rectangle of a chunk in tile coordinates
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkBounds {
+    pub x:      i32,
+    pub y:      i32,
+    pub width:  i32,
+    pub height: i32,
+}
+
+impl ChunkBounds {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    pub fn intersects(&self, x: i32, y: i32, width: i32, height: i32) -> bool {
+        x < self.x + self.width
+            && y < self.y + self.height
+            && x + width > self.x
+            && y + height > self.y
+    }
+
+    /// Memory taken by the decoded tiles
+    fn size(&self) -> usize {
+        (self.width.max(0) as usize) * (self.height.max(0) as usize) * std::mem::size_of::<i32>()
+    }
+}
+
+/// Decodes the chunks of a tile layer on demand and keeps the most recently
+/// used ones while their decoded size fits into a memory budget.
+///
+/// Only chunk positions are indexed up front, in a spatial hash with cells
+/// the size of the largest chunk. A finite layer is treated as one chunk.
+pub struct ChunkStreamer<'a> {
+    layer:     &'a TileLayer,
+    bounds:    Vec<ChunkBounds>,
+    cells:     HashMap<(i32, i32), Vec<usize>>,
+    cell_size: i32,
+    loaded:    LruCache<usize, Arc<TileGrid>>,
+    budget:    usize,
+    resident:  usize,
+}
+
+impl<'a> ChunkStreamer<'a> {
+    /// `budget` is the number of bytes decoded chunks may take
+    pub fn new(layer: &'a TileLayer, budget: usize) -> Self {
+        let bounds: Vec<ChunkBounds> = match &layer.chunks {
+            Some(chunks) if !chunks.is_empty() => chunks
+                .iter()
+                .map(|chunk| ChunkBounds {
+                    x:      chunk.x,
+                    y:      chunk.y,
+                    width:  chunk.width,
+                    height: chunk.height,
+                })
+                .collect(),
+            _ => vec![ChunkBounds {
+                x:      0,
+                y:      0,
+                width:  layer.width,
+                height: layer.height,
+            }],
+        };
+        let cell_size = bounds
+            .iter()
+            .map(|bounds| bounds.width.max(bounds.height))
+            .max()
+            .unwrap_or(1)
+            .max(1);
+
+        let mut streamer = Self {
+            layer,
+            bounds,
+            cells: HashMap::new(),
+            cell_size,
+            loaded: LruCache::unbounded(),
+            budget,
+            resident: 0,
+        };
+        for (index, bounds) in streamer.bounds.iter().enumerate() {
+            if bounds.width <= 0 || bounds.height <= 0 {
+                continue;
+            }
+            let (left, top) = streamer.cell(bounds.x, bounds.y);
+            let (right, bottom) =
+                streamer.cell(bounds.x + bounds.width - 1, bounds.y + bounds.height - 1);
+            for cell_y in top..=bottom {
+                for cell_x in left..=right {
+                    streamer
+                        .cells
+                        .entry((cell_x, cell_y))
+                        .or_default()
+                        .push(index);
+                }
+            }
+        }
+        streamer
+    }
+
+    pub fn bounds(&self) -> &[ChunkBounds] {
+        &self.bounds
+    }
+
+    /// Decoded size of the chunks currently kept
+    pub fn resident_bytes(&self) -> usize {
+        self.resident
+    }
+
+    pub fn loaded_count(&self) -> usize {
+        self.loaded.len()
+    }
+
+    /// Whether the chunk holding the tile at `(x, y)` is decoded
+    pub fn is_loaded(&self, x: i32, y: i32) -> bool {
+        self.chunks_in(x, y, 1, 1)
+            .iter()
+            .any(|index| self.loaded.contains(index))
+    }
+
+    /// Indices into `bounds` of the chunks intersecting the region, without
+    /// decoding any of them
+    pub fn chunks_in(&self, x: i32, y: i32, width: i32, height: i32) -> Vec<usize> {
+        if width <= 0 || height <= 0 {
+            return Vec::new();
+        }
+        let (left, top) = self.cell(x, y);
+        let (right, bottom) = self.cell(x + width - 1, y + height - 1);
+        let cell_count = (right - left + 1) as usize * (bottom - top + 1) as usize;
+
+        let mut indices: Vec<usize> = if cell_count > self.bounds.len() {
+            (0..self.bounds.len()).collect()
+        } else {
+            (top..=bottom)
+                .flat_map(|cell_y| (left..=right).map(move |cell_x| (cell_x, cell_y)))
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .copied()
+                .collect()
+        };
+        indices.sort_unstable();
+        indices.dedup();
+        indices.retain(|&index| self.bounds[index].intersects(x, y, width, height));
+        indices
+    }
+
+    /// Decoded chunks intersecting the region, decoding those not kept yet
+    /// and evicting the least recently used ones to stay within the budget.
+    /// Chunks larger than the whole budget are returned but not kept.
+    pub fn load_region(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<Vec<Arc<TileGrid>>, Error> {
+        self.chunks_in(x, y, width, height)
+            .into_iter()
+            .map(|index| self.load(index))
+            .collect()
+    }
+
+    pub fn tile(&mut self, x: i32, y: i32) -> Result<Option<Gid>, Error> {
+        for grid in self.load_region(x, y, 1, 1)? {
+            if let Some(gid) = grid.get(x, y) {
+                return Ok(Some(gid));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Drops every decoded chunk
+    pub fn clear(&mut self) {
+        self.loaded.clear();
+        self.resident = 0;
+    }
+
+    fn load(&mut self, index: usize) -> Result<Arc<TileGrid>, Error> {
+        if let Some(grid) = self.loaded.get(&index) {
+            return Ok(grid.clone());
+        }
+
+        let bounds = self.bounds[index];
+        let mut grid = TileGrid::new(bounds.x, bounds.y, bounds.width, bounds.height);
+        grid.tiles = self.decode(index)?;
+        let grid = Arc::new(grid);
+
+        let size = bounds.size();
+        if size <= self.budget {
+            while self.resident + size > self.budget {
+                match self.loaded.pop_lru() {
+                    Some((evicted, _)) => self.resident -= self.bounds[evicted].size(),
+                    None => break,
+                }
+            }
+            self.loaded.put(index, grid.clone());
+            self.resident += size;
+        }
+        Ok(grid)
+    }
+
+    fn decode(&self, index: usize) -> Result<Vec<i32>, Error> {
+        match &self.layer.chunks {
+            Some(chunks) if !chunks.is_empty() => self.layer.decode_chunk(&chunks[index]),
+            _ => self.layer.decode_data(),
+        }
+    }
+
+    fn cell(&self, x: i32, y: i32) -> (i32, i32) {
+        (x.div_euclid(self.cell_size), y.div_euclid(self.cell_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::models::Compression;
+    use crate::tme::models::Encoding;
+    use crate::tme::models::Layer;
+    use crate::tme::tile_grid::CHUNK_SIZE;
+
+    const CHUNK_BYTES: usize = (CHUNK_SIZE * CHUNK_SIZE * 4) as usize;
+
+    /// Infinite layer with chunks at chunk coordinates `-1..3, 0..2`, each
+    /// holding its index at its origin
+    fn sample_layer() -> TileLayer {
+        let builder = MapBuilder::orthogonal(16, 16, 16, 16);
+        let mut layer = match builder.tile_layer("ground").build() {
+            Layer::TileLayer(layer) => layer,
+            _ => unreachable!(),
+        };
+        let mut grid = TileGrid::new(-CHUNK_SIZE, 0, CHUNK_SIZE * 4, CHUNK_SIZE * 2);
+        for (index, (chunk_x, chunk_y)) in (0..2)
+            .flat_map(|chunk_y| (-1..3).map(move |chunk_x| (chunk_x, chunk_y)))
+            .enumerate()
+        {
+            grid.set(
+                chunk_x * CHUNK_SIZE,
+                chunk_y * CHUNK_SIZE,
+                Gid(index as u32 + 1),
+            );
+        }
+        layer.store_grid(&grid, true).unwrap();
+        layer
+            .encode(Encoding::Base64, Some(Compression::Zstd))
+            .unwrap();
+        layer
+    }
+
+    #[test]
+    fn indexes_chunks_without_decoding() {
+        let layer = sample_layer();
+        let streamer = ChunkStreamer::new(&layer, CHUNK_BYTES * 2);
+
+        assert_eq!(streamer.bounds().len(), 8);
+        assert_eq!(streamer.chunks_in(-1, 0, 2, 1).len(), 2);
+        assert_eq!(streamer.chunks_in(-100, -100, 1000, 1000).len(), 8);
+        assert_eq!(streamer.chunks_in(0, 40, 5, 5), Vec::<usize>::new());
+        assert_eq!(streamer.loaded_count(), 0);
+    }
+
+    #[test]
+    fn loads_regions_on_demand() {
+        let layer = sample_layer();
+        let mut streamer = ChunkStreamer::new(&layer, CHUNK_BYTES * 4);
+        let expected = layer.decode_grid().unwrap();
+
+        let grids = streamer.load_region(-4, 10, 8, 8).unwrap();
+        assert_eq!(grids.len(), 4);
+        for grid in grids {
+            for (x, y, gid) in grid.cells() {
+                assert_eq!(expected.get(x, y), Some(gid));
+            }
+        }
+        assert_eq!(streamer.tile(16, 16).unwrap(), Some(Gid(7)));
+        assert_eq!(streamer.tile(16, 40).unwrap(), None);
+        assert_eq!(streamer.resident_bytes(), CHUNK_BYTES * 4);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_chunks() {
+        let layer = sample_layer();
+        let mut streamer = ChunkStreamer::new(&layer, CHUNK_BYTES * 2);
+
+        assert_eq!(streamer.tile(-16, 0).unwrap(), Some(Gid(1)));
+        assert_eq!(streamer.tile(0, 0).unwrap(), Some(Gid(2)));
+        assert_eq!(streamer.tile(-16, 0).unwrap(), Some(Gid(1)));
+        assert_eq!(streamer.tile(16, 0).unwrap(), Some(Gid(3)));
+
+        assert!(streamer.is_loaded(-16, 0));
+        assert!(!streamer.is_loaded(0, 0));
+        assert!(streamer.is_loaded(16, 0));
+        assert_eq!(streamer.resident_bytes(), CHUNK_BYTES * 2);
+
+        let grids = streamer.load_region(-16, 0, 64, 1).unwrap();
+        assert_eq!(grids.len(), 4);
+        assert_eq!(streamer.loaded_count(), 2);
+        assert!(streamer.is_loaded(32, 0));
+        assert!(!streamer.is_loaded(-16, 0));
+
+        streamer.clear();
+        assert_eq!(streamer.resident_bytes(), 0);
+    }
+
+    #[test]
+    fn streams_finite_layers_as_one_chunk() {
+        let builder = MapBuilder::orthogonal(4, 4, 16, 16);
+        let layer = match builder.tile_layer("ground").with_tile(3, 3, Gid(5)).build() {
+            Layer::TileLayer(layer) => layer,
+            _ => unreachable!(),
+        };
+
+        let mut streamer = ChunkStreamer::new(&layer, 0);
+        assert_eq!(streamer.tile(3, 3).unwrap(), Some(Gid(5)));
+        assert_eq!(streamer.loaded_count(), 0);
+    }
+}
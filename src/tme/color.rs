@@ -1,3 +1,4 @@
+use std::fmt;
 use std::str::FromStr;
 
 use serde::Deserialize;
@@ -15,13 +16,13 @@ pub struct Color {
 }
 
 fn default_alpha() -> u8 {
-    u8::max_value()
+    u8::MAX
 }
 
 impl Color {
     pub fn new(r: u8, g: u8, b: u8) -> Self {
         Self {
-            a: u8::max_value(),
+            a: u8::MAX,
             r,
             g,
             b,
@@ -55,45 +56,436 @@ impl Color {
     pub fn new_transparent() -> Self {
         Self::with_alpha(0, 0, 0, 0)
     }
+
+    pub fn from_rgba_f32(rgba: [f32; 4]) -> Self {
+        let [r, g, b, a] = rgba.map(unit_to_u8);
+        Self::with_alpha(a, r, g, b)
+    }
+
+    /// Normalized sRGB channels in `r, g, b, a` order
+    pub fn to_rgba_f32(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| c as f32 / 255.0)
+    }
+
+    pub fn from_linear(rgba: [f32; 4]) -> Self {
+        let [r, g, b, a] = rgba;
+        Self::from_rgba_f32([linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a])
+    }
+
+    /// Linear light channels in `r, g, b, a` order, alpha is left as is
+    pub fn to_linear(self) -> [f32; 4] {
+        let [r, g, b, a] = self.to_rgba_f32();
+        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+    }
+
+    pub fn premultiplied(self) -> Self {
+        let [r, g, b, a] = self.to_rgba_f32();
+        Self::from_rgba_f32([r * a, g * a, b * a, a])
+    }
+
+    pub fn unpremultiplied(self) -> Self {
+        if self.a == 0 {
+            return Self::new_transparent();
+        }
+        let [r, g, b, a] = self.to_rgba_f32();
+        Self::from_rgba_f32([r / a, g / a, b / a, a])
+    }
+
+    pub fn from_hsv(hsv: Hsv) -> Self {
+        let chroma = hsv.v * hsv.s;
+        let (r, g, b) = hue_to_rgb(hsv.h, chroma);
+        let m = hsv.v - chroma;
+        Self::from_rgba_f32([r + m, g + m, b + m, hsv.a])
+    }
+
+    pub fn to_hsv(self) -> Hsv {
+        let (h, min, max, a) = self.hue();
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        Hsv { h, s, v: max, a }
+    }
+
+    pub fn from_hsl(hsl: Hsl) -> Self {
+        let chroma = (1.0 - (2.0 * hsl.l - 1.0).abs()) * hsl.s;
+        let (r, g, b) = hue_to_rgb(hsl.h, chroma);
+        let m = hsl.l - chroma / 2.0;
+        Self::from_rgba_f32([r + m, g + m, b + m, hsl.a])
+    }
+
+    pub fn to_hsl(self) -> Hsl {
+        let (h, min, max, a) = self.hue();
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        Hsl { h, s, l, a }
+    }
+
+    /// Porter-Duff source over: composites `self` on top of `background`
+    pub fn over(self, background: Color) -> Self {
+        let [sr, sg, sb, sa] = self.to_rgba_f32();
+        let [dr, dg, db, da] = background.to_rgba_f32();
+
+        let a = sa + da * (1.0 - sa);
+        if a == 0.0 {
+            return Self::new_transparent();
+        }
+        let blend = |s: f32, d: f32| (s * sa + d * da * (1.0 - sa)) / a;
+        Self::from_rgba_f32([blend(sr, dr), blend(sg, dg), blend(sb, db), a])
+    }
+
+    /// Multiplies alpha by `opacity`, as Tiled does for layer opacity
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        Self {
+            a: unit_to_u8(self.a as f32 / 255.0 * opacity),
+            ..self
+        }
+    }
+
+    /// Interpolates the sRGB channels, `t = 0` gives `self`
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        let from = self.to_rgba_f32();
+        let to = other.to_rgba_f32();
+        Self::from_rgba_f32([0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * t))
+    }
+
+    /// Interpolates in linear light, which avoids dark fringes between
+    /// saturated colors
+    pub fn lerp_linear(self, other: Color, t: f32) -> Self {
+        let from = self.to_linear();
+        let to = other.to_linear();
+        Self::from_linear([0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * t))
+    }
+
+    fn hue(self) -> (f32, f32, f32, f32) {
+        let [r, g, b, a] = self.to_rgba_f32();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+
+        (h, min, max, a)
+    }
+}
+
+/// Hue in degrees, other components in `0..=1`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Hsv {
+    pub h: f32,
+    pub s: f32,
+    pub v: f32,
+    pub a: f32,
+}
+
+/// Hue in degrees, other components in `0..=1`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Hsl {
+    pub h: f32,
+    pub s: f32,
+    pub l: f32,
+    pub a: f32,
+}
+
+fn unit_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn hue_to_rgb(hue: f32, chroma: f32) -> (f32, f32, f32) {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    match h as i32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    }
 }
 
 impl FromStr for Color {
     type Err = Error;
 
-    #[allow(clippy::many_single_char_names)]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = if s.starts_with('#') { &s[1..] } else { s };
-
-        let (a, r, g, b) = match s.len() {
-            6 => {
-                let a = u8::max_value();
-                let r = u8::from_str_radix(&s[0..2], 16).map_err(Error::ParseColorComponent)?;
-                let g = u8::from_str_radix(&s[2..4], 16).map_err(Error::ParseColorComponent)?;
-                let b = u8::from_str_radix(&s[4..6], 16).map_err(Error::ParseColorComponent)?;
-                (a, r, g, b)
-            }
-            8 => {
-                let a = u8::from_str_radix(&s[0..2], 16).map_err(Error::ParseColorComponent)?;
-                let r = u8::from_str_radix(&s[2..4], 16).map_err(Error::ParseColorComponent)?;
-                let g = u8::from_str_radix(&s[4..6], 16).map_err(Error::ParseColorComponent)?;
-                let b = u8::from_str_radix(&s[6..8], 16).map_err(Error::ParseColorComponent)?;
-                (a, r, g, b)
-            }
-            _ => {
-                return Error::ParseColor(s.to_owned()).fail();
-            }
-        };
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex);
+        }
 
-        Ok(Color::with_alpha(a, r, g, b))
+        let lowercase = s.to_ascii_lowercase();
+        if let Some(args) = lowercase
+            .strip_prefix("rgba(")
+            .or_else(|| lowercase.strip_prefix("rgb("))
+        {
+            return parse_rgb_function(s, args);
+        }
+
+        if lowercase == "transparent" {
+            return Ok(Color::new_transparent());
+        }
+
+        match NAMED_COLORS.iter().find(|(name, _)| *name == lowercase) {
+            Some(&(_, rgb)) => Ok(Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+            None => parse_hex(s),
+        }
     }
 }
 
-impl ToString for Color {
-    fn to_string(&self) -> String {
-        format!("#{:02X}{:02X}{:02X}{:02X}", self.a, self.r, self.g, self.b)
+#[allow(clippy::many_single_char_names)]
+fn parse_hex(s: &str) -> Result<Color, Error> {
+    let component = |range: std::ops::Range<usize>| {
+        s.get(range)
+            .ok_or_else(|| Error::ParseColor(s.to_owned()))
+            .and_then(|part| u8::from_str_radix(part, 16).map_err(Error::ParseColorComponent))
+    };
+
+    let (a, r, g, b) = match s.len() {
+        6 => (
+            u8::MAX,
+            component(0..2)?,
+            component(2..4)?,
+            component(4..6)?,
+        ),
+        8 => (
+            component(0..2)?,
+            component(2..4)?,
+            component(4..6)?,
+            component(6..8)?,
+        ),
+        _ => {
+            return Error::ParseColor(s.to_owned()).fail();
+        }
+    };
+
+    Ok(Color::with_alpha(a, r, g, b))
+}
+
+/// Parses the arguments of CSS `rgb(r, g, b)` and `rgba(r, g, b, a)`, where
+/// channels are `0..=255` or percentages and alpha is `0..=1` or a percentage
+fn parse_rgb_function(s: &str, args: &str) -> Result<Color, Error> {
+    let args = args
+        .strip_suffix(')')
+        .ok_or_else(|| Error::ParseColor(s.to_owned()))?;
+
+    let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return Error::ParseColor(s.to_owned()).fail();
     }
+
+    let parse = |part: &str, scale: f32| -> Result<u8, Error> {
+        let value = match part.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().map(|value| value / 100.0 * 255.0),
+            None => part.parse::<f32>().map(|value| value * scale),
+        }
+        .map_err(|_| Error::ParseColor(s.to_owned()))?;
+        Ok(value.round().clamp(0.0, 255.0) as u8)
+    };
+
+    let a = match parts.get(3) {
+        Some(alpha) => parse(alpha, 255.0)?,
+        None => u8::MAX,
+    };
+    Ok(Color::with_alpha(
+        a,
+        parse(parts[0], 1.0)?,
+        parse(parts[1], 1.0)?,
+        parse(parts[2], 1.0)?,
+    ))
 }
 
+/// `{}` formats as `#AARRGGBB` like Tiled does, `{:#}` as CSS `rgba()`
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            let alpha = (self.a as f32 / 255.0 * 1000.0).round() / 1000.0;
+            return write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha);
+        }
+
+        write!(
+            f,
+            "#{:02X}{:02X}{:02X}{:02X}",
+            self.a, self.r, self.g, self.b
+        )
+    }
+}
+
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
 pub mod opt_color_serde {
     use std::str::FromStr;
 
@@ -109,7 +501,7 @@ pub mod opt_color_serde {
         S: Serializer,
     {
         if let Some(ref c) = *date {
-            return s.serialize_str(&*c.to_string());
+            return s.serialize_str(&c.to_string());
         }
         s.serialize_none()
     }
@@ -141,7 +533,7 @@ pub mod color_serde {
     where
         S: Serializer,
     {
-        s.serialize_str(&*date.to_string())
+        s.serialize_str(&date.to_string())
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Color, D::Error>
@@ -304,4 +696,85 @@ mod tests {
             assert_eq!(actual, expected);
         }
     }
+
+    #[test]
+    fn parses_css_forms() {
+        let expecteds = vec![
+            ("rgb(255, 0, 10)", Color::new(255, 0, 10)),
+            ("RGBA(1,2,3,0.5)", Color::with_alpha(128, 1, 2, 3)),
+            ("rgba(100%, 0%, 50%, 100%)", Color::new(255, 0, 128)),
+            ("cornflowerblue", Color::new(0x64, 0x95, 0xED)),
+            ("Red", Color::new(255, 0, 0)),
+            ("transparent", Color::new_transparent()),
+            ("646464", Color::new(100, 100, 100)),
+        ];
+
+        for (s, expected) in expecteds {
+            assert_eq!(Color::from_str(s).unwrap(), expected);
+        }
+
+        for s in &[
+            "rgb(1, 2)",
+            "rgb(1, 2, 3",
+            "nocolor",
+            "#12345",
+            "rgb(a, b, c)",
+        ] {
+            assert!(Color::from_str(s).is_err());
+        }
+    }
+
+    #[test]
+    fn formats_css_rgba() {
+        let color = Color::with_alpha(128, 1, 2, 3);
+        assert_eq!(format!("{}", color), "#80010203");
+        assert_eq!(format!("{:#}", color), "rgba(1, 2, 3, 0.502)");
+        assert_eq!(Color::from_str(&format!("{:#}", color)).unwrap(), color);
+    }
+
+    #[test]
+    fn converts_color_spaces() {
+        let color = Color::with_alpha(200, 255, 128, 0);
+        assert_eq!(Color::from_rgba_f32(color.to_rgba_f32()), color);
+        assert_eq!(Color::from_linear(color.to_linear()), color);
+
+        let linear = Color::new(128, 128, 128).to_linear();
+        assert!((linear[0] - 0.2158).abs() < 1e-3);
+
+        let hsv = Color::new(255, 128, 0).to_hsv();
+        assert!((hsv.h - 30.1).abs() < 0.1);
+        assert_eq!((hsv.s, hsv.v), (1.0, 1.0));
+        assert_eq!(Color::from_hsv(hsv), Color::new(255, 128, 0));
+
+        let hsl = Color::new(0, 0, 255).to_hsl();
+        assert_eq!((hsl.h, hsl.s, hsl.l), (240.0, 1.0, 0.5));
+        assert_eq!(Color::from_hsl(hsl), Color::new(0, 0, 255));
+        assert_eq!(
+            Color::from_hsl(Color::new(90, 90, 90).to_hsl()),
+            Color::new(90, 90, 90)
+        );
+    }
+
+    #[test]
+    fn composites_colors() {
+        let half_red = Color::with_alpha(128, 255, 0, 0);
+        assert_eq!(half_red.premultiplied(), Color::with_alpha(128, 128, 0, 0));
+        assert_eq!(half_red.premultiplied().unpremultiplied(), half_red);
+
+        assert_eq!(half_red.over(Color::new_blue()), Color::new(128, 0, 127));
+        assert_eq!(Color::new_red().over(Color::new_blue()), Color::new_red());
+        assert_eq!(
+            Color::new_transparent().over(Color::new_transparent()),
+            Color::new_transparent()
+        );
+
+        assert_eq!(Color::new_white().with_opacity(0.5).a, 128);
+
+        let black = Color::new_black();
+        let white = Color::new_white();
+        assert_eq!(black.lerp(white, 0.0), black);
+        assert_eq!(black.lerp(white, 1.0), white);
+        assert_eq!(black.lerp(white, 0.5), Color::new(128, 128, 128));
+        assert_eq!(black.lerp_linear(white, 0.5), Color::new(188, 188, 188));
+    }
 }
@@ -0,0 +1,195 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use super::error::Error;
+use super::models::flatten_layers;
+use super::models::DataSource;
+use super::models::Layer;
+use super::models::Map;
+use super::models::TileLayer;
+use super::models::TileOrigin;
+
+/// Decoded tiles of a tile layer, `chunks` in the order of its chunks
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedLayer {
+    pub id:     i32,
+    pub data:   Vec<i32>,
+    pub chunks: Vec<Vec<i32>>,
+}
+
+/// Decodes the data and chunks of every tile layer of `map`, including
+/// those nested in groups, one after another
+pub fn decode_tile_layers(map: &Map) -> Result<Vec<DecodedLayer>, Error> {
+    let jobs = jobs(map);
+    let decoded = jobs.iter().map(|(layer, data, origin)| {
+        data.extract_tiles_of(layer.encoding, layer.compression, *origin)
+    });
+    collect(map, decoded)
+}
+
+/// Same as `decode_tile_layers`, but decodes all layer data and chunks
+/// concurrently on the rayon thread pool. Run it inside `ThreadPool::install`
+/// to use a pool other than the global one.
+#[cfg(feature = "parallel")]
+pub fn par_decode_tile_layers(map: &Map) -> Result<Vec<DecodedLayer>, Error> {
+    let jobs = jobs(map);
+    let decoded: Vec<_> = jobs
+        .par_iter()
+        .map(|(layer, data, origin)| {
+            data.extract_tiles_of(layer.encoding, layer.compression, *origin)
+        })
+        .collect();
+    collect(map, decoded)
+}
+
+fn tile_layers(map: &Map) -> impl Iterator<Item = &TileLayer> {
+    flatten_layers(map.layers())
+        .into_iter()
+        .filter_map(|layer| match layer {
+            Layer::TileLayer(tile_layer) => Some(tile_layer),
+            _ => None,
+        })
+}
+
+/// Data of each tile layer followed by its chunks
+fn jobs(map: &Map) -> Vec<(&TileLayer, &DataSource, TileOrigin)> {
+    tile_layers(map)
+        .flat_map(|layer| {
+            let chunks = layer.chunks.iter().flatten().map(move |chunk| {
+                let origin = TileOrigin::Chunk {
+                    layer: layer.id,
+                    x:     chunk.x,
+                    y:     chunk.y,
+                };
+                (&chunk.data, origin)
+            });
+            std::iter::once((&layer.data, TileOrigin::Layer(layer.id)))
+                .chain(chunks)
+                .map(move |(data, origin)| (layer, data, origin))
+        })
+        .collect()
+}
+
+/// Groups decoded jobs back into layers, reporting the first error in
+/// layer order whatever order the jobs finished in
+fn collect<I>(map: &Map, decoded: I) -> Result<Vec<DecodedLayer>, Error>
+where
+    I: IntoIterator<Item = Result<Vec<i32>, Error>>,
+{
+    let mut decoded = decoded.into_iter();
+    let mut next = || decoded.next().unwrap_or_else(|| Ok(Vec::new()));
+    tile_layers(map)
+        .map(|layer| {
+            let data = next()?;
+            let chunks = layer
+                .chunks
+                .iter()
+                .flatten()
+                .map(|_| next())
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(DecodedLayer {
+                id: layer.id,
+                data,
+                chunks,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::GroupLayerBuilder;
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::models::Compression;
+    use crate::tme::models::Encoding;
+
+    fn sample_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(4, 4, 16, 16);
+        for (index, compression) in [Compression::Zlib, Compression::Gzip, Compression::Zstd]
+            .iter()
+            .enumerate()
+        {
+            let mut layer = builder
+                .tile_layer("layer")
+                .with_tile(index as i32, 1, Gid(index as u32 + 1))
+                .build();
+            if let Layer::TileLayer(tile_layer) = &mut layer {
+                tile_layer
+                    .encode(Encoding::Base64, Some(*compression))
+                    .unwrap();
+            }
+            builder.add_layer(GroupLayerBuilder::new("group").with_layer(layer).build());
+        }
+        builder.build()
+    }
+
+    fn infinite_map() -> Map {
+        let mut map = sample_map();
+        map.set_infinite(true).unwrap();
+        for id in 1..=6 {
+            if let Some(Layer::TileLayer(layer)) = map.layer_mut(id) {
+                layer.set_tile(-20, 40, Gid(9)).unwrap();
+                layer
+                    .encode(Encoding::Base64, Some(Compression::Zstd))
+                    .unwrap();
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn decodes_all_tile_layers() {
+        let decoded = decode_tile_layers(&sample_map()).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[2].data[4 + 2], 3);
+        assert!(decoded.iter().all(|layer| layer.chunks.is_empty()));
+
+        let decoded = decode_tile_layers(&infinite_map()).unwrap();
+        assert!(decoded.iter().all(|layer| layer.chunks.len() == 2));
+        assert!(decoded
+            .iter()
+            .all(|layer| layer.chunks.iter().any(|chunk| chunk.contains(&9))));
+    }
+
+    #[test]
+    fn reports_first_error_in_layer_order() {
+        let mut map = sample_map();
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(4) {
+            layer.data = DataSource::Encoded(base64::encode("not gzip"));
+        }
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(6) {
+            layer.data = DataSource::Encoded("not base64".to_owned());
+        }
+        assert!(matches!(
+            decode_tile_layers(&map),
+            Err(Error::DecompressTiles(
+                TileOrigin::Layer(4),
+                Compression::Gzip,
+                _
+            ))
+        ));
+        #[cfg(feature = "parallel")]
+        assert!(matches!(
+            par_decode_tile_layers(&map),
+            Err(Error::DecompressTiles(
+                TileOrigin::Layer(4),
+                Compression::Gzip,
+                _
+            ))
+        ));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn decodes_in_parallel_like_sequentially() {
+        for map in &[sample_map(), infinite_map()] {
+            assert_eq!(
+                par_decode_tile_layers(map).unwrap(),
+                decode_tile_layers(map).unwrap()
+            );
+        }
+    }
+}
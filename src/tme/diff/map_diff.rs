@@ -0,0 +1,596 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::tme::editing::PropertyTarget;
+use crate::tme::editing::TilesetKey;
+use crate::tme::error::Error;
+use crate::tme::gid::Gid;
+use crate::tme::models::flatten_layers;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Property;
+use crate::tme::models::TilesetContainer;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct TileChange {
+    pub x:    i32,
+    pub y:    i32,
+    /// Raw gids including flip flags
+    pub from: u32,
+    pub to:   u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    MapResized {
+        from: (i32, i32),
+        to:   (i32, i32),
+    },
+    LayerAdded {
+        id:   i32,
+        name: String,
+    },
+    LayerRemoved {
+        id:   i32,
+        name: String,
+    },
+    LayerRenamed {
+        id:   i32,
+        from: String,
+        to:   String,
+    },
+    TilesChanged {
+        layer: i32,
+        tiles: Vec<TileChange>,
+    },
+    ObjectAdded {
+        layer: i32,
+        id:    i64,
+    },
+    ObjectRemoved {
+        layer: i32,
+        id:    i64,
+    },
+    ObjectMoved {
+        id:   i64,
+        from: (f64, f64),
+        to:   (f64, f64),
+    },
+    /// Any other change of the object such as its size, shape or name
+    ObjectModified {
+        id: i64,
+    },
+    PropertyChanged {
+        target: PropertyTarget,
+        name:   String,
+        from:   Option<Property>,
+        to:     Option<Property>,
+    },
+    TilesetAdded {
+        tileset:   String,
+        first_gid: Option<i32>,
+    },
+    TilesetRemoved {
+        tileset:   String,
+        first_gid: Option<i32>,
+    },
+    TilesetMoved {
+        tileset: String,
+        from:    Option<i32>,
+        to:      Option<i32>,
+    },
+    TilesetModified {
+        tileset: String,
+    },
+}
+
+/// Semantic differences between two versions of a map. Serializes to a list
+/// of tagged changes, `Display` renders one line per change.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MapDiff {
+    pub changes: Vec<Change>,
+}
+
+impl MapDiff {
+    /// Layers and objects are matched by id, tilesets by name or source
+    pub fn new(old: &Map, new: &Map) -> Result<Self, Error> {
+        let mut changes = Vec::new();
+
+        let (old_size, new_size) = ((old.width(), old.height()), (new.width(), new.height()));
+        if old_size != new_size {
+            changes.push(Change::MapResized {
+                from: old_size,
+                to:   new_size,
+            });
+        }
+        diff_properties(
+            &mut changes,
+            PropertyTarget::Map,
+            old.properties(),
+            new.properties(),
+        );
+        diff_tilesets(&mut changes, old.tile_sets(), new.tile_sets());
+        diff_layers(&mut changes, old, new)?;
+        diff_objects(&mut changes, old, new);
+
+        Ok(Self { changes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn diff_tilesets(changes: &mut Vec<Change>, old: &[TilesetContainer], new: &[TilesetContainer]) {
+    for container in old {
+        let key = TilesetKey::of(container);
+        let other = match new.iter().find(|other| TilesetKey::of(other) == key) {
+            Some(other) => other,
+            None => {
+                changes.push(Change::TilesetRemoved {
+                    tileset:   key.to_string(),
+                    first_gid: container.first_gid(),
+                });
+                continue;
+            }
+        };
+
+        let modified = if other.first_gid() == container.first_gid() {
+            other != container
+        } else {
+            changes.push(Change::TilesetMoved {
+                tileset: key.to_string(),
+                from:    container.first_gid(),
+                to:      other.first_gid(),
+            });
+            let mut renumbered = container.clone();
+            if let Some(first_gid) = other.first_gid() {
+                renumbered.set_first_gid(first_gid);
+            }
+            *other != renumbered
+        };
+        if modified {
+            changes.push(Change::TilesetModified {
+                tileset: key.to_string(),
+            });
+        }
+    }
+    for container in new {
+        let key = TilesetKey::of(container);
+        if !old.iter().any(|other| TilesetKey::of(other) == key) {
+            changes.push(Change::TilesetAdded {
+                tileset:   key.to_string(),
+                first_gid: container.first_gid(),
+            });
+        }
+    }
+}
+
+fn diff_layers(changes: &mut Vec<Change>, old: &Map, new: &Map) -> Result<(), Error> {
+    let old_layers = flatten_layers(old.layers());
+    let new_layers = flatten_layers(new.layers());
+
+    for layer in &old_layers {
+        if !new_layers.iter().any(|other| other.id() == layer.id()) {
+            changes.push(Change::LayerRemoved {
+                id:   layer.id(),
+                name: layer.name().to_owned(),
+            });
+        }
+    }
+
+    for layer in &new_layers {
+        let previous = match old_layers.iter().find(|other| other.id() == layer.id()) {
+            Some(previous) => previous,
+            None => {
+                changes.push(Change::LayerAdded {
+                    id:   layer.id(),
+                    name: layer.name().to_owned(),
+                });
+                continue;
+            }
+        };
+
+        if previous.name() != layer.name() {
+            changes.push(Change::LayerRenamed {
+                id:   layer.id(),
+                from: previous.name().to_owned(),
+                to:   layer.name().to_owned(),
+            });
+        }
+        diff_properties(
+            changes,
+            PropertyTarget::Layer(layer.id()),
+            previous.properties(),
+            layer.properties(),
+        );
+
+        if let (Layer::TileLayer(previous), Layer::TileLayer(layer)) = (previous, layer) {
+            let renumbered = old.tile_sets() != new.tile_sets();
+            let same = |from: Gid, to: Gid| {
+                if renumbered {
+                    resolve_tile(old.tile_sets(), from) == resolve_tile(new.tile_sets(), to)
+                } else {
+                    from == to
+                }
+            };
+            let (before, after) = (previous.decode_grid()?, layer.decode_grid()?);
+            let left = before.x.min(after.x);
+            let top = before.y.min(after.y);
+            let right = (before.x + before.width).max(after.x + after.width);
+            let bottom = (before.y + before.height).max(after.y + after.height);
+
+            let mut tiles = Vec::new();
+            for y in top..bottom {
+                for x in left..right {
+                    let from = before.get(x, y).unwrap_or_default();
+                    let to = after.get(x, y).unwrap_or_default();
+                    if !same(from, to) {
+                        tiles.push(TileChange {
+                            x,
+                            y,
+                            from: from.0,
+                            to: to.0,
+                        });
+                    }
+                }
+            }
+            if !tiles.is_empty() {
+                changes.push(Change::TilesChanged {
+                    layer: layer.id,
+                    tiles,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Tileset and local id of the tile a gid shows together with its flags,
+/// the raw gid when no tileset holds it
+fn resolve_tile(tilesets: &[TilesetContainer], gid: Gid) -> (Option<(TilesetKey, u32)>, u32) {
+    match TilesetKey::resolve(tilesets, gid) {
+        Some(tile) => (Some(tile), gid.flags()),
+        None => (None, gid.0),
+    }
+}
+
+fn diff_objects(changes: &mut Vec<Change>, old: &Map, new: &Map) {
+    let old_objects = old.objects();
+    let new_objects = new.objects();
+
+    for &(layer, object) in &old_objects {
+        // Objects moved to another layer show up as removed and added
+        let kept = new_objects
+            .iter()
+            .any(|&(other_layer, other)| other.id() == object.id() && other_layer == layer);
+        if !kept {
+            changes.push(Change::ObjectRemoved {
+                layer,
+                id: object.id(),
+            });
+        }
+    }
+
+    for &(layer, object) in &new_objects {
+        let previous = match old_objects
+            .iter()
+            .find(|&&(other_layer, other)| other.id() == object.id() && other_layer == layer)
+        {
+            Some(&(_, previous)) => previous,
+            None => {
+                changes.push(Change::ObjectAdded {
+                    layer,
+                    id: object.id(),
+                });
+                continue;
+            }
+        };
+
+        let (from, to) = ((previous.x(), previous.y()), (object.x(), object.y()));
+        if from != to {
+            changes.push(Change::ObjectMoved {
+                id: object.id(),
+                from,
+                to,
+            });
+        }
+
+        // Compare everything else with position and properties taken out
+        let mut before = previous.clone();
+        let mut after = object.clone();
+        before.set_position(0.0, 0.0);
+        after.set_position(0.0, 0.0);
+        *before.properties_mut() = None;
+        *after.properties_mut() = None;
+        if before != after {
+            changes.push(Change::ObjectModified { id: object.id() });
+        }
+
+        diff_properties(
+            changes,
+            PropertyTarget::Object(object.id()),
+            previous.properties(),
+            object.properties(),
+        );
+    }
+}
+
+fn diff_properties(
+    changes: &mut Vec<Change>,
+    target: PropertyTarget,
+    old: Option<&[Property]>,
+    new: Option<&[Property]>,
+) {
+    let (old, new) = (old.unwrap_or_default(), new.unwrap_or_default());
+    let names: BTreeSet<_> = old.iter().chain(new).map(Property::name).collect();
+    for name in names {
+        let from = old.iter().find(|property| property.name() == name);
+        let to = new.iter().find(|property| property.name() == name);
+        if from != to {
+            changes.push(Change::PropertyChanged {
+                target,
+                name: name.to_owned(),
+                from: from.cloned(),
+                to: to.cloned(),
+            });
+        }
+    }
+}
+
+struct Target(PropertyTarget);
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            PropertyTarget::Map => write!(f, "map"),
+            PropertyTarget::Layer(id) => write!(f, "layer {}", id),
+            PropertyTarget::Object(id) => write!(f, "object {}", id),
+        }
+    }
+}
+
+struct Value<'a>(&'a Option<Property>);
+
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None => write!(f, "unset"),
+            Some(Property::Int(property)) => write!(f, "{}", property.value),
+            Some(Property::Bool(property)) => write!(f, "{}", property.value),
+            Some(Property::File(property)) => write!(f, "{}", property.value.display()),
+            Some(Property::Color(property)) => write!(f, "{}", property.value),
+            Some(Property::Float(property)) => write!(f, "{}", property.value),
+            Some(Property::String(property)) => write!(f, "{:?}", property.value),
+        }
+    }
+}
+
+fn tileset_gid(first_gid: &Option<i32>) -> String {
+    first_gid.map_or_else(|| "?".to_owned(), |first_gid| first_gid.to_string())
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::MapResized { from, to } => write!(
+                f,
+                "map resized from {}x{} to {}x{}",
+                from.0, from.1, to.0, to.1
+            ),
+            Change::LayerAdded { id, name } => write!(f, "layer {} {:?} added", id, name),
+            Change::LayerRemoved { id, name } => write!(f, "layer {} {:?} removed", id, name),
+            Change::LayerRenamed { id, from, to } => {
+                write!(f, "layer {} renamed from {:?} to {:?}", id, from, to)
+            }
+            Change::TilesChanged { layer, tiles } => {
+                write!(f, "layer {}: {} tiles changed", layer, tiles.len())?;
+                for tile in tiles {
+                    write!(
+                        f,
+                        "\n  ({}, {}): {} -> {}",
+                        tile.x, tile.y, tile.from, tile.to
+                    )?;
+                }
+                Ok(())
+            }
+            Change::ObjectAdded { layer, id } => {
+                write!(f, "object {} added to layer {}", id, layer)
+            }
+            Change::ObjectRemoved { layer, id } => {
+                write!(f, "object {} removed from layer {}", id, layer)
+            }
+            Change::ObjectMoved { id, from, to } => write!(
+                f,
+                "object {} moved from ({}, {}) to ({}, {})",
+                id, from.0, from.1, to.0, to.1
+            ),
+            Change::ObjectModified { id } => write!(f, "object {} modified", id),
+            Change::PropertyChanged {
+                target,
+                name,
+                from,
+                to,
+            } => write!(
+                f,
+                "{} property {:?} changed from {} to {}",
+                Target(*target),
+                name,
+                Value(from),
+                Value(to)
+            ),
+            Change::TilesetAdded { tileset, first_gid } => write!(
+                f,
+                "tileset {:?} added at gid {}",
+                tileset,
+                tileset_gid(first_gid)
+            ),
+            Change::TilesetRemoved { tileset, first_gid } => write!(
+                f,
+                "tileset {:?} removed from gid {}",
+                tileset,
+                tileset_gid(first_gid)
+            ),
+            Change::TilesetMoved { tileset, from, to } => write!(
+                f,
+                "tileset {:?} moved from gid {} to {}",
+                tileset,
+                tileset_gid(from),
+                tileset_gid(to)
+            ),
+            Change::TilesetModified { tileset } => write!(f, "tileset {:?} modified", tileset),
+        }
+    }
+}
+
+impl fmt::Display for MapDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::models::IntProperty;
+    use serde_json::json;
+
+    fn speed(value: i32) -> Property {
+        Property::Int(IntProperty {
+            name: "speed".to_owned(),
+            value,
+        })
+    }
+
+    fn test_map(edited: bool) -> Map {
+        let mut builder = MapBuilder::orthogonal(3, 2, 16, 16);
+        builder.add_tileset(
+            TilesetBuilder::new("tiles", 16, 16)
+                .with_image("tiles.png", 64, 16)
+                .build(),
+        );
+        let mut ground = builder.tile_layer("ground").with_tile(0, 0, Gid(1));
+        if edited {
+            ground = ground.with_tile(0, 0, Gid(2)).with_tile(2, 1, Gid(3));
+        }
+        builder.add_layer(ground.build());
+
+        let mut player = ObjectBuilder::point(8.0, 8.0).with_property(speed(1));
+        let mut crate_box = ObjectBuilder::rectangle(16.0, 0.0, 16.0, 16.0);
+        if edited {
+            player = ObjectBuilder::point(24.0, 8.0).with_property(speed(2));
+            crate_box = ObjectBuilder::rectangle(16.0, 0.0, 32.0, 16.0);
+        }
+        let mut objects = ObjectLayerBuilder::new(if edited { "entities" } else { "objects" })
+            .with_object(player.build())
+            .with_object(crate_box.build());
+        if !edited {
+            objects = objects.with_object(ObjectBuilder::point(0.0, 0.0).build());
+        }
+        builder.add_layer(objects.build());
+
+        if edited {
+            let decoration = builder.tile_layer("decoration").build();
+            builder.add_layer(decoration);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn reports_changes() {
+        let diff = MapDiff::new(&test_map(false), &test_map(true)).unwrap();
+        assert_eq!(
+            diff.to_string(),
+            [
+                "layer 1: 2 tiles changed",
+                "  (0, 0): 1 -> 2",
+                "  (2, 1): 0 -> 3",
+                "layer 2 renamed from \"objects\" to \"entities\"",
+                "layer 3 \"decoration\" added",
+                "object 3 removed from layer 2",
+                "object 1 moved from (8, 8) to (24, 8)",
+                "object 1 property \"speed\" changed from 1 to 2",
+                "object 2 modified",
+                "",
+            ]
+            .join("\n")
+        );
+
+        assert!(MapDiff::new(&test_map(true), &test_map(true))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_moved_tileset_edits() {
+        let moved = Change::TilesetMoved {
+            tileset: "tiles".to_owned(),
+            from:    Some(1),
+            to:      Some(5),
+        };
+        let modified = Change::TilesetModified {
+            tileset: "tiles".to_owned(),
+        };
+
+        let old = test_map(false);
+        let mut new = test_map(false);
+        new.insert_tileset_ref(0, "walls.json", 4).unwrap();
+        let changes = MapDiff::new(&old, &new).unwrap().changes;
+        assert!(changes.contains(&moved));
+        assert!(!changes.contains(&modified));
+
+        if let Some(TilesetContainer::Tileset(tileset)) = new.tile_sets_mut().get_mut(1) {
+            tileset.spacing = 2;
+        }
+        let changes = MapDiff::new(&old, &new).unwrap().changes;
+        assert!(changes.contains(&moved));
+        assert!(changes.contains(&modified));
+    }
+
+    #[test]
+    fn serializes_changes() {
+        let old = test_map(false);
+        let mut new = test_map(false);
+        new.insert_tileset_ref(0, "walls.json", 4).unwrap();
+        // Tile 0 of "tiles" keeps showing at (0, 0) under its new gid 5
+        if let Some(Layer::TileLayer(layer)) = new.layer_mut(1) {
+            layer.set_tile(1, 0, Gid(5)).unwrap();
+        }
+
+        let diff = MapDiff::new(&old, &new).unwrap();
+        assert_eq!(
+            serde_json::to_value(&diff).unwrap(),
+            json!({
+                "changes": [
+                    {
+                        "change": "tileset_moved",
+                        "tileset": "tiles",
+                        "from": 1,
+                        "to": 5
+                    },
+                    {
+                        "change": "tileset_added",
+                        "tileset": "walls.json",
+                        "first_gid": 1
+                    },
+                    {
+                        "change": "tiles_changed",
+                        "layer": 1,
+                        "tiles": [{ "x": 1, "y": 0, "from": 0, "to": 5 }]
+                    }
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,743 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::tme::editing::PropertyTarget;
+use crate::tme::editing::TilesetKey;
+use crate::tme::error::Error;
+use crate::tme::models::find_layer_siblings_mut;
+use crate::tme::models::flatten_layers;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::Property;
+use crate::tme::models::TilesetContainer;
+use crate::tme::tile_grid::TileGrid;
+
+/// Edit made on both sides that could not be resolved, the merged map keeps
+/// our version
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "conflict", rename_all = "snake_case")]
+pub enum Conflict {
+    MapSize,
+    Tilesets,
+    Tile {
+        layer:  i32,
+        x:      i32,
+        y:      i32,
+        base:   u32,
+        ours:   u32,
+        theirs: u32,
+    },
+    /// Layer deleted on one side and modified on the other
+    Layer {
+        id: i32,
+    },
+    LayerName {
+        id:     i32,
+        ours:   String,
+        theirs: String,
+    },
+    /// Object deleted on one side and modified on the other, or changed
+    /// differently on both
+    Object {
+        id: i64,
+    },
+    Property {
+        target: PropertyTarget,
+        name:   String,
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::MapSize => write!(f, "map size changed on both sides"),
+            Conflict::Tilesets => write!(f, "tilesets changed on both sides"),
+            Conflict::Tile {
+                layer,
+                x,
+                y,
+                base,
+                ours,
+                theirs,
+            } => write!(
+                f,
+                "layer {} tile ({}, {}) changed from {} to {} and {}",
+                layer, x, y, base, ours, theirs
+            ),
+            Conflict::Layer { id } => write!(
+                f,
+                "layer {} deleted on one side and modified on the other",
+                id
+            ),
+            Conflict::LayerName { id, ours, theirs } => {
+                write!(f, "layer {} renamed to {:?} and {:?}", id, ours, theirs)
+            }
+            Conflict::Object { id } => write!(f, "object {} changed on both sides", id),
+            Conflict::Property { target, name } => {
+                let target = match target {
+                    PropertyTarget::Map => "map".to_owned(),
+                    PropertyTarget::Layer(id) => format!("layer {}", id),
+                    PropertyTarget::Object(id) => format!("object {}", id),
+                };
+                write!(f, "{} property {:?} changed on both sides", target, name)
+            }
+        }
+    }
+}
+
+/// Three-way merge of two maps derived from a common base. Edits made on one
+/// side only are taken over, conflicting ones are recorded in `conflicts`.
+#[derive(Debug, Clone)]
+pub struct MapMerge {
+    pub map:       Map,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MapMerge {
+    /// Layers and objects are matched by id, tiles per cell and properties
+    /// per name. Layers and objects added on both sides with the same id keep
+    /// their id on our side and get a fresh one on theirs.
+    pub fn new(base: &Map, ours: &Map, theirs: &Map) -> Result<Self, Error> {
+        // Tiles of all sides are compared and merged with the gids of the
+        // tilesets the merged map ends up with
+        let tilesets = match three_way(base.tile_sets(), ours.tile_sets(), theirs.tile_sets()) {
+            Merged::Ours => Some(ours.tile_sets()),
+            Merged::Theirs => Some(theirs.tile_sets()),
+            Merged::Conflict => None,
+        };
+        let renumbered = match tilesets {
+            Some(tilesets) => {
+                let base = renumbered(base, tilesets)?;
+                let ours = renumbered(ours, tilesets)?;
+                let theirs = renumbered(theirs, tilesets)?;
+                base.zip(ours)
+                    .zip(theirs)
+                    .map(|((base, ours), theirs)| (base, ours, theirs))
+            }
+            None => None,
+        };
+        let tilesets_conflict = renumbered.is_none();
+        let (base, ours, theirs) = renumbered.unwrap_or((
+            Cow::Borrowed(base),
+            Cow::Borrowed(ours),
+            Cow::Borrowed(theirs),
+        ));
+        let (base, ours, theirs) = (&*base, &*ours, &*theirs);
+
+        let mut merge = Self {
+            map:       ours.clone(),
+            conflicts: Vec::new(),
+        };
+        let next_layer_id = ours.next_layer_id().max(theirs.next_layer_id());
+        let next_object_id = ours.next_object_id().max(theirs.next_object_id());
+        merge.map.set_next_layer_id(next_layer_id);
+        merge.map.set_next_object_id(next_object_id);
+
+        let size = |map: &Map| (map.width(), map.height());
+        match three_way(&size(base), &size(ours), &size(theirs)) {
+            Merged::Theirs => merge.map.resize(theirs.width(), theirs.height(), 0, 0)?,
+            Merged::Conflict => merge.conflicts.push(Conflict::MapSize),
+            Merged::Ours => {}
+        }
+        if tilesets_conflict {
+            merge.conflicts.push(Conflict::Tilesets);
+        }
+        let properties = merge.properties(
+            PropertyTarget::Map,
+            base.properties(),
+            ours.properties(),
+            theirs.properties(),
+        );
+        *merge.map.properties_mut() = properties;
+
+        let added_layers = merge.layers(base, ours, theirs)?;
+        merge.objects(base, ours, theirs, &added_layers);
+        Ok(merge)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// Merges layers and their tiles, returns the ids in `theirs` of the
+    /// layers taken over from there
+    fn layers(&mut self, base: &Map, ours: &Map, theirs: &Map) -> Result<HashSet<i32>, Error> {
+        for layer in flatten_layers(base.layers()) {
+            let id = layer.id();
+            let (mine, other) = (ours.layer(id), theirs.layer(id));
+            match (mine, other) {
+                (Some(mine), None) if mine == layer => {
+                    if let Some((siblings, index)) =
+                        find_layer_siblings_mut(self.map.layers_mut(), id)
+                    {
+                        siblings.remove(index);
+                    }
+                }
+                (Some(_), None) => self.conflicts.push(Conflict::Layer { id }),
+                (None, Some(other)) if other != layer => {
+                    self.conflicts.push(Conflict::Layer { id })
+                }
+                (Some(mine), Some(other)) => self.layer(layer, mine, other)?,
+                _ => {}
+            }
+        }
+
+        let base_ids: HashSet<_> = flatten_layers(base.layers())
+            .iter()
+            .map(|layer| layer.id())
+            .collect();
+        let mut added = HashSet::new();
+        self.add_layers(theirs.layers(), None, &base_ids, &mut added);
+        Ok(added)
+    }
+
+    /// Copies the layers missing in base from theirs, keeping their position
+    /// among the siblings
+    fn add_layers(
+        &mut self,
+        layers: &[Layer],
+        parent: Option<i32>,
+        base_ids: &HashSet<i32>,
+        added: &mut HashSet<i32>,
+    ) {
+        for (index, layer) in layers.iter().enumerate() {
+            if base_ids.contains(&layer.id()) {
+                if let Layer::GroupLayer(group) = layer {
+                    self.add_layers(&group.layers, Some(group.id), base_ids, added);
+                }
+                continue;
+            }
+
+            added.extend(
+                flatten_layers(std::slice::from_ref(layer))
+                    .iter()
+                    .map(|layer| layer.id()),
+            );
+            let layer = self.fresh_ids(layer.clone());
+            let siblings = match parent {
+                None => Some(self.map.layers_mut()),
+                Some(parent) => match self.map.layer_mut(parent) {
+                    Some(Layer::GroupLayer(group)) => Some(&mut group.layers),
+                    _ => None,
+                },
+            };
+            match siblings {
+                Some(siblings) => siblings.insert(index.min(siblings.len()), layer),
+                None => self.conflicts.push(Conflict::Layer {
+                    id: parent.unwrap_or_default(),
+                }),
+            }
+        }
+    }
+
+    fn layer(&mut self, base: &Layer, ours: &Layer, theirs: &Layer) -> Result<(), Error> {
+        let id = base.id();
+        match three_way(base.name(), ours.name(), theirs.name()) {
+            Merged::Theirs => {
+                if let Some(layer) = self.map.layer_mut(id) {
+                    layer.set_name(theirs.name().to_owned());
+                }
+            }
+            Merged::Conflict => self.conflicts.push(Conflict::LayerName {
+                id,
+                ours: ours.name().to_owned(),
+                theirs: theirs.name().to_owned(),
+            }),
+            Merged::Ours => {}
+        }
+
+        let properties = self.properties(
+            PropertyTarget::Layer(id),
+            base.properties(),
+            ours.properties(),
+            theirs.properties(),
+        );
+        if let Some(layer) = self.map.layer_mut(id) {
+            *layer.properties_mut() = properties;
+        }
+
+        let (base, ours, theirs) = match (base, ours, theirs) {
+            (Layer::TileLayer(base), Layer::TileLayer(ours), Layer::TileLayer(theirs)) => (
+                base.decode_grid()?,
+                ours.decode_grid()?,
+                theirs.decode_grid()?,
+            ),
+            _ => return Ok(()),
+        };
+        let left = base.x.min(ours.x).min(theirs.x);
+        let top = base.y.min(ours.y).min(theirs.y);
+        let right = [&base, &ours, &theirs]
+            .iter()
+            .map(|grid| grid.x + grid.width)
+            .max()
+            .unwrap_or(0);
+        let bottom = [&base, &ours, &theirs]
+            .iter()
+            .map(|grid| grid.y + grid.height)
+            .max()
+            .unwrap_or(0);
+
+        let mut updates = Vec::new();
+        for y in top..bottom {
+            for x in left..right {
+                let cell = |grid: &TileGrid| grid.get(x, y).unwrap_or_default();
+                let (old, mine, other) = (cell(&base), cell(&ours), cell(&theirs));
+                match three_way(&old, &mine, &other) {
+                    Merged::Theirs => updates.push((x, y, other)),
+                    Merged::Conflict => self.conflicts.push(Conflict::Tile {
+                        layer: id,
+                        x,
+                        y,
+                        base: old.0,
+                        ours: mine.0,
+                        theirs: other.0,
+                    }),
+                    Merged::Ours => {}
+                }
+            }
+        }
+        // Written in one pass keeping the encoding and compression of ours
+        if let Some(Layer::TileLayer(layer)) = self.map.layer_mut(id) {
+            layer.set_tiles(updates)?;
+        }
+        Ok(())
+    }
+
+    fn objects(&mut self, base: &Map, ours: &Map, theirs: &Map, added_layers: &HashSet<i32>) {
+        let base_objects = base.objects();
+        for &(layer, object) in &base_objects {
+            let id = object.id();
+            let mine = ours.object(id);
+            let other = theirs.object(id);
+            match (mine, other) {
+                (Some((_, mine)), None) if mine == object => {
+                    self.remove_object(id);
+                }
+                (Some(_), None) => self.conflicts.push(Conflict::Object { id }),
+                (None, Some((_, other))) if other != object => {
+                    self.conflicts.push(Conflict::Object { id })
+                }
+                (Some((mine_layer, mine)), Some((other_layer, other))) => {
+                    self.object(object, mine, other);
+                    if other_layer != layer && mine_layer == layer {
+                        self.move_object(id, other_layer);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let base_ids: HashSet<_> = base_objects.iter().map(|(_, object)| object.id()).collect();
+        for (layer, object) in theirs.objects() {
+            if base_ids.contains(&object.id()) || added_layers.contains(&layer) {
+                continue;
+            }
+            let mut object = object.clone();
+            if self.map.object(object.id()).is_some() {
+                object.set_id(self.map.allocate_object_id());
+            }
+            match self.map.layer_mut(layer) {
+                Some(Layer::ObjectGroupLayer(group)) => group.objects.push(object),
+                _ => self.conflicts.push(Conflict::Layer { id: layer }),
+            }
+        }
+    }
+
+    fn object(&mut self, base: &Object, ours: &Object, theirs: &Object) {
+        let id = base.id();
+        let properties = self.properties(
+            PropertyTarget::Object(id),
+            base.properties(),
+            ours.properties(),
+            theirs.properties(),
+        );
+
+        let mut merged = match three_way(
+            &without_properties(base),
+            &without_properties(ours),
+            &without_properties(theirs),
+        ) {
+            Merged::Theirs => theirs.clone(),
+            Merged::Conflict => {
+                self.conflicts.push(Conflict::Object { id });
+                ours.clone()
+            }
+            Merged::Ours => ours.clone(),
+        };
+        *merged.properties_mut() = properties;
+        if let Some(object) = self.map.object_mut(id) {
+            *object = merged;
+        }
+    }
+
+    fn properties(
+        &mut self,
+        target: PropertyTarget,
+        base: Option<&[Property]>,
+        ours: Option<&[Property]>,
+        theirs: Option<&[Property]>,
+    ) -> Option<Vec<Property>> {
+        let find = |properties: Option<&[Property]>, name: &str| {
+            properties?
+                .iter()
+                .find(|property| property.name() == name)
+                .cloned()
+        };
+
+        let mut names: Vec<&str> = Vec::new();
+        for property in ours.into_iter().chain(theirs).chain(base).flatten() {
+            if !names.contains(&property.name()) {
+                names.push(property.name());
+            }
+        }
+
+        let mut merged = Vec::new();
+        for name in names {
+            let (old, mine, other) = (find(base, name), find(ours, name), find(theirs, name));
+            let property = match three_way(&old, &mine, &other) {
+                Merged::Theirs => other,
+                Merged::Conflict => {
+                    self.conflicts.push(Conflict::Property {
+                        target,
+                        name: name.to_owned(),
+                    });
+                    mine
+                }
+                Merged::Ours => mine,
+            };
+            merged.extend(property);
+        }
+
+        if merged.is_empty() && ours.is_none() {
+            None
+        } else {
+            Some(merged)
+        }
+    }
+
+    /// Gives a layer taken over from theirs and its content ids not used on
+    /// our side
+    fn fresh_ids(&mut self, mut layer: Layer) -> Layer {
+        if self.map.layer(layer.id()).is_some() {
+            layer.set_id(self.map.allocate_layer_id());
+        }
+        match &mut layer {
+            Layer::ObjectGroupLayer(group) => {
+                for object in group.objects.iter_mut() {
+                    if self.map.object(object.id()).is_some() {
+                        object.set_id(self.map.allocate_object_id());
+                    }
+                }
+            }
+            Layer::GroupLayer(group) => {
+                let children = std::mem::take(&mut group.layers);
+                group.layers = children
+                    .into_iter()
+                    .map(|child| self.fresh_ids(child))
+                    .collect();
+            }
+            _ => {}
+        }
+        layer
+    }
+
+    fn remove_object(&mut self, id: i64) -> Option<Object> {
+        let layer = self.map.object(id)?.0;
+        match self.map.layer_mut(layer)? {
+            Layer::ObjectGroupLayer(group) => {
+                let index = group.objects.iter().position(|object| object.id() == id)?;
+                Some(group.objects.remove(index))
+            }
+            _ => None,
+        }
+    }
+
+    fn move_object(&mut self, id: i64, layer: i32) {
+        if !matches!(self.map.layer(layer), Some(Layer::ObjectGroupLayer(_))) {
+            return;
+        }
+        if let Some(object) = self.remove_object(id) {
+            if let Some(Layer::ObjectGroupLayer(group)) = self.map.layer_mut(layer) {
+                group.objects.push(object);
+            }
+        }
+    }
+}
+
+/// `map` with the gids moved to the first gids the same tilesets have in
+/// `tilesets`, `None` when a used tileset is missing there
+fn renumbered<'a>(
+    map: &'a Map,
+    tilesets: &[TilesetContainer],
+) -> Result<Option<Cow<'a, Map>>, Error> {
+    if map.tile_sets() == tilesets {
+        return Ok(Some(Cow::Borrowed(map)));
+    }
+    let mut map = map.clone();
+    let old = map.tile_sets().to_vec();
+    let result = map.remap_gids(|gid| match TilesetKey::resolve(&old, gid) {
+        Some((key, local)) => match key.first_gid_in(tilesets) {
+            Some(first_gid) => Ok(gid.with_id(first_gid + local)),
+            None => Error::TilesetNotFound(key.to_string()).fail(),
+        },
+        None => Ok(gid),
+    });
+    match result {
+        Ok(()) => {
+            *map.tile_sets_mut() = tilesets.to_vec();
+            Ok(Some(Cow::Owned(map)))
+        }
+        Err(Error::TilesetNotFound(_)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+enum Merged {
+    Ours,
+    Theirs,
+    Conflict,
+}
+
+fn three_way<T: PartialEq + ?Sized>(base: &T, ours: &T, theirs: &T) -> Merged {
+    if theirs == base || ours == theirs {
+        Merged::Ours
+    } else if ours == base {
+        Merged::Theirs
+    } else {
+        Merged::Conflict
+    }
+}
+
+fn without_properties(object: &Object) -> Object {
+    let mut object = object.clone();
+    *object.properties_mut() = None;
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::models::Compression;
+    use crate::tme::models::DataSource;
+    use crate::tme::models::Encoding;
+    use crate::tme::models::StringProperty;
+
+    fn note(name: &str, value: &str) -> Property {
+        Property::String(StringProperty {
+            name:  name.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    fn base_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(2, 2, 16, 16);
+        let ground = builder.tile_layer("ground").build();
+        builder.add_layer(ground);
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::point(0.0, 0.0).build())
+            .with_object(ObjectBuilder::point(8.0, 8.0).build())
+            .build();
+        builder.add_layer(objects);
+        builder.build()
+    }
+
+    fn tile(map: &Map, x: i32, y: i32) -> Gid {
+        match map.layer(1) {
+            Some(Layer::TileLayer(layer)) => layer.tile(x, y).unwrap().unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn paint(map: &mut Map, x: i32, y: i32, gid: u32) {
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(1) {
+            layer.set_tile(x, y, Gid(gid)).unwrap();
+        }
+    }
+
+    #[test]
+    fn merges_tiles_per_cell() {
+        let base = base_map();
+        let mut ours = base_map();
+        let mut theirs = base_map();
+        paint(&mut ours, 0, 0, 1);
+        paint(&mut ours, 1, 1, 4);
+        paint(&mut theirs, 0, 0, 3);
+        paint(&mut theirs, 1, 0, 2);
+        paint(&mut theirs, 1, 1, 4);
+
+        let merge = MapMerge::new(&base, &ours, &theirs).unwrap();
+        assert_eq!(tile(&merge.map, 0, 0), Gid(1));
+        assert_eq!(tile(&merge.map, 1, 0), Gid(2));
+        assert_eq!(tile(&merge.map, 1, 1), Gid(4));
+        assert_eq!(
+            merge.conflicts,
+            vec![Conflict::Tile {
+                layer:  1,
+                x:      0,
+                y:      0,
+                base:   0,
+                ours:   1,
+                theirs: 3,
+            }]
+        );
+        assert_eq!(
+            merge.conflicts[0].to_string(),
+            "layer 1 tile (0, 0) changed from 0 to 1 and 3"
+        );
+    }
+
+    #[test]
+    fn merges_objects_and_properties() {
+        let base = base_map();
+        let mut ours = base_map();
+        let mut theirs = base_map();
+
+        *ours.object_mut(1).unwrap().properties_mut() = Some(vec![note("owner", "ours")]);
+        theirs.object_mut(1).unwrap().set_position(4.0, 4.0);
+        ours.add_layer(
+            ObjectLayerBuilder::new("ours")
+                .with_object(ObjectBuilder::point(1.0, 1.0).build())
+                .build(),
+        );
+        if let Some(Layer::ObjectGroupLayer(group)) = theirs.layer_mut(2) {
+            group.objects.retain(|object| object.id() != 2);
+            group.objects.push(ObjectBuilder::point(2.0, 2.0).build());
+        }
+        let id = theirs.allocate_object_id();
+        if let Some(Layer::ObjectGroupLayer(group)) = theirs.layer_mut(2) {
+            group.objects.last_mut().unwrap().set_id(id);
+        }
+        theirs.layer_mut(1).unwrap().set_name("floor".to_owned());
+        *ours.properties_mut() = Some(vec![note("weather", "rain")]);
+        *theirs.properties_mut() = Some(vec![note("weather", "snow"), note("music", "calm")]);
+
+        let merge = MapMerge::new(&base, &ours, &theirs).unwrap();
+        let map = &merge.map;
+
+        let moved = map.object(1).unwrap().1;
+        assert_eq!((moved.x(), moved.y()), (4.0, 4.0));
+        assert_eq!(moved.properties(), Some(&[note("owner", "ours")][..]));
+        assert!(map.object(2).is_none());
+        // Both sides added object 3, theirs gets the next free id
+        assert_eq!(map.object(3).map(|(layer, _)| layer), Some(3));
+        assert_eq!(map.object(4).map(|(_, object)| object.x()), Some(2.0));
+        assert_eq!(map.next_object_id(), 5);
+        assert_eq!(map.layer(1).map(Layer::name), Some("floor"));
+
+        assert_eq!(
+            map.properties(),
+            Some(&[note("weather", "rain"), note("music", "calm")][..])
+        );
+        assert_eq!(
+            merge.conflicts,
+            vec![Conflict::Property {
+                target: PropertyTarget::Map,
+                name:   "weather".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_deleted_and_modified_objects() {
+        let base = base_map();
+        let mut ours = base_map();
+        let mut theirs = base_map();
+        if let Some(Layer::ObjectGroupLayer(group)) = ours.layer_mut(2) {
+            group.objects.retain(|object| object.id() != 2);
+        }
+        theirs.object_mut(2).unwrap().set_position(9.0, 9.0);
+        theirs.add_layer(ObjectLayerBuilder::new("theirs").build());
+
+        let merge = MapMerge::new(&base, &ours, &theirs).unwrap();
+        assert_eq!(merge.conflicts, vec![Conflict::Object { id: 2 }]);
+        assert!(merge.map.object(2).is_none());
+        assert_eq!(merge.map.layer(3).map(Layer::name), Some("theirs"));
+        assert!(!merge.is_clean());
+    }
+
+    #[test]
+    fn keeps_encoding_of_infinite_layers() {
+        let mut base = base_map();
+        base.set_infinite(true).unwrap();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        if let Some(Layer::TileLayer(layer)) = ours.layer_mut(1) {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Zlib))
+                .unwrap();
+        }
+        paint(&mut ours, 1, 1, 4);
+        paint(&mut theirs, 40, -3, 2);
+
+        let merge = MapMerge::new(&base, &ours, &theirs).unwrap();
+        assert!(merge.is_clean());
+        assert_eq!(tile(&merge.map, 1, 1), Gid(4));
+        assert_eq!(tile(&merge.map, 40, -3), Gid(2));
+        match merge.map.layer(1) {
+            Some(Layer::TileLayer(layer)) => {
+                assert_eq!(
+                    (layer.encoding, layer.compression),
+                    (Some(Encoding::Base64), Some(Compression::Zlib))
+                );
+                let chunks = layer.chunks.as_ref().unwrap();
+                assert_eq!(chunks.len(), 2);
+                assert!(chunks
+                    .iter()
+                    .all(|chunk| matches!(chunk.data, DataSource::Encoded(_))));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn renumbers_gids_for_merged_tilesets() {
+        let mut base = base_map();
+        base.add_tileset(
+            TilesetBuilder::new("walls", 16, 16)
+                .with_image("walls.png", 64, 16)
+                .build(),
+        );
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+
+        paint(&mut ours, 0, 0, 2);
+        ours.add_layer(
+            ObjectLayerBuilder::new("props")
+                .with_object(ObjectBuilder::tile(Gid(3), 0.0, 0.0, 16.0, 16.0).build())
+                .build(),
+        );
+        let props = TilesetBuilder::new("props", 16, 16)
+            .with_image("props.png", 32, 16)
+            .build();
+        theirs.insert_tileset(0, props).unwrap();
+        paint(&mut theirs, 1, 0, 1);
+
+        // walls moved from 1 to 3 on their side
+        let merge = MapMerge::new(&base, &ours, &theirs).unwrap();
+        assert!(merge.is_clean());
+        assert_eq!(merge.map.tile_sets().len(), 2);
+        assert_eq!(tile(&merge.map, 0, 0), Gid(4));
+        assert_eq!(tile(&merge.map, 1, 0), Gid(1));
+        match merge.map.object(3) {
+            Some((_, Object::General(tile_object))) => assert_eq!(tile_object.gid, 5),
+            _ => unreachable!(),
+        }
+
+        let mut theirs = base.clone();
+        theirs.remove_tileset(0).unwrap();
+        let merge = MapMerge::new(&base, &ours, &theirs).unwrap();
+        assert_eq!(merge.conflicts, vec![Conflict::Tilesets]);
+        assert_eq!(merge.map.tile_sets().len(), 1);
+        assert_eq!(tile(&merge.map, 0, 0), Gid(2));
+    }
+}
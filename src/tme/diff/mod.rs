@@ -0,0 +1,5 @@
+pub mod map_diff;
+pub mod merge;
+
+pub use map_diff::*;
+pub use merge::*;
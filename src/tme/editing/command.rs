@@ -0,0 +1,539 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::tme::error::Error;
+use crate::tme::gid::Gid;
+use crate::tme::models::find_layer_siblings_mut;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::Property;
+use crate::tme::models::TileLayer;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum PropertyTarget {
+    Map,
+    Layer(i32),
+    Object(i64),
+}
+
+/// A reversible edit of a map. Layers and objects are addressed by id, so
+/// commands stay valid while other edits move things around.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    PaintTiles {
+        layer: i32,
+        tiles: Vec<(i32, i32, Gid)>,
+    },
+    FillRect {
+        layer:  i32,
+        x:      i32,
+        y:      i32,
+        width:  i32,
+        height: i32,
+        gid:    Gid,
+    },
+    /// Objects with id `0` get one from the map counter, `index` defaults
+    /// to the end of the layer
+    AddObject {
+        layer:  i32,
+        index:  Option<usize>,
+        object: Object,
+    },
+    DeleteObject {
+        object: i64,
+    },
+    MoveObject {
+        object: i64,
+        x:      f64,
+        y:      f64,
+    },
+    /// Moves a layer to `index` among its siblings
+    MoveLayer {
+        layer: i32,
+        index: usize,
+    },
+    RenameLayer {
+        layer: i32,
+        name:  String,
+    },
+    /// Adds, replaces or with `None` removes a property
+    SetProperty {
+        target: PropertyTarget,
+        name:   String,
+        value:  Option<Property>,
+    },
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    /// Applies the command and returns the command reverting it
+    pub fn apply(self, map: &mut Map) -> Result<Command, Error> {
+        match self {
+            Command::PaintTiles { layer, tiles } => {
+                let target = tile_layer_mut(map, layer)?;
+                let old = target.set_tiles(tiles.iter().copied())?;
+                let mut previous: Vec<_> = tiles
+                    .into_iter()
+                    .zip(old)
+                    .filter_map(|((x, y, _), old)| Some((x, y, old?)))
+                    .collect();
+                // Reverted back to front so the oldest value of a tile
+                // painted twice wins
+                previous.reverse();
+                Ok(Command::PaintTiles {
+                    layer,
+                    tiles: previous,
+                })
+            }
+            Command::FillRect { .. } => self.into_paint().apply(map),
+            Command::AddObject {
+                layer,
+                index,
+                mut object,
+            } => {
+                if !matches!(map.layer(layer), Some(Layer::ObjectGroupLayer(_))) {
+                    return wrong_layer(map, layer);
+                }
+                if object.id() == 0 {
+                    let id = map.allocate_object_id();
+                    object.set_id(id);
+                }
+
+                let id = object.id();
+                if let Some(Layer::ObjectGroupLayer(group)) = map.layer_mut(layer) {
+                    let index = index
+                        .unwrap_or(group.objects.len())
+                        .min(group.objects.len());
+                    group.objects.insert(index, object);
+                }
+                Ok(Command::DeleteObject { object: id })
+            }
+            Command::DeleteObject { object } => {
+                let layer = map.object(object).ok_or(Error::ObjectNotFound(object))?.0;
+                match map.layer_mut(layer) {
+                    Some(Layer::ObjectGroupLayer(group)) => {
+                        let index = group
+                            .objects
+                            .iter()
+                            .position(|candidate| candidate.id() == object)
+                            .ok_or(Error::ObjectNotFound(object))?;
+                        Ok(Command::AddObject {
+                            layer,
+                            index: Some(index),
+                            object: group.objects.remove(index),
+                        })
+                    }
+                    _ => Error::ObjectNotFound(object).fail(),
+                }
+            }
+            Command::MoveObject { object, x, y } => {
+                let target = map
+                    .object_mut(object)
+                    .ok_or(Error::ObjectNotFound(object))?;
+                let previous = Command::MoveObject {
+                    object,
+                    x: target.x(),
+                    y: target.y(),
+                };
+                target.set_position(x, y);
+                Ok(previous)
+            }
+            Command::MoveLayer { layer, index } => {
+                let (siblings, current) = find_layer_siblings_mut(map.layers_mut(), layer)
+                    .ok_or_else(|| Error::LayerNotFound(layer.to_string()))?;
+                let moved = siblings.remove(current);
+                siblings.insert(index.min(siblings.len()), moved);
+                Ok(Command::MoveLayer {
+                    layer,
+                    index: current,
+                })
+            }
+            Command::RenameLayer { layer, name } => {
+                let target = map
+                    .layer_mut(layer)
+                    .ok_or_else(|| Error::LayerNotFound(layer.to_string()))?;
+                Ok(Command::RenameLayer {
+                    layer,
+                    name: target.set_name(name),
+                })
+            }
+            Command::SetProperty {
+                target,
+                name,
+                value,
+            } => {
+                let properties = match target {
+                    PropertyTarget::Map => map.properties_mut(),
+                    PropertyTarget::Layer(layer) => map
+                        .layer_mut(layer)
+                        .ok_or_else(|| Error::LayerNotFound(layer.to_string()))?
+                        .properties_mut(),
+                    PropertyTarget::Object(object) => map
+                        .object_mut(object)
+                        .ok_or(Error::ObjectNotFound(object))?
+                        .properties_mut(),
+                };
+
+                let list = properties.get_or_insert_with(Vec::new);
+                let position = list.iter().position(|property| property.name() == name);
+                let previous = match (position, value) {
+                    (Some(index), Some(value)) => Some(std::mem::replace(&mut list[index], value)),
+                    (Some(index), None) => Some(list.remove(index)),
+                    (None, Some(value)) => {
+                        list.push(value);
+                        None
+                    }
+                    (None, None) => None,
+                };
+                if list.is_empty() {
+                    *properties = None;
+                }
+
+                Ok(Command::SetProperty {
+                    target,
+                    name,
+                    value: previous,
+                })
+            }
+            Command::Batch(commands) => {
+                let mut inverses = Vec::with_capacity(commands.len());
+                for command in commands {
+                    match command.apply(map) {
+                        Ok(inverse) => inverses.push(inverse),
+                        Err(error) => {
+                            // Leave the map as it was before the batch
+                            for inverse in inverses.into_iter().rev() {
+                                inverse.apply(map)?;
+                            }
+                            return Err(error);
+                        }
+                    }
+                }
+                inverses.reverse();
+                Ok(Command::Batch(inverses))
+            }
+        }
+    }
+
+    /// Whether `next` edits the same thing and can be folded into this
+    /// command, like the steps of a brush stroke or of dragging an object
+    pub fn can_merge(&self, next: &Command) -> bool {
+        match (self, next) {
+            (
+                Command::PaintTiles { layer, .. } | Command::FillRect { layer, .. },
+                Command::PaintTiles { layer: next, .. } | Command::FillRect { layer: next, .. },
+            ) => layer == next,
+            (Command::MoveObject { object, .. }, Command::MoveObject { object: next, .. }) => {
+                object == next
+            }
+            (Command::RenameLayer { layer, .. }, Command::RenameLayer { layer: next, .. }) => {
+                layer == next
+            }
+            (
+                Command::SetProperty { target, name, .. },
+                Command::SetProperty {
+                    target: next_target,
+                    name: next_name,
+                    ..
+                },
+            ) => target == next_target && name == next_name,
+            _ => false,
+        }
+    }
+
+    /// One command with the effect of applying `self` and then `next`
+    pub fn merge(self, next: Command) -> Command {
+        if !self.can_merge(&next) {
+            let mut commands = match self {
+                Command::Batch(commands) => commands,
+                command => vec![command],
+            };
+            commands.push(next);
+            return Command::Batch(commands);
+        }
+
+        match (self.into_paint(), next.into_paint()) {
+            (
+                Command::PaintTiles { layer, mut tiles },
+                Command::PaintTiles {
+                    tiles: next_tiles, ..
+                },
+            ) => {
+                tiles.extend(next_tiles);
+                Command::PaintTiles { layer, tiles }
+            }
+            // Moves, renames and property changes only keep the last value
+            (_, next) => next,
+        }
+    }
+
+    fn into_paint(self) -> Command {
+        match self {
+            Command::FillRect {
+                layer,
+                x,
+                y,
+                width,
+                height,
+                gid,
+            } => Command::PaintTiles {
+                layer,
+                tiles: (y..y + height)
+                    .flat_map(|y| (x..x + width).map(move |x| (x, y, gid)))
+                    .collect(),
+            },
+            command => command,
+        }
+    }
+}
+
+fn tile_layer_mut(map: &mut Map, layer: i32) -> Result<&mut TileLayer, Error> {
+    match map.layer_mut(layer) {
+        Some(Layer::TileLayer(target)) => Ok(target),
+        Some(_) => Error::WrongLayerType(layer).fail(),
+        None => Error::LayerNotFound(layer.to_string()).fail(),
+    }
+}
+
+fn wrong_layer<T>(map: &Map, layer: i32) -> Result<T, Error> {
+    match map.layer(layer) {
+        Some(_) => Error::WrongLayerType(layer).fail(),
+        None => Error::LayerNotFound(layer.to_string()).fail(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::models::BoolProperty;
+    use crate::tme::models::Compression;
+    use crate::tme::models::DataSource;
+    use crate::tme::models::Encoding;
+    use crate::tme::models::StringProperty;
+
+    fn test_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(4, 4, 16, 16);
+        let ground = builder.tile_layer("ground").build();
+        builder.add_layer(ground);
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::point(1.0, 2.0).build())
+            .build();
+        builder.add_layer(objects);
+        builder.build()
+    }
+
+    fn tile(map: &Map, x: i32, y: i32) -> Gid {
+        match map.layer(1) {
+            Some(Layer::TileLayer(layer)) => layer.tile(x, y).unwrap().unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn reverts_tile_edits() {
+        let mut map = test_map();
+        let fill = Command::FillRect {
+            layer:  1,
+            x:      1,
+            y:      1,
+            width:  2,
+            height: 2,
+            gid:    Gid(3),
+        };
+        let undo_fill = fill.apply(&mut map).unwrap();
+        let paint = Command::PaintTiles {
+            layer: 1,
+            tiles: vec![(1, 1, Gid(5)), (1, 1, Gid(6)), (9, 9, Gid(6))],
+        };
+        let undo_paint = paint.apply(&mut map).unwrap();
+        assert_eq!(tile(&map, 1, 1), Gid(6));
+        assert_eq!(tile(&map, 2, 2), Gid(3));
+
+        undo_paint.apply(&mut map).unwrap();
+        assert_eq!(tile(&map, 1, 1), Gid(3));
+        undo_fill.apply(&mut map).unwrap();
+        assert_eq!(tile(&map, 1, 1), Gid(0));
+        assert_eq!(tile(&map, 2, 2), Gid(0));
+
+        let error = Command::PaintTiles {
+            layer: 2,
+            tiles: vec![],
+        }
+        .apply(&mut map);
+        assert!(matches!(error, Err(Error::WrongLayerType(2))));
+    }
+
+    #[test]
+    fn reverts_paint_of_encoded_infinite_layer() {
+        let mut map = test_map();
+        map.set_infinite(true).unwrap();
+        let layer = tile_layer_mut(&mut map, 1).unwrap();
+        layer.set_tile(2, 2, Gid(1)).unwrap();
+        layer
+            .encode(Encoding::Base64, Some(Compression::Zlib))
+            .unwrap();
+        let before = serde_json::to_value(&map).unwrap();
+
+        let paint = Command::PaintTiles {
+            layer: 1,
+            tiles: vec![(3, 3, Gid(2)), (-40, 20, Gid(3)), (-40, 20, Gid(4))],
+        };
+        let undo = paint.apply(&mut map).unwrap();
+        let layer = tile_layer_mut(&mut map, 1).unwrap();
+        assert_eq!(
+            (layer.encoding, layer.compression),
+            (Some(Encoding::Base64), Some(Compression::Zlib))
+        );
+        assert!(layer
+            .chunks
+            .iter()
+            .flatten()
+            .all(|chunk| matches!(chunk.data, DataSource::Encoded(_))));
+        assert_eq!(layer.chunks.as_ref().map(Vec::len), Some(2));
+        assert_eq!((layer.start_x, layer.start_y), (Some(-48), Some(0)));
+        assert_eq!(layer.tile(-40, 20).unwrap(), Some(Gid(4)));
+
+        undo.apply(&mut map).unwrap();
+        assert_eq!(serde_json::to_value(&map).unwrap(), before);
+    }
+
+    #[test]
+    fn reverts_object_and_layer_edits() {
+        let mut map = test_map();
+        let undo_add = Command::AddObject {
+            layer:  2,
+            index:  Some(0),
+            object: ObjectBuilder::rectangle(0.0, 0.0, 4.0, 4.0).build(),
+        }
+        .apply(&mut map)
+        .unwrap();
+        assert_eq!(undo_add, Command::DeleteObject { object: 2 });
+        assert_eq!(map.object(2).map(|(layer, _)| layer), Some(2));
+
+        let undo_move = Command::MoveObject {
+            object: 1,
+            x:      8.0,
+            y:      8.0,
+        }
+        .apply(&mut map)
+        .unwrap();
+        let undo_delete = Command::DeleteObject { object: 1 }.apply(&mut map).unwrap();
+        assert!(map.object(1).is_none());
+        undo_delete.apply(&mut map).unwrap();
+        undo_move.apply(&mut map).unwrap();
+        assert_eq!(
+            map.object(1).map(|(_, object)| (object.x(), object.y())),
+            Some((1.0, 2.0))
+        );
+
+        let undo_batch = Command::Batch(vec![
+            Command::MoveLayer { layer: 2, index: 0 },
+            Command::RenameLayer {
+                layer: 1,
+                name:  "floor".to_owned(),
+            },
+        ])
+        .apply(&mut map)
+        .unwrap();
+        let names: Vec<_> = map.layers().iter().map(Layer::name).collect();
+        assert_eq!(names, vec!["objects", "floor"]);
+        undo_batch.apply(&mut map).unwrap();
+        let names: Vec<_> = map.layers().iter().map(Layer::name).collect();
+        assert_eq!(names, vec!["ground", "objects"]);
+    }
+
+    #[test]
+    fn reverts_property_edits() {
+        let mut map = test_map();
+        let set = |value| Command::SetProperty {
+            target: PropertyTarget::Layer(1),
+            name: "solid".to_owned(),
+            value,
+        };
+
+        let undo_add = set(Some(Property::Bool(BoolProperty {
+            name:  "solid".to_owned(),
+            value: true,
+        })))
+        .apply(&mut map)
+        .unwrap();
+        assert_eq!(undo_add, set(None));
+
+        let undo_replace = set(Some(Property::String(StringProperty {
+            name:  "solid".to_owned(),
+            value: "yes".to_owned(),
+        })))
+        .apply(&mut map)
+        .unwrap();
+        assert_eq!(
+            map.layer(1)
+                .and_then(Layer::properties)
+                .map(|properties| properties.len()),
+            Some(1)
+        );
+
+        undo_replace.apply(&mut map).unwrap();
+        undo_add.apply(&mut map).unwrap();
+        assert!(map.layer(1).unwrap().properties().is_none());
+    }
+
+    #[test]
+    fn merges_commands() {
+        let first = Command::FillRect {
+            layer:  1,
+            x:      0,
+            y:      0,
+            width:  2,
+            height: 1,
+            gid:    Gid(1),
+        };
+        let second = Command::PaintTiles {
+            layer: 1,
+            tiles: vec![(3, 3, Gid(2))],
+        };
+        assert!(first.can_merge(&second));
+        assert_eq!(
+            first.merge(second),
+            Command::PaintTiles {
+                layer: 1,
+                tiles: vec![(0, 0, Gid(1)), (1, 0, Gid(1)), (3, 3, Gid(2))],
+            }
+        );
+
+        let first = Command::MoveObject {
+            object: 1,
+            x:      1.0,
+            y:      1.0,
+        };
+        let second = Command::MoveObject {
+            object: 1,
+            x:      2.0,
+            y:      2.0,
+        };
+        assert_eq!(
+            first.merge(second),
+            Command::MoveObject {
+                object: 1,
+                x:      2.0,
+                y:      2.0,
+            }
+        );
+
+        let first = Command::DeleteObject { object: 1 };
+        let second = Command::DeleteObject { object: 2 };
+        assert!(!first.can_merge(&second));
+        assert_eq!(
+            first.merge(second),
+            Command::Batch(vec![
+                Command::DeleteObject { object: 1 },
+                Command::DeleteObject { object: 2 },
+            ])
+        );
+    }
+}
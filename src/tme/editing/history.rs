@@ -0,0 +1,181 @@
+use crate::tme::editing::Command;
+use crate::tme::error::Error;
+use crate::tme::models::Map;
+
+/// Undo and redo stacks of the commands executed on a map
+#[derive(Debug, Default)]
+pub struct History {
+    undo:  Vec<Command>,
+    redo:  Vec<Command>,
+    limit: Option<usize>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only keeps the last `limit` undo steps
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    pub fn execute(&mut self, map: &mut Map, command: Command) -> Result<(), Error> {
+        let inverse = command.apply(map)?;
+        self.push_undo(inverse);
+        self.redo.clear();
+        Ok(())
+    }
+
+    /// Executes the command and folds it into the last undo step when both
+    /// edit the same thing, so a whole brush stroke is undone at once
+    pub fn execute_merge(&mut self, map: &mut Map, command: Command) -> Result<(), Error> {
+        let inverse = command.apply(map)?;
+        self.redo.clear();
+        match self.undo.pop() {
+            // The new inverse has to run first to restore the older state
+            Some(last) if inverse.can_merge(&last) => self.undo.push(inverse.merge(last)),
+            Some(last) => {
+                self.undo.push(last);
+                self.push_undo(inverse);
+            }
+            None => self.push_undo(inverse),
+        }
+        Ok(())
+    }
+
+    /// Returns `false` when there is nothing to undo
+    pub fn undo(&mut self, map: &mut Map) -> Result<bool, Error> {
+        match self.undo.pop() {
+            Some(command) => {
+                self.redo.push(command.apply(map)?);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Returns `false` when there is nothing to redo
+    pub fn redo(&mut self, map: &mut Map) -> Result<bool, Error> {
+        match self.redo.pop() {
+            Some(command) => {
+                let inverse = command.apply(map)?;
+                self.push_undo(inverse);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn push_undo(&mut self, inverse: Command) {
+        self.undo.push(inverse);
+        if let Some(limit) = self.limit {
+            if self.undo.len() > limit {
+                let excess = self.undo.len() - limit;
+                self.undo.drain(..excess);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::models::Layer;
+
+    fn test_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(4, 4, 16, 16);
+        let ground = builder.tile_layer("ground").build();
+        builder.add_layer(ground);
+        builder.build()
+    }
+
+    fn tiles(map: &Map) -> Vec<u32> {
+        match map.layer(1) {
+            Some(Layer::TileLayer(layer)) => (0..4)
+                .map(|x| layer.tile(x, 0).unwrap().unwrap().0)
+                .collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn paint(x: i32, gid: u32) -> Command {
+        Command::PaintTiles {
+            layer: 1,
+            tiles: vec![(x, 0, Gid(gid))],
+        }
+    }
+
+    #[test]
+    fn undoes_and_redoes() {
+        let mut map = test_map();
+        let mut history = History::new();
+        assert!(!history.undo(&mut map).unwrap());
+
+        history.execute(&mut map, paint(0, 1)).unwrap();
+        history.execute(&mut map, paint(0, 2)).unwrap();
+        assert_eq!(tiles(&map), vec![2, 0, 0, 0]);
+
+        assert!(history.undo(&mut map).unwrap());
+        assert_eq!(tiles(&map), vec![1, 0, 0, 0]);
+        assert!(history.can_redo());
+        assert!(history.redo(&mut map).unwrap());
+        assert_eq!(tiles(&map), vec![2, 0, 0, 0]);
+
+        history.undo(&mut map).unwrap();
+        history.execute(&mut map, paint(1, 3)).unwrap();
+        assert!(!history.can_redo());
+        history.undo(&mut map).unwrap();
+        history.undo(&mut map).unwrap();
+        assert_eq!(tiles(&map), vec![0, 0, 0, 0]);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn merges_brush_strokes() {
+        let mut map = test_map();
+        let mut history = History::new();
+        history.execute(&mut map, paint(3, 9)).unwrap();
+        // A stroke starts with a plain execute and continues with merges
+        history.execute(&mut map, paint(0, 1)).unwrap();
+        for (x, gid) in [(1, 1), (0, 2), (2, 1)].iter() {
+            history.execute_merge(&mut map, paint(*x, *gid)).unwrap();
+        }
+        assert_eq!(tiles(&map), vec![2, 1, 1, 9]);
+
+        history.undo(&mut map).unwrap();
+        assert_eq!(tiles(&map), vec![0, 0, 0, 9]);
+        history.redo(&mut map).unwrap();
+        assert_eq!(tiles(&map), vec![2, 1, 1, 9]);
+    }
+
+    #[test]
+    fn limits_undo_steps() {
+        let mut map = test_map();
+        let mut history = History::with_limit(2);
+        for x in 0..4 {
+            history.execute(&mut map, paint(x, 1)).unwrap();
+        }
+        while history.undo(&mut map).unwrap() {}
+        assert_eq!(tiles(&map), vec![1, 1, 0, 0]);
+    }
+}
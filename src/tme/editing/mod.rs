@@ -0,0 +1,9 @@
+pub mod command;
+pub mod history;
+pub mod resize;
+pub mod stamp;
+pub mod tilesets;
+
+pub use command::*;
+pub use history::*;
+pub use stamp::*;
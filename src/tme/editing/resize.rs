@@ -0,0 +1,293 @@
+use crate::tme::error::Error;
+use crate::tme::models::map::with_map;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Orientation;
+use crate::tme::render::MapGeometry;
+use crate::tme::tile_grid::TileGrid;
+
+impl Map {
+    /// Changes the size of the map and moves its content by `offset_x` and
+    /// `offset_y` tiles, like the resize dialog of Tiled. Tiles moved out of
+    /// a finite map are lost, objects are translated but never removed and
+    /// image layers stay where they are.
+    pub fn resize(
+        &mut self,
+        width: i32,
+        height: i32,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Result<(), Error> {
+        let bounds = if self.infinite() {
+            None
+        } else {
+            Some(TileGrid::new(0, 0, width, height))
+        };
+        let pixel_offset = self.object_offset(offset_x, offset_y);
+        transform_layers(
+            self.layers_mut(),
+            bounds.as_ref(),
+            (offset_x, offset_y),
+            pixel_offset,
+        )?;
+        with_map!(self, map => {
+            map.width = width;
+            map.height = height;
+        });
+        Ok(())
+    }
+
+    /// Keeps the `width` × `height` tiles at `(x, y)` and moves them to the
+    /// origin
+    pub fn crop(&mut self, x: i32, y: i32, width: i32, height: i32) -> Result<(), Error> {
+        self.resize(width, height, -x, -y)
+    }
+
+    /// Moves all tiles and objects by `dx` and `dy` tiles, keeping the size
+    pub fn shift(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        self.resize(self.width(), self.height(), dx, dy)
+    }
+
+    /// Converts all tile layers between plain data and chunks. A map made
+    /// finite is cropped to the tiles in use, moving them to the origin.
+    pub fn set_infinite(&mut self, infinite: bool) -> Result<(), Error> {
+        if infinite == self.infinite() {
+            return Ok(());
+        }
+
+        with_map!(self, map => map.infinite = infinite);
+        if infinite {
+            return transform_layers(self.layers_mut(), None, (0, 0), (0.0, 0.0));
+        }
+
+        match used_bounds(self.layers())? {
+            Some(used) => self.crop(used.x, used.y, used.width, used.height),
+            None => self.resize(self.width(), self.height(), 0, 0),
+        }
+    }
+
+    /// Translation of object positions for an offset in tiles. Objects of
+    /// isometric maps use a projection where a tile is `tile_height` wide.
+    pub(crate) fn object_offset(&self, dx: i32, dy: i32) -> (f64, f64) {
+        if self.orientation() == Orientation::Isometric {
+            let tile_height = self.tile_height() as f64;
+            return (dx as f64 * tile_height, dy as f64 * tile_height);
+        }
+
+        let geometry = MapGeometry::from_map(self);
+        let origin = geometry.tile_to_pixel(0, 0);
+        let moved = geometry.tile_to_pixel(dx, dy);
+        (moved.0 - origin.0, moved.1 - origin.1)
+    }
+}
+
+fn transform_layers(
+    layers: &mut [Layer],
+    bounds: Option<&TileGrid>,
+    offset: (i32, i32),
+    pixel_offset: (f64, f64),
+) -> Result<(), Error> {
+    for layer in layers {
+        match layer {
+            Layer::TileLayer(layer) => {
+                let grid = layer.decode_grid()?;
+                let mut moved = match bounds {
+                    Some(bounds) => bounds.clone(),
+                    None => TileGrid::new(
+                        grid.x + offset.0,
+                        grid.y + offset.1,
+                        grid.width,
+                        grid.height,
+                    ),
+                };
+                for (x, y, gid) in grid.cells() {
+                    moved.set(x + offset.0, y + offset.1, gid);
+                }
+                layer.store_grid(&moved, bounds.is_none())?;
+            }
+            Layer::ObjectGroupLayer(group) => {
+                for object in group.objects.iter_mut() {
+                    let (x, y) = (object.x(), object.y());
+                    object.set_position(x + pixel_offset.0, y + pixel_offset.1);
+                }
+            }
+            Layer::GroupLayer(group) => {
+                transform_layers(&mut group.layers, bounds, offset, pixel_offset)?
+            }
+            Layer::ImageLayer(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Smallest rectangle holding every tile of the layers
+fn used_bounds(layers: &[Layer]) -> Result<Option<TileGrid>, Error> {
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for layer in layers {
+        let used = match layer {
+            Layer::TileLayer(layer) => {
+                let grid = layer.decode_grid()?;
+                grid.cells()
+                    .filter(|(_, _, gid)| !gid.is_empty())
+                    .fold(None, |bounds, (x, y, _)| {
+                        extend(bounds, (x, y, x + 1, y + 1))
+                    })
+            }
+            Layer::GroupLayer(group) => used_bounds(&group.layers)?
+                .map(|used| (used.x, used.y, used.x + used.width, used.y + used.height)),
+            _ => None,
+        };
+        if let Some(used) = used {
+            bounds = extend(bounds, used);
+        }
+    }
+    Ok(bounds
+        .map(|(left, top, right, bottom)| TileGrid::new(left, top, right - left, bottom - top)))
+}
+
+fn extend(
+    bounds: Option<(i32, i32, i32, i32)>,
+    (left, top, right, bottom): (i32, i32, i32, i32),
+) -> Option<(i32, i32, i32, i32)> {
+    Some(match bounds {
+        Some(bounds) => (
+            bounds.0.min(left),
+            bounds.1.min(top),
+            bounds.2.max(right),
+            bounds.3.max(bottom),
+        ),
+        None => (left, top, right, bottom),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::models::Compression;
+    use crate::tme::models::DataSource;
+    use crate::tme::models::Encoding;
+    use crate::tme::models::TileLayer;
+
+    fn test_map(builder: MapBuilder) -> Map {
+        let mut builder = builder;
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(0, 0, Gid(1))
+            .with_tile(2, 1, Gid(2))
+            .build();
+        builder.add_layer(ground);
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::point(8.0, 8.0).build())
+            .build();
+        builder.add_layer(objects);
+        builder.build()
+    }
+
+    fn ground_mut(map: &mut Map) -> &mut TileLayer {
+        match map.layer_mut(1) {
+            Some(Layer::TileLayer(layer)) => layer,
+            _ => unreachable!(),
+        }
+    }
+
+    fn ground(map: &Map) -> &TileLayer {
+        match map.layer(1) {
+            Some(Layer::TileLayer(layer)) => layer,
+            _ => unreachable!(),
+        }
+    }
+
+    fn point(map: &Map) -> (f64, f64) {
+        let object = map.object(1).unwrap().1;
+        (object.x(), object.y())
+    }
+
+    #[test]
+    fn resizes_finite_map() {
+        let mut map = test_map(MapBuilder::orthogonal(3, 2, 16, 16));
+        map.resize(4, 3, 1, 1).unwrap();
+        assert_eq!((map.width(), map.height()), (4, 3));
+        let layer = ground(&map);
+        assert_eq!((layer.width, layer.height), (4, 3));
+        assert_eq!(layer.tile(1, 1).unwrap(), Some(Gid(1)));
+        assert_eq!(layer.tile(3, 2).unwrap(), Some(Gid(2)));
+        assert_eq!(point(&map), (24.0, 24.0));
+
+        map.crop(2, 1, 2, 2).unwrap();
+        assert_eq!((map.width(), map.height()), (2, 2));
+        assert_eq!(ground(&map).data, DataSource::Raw(vec![0, 0, 0, 2]));
+        assert_eq!(point(&map), (-8.0, 8.0));
+
+        map.shift(-1, 0).unwrap();
+        assert_eq!(ground(&map).tile(0, 1).unwrap(), Some(Gid(2)));
+        assert_eq!(ground(&map).tile(1, 1).unwrap(), Some(Gid(0)));
+    }
+
+    #[test]
+    fn shifts_isometric_objects_by_tile_height() {
+        let mut map = test_map(MapBuilder::isometric(3, 2, 32, 16));
+        map.shift(1, 2).unwrap();
+        assert_eq!(point(&map), (24.0, 40.0));
+    }
+
+    #[test]
+    fn converts_between_finite_and_infinite() {
+        let mut map = test_map(MapBuilder::orthogonal(3, 2, 16, 16));
+        map.set_infinite(true).unwrap();
+        assert!(map.infinite());
+        assert_eq!(ground(&map).chunks.as_ref().map(Vec::len), Some(1));
+
+        map.shift(-20, 0).unwrap();
+        let layer = ground(&map);
+        assert_eq!(layer.start_x, Some(-32));
+        assert_eq!(layer.tile(-20, 0).unwrap(), Some(Gid(1)));
+        assert_eq!(layer.tile(-18, 1).unwrap(), Some(Gid(2)));
+
+        map.set_infinite(false).unwrap();
+        assert!(!map.infinite());
+        assert_eq!((map.width(), map.height()), (3, 2));
+        let layer = ground(&map);
+        assert_eq!(layer.chunks, None);
+        assert_eq!(layer.tile(0, 0).unwrap(), Some(Gid(1)));
+        assert_eq!(layer.tile(2, 1).unwrap(), Some(Gid(2)));
+        assert_eq!(point(&map), (8.0, 8.0));
+    }
+
+    #[test]
+    fn keeps_encoding_and_compression() {
+        let mut map = test_map(MapBuilder::orthogonal(3, 2, 16, 16));
+        ground_mut(&mut map)
+            .encode(Encoding::Base64, Some(Compression::Zstd))
+            .unwrap();
+
+        map.resize(4, 3, 1, 1).unwrap();
+        map.set_infinite(true).unwrap();
+        map.shift(-20, 0).unwrap();
+        let layer = ground(&map);
+        assert_eq!(
+            (layer.encoding, layer.compression),
+            (Some(Encoding::Base64), Some(Compression::Zstd))
+        );
+        assert!(layer
+            .chunks
+            .iter()
+            .flatten()
+            .all(|chunk| matches!(chunk.data, DataSource::Encoded(_))));
+        assert_eq!(layer.tile(-19, 1).unwrap(), Some(Gid(1)));
+
+        map.set_infinite(false).unwrap();
+        let layer = ground(&map);
+        assert_eq!(
+            (layer.encoding, layer.compression),
+            (Some(Encoding::Base64), Some(Compression::Zstd))
+        );
+        assert!(matches!(layer.data, DataSource::Encoded(_)));
+        assert_eq!(layer.tile(0, 0).unwrap(), Some(Gid(1)));
+        assert_eq!(layer.tile(2, 1).unwrap(), Some(Gid(2)));
+    }
+}
@@ -0,0 +1,494 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::tme::builder::ObjectLayerBuilder;
+use crate::tme::builder::TileLayerBuilder;
+use crate::tme::error::Error;
+use crate::tme::gid::Gid;
+use crate::tme::models::flatten_layers;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::Orientation;
+use crate::tme::models::TilesetContainer;
+use crate::tme::render::MapGeometry;
+use crate::tme::tile_grid::TileGrid;
+
+/// Identifies a tileset across maps: embedded tilesets by name, external
+/// ones by their source path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TilesetKey {
+    Name(String),
+    Source(PathBuf),
+}
+
+impl TilesetKey {
+    pub fn of(container: &TilesetContainer) -> Self {
+        match container {
+            TilesetContainer::Tileset(tileset) => TilesetKey::Name(tileset.name.clone()),
+            TilesetContainer::TilesetRef(reference) => TilesetKey::Source(reference.source.clone()),
+        }
+    }
+
+    /// Key of the tileset among `tilesets` holding `gid` and the local id of
+    /// the tile, `None` for empty gids and gids below every tileset
+    pub fn resolve(tilesets: &[TilesetContainer], gid: Gid) -> Option<(Self, u32)> {
+        if gid.is_empty() {
+            return None;
+        }
+        let (first_gid, container) = tilesets
+            .iter()
+            .filter_map(|container| Some((container.first_gid()? as u32, container)))
+            .filter(|&(first_gid, _)| first_gid <= gid.id())
+            .max_by_key(|&(first_gid, _)| first_gid)?;
+        Some((Self::of(container), gid.id() - first_gid))
+    }
+
+    /// First gid of the tileset with this key among `tilesets`
+    pub fn first_gid_in(&self, tilesets: &[TilesetContainer]) -> Option<u32> {
+        tilesets
+            .iter()
+            .find(|container| Self::of(container) == *self)
+            .and_then(TilesetContainer::first_gid)
+            .map(|first_gid| first_gid as u32)
+    }
+}
+
+impl fmt::Display for TilesetKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilesetKey::Name(name) => write!(f, "{}", name),
+            TilesetKey::Source(source) => write!(f, "{}", source.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampTileset {
+    pub key:       TilesetKey,
+    pub first_gid: u32,
+}
+
+/// Tiles of a layer with the same name, the grid starts at the origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampTileLayer {
+    pub name:  String,
+    pub tiles: TileGrid,
+}
+
+/// Objects of a layer with the same name, positioned relative to the stamp
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampObjectLayer {
+    pub name:    String,
+    pub objects: Vec<Object>,
+}
+
+/// Rectangular piece of a map that can be placed into other maps. Gids keep
+/// the values of the source map and are remapped through `tilesets` when
+/// pasting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamp {
+    pub width:         i32,
+    pub height:        i32,
+    pub tilesets:      Vec<StampTileset>,
+    pub tile_layers:   Vec<StampTileLayer>,
+    pub object_layers: Vec<StampObjectLayer>,
+}
+
+impl Stamp {
+    /// Copies the tiles of all tile layers in the `width` × `height` region
+    /// at `(x, y)` together with the objects positioned inside of it
+    pub fn copy(map: &Map, x: i32, y: i32, width: i32, height: i32) -> Result<Self, Error> {
+        let mut tilesets: Vec<_> = map
+            .tile_sets()
+            .iter()
+            .filter_map(|container| {
+                Some(StampTileset {
+                    key:       TilesetKey::of(container),
+                    first_gid: container.first_gid()? as u32,
+                })
+            })
+            .collect();
+        tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+        let region = ObjectRegion::new(map, x, y, width, height);
+        let mut tile_layers = Vec::new();
+        let mut object_layers = Vec::new();
+        for layer in flatten_layers(map.layers()) {
+            match layer {
+                Layer::TileLayer(layer) => {
+                    let source = layer.decode_grid()?;
+                    let mut tiles = TileGrid::new(0, 0, width, height);
+                    for (tile_x, tile_y, _) in tiles.clone().cells() {
+                        if let Some(gid) = source.get(x + tile_x, y + tile_y) {
+                            tiles.set(tile_x, tile_y, gid);
+                        }
+                    }
+                    if tiles.tiles.iter().any(|&raw| !Gid::from(raw).is_empty()) {
+                        tile_layers.push(StampTileLayer {
+                            name: layer.name.clone(),
+                            tiles,
+                        });
+                    }
+                }
+                Layer::ObjectGroupLayer(group) => {
+                    let objects: Vec<_> = group
+                        .objects
+                        .iter()
+                        .filter(|object| region.contains(object))
+                        .map(|object| {
+                            let mut object = object.clone();
+                            object.set_position(object.x() - region.x, object.y() - region.y);
+                            object
+                        })
+                        .collect();
+                    if !objects.is_empty() {
+                        object_layers.push(StampObjectLayer {
+                            name: group.name.clone(),
+                            objects,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            tilesets,
+            tile_layers,
+            object_layers,
+        })
+    }
+
+    /// Like `copy` but also clears the tiles and removes the objects of the
+    /// region from the map
+    pub fn cut(map: &mut Map, x: i32, y: i32, width: i32, height: i32) -> Result<Self, Error> {
+        let stamp = Self::copy(map, x, y, width, height)?;
+        let region = ObjectRegion::new(map, x, y, width, height);
+        clear_region(map.layers_mut(), &region)?;
+        Ok(stamp)
+    }
+
+    /// Places the stamp with its top-left corner at `(x, y)`. Empty tiles of
+    /// the stamp keep the tiles of the map, layers missing in the map are
+    /// created and pasted objects get fresh ids. Fails without touching the
+    /// map when a used tileset is missing from it.
+    pub fn paste(&self, map: &mut Map, x: i32, y: i32) -> Result<(), Error> {
+        let targets: Vec<_> = self
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                map.tile_sets()
+                    .iter()
+                    .find(|container| TilesetKey::of(container) == tileset.key)
+                    .and_then(TilesetContainer::first_gid)
+                    .map(|first_gid| first_gid as u32)
+            })
+            .collect();
+        let remap = |gid: Gid| -> Result<Gid, Error> {
+            if gid.is_empty() {
+                return Ok(gid);
+            }
+            let index = match self
+                .tilesets
+                .iter()
+                .rposition(|tileset| tileset.first_gid <= gid.id())
+            {
+                Some(index) => index,
+                None => return Ok(gid),
+            };
+            match targets[index] {
+                Some(first_gid) => {
+                    Ok(gid.with_id(gid.id() - self.tilesets[index].first_gid + first_gid))
+                }
+                None => Error::TilesetNotFound(self.tilesets[index].key.to_string()).fail(),
+            }
+        };
+
+        let mut tile_layers = Vec::with_capacity(self.tile_layers.len());
+        for layer in &self.tile_layers {
+            let mut tiles = Vec::new();
+            for (tile_x, tile_y, gid) in layer.tiles.cells() {
+                if !gid.is_empty() {
+                    tiles.push((x + tile_x, y + tile_y, remap(gid)?));
+                }
+            }
+            tile_layers.push((&layer.name, tiles));
+        }
+
+        let (offset_x, offset_y) = map.object_offset(x, y);
+        let mut object_layers = Vec::with_capacity(self.object_layers.len());
+        for layer in &self.object_layers {
+            let mut objects = Vec::with_capacity(layer.objects.len());
+            for object in &layer.objects {
+                let mut object = object.clone();
+                object.set_position(object.x() + offset_x, object.y() + offset_y);
+                if let Object::General(tile_object) = &mut object {
+                    tile_object.gid = remap(Gid(tile_object.gid as u32))?.0 as i64;
+                }
+                objects.push(object);
+            }
+            object_layers.push((&layer.name, objects));
+        }
+
+        for (name, tiles) in tile_layers {
+            let id = tile_layer_id(map, name)?;
+            if let Some(Layer::TileLayer(layer)) = map.layer_mut(id) {
+                layer.set_tiles(tiles)?;
+            }
+        }
+        for (name, objects) in object_layers {
+            let id = object_layer_id(map, name);
+            for mut object in objects {
+                object.set_id(map.allocate_object_id());
+                if let Some(Layer::ObjectGroupLayer(group)) = map.layer_mut(id) {
+                    group.objects.push(object);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Region of a map in tiles, holding the objects positioned on one of its
+/// tiles
+struct ObjectRegion {
+    geometry: MapGeometry,
+    /// Object position of the top-left corner
+    x:        f64,
+    y:        f64,
+    tiles:    TileGrid,
+}
+
+impl ObjectRegion {
+    fn new(map: &Map, x: i32, y: i32, width: i32, height: i32) -> Self {
+        let (left, top) = map.object_offset(x, y);
+        Self {
+            geometry: MapGeometry::from_map(map),
+            x:        left,
+            y:        top,
+            tiles:    TileGrid::new(x, y, width, height),
+        }
+    }
+
+    fn contains(&self, object: &Object) -> bool {
+        let (x, y) = match self.geometry.orientation {
+            // Objects of isometric maps use a projection where a tile is
+            // `tile_height` wide
+            Orientation::Isometric => {
+                let tile_height = self.geometry.tile_height.max(1) as f64;
+                (
+                    (object.x() / tile_height).floor() as i32,
+                    (object.y() / tile_height).floor() as i32,
+                )
+            }
+            _ => self.geometry.pixel_to_tile(object.x(), object.y()),
+        };
+        self.tiles.contains(x, y)
+    }
+}
+
+fn clear_region(layers: &mut [Layer], region: &ObjectRegion) -> Result<(), Error> {
+    for layer in layers {
+        match layer {
+            Layer::TileLayer(layer) => {
+                let cleared = region.tiles.cells().map(|(x, y, _)| (x, y, Gid::EMPTY));
+                layer.set_tiles(cleared)?;
+            }
+            Layer::ObjectGroupLayer(group) => {
+                group.objects.retain(|object| !region.contains(object));
+            }
+            Layer::GroupLayer(group) => clear_region(&mut group.layers, region)?,
+            Layer::ImageLayer(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn tile_layer_id(map: &mut Map, name: &str) -> Result<i32, Error> {
+    let existing = flatten_layers(map.layers())
+        .into_iter()
+        .find(|layer| matches!(layer, Layer::TileLayer(_)) && layer.name() == name)
+        .map(Layer::id);
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+    let mut layer = TileLayerBuilder::new(name, map.width(), map.height()).build();
+    if let (Layer::TileLayer(tiles), true) = (&mut layer, map.infinite()) {
+        tiles.store_grid(&TileGrid::new(0, 0, 0, 0), true)?;
+    }
+    Ok(map.add_layer(layer))
+}
+
+fn object_layer_id(map: &mut Map, name: &str) -> i32 {
+    let existing = flatten_layers(map.layers())
+        .into_iter()
+        .find(|layer| matches!(layer, Layer::ObjectGroupLayer(_)) && layer.name() == name)
+        .map(Layer::id);
+    existing.unwrap_or_else(|| map.add_layer(ObjectLayerBuilder::new(name).build()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::gid::FLIPPED_HORIZONTALLY_FLAG;
+    use crate::tme::models::Compression;
+    use crate::tme::models::DataSource;
+    use crate::tme::models::Encoding;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+
+    fn source_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(4, 4, 16, 16);
+        builder.add_tileset(
+            TilesetBuilder::new("walls", 16, 16)
+                .with_image("walls.png", 160, 16)
+                .build(),
+        );
+        builder.add_tileset(
+            TilesetBuilder::new("props", 16, 16)
+                .with_image("props.png", 160, 16)
+                .build(),
+        );
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(1, 1, Gid(2))
+            .with_tile(2, 2, Gid::new(13, FLIPPED_HORIZONTALLY_FLAG))
+            .with_tile(3, 3, Gid(4))
+            .build();
+        builder.add_layer(ground);
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::tile(Gid(12), 20.0, 24.0, 16.0, 16.0).build())
+            .with_object(ObjectBuilder::point(60.0, 60.0).build())
+            .build();
+        builder.add_layer(objects);
+        builder.build()
+    }
+
+    fn target_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(4, 4, 16, 16);
+        builder.add_tileset(
+            TilesetBuilder::new("props", 16, 16)
+                .with_image("props.png", 80, 16)
+                .build(),
+        );
+        builder.add_tileset(
+            TilesetBuilder::new("walls", 16, 16)
+                .with_image("walls.png", 160, 16)
+                .build(),
+        );
+        let mut map = builder.build();
+        map.add_layer(ObjectLayerBuilder::new("objects").build());
+        map.add_layer(
+            ObjectLayerBuilder::new("extra")
+                .with_object(ObjectBuilder::point(0.0, 0.0).build())
+                .build(),
+        );
+        map
+    }
+
+    #[test]
+    fn copies_region() {
+        let map = source_map();
+        let stamp = Stamp::copy(&map, 1, 1, 2, 2).unwrap();
+        assert_eq!(stamp.tilesets.len(), 2);
+        assert_eq!(stamp.tile_layers.len(), 1);
+        assert_eq!(stamp.tile_layers[0].tiles.get(0, 0), Some(Gid(2)));
+        assert_eq!(stamp.tile_layers[0].tiles.get(1, 1).map(Gid::id), Some(13));
+        assert_eq!(stamp.object_layers[0].objects.len(), 1);
+        assert_eq!(stamp.object_layers[0].objects[0].x(), 4.0);
+    }
+
+    #[test]
+    fn cuts_region() {
+        let mut map = source_map();
+        Stamp::cut(&mut map, 1, 1, 2, 2).unwrap();
+        let ground = map.find_tile_layer("ground").unwrap();
+        assert_eq!(ground.tile(1, 1).unwrap(), Some(Gid::EMPTY));
+        assert_eq!(ground.tile(3, 3).unwrap(), Some(Gid(4)));
+        assert!(map.object(1).is_none());
+        assert!(map.object(2).is_some());
+    }
+
+    #[test]
+    fn pastes_with_remapped_gids() {
+        let stamp = Stamp::copy(&source_map(), 1, 1, 2, 2).unwrap();
+        let mut map = target_map();
+        stamp.paste(&mut map, 2, 0).unwrap();
+
+        // walls moved from 1 to 6 and props from 11 to 1
+        let ground = map.find_tile_layer("ground").unwrap();
+        assert_eq!(ground.tile(2, 0).unwrap(), Some(Gid(7)));
+        let flipped = ground.tile(3, 1).unwrap().unwrap();
+        assert_eq!((flipped.id(), flipped.flipped_horizontally()), (3, true));
+
+        let (layer, object) = map.object(2).unwrap();
+        assert_eq!(map.layer(layer).map(Layer::name), Some("objects"));
+        assert_eq!((object.x(), object.y()), (36.0, 8.0));
+        match object {
+            Object::General(tile_object) => assert_eq!(tile_object.gid, 2),
+            _ => unreachable!(),
+        }
+
+        stamp.paste(&mut map, 0, 2).unwrap();
+        assert_eq!(map.object(3).map(|(_, object)| object.x()), Some(4.0));
+    }
+
+    #[test]
+    fn fails_on_missing_tileset() {
+        let stamp = Stamp::copy(&source_map(), 1, 1, 2, 2).unwrap();
+        let mut map = MapBuilder::orthogonal(4, 4, 16, 16).build();
+        let result = stamp.paste(&mut map, 0, 0);
+        assert!(matches!(result, Err(Error::TilesetNotFound(_))));
+        assert!(map.layers().is_empty());
+    }
+
+    #[test]
+    fn keeps_encoding_of_target_layers() {
+        let mut map = source_map();
+        map.set_infinite(true).unwrap();
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(1) {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Zstd))
+                .unwrap();
+        }
+        let stamp = Stamp::cut(&mut map, 1, 1, 2, 2).unwrap();
+        stamp.paste(&mut map, -20, 0).unwrap();
+
+        let ground = map.find_tile_layer("ground").unwrap();
+        assert_eq!(
+            (ground.encoding, ground.compression),
+            (Some(Encoding::Base64), Some(Compression::Zstd))
+        );
+        let chunks = ground.chunks.as_ref().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks
+            .iter()
+            .all(|chunk| matches!(chunk.data, DataSource::Encoded(_))));
+        assert_eq!(ground.tile(1, 1).unwrap(), Some(Gid::EMPTY));
+        assert_eq!(ground.tile(-20, 0).unwrap(), Some(Gid(2)));
+    }
+
+    #[test]
+    fn finds_objects_on_staggered_tiles() {
+        let mut builder = MapBuilder::staggered(4, 4, 32, 16, StaggerAxis::Y, StaggerIndex::Odd);
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::point(40.0, 14.0).build())
+            .with_object(ObjectBuilder::point(4.0, 14.0).build())
+            .build();
+        builder.add_layer(objects);
+        let map = builder.build();
+
+        // The first point lies on the shifted tile (0, 1), the second one on
+        // (-1, 1) to the left of the region
+        let stamp = Stamp::copy(&map, 0, 0, 1, 2).unwrap();
+        assert_eq!(stamp.object_layers[0].objects.len(), 1);
+        assert_eq!(stamp.object_layers[0].objects[0].x(), 40.0);
+    }
+}
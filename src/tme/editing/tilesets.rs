@@ -0,0 +1,464 @@
+use std::path::PathBuf;
+
+use crate::tme::error::Error;
+use crate::tme::gid::Gid;
+use crate::tme::models::DataSource;
+use crate::tme::models::Encoding;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+use crate::tme::models::TilesetRef;
+
+impl Map {
+    /// Rewrites every gid of the tile layers, chunks and tile objects.
+    /// `remap` is only called for non-empty gids, which are all checked
+    /// before the first one is written so an error leaves the map untouched.
+    pub fn remap_gids<F>(&mut self, remap: F) -> Result<(), Error>
+    where
+        F: Fn(Gid) -> Result<Gid, Error>,
+    {
+        visit_gids(self.layers(), &mut |gid| remap(gid).map(drop))?;
+        rewrite_gids(self.layers_mut(), &remap)
+    }
+
+    /// Inserts an embedded tileset at `index` of the tileset list and
+    /// returns its first gid, the gids of later tilesets move up
+    pub fn insert_tileset(&mut self, index: usize, tileset: Tileset) -> Result<i32, Error> {
+        let span = tileset.tile_count as u32;
+        self.insert_tileset_container(index, TilesetContainer::Tileset(tileset), span)
+    }
+
+    /// Inserts a reference to an external tileset holding `tile_count` tiles
+    pub fn insert_tileset_ref<P: Into<PathBuf>>(
+        &mut self,
+        index: usize,
+        source: P,
+        tile_count: usize,
+    ) -> Result<i32, Error> {
+        let reference = TilesetContainer::TilesetRef(TilesetRef {
+            first_gid: 0,
+            source:    source.into(),
+        });
+        self.insert_tileset_container(index, reference, tile_count as u32)
+    }
+
+    /// Removes an unused tileset, fails with `TileLost` when a tile of it is
+    /// still placed somewhere
+    pub fn remove_tileset(&mut self, index: usize) -> Result<TilesetContainer, Error> {
+        let ranges = self.tileset_ranges()?;
+        check_index(&ranges, index)?;
+
+        let mut spans = spans(&ranges);
+        spans.remove(index);
+        let first_gids = self.retarget_tilesets(&ranges, &spans, |old, local| match old {
+            old if old == index => None,
+            old if old > index => Some((old - 1, local)),
+            old => Some((old, local)),
+        })?;
+
+        let removed = self.tile_sets_mut().remove(index);
+        self.assign_first_gids(&first_gids);
+        Ok(removed)
+    }
+
+    /// Swaps a tileset for another one, placed tiles keep their local ids
+    /// and have to exist in the new tileset
+    pub fn replace_tileset(
+        &mut self,
+        index: usize,
+        tileset: Tileset,
+    ) -> Result<TilesetContainer, Error> {
+        let ranges = self.tileset_ranges()?;
+        check_index(&ranges, index)?;
+
+        let mut spans = spans(&ranges);
+        spans[index] = tileset.tile_count as u32;
+        let first_gids =
+            self.retarget_tilesets(&ranges, &spans, |old, local| Some((old, local)))?;
+
+        let replaced = std::mem::replace(
+            &mut self.tile_sets_mut()[index],
+            TilesetContainer::Tileset(tileset),
+        );
+        self.assign_first_gids(&first_gids);
+        Ok(replaced)
+    }
+
+    /// Moves all tiles of the tileset at `from` to the one at `into`, with
+    /// `mapping` translating local ids, and removes the emptied tileset
+    pub fn merge_tilesets<F>(
+        &mut self,
+        from: usize,
+        into: usize,
+        mapping: F,
+    ) -> Result<TilesetContainer, Error>
+    where
+        F: Fn(u32) -> Option<u32>,
+    {
+        let ranges = self.tileset_ranges()?;
+        check_index(&ranges, from)?;
+        check_index(&ranges, into)?;
+        if from == into {
+            return Error::MergeTilesetIntoItself(from).fail();
+        }
+
+        let shift = |index: usize| if index > from { index - 1 } else { index };
+        let mut spans = spans(&ranges);
+        spans.remove(from);
+        let first_gids = self.retarget_tilesets(&ranges, &spans, |old, local| {
+            if old == from {
+                mapping(local).map(|local| (shift(into), local))
+            } else {
+                Some((shift(old), local))
+            }
+        })?;
+
+        let removed = self.tile_sets_mut().remove(from);
+        self.assign_first_gids(&first_gids);
+        Ok(removed)
+    }
+
+    /// First gid and number of gids of every tileset. The size of external
+    /// tilesets is unknown, they are assumed to reach up to the next tileset
+    /// or the highest gid in use.
+    fn tileset_ranges(&self) -> Result<Vec<(u32, u32)>, Error> {
+        let first_gids: Vec<u32> = self
+            .tile_sets()
+            .iter()
+            .map(|container| container.first_gid().unwrap_or(1) as u32)
+            .collect();
+        let mut max_used = 0;
+        visit_gids(self.layers(), &mut |gid| {
+            max_used = max_used.max(gid.id());
+            Ok(())
+        })?;
+
+        Ok(self
+            .tile_sets()
+            .iter()
+            .zip(&first_gids)
+            .map(|(container, &first_gid)| {
+                let span = match container {
+                    TilesetContainer::Tileset(tileset) => tileset.tile_count as u32,
+                    TilesetContainer::TilesetRef(_) => first_gids
+                        .iter()
+                        .filter(|&&next| next > first_gid)
+                        .min()
+                        .map(|next| next - first_gid)
+                        .unwrap_or_else(|| (max_used + 1).saturating_sub(first_gid).max(1)),
+                };
+                (first_gid, span)
+            })
+            .collect())
+    }
+
+    /// Rewrites the gids for tilesets laid out back to back with `spans`
+    /// starting at gid `1`. `target` maps the index of the old tileset and a
+    /// local id to the new tileset and local id. Returns the new first gids.
+    fn retarget_tilesets<F>(
+        &mut self,
+        ranges: &[(u32, u32)],
+        spans: &[u32],
+        target: F,
+    ) -> Result<Vec<u32>, Error>
+    where
+        F: Fn(usize, u32) -> Option<(usize, u32)>,
+    {
+        let first_gids: Vec<u32> = spans
+            .iter()
+            .scan(1, |next, span| {
+                let first_gid = *next;
+                *next += span;
+                Some(first_gid)
+            })
+            .collect();
+
+        self.remap_gids(|gid| {
+            let index = match ranges
+                .iter()
+                .position(|&(first_gid, span)| gid.id() >= first_gid && gid.id() < first_gid + span)
+            {
+                Some(index) => index,
+                None => return Ok(gid),
+            };
+            match target(index, gid.id() - ranges[index].0) {
+                Some((new_index, local)) if local < spans[new_index] => {
+                    Ok(gid.with_id(first_gids[new_index] + local))
+                }
+                _ => Error::TileLost(gid.id()).fail(),
+            }
+        })?;
+        Ok(first_gids)
+    }
+
+    fn insert_tileset_container(
+        &mut self,
+        index: usize,
+        container: TilesetContainer,
+        span: u32,
+    ) -> Result<i32, Error> {
+        let ranges = self.tileset_ranges()?;
+        let index = index.min(ranges.len());
+
+        let mut spans = spans(&ranges);
+        spans.insert(index, span);
+        let first_gids = self.retarget_tilesets(&ranges, &spans, |old, local| {
+            Some((if old >= index { old + 1 } else { old }, local))
+        })?;
+
+        self.tile_sets_mut().insert(index, container);
+        self.assign_first_gids(&first_gids);
+        Ok(first_gids[index] as i32)
+    }
+
+    fn assign_first_gids(&mut self, first_gids: &[u32]) {
+        for (container, &first_gid) in self.tile_sets_mut().iter_mut().zip(first_gids) {
+            container.set_first_gid(first_gid as i32);
+        }
+    }
+}
+
+fn spans(ranges: &[(u32, u32)]) -> Vec<u32> {
+    ranges.iter().map(|&(_, span)| span).collect()
+}
+
+fn check_index(ranges: &[(u32, u32)], index: usize) -> Result<(), Error> {
+    if index < ranges.len() {
+        Ok(())
+    } else {
+        Error::TilesetNotFound(index.to_string()).fail()
+    }
+}
+
+fn visit_gids(
+    layers: &[Layer],
+    visit: &mut dyn FnMut(Gid) -> Result<(), Error>,
+) -> Result<(), Error> {
+    for layer in layers {
+        match layer {
+            Layer::TileLayer(layer) => {
+                for (_, _, gid) in layer.decode_grid()?.cells() {
+                    if !gid.is_empty() {
+                        visit(gid)?;
+                    }
+                }
+            }
+            Layer::ObjectGroupLayer(group) => {
+                for object in &group.objects {
+                    if let Object::General(tile_object) = object {
+                        let gid = Gid(tile_object.gid as u32);
+                        if !gid.is_empty() {
+                            visit(gid)?;
+                        }
+                    }
+                }
+            }
+            Layer::GroupLayer(group) => visit_gids(&group.layers, visit)?,
+            Layer::ImageLayer(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_gids(
+    layers: &mut [Layer],
+    remap: &dyn Fn(Gid) -> Result<Gid, Error>,
+) -> Result<(), Error> {
+    let rewrite = |mut tiles: Vec<i32>| -> Result<Vec<i32>, Error> {
+        for raw in tiles.iter_mut() {
+            let gid = Gid::from(*raw);
+            if !gid.is_empty() {
+                *raw = remap(gid)?.into();
+            }
+        }
+        Ok(tiles)
+    };
+
+    for layer in layers {
+        match layer {
+            Layer::TileLayer(layer) => {
+                let encoding = layer.encoding.unwrap_or(Encoding::Csv);
+                let compression = layer.compression;
+                // Infinite layers keep their tiles in chunks only
+                if layer.chunks.is_none() {
+                    let tiles = rewrite(layer.decode_data()?)?;
+                    layer.data = DataSource::encode(&tiles, encoding, compression)?;
+                }
+                let chunks = layer
+                    .chunks
+                    .iter()
+                    .flatten()
+                    .map(|chunk| rewrite(layer.decode_chunk(chunk)?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (chunk, tiles) in layer.chunks.iter_mut().flatten().zip(chunks) {
+                    chunk.data = DataSource::encode(&tiles, encoding, compression)?;
+                }
+            }
+            Layer::ObjectGroupLayer(group) => {
+                for object in group.objects.iter_mut() {
+                    if let Object::General(tile_object) = object {
+                        let gid = Gid(tile_object.gid as u32);
+                        if !gid.is_empty() {
+                            tile_object.gid = remap(gid)?.0 as i64;
+                        }
+                    }
+                }
+            }
+            Layer::GroupLayer(group) => rewrite_gids(&mut group.layers, remap)?,
+            Layer::ImageLayer(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::gid::FLIPPED_HORIZONTALLY_FLAG;
+    use crate::tme::models::Compression;
+    use crate::tme::tile_grid::TileGrid;
+
+    fn tileset(name: &str, tile_count: i32) -> Tileset {
+        TilesetBuilder::new(name, 16, 16)
+            .with_image("tiles.png", 16 * tile_count, 16)
+            .build()
+    }
+
+    fn test_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(3, 1, 16, 16);
+        builder.add_tileset(tileset("a", 4));
+        builder.add_tileset(tileset("b", 4));
+        let ground = builder
+            .tile_layer("ground")
+            .with_tiles(&[Gid(2), Gid::new(6, FLIPPED_HORIZONTALLY_FLAG), Gid::EMPTY])
+            .build();
+        builder.add_layer(ground);
+
+        let mut chunked = builder.tile_layer("chunked").build();
+        if let Layer::TileLayer(layer) = &mut chunked {
+            let mut grid = TileGrid::new(-20, 0, 1, 1);
+            grid.set(-20, 0, Gid(8));
+            layer.store_grid(&grid, true).unwrap();
+        }
+        builder.add_layer(chunked);
+
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_object(ObjectBuilder::tile(Gid(7), 0.0, 0.0, 16.0, 16.0).build())
+            .build();
+        builder.add_layer(objects);
+        builder.build()
+    }
+
+    fn gids(map: &Map) -> Vec<u32> {
+        let mut gids = Vec::new();
+        visit_gids(map.layers(), &mut |gid| {
+            gids.push(gid.0);
+            Ok(())
+        })
+        .unwrap();
+        gids
+    }
+
+    fn first_gids(map: &Map) -> Vec<Option<i32>> {
+        map.tile_sets()
+            .iter()
+            .map(TilesetContainer::first_gid)
+            .collect()
+    }
+
+    #[test]
+    fn inserts_tilesets() {
+        let mut map = test_map();
+        assert_eq!(map.insert_tileset(0, tileset("c", 3)).unwrap(), 1);
+        assert_eq!(first_gids(&map), vec![Some(1), Some(4), Some(8)]);
+        assert_eq!(gids(&map), vec![5, FLIPPED_HORIZONTALLY_FLAG | 9, 11, 10]);
+
+        assert_eq!(map.insert_tileset_ref(9, "d.json", 2).unwrap(), 12);
+        assert_eq!(first_gids(&map), vec![Some(1), Some(4), Some(8), Some(12)]);
+    }
+
+    #[test]
+    fn removes_only_unused_tilesets() {
+        let mut map = test_map();
+        assert!(matches!(map.remove_tileset(0), Err(Error::TileLost(2))));
+        assert_eq!(gids(&map), vec![2, FLIPPED_HORIZONTALLY_FLAG | 6, 8, 7]);
+
+        map.insert_tileset(0, tileset("c", 3)).unwrap();
+        map.remove_tileset(0).unwrap();
+        assert_eq!(first_gids(&map), vec![Some(1), Some(5)]);
+        assert_eq!(gids(&map), vec![2, FLIPPED_HORIZONTALLY_FLAG | 6, 8, 7]);
+        assert!(matches!(
+            map.remove_tileset(2),
+            Err(Error::TilesetNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn replaces_tilesets() {
+        let mut map = test_map();
+        assert!(matches!(
+            map.replace_tileset(1, tileset("small", 2)),
+            Err(Error::TileLost(8))
+        ));
+
+        let replaced = map.replace_tileset(0, tileset("big", 6)).unwrap();
+        assert!(matches!(replaced, TilesetContainer::Tileset(tileset) if tileset.name == "a"));
+        assert_eq!(first_gids(&map), vec![Some(1), Some(7)]);
+        assert_eq!(gids(&map), vec![2, FLIPPED_HORIZONTALLY_FLAG | 8, 10, 9]);
+    }
+
+    #[test]
+    fn merges_tilesets() {
+        let mut map = test_map();
+        map.merge_tilesets(1, 0, Some).unwrap();
+        assert_eq!(first_gids(&map), vec![Some(1)]);
+        assert_eq!(gids(&map), vec![2, FLIPPED_HORIZONTALLY_FLAG | 2, 4, 3]);
+
+        let mut map = test_map();
+        let result = map.merge_tilesets(0, 1, |local| Some(local).filter(|&local| local != 1));
+        assert!(matches!(result, Err(Error::TileLost(2))));
+        assert_eq!(map.tile_sets().len(), 2);
+        assert!(matches!(
+            map.merge_tilesets(1, 1, Some),
+            Err(Error::MergeTilesetIntoItself(1))
+        ));
+    }
+
+    #[test]
+    fn keeps_encoding_when_remapping() {
+        let mut map = test_map();
+        for id in 1..=2 {
+            if let Some(Layer::TileLayer(layer)) = map.layer_mut(id) {
+                layer
+                    .encode(Encoding::Base64, Some(Compression::Gzip))
+                    .unwrap();
+            }
+        }
+
+        map.insert_tileset(0, tileset("c", 3)).unwrap();
+        assert_eq!(gids(&map), vec![5, FLIPPED_HORIZONTALLY_FLAG | 9, 11, 10]);
+        for id in 1..=2 {
+            match map.layer(id) {
+                Some(Layer::TileLayer(layer)) => {
+                    assert_eq!(
+                        (layer.encoding, layer.compression),
+                        (Some(Encoding::Base64), Some(Compression::Gzip))
+                    );
+                    let data = match &layer.chunks {
+                        Some(chunks) => &chunks[0].data,
+                        None => &layer.data,
+                    };
+                    assert!(matches!(data, DataSource::Encoded(_)));
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+}
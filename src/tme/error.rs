@@ -1,3 +1,6 @@
+use crate::tme::models::Compression;
+use crate::tme::models::TileOrigin;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Unable parse color from string: {0}")]
@@ -22,6 +25,10 @@ pub enum Error {
     ParseEncoding(String),
     #[error("Unable parse layer type from string: {0}")]
     ParseLayerType(String),
+    #[error("Unable parse horizontal align from string: {0}")]
+    ParseHorizontalAlign(String),
+    #[error("Unable parse vertical align from string: {0}")]
+    ParseVerticalAlign(String),
     #[error("Unable parse data from string: {0}")]
     ParseDataSource(String),
     #[error("Invalid data source format: {0}")]
@@ -30,6 +37,54 @@ pub enum Error {
     ConvertBytesToPrimitive(String),
     #[error("Unable convert slice of u8 to slice of another type: {0:?}")]
     TypesCastError(bytemuck::PodCastError),
+    #[error("Unable decompress {1:?} tile data of {0}: {2}")]
+    DecompressTiles(TileOrigin, Compression, std::io::Error),
+    #[error("Decompressed tile data of {0} exceeds {1} bytes")]
+    DecompressedSizeLimit(TileOrigin, usize),
+    #[error("Tile data of {origin} has {actual} tiles instead of {expected}")]
+    TileCountMismatch {
+        origin:   TileOrigin,
+        expected: usize,
+        actual:   usize,
+    },
+    #[error("Tile data of {0} is {1} bytes long, which is not a whole number of tiles")]
+    MisalignedTileData(TileOrigin, usize),
+    #[error("Unable parse CSV tile data of {0}, invalid gid: {1:?}")]
+    ParseCsvTile(TileOrigin, String),
+    #[error("Layer not found: {0}")]
+    LayerNotFound(String),
+    #[error("Layer {0} has the wrong type for this operation")]
+    WrongLayerType(i32),
+    #[error("Object not found: {0}")]
+    ObjectNotFound(i64),
+    #[error("Tileset not found: {0}")]
+    TilesetNotFound(String),
+    #[error("Tile {0} is in use and would be lost")]
+    TileLost(u32),
+    #[error("Unable merge tileset {0} into itself")]
+    MergeTilesetIntoItself(usize),
+    #[error("Unable parse XML: {0}")]
+    ParseXml(#[from] roxmltree::Error),
+    #[error("Missing {0} in TMX")]
+    MissingTmxValue(String),
+    #[error("Unable parse {0} in TMX from string: {1}")]
+    ParseTmxValue(String, String),
+    #[error("Not a runtime map, the magic header is missing")]
+    InvalidRuntimeMagic,
+    #[error("Unsupported runtime map version: {0}")]
+    UnsupportedRuntimeVersion(u32),
+    #[error("Runtime map checksum mismatch, expected {expected:#010x} but got {actual:#010x}")]
+    RuntimeChecksumMismatch { expected: u32, actual: u32 },
+    #[error("Runtime map is truncated")]
+    TruncatedRuntimeMap,
+    #[error("Invalid {0} in runtime map")]
+    InvalidRuntimeValue(String),
+    #[error("Invalid tile cache: {0}")]
+    InvalidTileCache(String),
+    #[error("Invalid world pattern {0}: {1}")]
+    InvalidWorldPattern(String, regex::Error),
+    #[error("Unable parse JSON: {0}")]
+    ParseJson(#[from] serde_json::Error),
     #[error(transparent)]
     DecodeBase64(#[from] base64::DecodeError),
     #[error(transparent)]
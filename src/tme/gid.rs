@@ -0,0 +1,100 @@
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+use serde::Deserialize;
+use serde::Serialize;
+
+pub const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY_FLAG: u32 = 0x4000_0000;
+pub const FLIPPED_DIAGONALLY_FLAG: u32 = 0x2000_0000;
+pub const ROTATED_HEXAGONAL_120_FLAG: u32 = 0x1000_0000;
+
+const FLAGS_MASK: u32 = FLIPPED_HORIZONTALLY_FLAG
+    | FLIPPED_VERTICALLY_FLAG
+    | FLIPPED_DIAGONALLY_FLAG
+    | ROTATED_HEXAGONAL_120_FLAG;
+
+/// Global tile id as stored in tile layer data, including the flip flags
+/// in the upper bits
+#[repr(transparent)]
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Pod, Zeroable, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Gid(pub u32);
+
+impl Gid {
+    pub const EMPTY: Gid = Gid(0);
+
+    pub fn new(id: u32, flags: u32) -> Self {
+        Self((id & !FLAGS_MASK) | (flags & FLAGS_MASK))
+    }
+
+    pub fn id(self) -> u32 {
+        self.0 & !FLAGS_MASK
+    }
+
+    pub fn flags(self) -> u32 {
+        self.0 & FLAGS_MASK
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.id() == 0
+    }
+
+    pub fn flipped_horizontally(self) -> bool {
+        self.0 & FLIPPED_HORIZONTALLY_FLAG != 0
+    }
+
+    pub fn flipped_vertically(self) -> bool {
+        self.0 & FLIPPED_VERTICALLY_FLAG != 0
+    }
+
+    pub fn flipped_diagonally(self) -> bool {
+        self.0 & FLIPPED_DIAGONALLY_FLAG != 0
+    }
+
+    pub fn rotated_hexagonal_120(self) -> bool {
+        self.0 & ROTATED_HEXAGONAL_120_FLAG != 0
+    }
+
+    pub fn with_id(self, id: u32) -> Self {
+        Self::new(id, self.flags())
+    }
+}
+
+impl From<i32> for Gid {
+    fn from(raw: i32) -> Self {
+        Self(raw as u32)
+    }
+}
+
+impl From<Gid> for i32 {
+    fn from(gid: Gid) -> Self {
+        gid.0 as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_id_and_flags() {
+        let gid = Gid::from(0x8000_0000u32 as i32 | 5);
+        assert_eq!(gid.id(), 5);
+        assert!(gid.flipped_horizontally());
+        assert!(!gid.flipped_vertically());
+        assert!(!gid.flipped_diagonally());
+        assert!(!gid.is_empty());
+
+        let gid = Gid::new(7, FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG);
+        assert_eq!(gid.id(), 7);
+        assert!(gid.flipped_vertically());
+        assert!(gid.flipped_diagonally());
+        assert_eq!(gid.with_id(9).flags(), gid.flags());
+        assert_eq!(gid.with_id(9).id(), 9);
+
+        assert!(Gid::from(0).is_empty());
+        assert!(Gid(FLIPPED_HORIZONTALLY_FLAG).is_empty());
+    }
+}
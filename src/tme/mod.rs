@@ -1,7 +1,39 @@
+pub mod builder;
+pub mod chunk_streamer;
 pub mod color;
+pub mod decoding;
+pub mod diff;
+pub mod editing;
 pub mod error;
+pub mod gid;
 pub mod models;
+pub mod navigation;
+pub mod render;
+pub mod runtime;
+pub mod tile_cache;
+pub mod tile_grid;
+pub mod tileset_lookup;
+pub mod tmx;
+pub mod validation;
+pub mod world;
 
+pub use builder::*;
+pub use chunk_streamer::*;
 pub use color::Color;
+pub use color::Hsl;
+pub use color::Hsv;
+pub use decoding::*;
+pub use diff::*;
+pub use editing::*;
 pub use error::*;
+pub use gid::*;
 pub use models::*;
+pub use navigation::*;
+pub use render::*;
+pub use runtime::*;
+pub use tile_cache::*;
+pub use tile_grid::*;
+pub use tileset_lookup::*;
+pub use tmx::*;
+pub use validation::*;
+pub use world::*;
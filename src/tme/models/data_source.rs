@@ -1,13 +1,38 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use std::fmt;
 use std::io::Read;
+use std::io::Write;
 use std::str::FromStr;
 
 use crate::tme::error::Error;
 use crate::tme::models::layer::Compression;
+use crate::tme::models::layer::Encoding;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Upper bound of the decompressed size of one layer or chunk, guards
+/// against decompression bombs
+pub const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Layer or chunk tile data belongs to, named in decoding errors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileOrigin {
+    Unknown,
+    Layer(i32),
+    Chunk { layer: i32, x: i32, y: i32 },
+}
+
+impl fmt::Display for TileOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileOrigin::Unknown => write!(f, "unknown layer"),
+            TileOrigin::Layer(id) => write!(f, "layer {}", id),
+            TileOrigin::Chunk { layer, x, y } => write!(f, "chunk {},{} of layer {}", x, y, layer),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum DataSource {
     Raw(Vec<i32>),
@@ -15,51 +40,178 @@ pub enum DataSource {
 }
 
 impl DataSource {
-    pub fn extract_tiles(&self, compression: Option<Compression>) -> Result<Vec<i32>, Error> {
-        match (&self, compression) {
-            (DataSource::Raw(tiles), _) => Ok(tiles.clone()),
-            (DataSource::Encoded(data), None) => bytemuck::try_cast_slice(&decode_base64(data)?)
-                .map(<[_]>::to_vec)
-                .map_err(Error::TypesCastError),
-            (DataSource::Encoded(data), Some(compression)) => {
-                decompress(decode_base64(data)?.as_slice(), compression)
+    /// Tiles of the data, an encoded string is read as CSV or base64
+    /// depending on `encoding`, base64 being the default
+    pub fn extract_tiles(
+        &self,
+        encoding: Option<Encoding>,
+        compression: Option<Compression>,
+    ) -> Result<Vec<i32>, Error> {
+        self.extract_tiles_of(encoding, compression, TileOrigin::Unknown)
+    }
+
+    /// Same as `extract_tiles`, naming `origin` in errors
+    pub fn extract_tiles_of(
+        &self,
+        encoding: Option<Encoding>,
+        compression: Option<Compression>,
+        origin: TileOrigin,
+    ) -> Result<Vec<i32>, Error> {
+        match (&self, encoding, compression) {
+            (DataSource::Raw(tiles), _, _) => Ok(tiles.clone()),
+            (DataSource::Encoded(data), Some(Encoding::Csv), _) => parse_csv(data, origin),
+            (DataSource::Encoded(data), _, None) => cast_tiles(&decode_base64(data)?, origin),
+            (DataSource::Encoded(data), _, Some(compression)) => decompress(
+                decode_base64(data)?.as_slice(),
+                compression,
+                origin,
+                MAX_DECOMPRESSED_SIZE,
+            ),
+        }
+    }
+
+    /// Decodes exactly `expected` tiles, the size of the layer or chunk
+    pub fn decode_tiles(
+        &self,
+        encoding: Option<Encoding>,
+        compression: Option<Compression>,
+        origin: TileOrigin,
+        expected: usize,
+    ) -> Result<Vec<i32>, Error> {
+        let tiles = self.extract_tiles_of(encoding, compression, origin)?;
+        if tiles.len() != expected {
+            return Error::TileCountMismatch {
+                origin,
+                expected,
+                actual: tiles.len(),
             }
+            .fail();
+        }
+        Ok(tiles)
+    }
+
+    /// Stores `tiles` the way Tiled writes them, as a plain array for CSV or
+    /// as base64 of the optionally compressed little endian bytes
+    pub fn encode(
+        tiles: &[i32],
+        encoding: Encoding,
+        compression: Option<Compression>,
+    ) -> Result<Self, Error> {
+        if encoding == Encoding::Csv {
+            return Ok(DataSource::Raw(tiles.to_vec()));
         }
+
+        let bytes: Vec<u8> = tiles.iter().flat_map(|tile| tile.to_le_bytes()).collect();
+        let bytes = match compression.unwrap_or(Compression::None) {
+            Compression::Zstd => zstd::encode_all(bytes.as_slice(), 0)?,
+            Compression::Zlib => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()?
+            }
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()?
+            }
+            Compression::None => bytes,
+        };
+        Ok(DataSource::Encoded(base64::encode(bytes)))
     }
 }
 
-fn decode_base64<T: AsRef<[u8]>>(s: T) -> Result<Vec<u8>, Error> {
+/// Reads comma separated gids, surrounding whitespace and a trailing comma
+/// are ignored the way Tiled writes them in TMX
+pub fn parse_csv(text: &str, origin: TileOrigin) -> Result<Vec<i32>, Error> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    let text = text.strip_suffix(',').unwrap_or(text);
+    text.split(',')
+        .map(str::trim)
+        .map(|gid| {
+            gid.parse::<u32>()
+                .map(|gid| gid as i32)
+                .map_err(|_| Error::ParseCsvTile(origin, gid.to_owned()))
+        })
+        .collect()
+}
+
+/// Writes tiles as comma separated gids with a line per row of `width` tiles
+pub fn write_csv(tiles: &[i32], width: i32) -> String {
+    tiles
+        .chunks(width.max(1) as usize)
+        .map(|row| {
+            row.iter()
+                .map(|&gid| (gid as u32).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+pub(crate) fn decode_base64<T: AsRef<[u8]>>(s: T) -> Result<Vec<u8>, Error> {
     let result = base64::decode(s)?;
     Ok(result)
 }
 
-fn decompress(buf: &[u8], compression: Compression) -> Result<Vec<i32>, Error> {
+/// Decompresses `buf`, failing once more than `limit` bytes come out
+pub(crate) fn decompress(
+    buf: &[u8],
+    compression: Compression,
+    origin: TileOrigin,
+    limit: usize,
+) -> Result<Vec<i32>, Error> {
     match compression {
         Compression::Zstd => {
-            let decoder = zstd::Decoder::new(buf)?;
-            decompress_with_decoder(decoder)
+            let decoder = zstd::Decoder::new(buf)
+                .map_err(|error| Error::DecompressTiles(origin, compression, error))?;
+            decompress_with_decoder(decoder, compression, origin, limit)
         }
         Compression::Zlib => {
             let decoder = flate2::read::ZlibDecoder::new(buf);
-            decompress_with_decoder(decoder)
+            decompress_with_decoder(decoder, compression, origin, limit)
         }
         Compression::Gzip => {
             let decoder = flate2::read::GzDecoder::new(buf);
-            decompress_with_decoder(decoder)
+            decompress_with_decoder(decoder, compression, origin, limit)
         }
-        Compression::None => bytemuck::try_cast_slice(buf)
-            .map(<[_]>::to_vec)
-            .map_err(Error::TypesCastError),
+        Compression::None => cast_tiles(buf, origin),
     }
 }
 
-fn decompress_with_decoder<T: Read>(mut decoder: T) -> Result<Vec<i32>, Error> {
+fn decompress_with_decoder<T: Read>(
+    decoder: T,
+    compression: Compression,
+    origin: TileOrigin,
+    limit: usize,
+) -> Result<Vec<i32>, Error> {
     let mut buf = Vec::new();
-    let _ = decoder.read_to_end(&mut buf);
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut buf)
+        .map_err(|error| Error::DecompressTiles(origin, compression, error))?;
+    if buf.len() > limit {
+        return Error::DecompressedSizeLimit(origin, limit).fail();
+    }
 
-    bytemuck::try_cast_slice(&buf)
-        .map(<[_]>::to_vec)
-        .map_err(Error::TypesCastError)
+    cast_tiles(&buf, origin)
+}
+
+/// Reads little endian tiles, the buffer is copied since decoded bytes are
+/// not guaranteed to be aligned for `i32`
+fn cast_tiles(buf: &[u8], origin: TileOrigin) -> Result<Vec<i32>, Error> {
+    if !buf.len().is_multiple_of(std::mem::size_of::<i32>()) {
+        return Error::MisalignedTileData(origin, buf.len()).fail();
+    }
+    Ok(buf
+        .chunks_exact(std::mem::size_of::<i32>())
+        .map(|bytes| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
 }
 
 impl FromStr for DataSource {
@@ -146,44 +298,170 @@ mod tests {
     #[test]
     fn decodes_into_tiles() {
         assert_eq!(
-            DataSource::Raw(vec![2]).extract_tiles(None).unwrap(),
+            DataSource::Raw(vec![2]).extract_tiles(None, None).unwrap(),
             vec![2]
         );
 
         // Raw data source should ignore compression if specified
         assert_eq!(
             DataSource::Raw(vec![2])
-                .extract_tiles(Some(Compression::Zlib))
+                .extract_tiles(None, Some(Compression::Zlib))
                 .unwrap(),
             vec![2]
         );
 
         assert_eq!(
             DataSource::Encoded("AgAAAAIAAAA=".to_owned())
-                .extract_tiles(None)
+                .extract_tiles(None, None)
                 .unwrap(),
             vec![2, 2]
         );
 
         assert_eq!(
             DataSource::Encoded("eJxjYmBgAAAADAAD".to_owned())
-                .extract_tiles(Some(Compression::Zlib))
+                .extract_tiles(None, Some(Compression::Zlib))
                 .unwrap(),
             vec![2]
         );
 
         assert_eq!(
             DataSource::Encoded("KLUv/SAEIQAAAgAAAA==".to_owned())
-                .extract_tiles(Some(Compression::Zstd))
+                .extract_tiles(None, Some(Compression::Zstd))
                 .unwrap(),
             vec![2]
         );
 
         assert_eq!(
             DataSource::Encoded("H4sIAAAAAAAACmNiYGAAAJcXTYsEAAAA".to_owned())
-                .extract_tiles(Some(Compression::Gzip))
+                .extract_tiles(None, Some(Compression::Gzip))
                 .unwrap(),
             vec![2]
         );
     }
+
+    #[test]
+    fn encodes_tiles() {
+        let tiles = vec![2, 0, -2147483647];
+        assert_eq!(
+            DataSource::encode(&tiles, Encoding::Csv, Some(Compression::Zlib)).unwrap(),
+            Raw(tiles.clone())
+        );
+        assert_eq!(
+            DataSource::encode(&[2, 2], Encoding::Base64, None).unwrap(),
+            Encoded("AgAAAAIAAAA=".to_owned())
+        );
+
+        for compression in [Compression::Zstd, Compression::Zlib, Compression::Gzip] {
+            let data = DataSource::encode(&tiles, Encoding::Base64, Some(compression)).unwrap();
+            assert_eq!(
+                data.extract_tiles(Some(Encoding::Base64), Some(compression))
+                    .unwrap(),
+                tiles
+            );
+        }
+    }
+
+    #[test]
+    fn reports_damaged_tile_data() {
+        let origin = TileOrigin::Chunk {
+            layer: 3,
+            x:     16,
+            y:     -16,
+        };
+        let tiles: Vec<i32> = (0..256).collect();
+        for compression in [Compression::Zstd, Compression::Zlib, Compression::Gzip] {
+            let bytes = match DataSource::encode(&tiles, Encoding::Base64, Some(compression)) {
+                Ok(Encoded(data)) => decode_base64(data).unwrap(),
+                _ => unreachable!(),
+            };
+            let truncated = Encoded(base64::encode(&bytes[..bytes.len() / 2]));
+            let error = truncated
+                .extract_tiles_of(None, Some(compression), origin)
+                .unwrap_err();
+            assert!(
+                matches!(error, Error::DecompressTiles(o, c, _) if o == origin && c == compression)
+            );
+            assert!(error.to_string().contains("chunk 16,-16 of layer 3"));
+
+            assert!(matches!(
+                decompress(&bytes, compression, origin, 1000),
+                Err(Error::DecompressedSizeLimit(o, 1000)) if o == origin
+            ));
+            assert_eq!(
+                decompress(&bytes, compression, origin, 1024).unwrap(),
+                tiles
+            );
+        }
+
+        assert!(matches!(
+            Encoded("AgAAAAIAAA==".to_owned()).extract_tiles_of(None, None, TileOrigin::Layer(2)),
+            Err(Error::MisalignedTileData(TileOrigin::Layer(2), 7))
+        ));
+    }
+
+    #[test]
+    fn checks_tile_count() {
+        let data = Encoded("AgAAAAIAAAA=".to_owned());
+        assert_eq!(
+            data.decode_tiles(None, None, TileOrigin::Layer(1), 2)
+                .unwrap(),
+            vec![2, 2]
+        );
+        assert!(matches!(
+            data.decode_tiles(None, None, TileOrigin::Layer(1), 4),
+            Err(Error::TileCountMismatch {
+                origin:   TileOrigin::Layer(1),
+                expected: 4,
+                actual:   2,
+            })
+        ));
+    }
+
+    #[test]
+    fn reads_and_writes_csv() {
+        let text = "\n1,2,3,\n  4,0,2147483649,\r\n7,8,9\n";
+        let tiles = vec![1, 2, 3, 4, 0, -2147483647, 7, 8, 9];
+        assert_eq!(parse_csv(text, TileOrigin::Unknown).unwrap(), tiles);
+        assert_eq!(parse_csv("1,2,", TileOrigin::Unknown).unwrap(), vec![1, 2]);
+        assert_eq!(
+            parse_csv(" \n", TileOrigin::Unknown).unwrap(),
+            Vec::<i32>::new()
+        );
+
+        let csv = write_csv(&tiles, 3);
+        assert_eq!(csv, "1,2,3,\n4,0,2147483649,\n7,8,9");
+        assert_eq!(parse_csv(&csv, TileOrigin::Unknown).unwrap(), tiles);
+
+        assert!(matches!(
+            parse_csv("1,,2", TileOrigin::Layer(5)),
+            Err(Error::ParseCsvTile(TileOrigin::Layer(5), gid)) if gid.is_empty()
+        ));
+        assert!(matches!(
+            parse_csv("1,x", TileOrigin::Layer(5)),
+            Err(Error::ParseCsvTile(TileOrigin::Layer(5), gid)) if gid == "x"
+        ));
+    }
+
+    #[test]
+    fn dispatches_on_encoding() {
+        let csv = Encoded("1,2,\n3,4".to_owned());
+        assert_eq!(
+            csv.extract_tiles(Some(Encoding::Csv), None).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(matches!(
+            csv.extract_tiles(Some(Encoding::Base64), None),
+            Err(Error::DecodeBase64(_))
+        ));
+
+        let base64 = Encoded("AgAAAAIAAAA=".to_owned());
+        assert_eq!(
+            base64.extract_tiles(Some(Encoding::Base64), None).unwrap(),
+            vec![2, 2]
+        );
+        assert!(matches!(
+            base64.extract_tiles(Some(Encoding::Csv), None),
+            Err(Error::ParseCsvTile(..))
+        ));
+    }
 }
@@ -4,7 +4,7 @@ use serde::Serialize;
 use super::property::Property;
 use super::utils;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase", deny_unknown_fields)]
 pub struct GeneralObject {
     pub gid:        i64,
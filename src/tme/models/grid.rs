@@ -3,7 +3,7 @@ use serde::Serialize;
 
 use super::orientation::Orientation;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub struct Grid {
     pub height:      i32,
@@ -4,7 +4,7 @@ use serde::Serialize;
 use super::layer::Layer;
 use super::property::Property;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct GroupLayer {
     pub id:         i32,
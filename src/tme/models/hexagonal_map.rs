@@ -13,7 +13,7 @@ use super::utils;
 use crate::tme::color::color_serde;
 use crate::tme::color::Color;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct HexagonalMap {
     #[serde(with = "color_serde")]
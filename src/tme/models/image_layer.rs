@@ -5,7 +5,7 @@ use super::property::Property;
 use crate::tme::color::opt_color_serde;
 use crate::tme::color::Color;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct ImageLayer {
     pub id:                i32,
@@ -6,10 +6,11 @@ use serde::Serialize;
 use super::group_layer::GroupLayer;
 use super::image_layer::ImageLayer;
 use super::object_group_layer::ObjectGroupLayer;
+use super::property::Property;
 use super::tile_layer::TileLayer;
 use crate::tme::error::Error;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase", tag = "type")]
 pub enum Layer {
     #[serde(rename = "tilelayer")]
@@ -22,6 +23,107 @@ pub enum Layer {
     GroupLayer(GroupLayer),
 }
 
+impl Layer {
+    pub fn id(&self) -> i32 {
+        match self {
+            Layer::TileLayer(layer) => layer.id,
+            Layer::ObjectGroupLayer(layer) => layer.id,
+            Layer::ImageLayer(layer) => layer.id,
+            Layer::GroupLayer(layer) => layer.id,
+        }
+    }
+
+    pub fn set_id(&mut self, id: i32) {
+        match self {
+            Layer::TileLayer(layer) => layer.id = id,
+            Layer::ObjectGroupLayer(layer) => layer.id = id,
+            Layer::ImageLayer(layer) => layer.id = id,
+            Layer::GroupLayer(layer) => layer.id = id,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Layer::TileLayer(layer) => &layer.name,
+            Layer::ObjectGroupLayer(layer) => &layer.name,
+            Layer::ImageLayer(layer) => &layer.name,
+            Layer::GroupLayer(layer) => &layer.name,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) -> String {
+        match self {
+            Layer::TileLayer(layer) => std::mem::replace(&mut layer.name, name),
+            Layer::ObjectGroupLayer(layer) => std::mem::replace(&mut layer.name, name),
+            Layer::ImageLayer(layer) => std::mem::replace(&mut layer.name, name),
+            Layer::GroupLayer(layer) => std::mem::replace(&mut layer.name, name),
+        }
+    }
+
+    pub fn properties(&self) -> Option<&[Property]> {
+        match self {
+            Layer::TileLayer(layer) => layer.properties.as_deref(),
+            Layer::ObjectGroupLayer(layer) => layer.properties.as_deref(),
+            Layer::ImageLayer(layer) => layer.properties.as_deref(),
+            Layer::GroupLayer(layer) => layer.properties.as_deref(),
+        }
+    }
+
+    pub fn properties_mut(&mut self) -> &mut Option<Vec<Property>> {
+        match self {
+            Layer::TileLayer(layer) => &mut layer.properties,
+            Layer::ObjectGroupLayer(layer) => &mut layer.properties,
+            Layer::ImageLayer(layer) => &mut layer.properties,
+            Layer::GroupLayer(layer) => &mut layer.properties,
+        }
+    }
+}
+
+/// Depth-first walk over layers and the contents of group layers, parents
+/// come before their children
+pub fn flatten_layers(layers: &[Layer]) -> Vec<&Layer> {
+    let mut result = Vec::new();
+    for layer in layers {
+        result.push(layer);
+        if let Layer::GroupLayer(group) = layer {
+            result.extend(flatten_layers(&group.layers));
+        }
+    }
+    result
+}
+
+pub fn find_layer_mut(layers: &mut [Layer], id: i32) -> Option<&mut Layer> {
+    for layer in layers.iter_mut() {
+        if layer.id() == id {
+            return Some(layer);
+        }
+        if let Layer::GroupLayer(group) = layer {
+            if let Some(found) = find_layer_mut(&mut group.layers, id) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// The list holding the layer with the given id together with its position
+pub fn find_layer_siblings_mut(
+    layers: &mut Vec<Layer>,
+    id: i32,
+) -> Option<(&mut Vec<Layer>, usize)> {
+    if let Some(index) = layers.iter().position(|layer| layer.id() == id) {
+        return Some((layers, index));
+    }
+    for layer in layers.iter_mut() {
+        if let Layer::GroupLayer(group) = layer {
+            if let Some(found) = find_layer_siblings_mut(&mut group.layers, id) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Compression {
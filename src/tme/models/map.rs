@@ -5,12 +5,21 @@ use serde::Serialize;
 
 use super::hexagonal_map::HexagonalMap;
 use super::isometric_map::IsometricMap;
+use super::layer::find_layer_mut;
+use super::layer::flatten_layers;
+use super::layer::Layer;
+use super::object::Object;
+use super::orientation::Orientation;
 use super::orthogonal_map::OrthogonalMap;
+use super::property::Property;
 use super::staggered_map::StaggeredMap;
+use super::tile_layer::TileLayer;
+use super::tileset::Tileset;
+use super::tileset::TilesetContainer;
 
 use crate::tme::error::Error;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "orientation", rename_all = "lowercase")]
 pub enum Map {
     Orthogonal(OrthogonalMap),
@@ -19,6 +28,244 @@ pub enum Map {
     Hexagonal(HexagonalMap),
 }
 
+macro_rules! with_map {
+    ($map:expr, $inner:ident => $body:expr) => {
+        match $map {
+            Map::Orthogonal($inner) => $body,
+            Map::Isometric($inner) => $body,
+            Map::Staggered($inner) => $body,
+            Map::Hexagonal($inner) => $body,
+        }
+    };
+}
+
+pub(crate) use with_map;
+
+impl Map {
+    pub fn orientation(&self) -> Orientation {
+        match self {
+            Map::Orthogonal(_) => Orientation::Orthogonal,
+            Map::Isometric(_) => Orientation::Isometric,
+            Map::Staggered(_) => Orientation::Staggered,
+            Map::Hexagonal(_) => Orientation::Hexagonal,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        with_map!(self, map => map.width)
+    }
+
+    pub fn height(&self) -> i32 {
+        with_map!(self, map => map.height)
+    }
+
+    pub fn tile_width(&self) -> i32 {
+        with_map!(self, map => map.tile_width)
+    }
+
+    pub fn tile_height(&self) -> i32 {
+        with_map!(self, map => map.tile_height)
+    }
+
+    pub fn infinite(&self) -> bool {
+        with_map!(self, map => map.infinite)
+    }
+
+    pub fn render_order(&self) -> RenderOrder {
+        with_map!(self, map => map.render_order)
+    }
+
+    pub fn stagger_axis(&self) -> Option<StaggerAxis> {
+        match self {
+            Map::Staggered(map) => Some(map.stagger_axis),
+            Map::Hexagonal(map) => Some(map.stagger_axis),
+            _ => None,
+        }
+    }
+
+    pub fn stagger_index(&self) -> Option<StaggerIndex> {
+        match self {
+            Map::Staggered(map) => Some(map.stagger_index),
+            Map::Hexagonal(map) => Some(map.stagger_index),
+            _ => None,
+        }
+    }
+
+    pub fn hex_side_length(&self) -> i32 {
+        match self {
+            Map::Hexagonal(map) => map.hex_side_length,
+            _ => 0,
+        }
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        with_map!(self, map => &map.layers)
+    }
+
+    pub fn layers_mut(&mut self) -> &mut Vec<Layer> {
+        with_map!(self, map => &mut map.layers)
+    }
+
+    pub fn tile_sets(&self) -> &[TilesetContainer] {
+        with_map!(self, map => &map.tile_sets)
+    }
+
+    pub fn tile_sets_mut(&mut self) -> &mut Vec<TilesetContainer> {
+        with_map!(self, map => &mut map.tile_sets)
+    }
+
+    pub fn properties(&self) -> Option<&[Property]> {
+        with_map!(self, map => map.properties.as_deref())
+    }
+
+    pub fn properties_mut(&mut self) -> &mut Option<Vec<Property>> {
+        with_map!(self, map => &mut map.properties)
+    }
+
+    /// Finds a layer by id, including those nested in groups
+    pub fn layer(&self, id: i32) -> Option<&Layer> {
+        flatten_layers(self.layers())
+            .into_iter()
+            .find(|layer| layer.id() == id)
+    }
+
+    pub fn layer_mut(&mut self, id: i32) -> Option<&mut Layer> {
+        find_layer_mut(self.layers_mut(), id)
+    }
+
+    /// Finds an object by id together with the id of its layer
+    pub fn object(&self, id: i64) -> Option<(i32, &Object)> {
+        flatten_layers(self.layers())
+            .into_iter()
+            .find_map(|layer| match layer {
+                Layer::ObjectGroupLayer(group) => group
+                    .objects
+                    .iter()
+                    .find(|object| object.id() == id)
+                    .map(|object| (group.id, object)),
+                _ => None,
+            })
+    }
+
+    /// All objects of the map together with the ids of their layers
+    pub fn objects(&self) -> Vec<(i32, &Object)> {
+        flatten_layers(self.layers())
+            .into_iter()
+            .filter_map(|layer| match layer {
+                Layer::ObjectGroupLayer(group) => Some(group),
+                _ => None,
+            })
+            .flat_map(|group| group.objects.iter().map(move |object| (group.id, object)))
+            .collect()
+    }
+
+    pub fn object_mut(&mut self, id: i64) -> Option<&mut Object> {
+        let layer = self.object(id)?.0;
+        match self.layer_mut(layer)? {
+            Layer::ObjectGroupLayer(group) => {
+                group.objects.iter_mut().find(|object| object.id() == id)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn next_layer_id(&self) -> i32 {
+        with_map!(self, map => map.next_layer_id)
+    }
+
+    pub fn next_object_id(&self) -> i32 {
+        with_map!(self, map => map.next_object_id)
+    }
+
+    pub fn set_next_layer_id(&mut self, next_layer_id: i32) {
+        with_map!(self, map => map.next_layer_id = next_layer_id)
+    }
+
+    pub fn set_next_object_id(&mut self, next_object_id: i32) {
+        with_map!(self, map => map.next_object_id = next_object_id)
+    }
+
+    pub fn allocate_layer_id(&mut self) -> i32 {
+        let id = self.next_layer_id();
+        self.set_next_layer_id(id + 1);
+        id
+    }
+
+    pub fn allocate_object_id(&mut self) -> i64 {
+        let id = self.next_object_id();
+        self.set_next_object_id(id + 1);
+        id as i64
+    }
+
+    /// Appends a layer, giving it and every nested layer or object with id
+    /// `0` a fresh id. Returns the id of the layer.
+    pub fn add_layer(&mut self, mut layer: Layer) -> i32 {
+        self.assign_ids(&mut layer);
+        let id = layer.id();
+        self.layers_mut().push(layer);
+        id
+    }
+
+    /// Appends an embedded tileset and returns its first gid. A tileset
+    /// without `first_gid` is placed after the gids used by the existing
+    /// ones, where external tilesets are assumed to hold a single tile since
+    /// their size is unknown.
+    pub fn add_tileset(&mut self, mut tileset: Tileset) -> i32 {
+        let first_gid = tileset.first_gid.unwrap_or_else(|| self.next_first_gid());
+        tileset.first_gid = Some(first_gid);
+        self.tile_sets_mut()
+            .push(TilesetContainer::Tileset(tileset));
+        first_gid
+    }
+
+    /// First gid not used by any tileset of the map
+    pub fn next_first_gid(&self) -> i32 {
+        self.tile_sets()
+            .iter()
+            .map(|container| match container {
+                TilesetContainer::Tileset(tileset) => {
+                    tileset.first_gid.unwrap_or(1) + tileset.tile_count as i32
+                }
+                TilesetContainer::TilesetRef(reference) => reference.first_gid + 1,
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn assign_ids(&mut self, layer: &mut Layer) {
+        if layer.id() == 0 {
+            let id = self.allocate_layer_id();
+            layer.set_id(id);
+        }
+
+        match layer {
+            Layer::ObjectGroupLayer(group) => {
+                for object in group.objects.iter_mut() {
+                    if object.id() == 0 {
+                        let id = self.allocate_object_id();
+                        object.set_id(id);
+                    }
+                }
+            }
+            Layer::GroupLayer(group) => {
+                for child in group.layers.iter_mut() {
+                    self.assign_ids(child);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn find_tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        flatten_layers(self.layers())
+            .into_iter()
+            .find_map(|layer| match layer {
+                Layer::TileLayer(tile_layer) if tile_layer.name == name => Some(tile_layer),
+                _ => None,
+            })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MapType {
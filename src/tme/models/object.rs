@@ -6,10 +6,11 @@ use super::general_object::GeneralObject;
 use super::point_object::PointObject;
 use super::polygon_object::PolygonObject;
 use super::polyline_object::PolylineObject;
+use super::property::Property;
 use super::rectangle_object::RectangleObject;
 use super::text_object::TextObject;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase", untagged)]
 pub enum Object {
     General(GeneralObject),
@@ -20,3 +21,122 @@ pub enum Object {
     Polyline(PolylineObject),
     Text(TextObject),
 }
+
+macro_rules! with_object {
+    ($object:expr, $inner:ident => $body:expr) => {
+        match $object {
+            Object::General($inner) => $body,
+            Object::Ellipse($inner) => $body,
+            Object::Rectangle($inner) => $body,
+            Object::Point($inner) => $body,
+            Object::Polygon($inner) => $body,
+            Object::Polyline($inner) => $body,
+            Object::Text($inner) => $body,
+        }
+    };
+}
+
+impl Object {
+    pub fn id(&self) -> i64 {
+        with_object!(self, object => object.id)
+    }
+
+    pub fn set_id(&mut self, id: i64) {
+        with_object!(self, object => object.id = id)
+    }
+
+    pub fn name(&self) -> &str {
+        with_object!(self, object => &object.name)
+    }
+
+    pub fn obj_type(&self) -> &str {
+        with_object!(self, object => &object.obj_type)
+    }
+
+    pub fn x(&self) -> f64 {
+        with_object!(self, object => object.x)
+    }
+
+    pub fn y(&self) -> f64 {
+        with_object!(self, object => object.y)
+    }
+
+    pub fn width(&self) -> f64 {
+        with_object!(self, object => object.width)
+    }
+
+    pub fn height(&self) -> f64 {
+        with_object!(self, object => object.height)
+    }
+
+    pub fn rotation(&self) -> f64 {
+        with_object!(self, object => object.rotation)
+    }
+
+    pub fn visible(&self) -> bool {
+        with_object!(self, object => object.visible)
+    }
+
+    pub fn template(&self) -> Option<&str> {
+        with_object!(self, object => object.template.as_deref())
+    }
+
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        with_object!(self, object => {
+            object.x = x;
+            object.y = y;
+        })
+    }
+
+    pub fn properties(&self) -> Option<&[Property]> {
+        with_object!(self, object => object.properties.as_deref())
+    }
+
+    pub fn properties_mut(&mut self) -> &mut Option<Vec<Property>> {
+        with_object!(self, object => &mut object.properties)
+    }
+
+    /// Closed outline in map pixels with rotation applied, ellipses are
+    /// approximated by `ellipse_segments` points. Points and polylines have
+    /// no area and yield `None`.
+    pub fn outline(&self, ellipse_segments: usize) -> Option<Vec<(f64, f64)>> {
+        let local: Vec<(f64, f64)> = match self {
+            Object::Rectangle(RectangleObject { width, height, .. })
+            | Object::Text(TextObject { width, height, .. }) => {
+                vec![(0.0, 0.0), (*width, 0.0), (*width, *height), (0.0, *height)]
+            }
+            // Tile objects are anchored at their bottom-left corner
+            Object::General(GeneralObject { width, height, .. }) => {
+                vec![(0.0, -height), (*width, -height), (*width, 0.0), (0.0, 0.0)]
+            }
+            Object::Ellipse(EllipseObject { width, height, .. }) => {
+                let segments = ellipse_segments.max(3);
+                let (radius_x, radius_y) = (width / 2.0, height / 2.0);
+                (0..segments)
+                    .map(|i| {
+                        let angle = std::f64::consts::TAU * i as f64 / segments as f64;
+                        (
+                            radius_x + radius_x * angle.cos(),
+                            radius_y + radius_y * angle.sin(),
+                        )
+                    })
+                    .collect()
+            }
+            Object::Polygon(polygon) => polygon
+                .polygon
+                .iter()
+                .map(|point| (point.x, point.y))
+                .collect(),
+            Object::Point(_) | Object::Polyline(_) => return None,
+        };
+
+        let (sin, cos) = self.rotation().to_radians().sin_cos();
+        let (x, y) = (self.x(), self.y());
+        Some(
+            local
+                .into_iter()
+                .map(|(dx, dy)| (x + dx * cos - dy * sin, y + dx * sin + dy * cos))
+                .collect(),
+        )
+    }
+}
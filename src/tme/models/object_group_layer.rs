@@ -5,7 +5,7 @@ use super::layer::DrawOrder;
 use super::object::Object;
 use super::property::Property;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct ObjectGroupLayer {
     #[serde(rename = "draworder")]
@@ -4,7 +4,7 @@ use serde::Serialize;
 use super::object::Object;
 use super::tileset::TilesetRef;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct ObjectTemplate {
     #[serde(rename = "type")]
@@ -6,7 +6,7 @@ use std::path::PathBuf;
 use crate::tme::color::color_serde;
 use crate::tme::color::Color;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum Property {
     Int(IntProperty),
@@ -17,43 +17,82 @@ pub enum Property {
     String(StringProperty),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct IntProperty {
     pub name:  String,
     pub value: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BoolProperty {
     pub name:  String,
     pub value: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileProperty {
     pub name:  String,
     pub value: PathBuf,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ColorProperty {
     pub name:  String,
     #[serde(with = "color_serde")]
     pub value: Color,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FloatProperty {
     pub name:  String,
     pub value: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct StringProperty {
     pub name:  String,
     pub value: String,
 }
 
+impl Property {
+    pub fn name(&self) -> &str {
+        match self {
+            Property::Int(property) => &property.name,
+            Property::Bool(property) => &property.name,
+            Property::File(property) => &property.name,
+            Property::Color(property) => &property.name,
+            Property::Float(property) => &property.name,
+            Property::String(property) => &property.name,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Property::Int(property) => Some(property.value as f64),
+            Property::Float(property) => Some(property.value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Property::Bool(property) => Some(property.value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Property::String(property) => Some(&property.value),
+            _ => None,
+        }
+    }
+}
+
+pub fn find_property<'a>(properties: Option<&'a [Property]>, name: &str) -> Option<&'a Property> {
+    properties?.iter().find(|property| property.name() == name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,7 +3,7 @@ use serde::Serialize;
 
 use super::property::Property;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct Terrain {
     pub name:       String,
@@ -1,12 +1,15 @@
+use std::str::FromStr;
+
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::tme::color::color_serde;
 use crate::tme::color::Color;
+use crate::tme::error::Error;
 
 use super::utils;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct Text {
     #[serde(default = "utils::make_false")]
@@ -40,40 +43,57 @@ pub struct Text {
     pub wrap:        bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum HorizontalAlign {
     Center,
     Right,
     Justify,
+    #[default]
     Left,
 }
 
-impl Default for HorizontalAlign {
-    fn default() -> Self {
-        HorizontalAlign::Left
+impl FromStr for HorizontalAlign {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "center" => Ok(HorizontalAlign::Center),
+            "right" => Ok(HorizontalAlign::Right),
+            "justify" => Ok(HorizontalAlign::Justify),
+            "left" => Ok(HorizontalAlign::Left),
+            _ => Error::ParseHorizontalAlign(s.to_owned()).fail(),
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum VerticalAlign {
     Center,
     Bottom,
+    #[default]
     Top,
 }
 
-impl Default for VerticalAlign {
-    fn default() -> Self {
-        VerticalAlign::Top
+impl FromStr for VerticalAlign {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "center" => Ok(VerticalAlign::Center),
+            "bottom" => Ok(VerticalAlign::Bottom),
+            "top" => Ok(VerticalAlign::Top),
+            _ => Error::ParseVerticalAlign(s.to_owned()).fail(),
+        }
     }
 }
 
-fn default_font_family() -> String {
+pub(crate) fn default_font_family() -> String {
     "sans-serif".to_owned()
 }
 
-fn default_pixel_size() -> i32 {
+pub(crate) fn default_pixel_size() -> i32 {
     16
 }
 
@@ -5,7 +5,7 @@ use super::property::Property;
 use super::text::Text;
 use super::utils;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase", deny_unknown_fields)]
 pub struct TextObject {
     pub height:     f64,
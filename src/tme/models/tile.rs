@@ -5,7 +5,7 @@ use super::frame::Frame;
 use super::layer::Layer;
 use super::property::Property;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct Tile {
     pub animation:    Option<Vec<Frame>>,
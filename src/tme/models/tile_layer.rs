@@ -7,7 +7,7 @@ use super::layer::Compression;
 use super::layer::Encoding;
 use super::property::Property;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct TileLayer {
     pub chunks:      Option<Vec<Chunk>>,
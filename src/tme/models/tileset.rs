@@ -15,14 +15,15 @@ use crate::tme::color::color_serde;
 use crate::tme::color::opt_color_serde;
 use crate::tme::color::Color;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase", untagged)]
 pub enum TilesetContainer {
     Tileset(Tileset),
     TilesetRef(TilesetRef),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct Tileset {
     #[serde(with = "color_serde")]
@@ -30,6 +31,10 @@ pub struct Tileset {
     #[serde(default = "Color::new_transparent")]
     pub background_color:  Color,
     pub columns:           usize,
+    #[serde(rename = "firstgid")]
+    #[serde(default = "utils::make_none_option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_gid:         Option<i32>,
     #[serde(default = "utils::make_none_option")]
     pub grid:              Option<Grid>,
     #[serde(default = "utils::make_none_option")]
@@ -74,7 +79,7 @@ pub struct Tileset {
     pub wang_sets:         Option<Vec<WangSet>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct TilesetRef {
     #[serde(rename = "firstgid")]
@@ -82,6 +87,77 @@ pub struct TilesetRef {
     pub source:    PathBuf,
 }
 
+impl TilesetContainer {
+    pub fn first_gid(&self) -> Option<i32> {
+        match self {
+            TilesetContainer::Tileset(tileset) => tileset.first_gid,
+            TilesetContainer::TilesetRef(tileset_ref) => Some(tileset_ref.first_gid),
+        }
+    }
+
+    pub fn set_first_gid(&mut self, first_gid: i32) {
+        match self {
+            TilesetContainer::Tileset(tileset) => tileset.first_gid = Some(first_gid),
+            TilesetContainer::TilesetRef(tileset_ref) => tileset_ref.first_gid = first_gid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileImage<'a> {
+    pub image:        &'a str,
+    pub image_width:  i32,
+    pub image_height: i32,
+    pub x:            i32,
+    pub y:            i32,
+    pub width:        i32,
+    pub height:       i32,
+}
+
+impl Tileset {
+    pub fn tile(&self, id: i32) -> Option<&Tile> {
+        self.tiles.as_ref()?.iter().find(|tile| tile.id == id)
+    }
+
+    /// Image and source rectangle of the tile with the given local id, either
+    /// from the tileset atlas or from the tile itself for image collections
+    pub fn tile_image(&self, id: i32) -> Option<TileImage<'_>> {
+        if id < 0 {
+            return None;
+        }
+
+        if let Some(image) = &self.image {
+            let columns = self.columns as i32;
+            if columns == 0 || id as usize >= self.tile_count {
+                return None;
+            }
+
+            return Some(TileImage {
+                image,
+                image_width: self.image_width.unwrap_or_default(),
+                image_height: self.image_height.unwrap_or_default(),
+                x: self.margin + (id % columns) * (self.tile_width + self.spacing),
+                y: self.margin + (id / columns) * (self.tile_height + self.spacing),
+                width: self.tile_width,
+                height: self.tile_height,
+            });
+        }
+
+        let tile = self.tile(id)?;
+        let image_width = tile.image_width.unwrap_or(self.tile_width);
+        let image_height = tile.image_height.unwrap_or(self.tile_height);
+        Some(TileImage {
+            image: tile.image.as_deref()?,
+            image_width,
+            image_height,
+            x: 0,
+            y: 0,
+            width: image_width,
+            height: image_height,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,6 +196,7 @@ mod tests {
         let expecteds: Vec<Tileset> = vec![Tileset {
             background_color:  Color::new_transparent(),
             columns:           5,
+            first_gid:         None,
             grid:              None,
             image:             None,
             image_height:      None,
@@ -180,6 +257,7 @@ mod tests {
         let actuals: Vec<String> = vec![Tileset {
             background_color:  Color::new_transparent(),
             columns:           5,
+            first_gid:         None,
             grid:              None,
             image:             None,
             image_height:      None,
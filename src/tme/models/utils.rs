@@ -25,13 +25,18 @@ pub fn make_none_option<T>() -> Option<T> {
     None
 }
 
+/// Takes strings as they are and any other value, like the number `1.2` older
+/// Tiled versions write as format version, as its JSON text
 #[allow(dead_code)]
 pub fn deserialize_value_to_string<'de, D>(d: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
 {
     let v: serde_json::Value = Deserialize::deserialize(d)?;
-    Ok(v.to_string())
+    match v {
+        serde_json::Value::String(s) => Ok(s),
+        v => Ok(v.to_string()),
+    }
 }
 
 #[allow(dead_code)]
@@ -120,3 +125,53 @@ where
 
     deserializer.deserialize_any(StringOrStruct(PhantomData))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::models::Map;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+    use crate::tme::models::Tileset;
+
+    #[derive(Deserialize)]
+    struct Versioned {
+        #[serde(deserialize_with = "deserialize_value_to_string")]
+        version: String,
+    }
+
+    #[test]
+    fn reads_strings_and_other_values_as_string() {
+        let read = |value| serde_json::from_value::<Versioned>(value).unwrap().version;
+        assert_eq!(read(json!({ "version": "1.10" })), "1.10");
+        assert_eq!(read(json!({ "version": 1.2 })), "1.2");
+        assert_eq!(read(json!({ "version": true })), "true");
+    }
+
+    #[test]
+    fn round_trips_versions_of_maps_and_tilesets() {
+        let maps = vec![
+            MapBuilder::orthogonal(2, 2, 16, 16).build(),
+            MapBuilder::isometric(2, 2, 32, 16).build(),
+            MapBuilder::staggered(2, 2, 32, 16, StaggerAxis::Y, StaggerIndex::Odd).build(),
+            MapBuilder::hexagonal(2, 2, 32, 16, 8, StaggerAxis::X, StaggerIndex::Even).build(),
+        ];
+        for map in maps {
+            let value = serde_json::to_value(&map).unwrap();
+            let read: Map = serde_json::from_value(value.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&read).unwrap(), value);
+        }
+
+        let tileset = TilesetBuilder::new("tiles", 16, 16)
+            .with_image("tiles.png", 64, 16)
+            .build();
+        let value = serde_json::to_value(&tileset).unwrap();
+        let read: Tileset = serde_json::from_value(value).unwrap();
+        assert_eq!(read.version, tileset.version);
+    }
+}
@@ -5,7 +5,7 @@ use super::property::Property;
 use super::wang_color::WangColor;
 use super::wang_tile::WangTile;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub struct WangSet {
     #[serde(rename = "cornercolors")]
@@ -0,0 +1,424 @@
+use crate::tme::models::Orientation;
+use crate::tme::models::StaggerAxis;
+use crate::tme::models::StaggerIndex;
+use crate::tme::render::MapGeometry;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// Only tiles sharing an edge
+    Edges,
+    /// Tiles sharing an edge or a corner, ignored for hexagonal maps
+    EdgesAndCorners,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Neighbour {
+    pub x:      i32,
+    pub y:      i32,
+    pub corner: bool,
+}
+
+/// Tile neighbourhood rules for every map orientation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TileAdjacency {
+    pub orientation:   Orientation,
+    pub stagger_axis:  StaggerAxis,
+    pub stagger_index: StaggerIndex,
+    pub neighbourhood: Neighbourhood,
+}
+
+impl TileAdjacency {
+    pub fn new(geometry: &MapGeometry, neighbourhood: Neighbourhood) -> Self {
+        Self {
+            orientation: geometry.orientation,
+            stagger_axis: geometry.stagger_axis,
+            stagger_index: geometry.stagger_index,
+            neighbourhood,
+        }
+    }
+
+    pub fn neighbours(&self, x: i32, y: i32) -> Vec<Neighbour> {
+        let corners = self.neighbourhood == Neighbourhood::EdgesAndCorners;
+        let edge = |(x, y)| Neighbour {
+            x,
+            y,
+            corner: false,
+        };
+        let corner = |(x, y)| Neighbour { x, y, corner: true };
+
+        let mut result: Vec<Neighbour>;
+        match self.orientation {
+            Orientation::Orthogonal | Orientation::Isometric => {
+                result = [(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)]
+                    .iter()
+                    .copied()
+                    .map(edge)
+                    .collect();
+                if corners {
+                    result.extend(
+                        [
+                            (x + 1, y - 1),
+                            (x + 1, y + 1),
+                            (x - 1, y + 1),
+                            (x - 1, y - 1),
+                        ]
+                        .iter()
+                        .copied()
+                        .map(corner),
+                    );
+                }
+            }
+            Orientation::Staggered => {
+                result = self.diagonals(x, y).iter().copied().map(edge).collect();
+                if corners {
+                    let opposite = match self.stagger_axis {
+                        StaggerAxis::X => [(x, y - 1), (x + 2, y), (x, y + 1), (x - 2, y)],
+                        StaggerAxis::Y => [(x, y - 2), (x + 1, y), (x, y + 2), (x - 1, y)],
+                    };
+                    result.extend(opposite.iter().copied().map(corner));
+                }
+            }
+            Orientation::Hexagonal => {
+                result = self.diagonals(x, y).iter().copied().map(edge).collect();
+                let opposite = match self.stagger_axis {
+                    StaggerAxis::X => [(x, y - 1), (x, y + 1)],
+                    StaggerAxis::Y => [(x - 1, y), (x + 1, y)],
+                };
+                result.extend(opposite.iter().copied().map(edge));
+            }
+        }
+        result
+    }
+
+    /// Tiles that are edge neighbours of both given tiles, used to prevent
+    /// cutting corners
+    pub fn shared_edge_neighbours(&self, a: (i32, i32), b: (i32, i32)) -> Vec<(i32, i32)> {
+        let edges = |(x, y)| {
+            self.neighbours(x, y)
+                .into_iter()
+                .filter(|neighbour| !neighbour.corner)
+                .map(|neighbour| (neighbour.x, neighbour.y))
+                .collect::<Vec<_>>()
+        };
+        let b_edges = edges(b);
+        edges(a)
+            .into_iter()
+            .filter(|tile| b_edges.contains(tile))
+            .collect()
+    }
+
+    /// Lower bound of the number of steps between two tiles
+    pub fn distance(&self, a: (i32, i32), b: (i32, i32)) -> f64 {
+        let corners = self.neighbourhood == Neighbourhood::EdgesAndCorners;
+        match self.orientation {
+            Orientation::Orthogonal | Orientation::Isometric => {
+                grid_distance((b.0 - a.0) as f64, (b.1 - a.1) as f64, corners)
+            }
+            Orientation::Staggered => {
+                let (au, av) = self.diamond_coords(a);
+                let (bu, bv) = self.diamond_coords(b);
+                grid_distance((bu - au) as f64, (bv - av) as f64, corners)
+            }
+            Orientation::Hexagonal => {
+                let (aq, ar) = self.axial_coords(a);
+                let (bq, br) = self.axial_coords(b);
+                let (dq, dr) = (bq - aq, br - ar);
+                dq.abs().max(dr.abs()).max((dq + dr).abs()) as f64
+            }
+        }
+    }
+
+    /// Tiles crossed by a straight line between two tiles, both included.
+    /// Grids use Bresenham lines, hexagonal maps interpolate in cube
+    /// coordinates.
+    pub fn line(&self, from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+        match self.orientation {
+            Orientation::Orthogonal | Orientation::Isometric => bresenham(from, to),
+            Orientation::Staggered => bresenham(self.diamond_coords(from), self.diamond_coords(to))
+                .into_iter()
+                .map(|tile| self.diamond_to_tile(tile))
+                .collect(),
+            Orientation::Hexagonal => {
+                let (aq, ar) = self.axial_coords(from);
+                let (bq, br) = self.axial_coords(to);
+                let steps = self.distance(from, to) as i32;
+                if steps == 0 {
+                    return vec![from];
+                }
+
+                // Nudged so that lines along edges pick a consistent side
+                let (aq, ar) = (aq as f64 + 1e-6, ar as f64 + 2e-6);
+                let (bq, br) = (bq as f64 + 1e-6, br as f64 + 2e-6);
+                (0..=steps)
+                    .map(|step| {
+                        let t = step as f64 / steps as f64;
+                        let tile = cube_round(aq + (bq - aq) * t, ar + (br - ar) * t);
+                        self.axial_to_tile(tile)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn is_staggered(&self, index: i32) -> bool {
+        ((index & 1) != 0) ^ (self.stagger_index == StaggerIndex::Even)
+    }
+
+    /// The four neighbours in the diagonal screen directions of a staggered
+    /// or hexagonal tile
+    fn diagonals(&self, x: i32, y: i32) -> [(i32, i32); 4] {
+        match self.stagger_axis {
+            StaggerAxis::X => {
+                let top = if self.is_staggered(x) { y } else { y - 1 };
+                [
+                    (x + 1, top),
+                    (x + 1, top + 1),
+                    (x - 1, top + 1),
+                    (x - 1, top),
+                ]
+            }
+            StaggerAxis::Y => {
+                let left = if self.is_staggered(y) { x } else { x - 1 };
+                [
+                    (left + 1, y - 1),
+                    (left + 1, y + 1),
+                    (left, y + 1),
+                    (left, y - 1),
+                ]
+            }
+        }
+    }
+
+    /// Coordinates of a staggered tile on the equivalent isometric grid
+    pub(crate) fn diamond_coords(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        // Doubled coordinates share their parity, which only depends on the
+        // stagger index
+        let (u, v) = match self.stagger_axis {
+            StaggerAxis::X => {
+                let sum = 2 * y + self.is_staggered(x) as i32;
+                (sum + x, sum - x)
+            }
+            StaggerAxis::Y => {
+                let difference = 2 * x + self.is_staggered(y) as i32;
+                (y + difference, y - difference)
+            }
+        };
+        (u.div_euclid(2), v.div_euclid(2))
+    }
+
+    pub(crate) fn diamond_to_tile(&self, (u, v): (i32, i32)) -> (i32, i32) {
+        let parity = (self.stagger_index == StaggerIndex::Even) as i32;
+        match self.stagger_axis {
+            StaggerAxis::X => {
+                let x = u - v;
+                (
+                    x,
+                    (u + v + parity - self.is_staggered(x) as i32).div_euclid(2),
+                )
+            }
+            StaggerAxis::Y => {
+                let y = u + v + parity;
+                ((u - v - self.is_staggered(y) as i32).div_euclid(2), y)
+            }
+        }
+    }
+
+    /// Axial coordinates of a hexagonal tile
+    pub(crate) fn axial_coords(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self.stagger_axis {
+            StaggerAxis::X => (x, y - self.shifted_before(x)),
+            StaggerAxis::Y => (x - self.shifted_before(y), y),
+        }
+    }
+
+    pub(crate) fn axial_to_tile(&self, (q, r): (i32, i32)) -> (i32, i32) {
+        match self.stagger_axis {
+            StaggerAxis::X => (q, r + self.shifted_before(q)),
+            StaggerAxis::Y => (q + self.shifted_before(r), r),
+        }
+    }
+
+    /// Number of shifted rows or columns before the given index
+    fn shifted_before(&self, index: i32) -> i32 {
+        match self.stagger_index {
+            StaggerIndex::Odd => (index - (index & 1)) / 2,
+            StaggerIndex::Even => (index + (index & 1)) / 2,
+        }
+    }
+}
+
+fn bresenham(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    let mut tiles = vec![from];
+    while (x, y) != to {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        tiles.push((x, y));
+    }
+    tiles
+}
+
+fn cube_round(q: f64, r: f64) -> (i32, i32) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as i32, rr as i32)
+}
+
+fn grid_distance(dx: f64, dy: f64, corners: bool) -> f64 {
+    let (dx, dy) = (dx.abs(), dy.abs());
+    if corners {
+        dx.max(dy) + (std::f64::consts::SQRT_2 - 1.0) * dx.min(dy)
+    } else {
+        dx + dy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adjacency(
+        orientation: Orientation,
+        stagger_axis: StaggerAxis,
+        stagger_index: StaggerIndex,
+    ) -> TileAdjacency {
+        TileAdjacency {
+            orientation,
+            stagger_axis,
+            stagger_index,
+            neighbourhood: Neighbourhood::Edges,
+        }
+    }
+
+    fn positions(neighbours: Vec<Neighbour>) -> Vec<(i32, i32)> {
+        let mut positions: Vec<_> = neighbours.into_iter().map(|n| (n.x, n.y)).collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    #[test]
+    fn orthogonal_neighbours() {
+        let mut adjacency = adjacency(Orientation::Orthogonal, StaggerAxis::Y, StaggerIndex::Odd);
+        assert_eq!(
+            positions(adjacency.neighbours(0, 0)),
+            vec![(-1, 0), (0, -1), (0, 1), (1, 0)]
+        );
+
+        adjacency.neighbourhood = Neighbourhood::EdgesAndCorners;
+        let neighbours = adjacency.neighbours(0, 0);
+        assert_eq!(neighbours.len(), 8);
+        assert_eq!(neighbours.iter().filter(|n| n.corner).count(), 4);
+        assert_eq!(
+            adjacency.shared_edge_neighbours((0, 0), (1, 1)),
+            vec![(1, 0), (0, 1)]
+        );
+    }
+
+    #[test]
+    fn staggered_neighbours() {
+        let adjacency = adjacency(Orientation::Staggered, StaggerAxis::Y, StaggerIndex::Odd);
+        // Odd rows are shifted to the right
+        assert_eq!(
+            positions(adjacency.neighbours(1, 1)),
+            vec![(1, 0), (1, 2), (2, 0), (2, 2)]
+        );
+        assert_eq!(
+            positions(adjacency.neighbours(1, 2)),
+            vec![(0, 1), (0, 3), (1, 1), (1, 3)]
+        );
+        assert_eq!(adjacency.distance((1, 1), (2, 0)), 1.0);
+        assert_eq!(adjacency.distance((1, 1), (1, 3)), 2.0);
+    }
+
+    #[test]
+    fn hexagonal_neighbours() {
+        let adjacency = adjacency(Orientation::Hexagonal, StaggerAxis::X, StaggerIndex::Even);
+        // Even columns are shifted down
+        assert_eq!(
+            positions(adjacency.neighbours(2, 2)),
+            vec![(1, 2), (1, 3), (2, 1), (2, 3), (3, 2), (3, 3)]
+        );
+        assert_eq!(
+            positions(adjacency.neighbours(1, 2)),
+            vec![(0, 1), (0, 2), (1, 1), (1, 3), (2, 1), (2, 2)]
+        );
+
+        for (x, y) in &[(2, 2), (1, 2), (-3, -1)] {
+            for neighbour in adjacency.neighbours(*x, *y) {
+                assert_eq!(
+                    adjacency.distance((*x, *y), (neighbour.x, neighbour.y)),
+                    1.0
+                );
+            }
+        }
+        assert_eq!(adjacency.distance((0, 0), (4, 0)), 4.0);
+
+        let adjacency = TileAdjacency {
+            stagger_axis: StaggerAxis::Y,
+            stagger_index: StaggerIndex::Odd,
+            ..adjacency
+        };
+        for (x, y) in &[(2, 2), (1, 1), (-2, -3)] {
+            for neighbour in adjacency.neighbours(*x, *y) {
+                assert_eq!(
+                    adjacency.distance((*x, *y), (neighbour.x, neighbour.y)),
+                    1.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn coordinate_round_trips() {
+        for &axis in &[StaggerAxis::X, StaggerAxis::Y] {
+            for &index in &[StaggerIndex::Odd, StaggerIndex::Even] {
+                let staggered = adjacency(Orientation::Staggered, axis, index);
+                let hexagonal = adjacency(Orientation::Hexagonal, axis, index);
+                for x in -3..3 {
+                    for y in -3..3 {
+                        let diamond = staggered.diamond_coords((x, y));
+                        assert_eq!(staggered.diamond_to_tile(diamond), (x, y));
+                        let axial = hexagonal.axial_coords((x, y));
+                        assert_eq!(hexagonal.axial_to_tile(axial), (x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let orthogonal = adjacency(Orientation::Orthogonal, StaggerAxis::Y, StaggerIndex::Odd);
+        assert_eq!(
+            orthogonal.line((0, 0), (4, 2)),
+            vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]
+        );
+        assert_eq!(orthogonal.line((1, 1), (1, 1)), vec![(1, 1)]);
+
+        let hexagonal = adjacency(Orientation::Hexagonal, StaggerAxis::Y, StaggerIndex::Odd);
+        let line = hexagonal.line((0, 0), (3, 4));
+        assert_eq!(line.len() as f64, hexagonal.distance((0, 0), (3, 4)) + 1.0);
+        for pair in line.windows(2) {
+            assert_eq!(hexagonal.distance(pair[0], pair[1]), 1.0);
+        }
+
+        let staggered = adjacency(Orientation::Staggered, StaggerAxis::Y, StaggerIndex::Odd);
+        assert_eq!(staggered.line((1, 1), (1, 5)), vec![(1, 1), (1, 3), (1, 5)]);
+    }
+}
@@ -0,0 +1,11 @@
+pub mod adjacency;
+pub mod navmesh;
+pub mod pathfinding;
+pub mod regions;
+pub mod visibility;
+
+pub use adjacency::*;
+pub use navmesh::*;
+pub use pathfinding::*;
+pub use regions::*;
+pub use visibility::*;
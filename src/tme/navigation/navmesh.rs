@@ -0,0 +1,793 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use crate::tme::gid::Gid;
+use crate::tme::models::Object;
+use crate::tme::models::Orientation;
+use crate::tme::render::MapGeometry;
+use crate::tme::tile_grid::TileGrid;
+
+const EPSILON: f64 = 1e-6;
+
+pub type Vec2 = (f64, f64);
+
+/// Collects walkable areas and obstacles in pixel space. Object coordinates
+/// are used as they are, so objects of isometric maps have to be projected
+/// by the caller.
+#[derive(Debug, Clone)]
+pub struct NavMeshBuilder {
+    walkable:         Vec<Vec<Vec2>>,
+    obstacles:        Vec<Vec<Vec2>>,
+    ellipse_segments: usize,
+}
+
+impl Default for NavMeshBuilder {
+    fn default() -> Self {
+        Self {
+            walkable:         Vec::new(),
+            obstacles:        Vec::new(),
+            ellipse_segments: 16,
+        }
+    }
+}
+
+impl NavMeshBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of points used to approximate ellipse obstacles
+    pub fn with_ellipse_segments(mut self, ellipse_segments: usize) -> Self {
+        self.ellipse_segments = ellipse_segments.max(3);
+        self
+    }
+
+    pub fn add_walkable(&mut self, polygon: Vec<Vec2>) {
+        self.walkable.push(polygon);
+    }
+
+    pub fn add_obstacle(&mut self, polygon: Vec<Vec2>) {
+        self.obstacles.push(polygon);
+    }
+
+    /// Polygon objects accepted by `is_walkable` become walkable areas, every
+    /// other object with an area is an obstacle
+    pub fn add_objects<'a, I, F>(&mut self, objects: I, offset: Vec2, is_walkable: F)
+    where
+        I: IntoIterator<Item = &'a Object>,
+        F: Fn(&Object) -> bool,
+    {
+        for object in objects {
+            let outline = match object.outline(self.ellipse_segments) {
+                Some(outline) => outline,
+                None => continue,
+            };
+            let outline = outline
+                .into_iter()
+                .map(|(x, y)| (x + offset.0, y + offset.1))
+                .collect();
+
+            match object {
+                Object::Polygon(_) if is_walkable(object) => self.add_walkable(outline),
+                _ => self.add_obstacle(outline),
+            }
+        }
+    }
+
+    /// Every non-empty tile accepted by `is_walkable` becomes a walkable area.
+    /// Runs of orthogonal tiles are merged to keep the mesh small.
+    pub fn add_walkable_tiles<F>(&mut self, geometry: &MapGeometry, grid: &TileGrid, is_walkable: F)
+    where
+        F: Fn(Gid) -> bool,
+    {
+        let walkable = |x, y| grid.get(x, y).is_some_and(&is_walkable);
+
+        if geometry.orientation != Orientation::Orthogonal {
+            for (x, y, gid) in grid.cells() {
+                if is_walkable(gid) {
+                    self.add_walkable(geometry.tile_polygon(x, y));
+                }
+            }
+            return;
+        }
+
+        for y in grid.y..grid.y + grid.height {
+            let mut x = grid.x;
+            while x < grid.x + grid.width {
+                if !walkable(x, y) {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+                while x < grid.x + grid.width && walkable(x, y) {
+                    x += 1;
+                }
+                let (left, top) = geometry.tile_to_pixel(start, y);
+                let (right, bottom) = geometry.tile_to_pixel(x, y + 1);
+                self.add_walkable(vec![
+                    (left, top),
+                    (right, top),
+                    (right, bottom),
+                    (left, bottom),
+                ]);
+            }
+        }
+    }
+
+    pub fn build(&self) -> NavMesh {
+        let mut pieces: Vec<Vec<Vec2>> = self
+            .walkable
+            .iter()
+            .flat_map(|polygon| triangulate(polygon))
+            .map(|triangle| triangle.to_vec())
+            .collect();
+
+        for obstacle in &self.obstacles {
+            for part in triangulate(obstacle) {
+                pieces = pieces
+                    .into_iter()
+                    .flat_map(|piece| subtract_convex(piece, &part))
+                    .collect();
+            }
+        }
+
+        let triangles = pieces
+            .iter()
+            .flat_map(|piece| (1..piece.len() - 1).map(move |i| [piece[0], piece[i], piece[i + 1]]))
+            .filter(|triangle| area2(triangle) > EPSILON)
+            .collect();
+
+        NavMesh::new(triangles)
+    }
+}
+
+/// Edge shared between two triangles of the mesh
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NavLink {
+    pub triangle: usize,
+    pub portal:   [Vec2; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavPath {
+    pub points: Vec<Vec2>,
+    pub length: f64,
+}
+
+/// Triangles in counterclockwise order with their connections
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavMesh {
+    triangles: Vec<[Vec2; 3]>,
+    links:     Vec<Vec<NavLink>>,
+}
+
+impl NavMesh {
+    pub fn new(triangles: Vec<[Vec2; 3]>) -> Self {
+        let triangles: Vec<[Vec2; 3]> = triangles
+            .into_iter()
+            .map(|[a, b, c]| {
+                if cross(a, b, c) < 0.0 {
+                    [a, c, b]
+                } else {
+                    [a, b, c]
+                }
+            })
+            .collect();
+
+        let mut links = vec![Vec::new(); triangles.len()];
+        let buckets = Buckets::new(&triangles);
+        for i in 0..triangles.len() {
+            for j in buckets.candidates(i) {
+                if let Some(portal) = shared_edge(&triangles[i], &triangles[j]) {
+                    links[i].push(NavLink {
+                        triangle: j,
+                        portal,
+                    });
+                    links[j].push(NavLink {
+                        triangle: i,
+                        portal,
+                    });
+                }
+            }
+        }
+
+        Self { triangles, links }
+    }
+
+    pub fn triangles(&self) -> &[[Vec2; 3]] {
+        &self.triangles
+    }
+
+    pub fn links(&self, triangle: usize) -> &[NavLink] {
+        self.links.get(triangle).map_or(&[], Vec::as_slice)
+    }
+
+    /// Index of a triangle containing the point, points on edges count
+    pub fn locate(&self, point: Vec2) -> Option<usize> {
+        self.triangles.iter().position(|&[a, b, c]| {
+            let tolerance = |p, q| EPSILON * distance(p, q).max(1.0);
+            cross(a, b, point) >= -tolerance(a, b)
+                && cross(b, c, point) >= -tolerance(b, c)
+                && cross(c, a, point) >= -tolerance(c, a)
+        })
+    }
+
+    /// Shortest route through the triangle corridor found by A*, straightened
+    /// with the funnel algorithm
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<NavPath> {
+        let start_triangle = self.locate(start)?;
+        let goal_triangle = self.locate(goal)?;
+        let corridor = self.find_corridor(start_triangle, goal_triangle, start, goal)?;
+
+        let mut portals = vec![(start, start)];
+        for pair in corridor.windows(2) {
+            let link = self.links[pair[0]]
+                .iter()
+                .find(|link| link.triangle == pair[1])?;
+            let [p, q] = link.portal;
+            // Orient the portal as seen from inside the current triangle
+            let center = centroid(&self.triangles[pair[0]]);
+            portals.push(if cross(center, p, q) > 0.0 {
+                (q, p)
+            } else {
+                (p, q)
+            });
+        }
+        portals.push((goal, goal));
+
+        let points = string_pull(&portals);
+        let length = points
+            .windows(2)
+            .map(|pair| distance(pair[0], pair[1]))
+            .sum();
+        Some(NavPath { points, length })
+    }
+
+    fn find_corridor(&self, start: usize, goal: usize, from: Vec2, to: Vec2) -> Option<Vec<usize>> {
+        let count = self.triangles.len();
+        let mut costs = vec![f64::INFINITY; count];
+        let mut positions = vec![from; count];
+        let mut previous = vec![usize::MAX; count];
+        let mut queue = BinaryHeap::new();
+
+        costs[start] = 0.0;
+        queue.push(QueueItem {
+            priority: distance(from, to),
+            cost:     0.0,
+            index:    start,
+        });
+
+        while let Some(QueueItem { cost, index, .. }) = queue.pop() {
+            if index == goal {
+                let mut corridor = vec![goal];
+                let mut current = goal;
+                while current != start {
+                    current = previous[current];
+                    corridor.push(current);
+                }
+                corridor.reverse();
+                return Some(corridor);
+            }
+            if cost > costs[index] {
+                continue;
+            }
+
+            for link in &self.links[index] {
+                let [p, q] = link.portal;
+                let entry = ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0);
+                let next_cost = cost + distance(positions[index], entry);
+                if next_cost < costs[link.triangle] {
+                    costs[link.triangle] = next_cost;
+                    positions[link.triangle] = entry;
+                    previous[link.triangle] = index;
+                    queue.push(QueueItem {
+                        priority: next_cost + distance(entry, to),
+                        cost:     next_cost,
+                        index:    link.triangle,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct QueueItem {
+    priority: f64,
+    cost:     f64,
+    index:    usize,
+}
+
+impl PartialEq for QueueItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueItem {}
+
+impl PartialOrd for QueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// Simple stupid funnel algorithm, portals are `(left, right)` pairs where
+/// left lies counterclockwise of right when seen from the previous triangle.
+/// Points collinear with the apex count as inside the funnel, axis aligned
+/// obstacles produce such portals all the time.
+fn string_pull(portals: &[(Vec2, Vec2)]) -> Vec<Vec2> {
+    let mut points = vec![portals[0].0];
+    let mut apex = portals[0].0;
+    let (mut left, mut right) = portals[0];
+    let (mut left_index, mut right_index) = (0, 0);
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+
+        if cross(apex, right, next_right) >= 0.0 {
+            if apex == right || cross(apex, left, next_right) <= 0.0 {
+                right = next_right;
+                right_index = i;
+            } else {
+                apex = left;
+                points.push(apex);
+                right = apex;
+                right_index = left_index;
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        if cross(apex, left, next_left) <= 0.0 {
+            if apex == left || cross(apex, right, next_left) >= 0.0 {
+                left = next_left;
+                left_index = i;
+            } else {
+                apex = right;
+                points.push(apex);
+                left = apex;
+                left_index = right_index;
+                i = right_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    let goal = portals[portals.len() - 1].0;
+    if points.last() != Some(&goal) {
+        points.push(goal);
+    }
+    points
+}
+
+fn cross(a: Vec2, b: Vec2, c: Vec2) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+fn distance(a: Vec2, b: Vec2) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+fn area2(polygon: &[Vec2]) -> f64 {
+    (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum()
+}
+
+fn centroid(triangle: &[Vec2; 3]) -> Vec2 {
+    (
+        (triangle[0].0 + triangle[1].0 + triangle[2].0) / 3.0,
+        (triangle[0].1 + triangle[1].1 + triangle[2].1) / 3.0,
+    )
+}
+
+/// Uniform grid over the triangle bounds, so neighbour search only compares
+/// triangles close to each other. Neighbours may share just a part of an
+/// edge where obstacles split the mesh, so edges can't be matched exactly.
+struct Buckets {
+    bounds: Vec<(Vec2, Vec2)>,
+    size:   f64,
+    cells:  HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl Buckets {
+    fn new(triangles: &[[Vec2; 3]]) -> Self {
+        let bounds: Vec<(Vec2, Vec2)> = triangles.iter().map(|triangle| bounds(triangle)).collect();
+        let extent: f64 = bounds
+            .iter()
+            .map(|(min, max)| (max.0 - min.0).max(max.1 - min.1))
+            .sum();
+        let size = (extent / bounds.len().max(1) as f64).max(EPSILON);
+
+        let mut buckets = Self {
+            bounds,
+            size,
+            cells: HashMap::new(),
+        };
+        for index in 0..buckets.bounds.len() {
+            for cell in buckets.cells_of(index) {
+                buckets.cells.entry(cell).or_default().push(index);
+            }
+        }
+        buckets
+    }
+
+    fn cells_of(&self, index: usize) -> impl Iterator<Item = (i64, i64)> {
+        let (min, max) = self.bounds[index];
+        let cell = |value: f64| ((value / self.size).floor()) as i64;
+        let (left, top) = (cell(min.0 - EPSILON), cell(min.1 - EPSILON));
+        let (right, bottom) = (cell(max.0 + EPSILON), cell(max.1 + EPSILON));
+        (top..=bottom).flat_map(move |y| (left..=right).map(move |x| (x, y)))
+    }
+
+    /// Triangles after `index` whose bounds touch its bounds, in ascending
+    /// order
+    fn candidates(&self, index: usize) -> Vec<usize> {
+        let (min_a, max_a) = self.bounds[index];
+        let mut candidates: Vec<usize> = self
+            .cells_of(index)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|&other| other > index)
+            .filter(|&other| {
+                let (min_b, max_b) = self.bounds[other];
+                min_a.0 <= max_b.0 + EPSILON
+                    && min_b.0 <= max_a.0 + EPSILON
+                    && min_a.1 <= max_b.1 + EPSILON
+                    && min_b.1 <= max_a.1 + EPSILON
+            })
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+}
+
+fn bounds(points: &[Vec2]) -> (Vec2, Vec2) {
+    points.iter().fold(
+        ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN)),
+        |(min, max), point| {
+            (
+                (min.0.min(point.0), min.1.min(point.1)),
+                (max.0.max(point.0), max.1.max(point.1)),
+            )
+        },
+    )
+}
+
+/// Removes duplicate and collinear points and orders the polygon
+/// counterclockwise
+fn normalize(polygon: &[Vec2]) -> Vec<Vec2> {
+    let mut points: Vec<Vec2> = Vec::with_capacity(polygon.len());
+    for &point in polygon {
+        if points
+            .last()
+            .is_none_or(|&last| distance(last, point) > EPSILON)
+        {
+            points.push(point);
+        }
+    }
+    while points.len() > 1 && distance(points[0], points[points.len() - 1]) <= EPSILON {
+        points.pop();
+    }
+
+    let mut i = 0;
+    while points.len() >= 3 && i < points.len() {
+        let count = points.len();
+        let (a, b, c) = (
+            points[(i + count - 1) % count],
+            points[i],
+            points[(i + 1) % count],
+        );
+        if cross(a, b, c).abs() <= EPSILON * distance(a, c).max(1.0) {
+            points.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+
+    if area2(&points) < 0.0 {
+        points.reverse();
+    }
+    points
+}
+
+/// Ear clipping triangulation of a simple polygon
+fn triangulate(polygon: &[Vec2]) -> Vec<[Vec2; 3]> {
+    let mut points = normalize(polygon);
+    let mut triangles = Vec::new();
+    if points.len() < 3 {
+        return triangles;
+    }
+
+    while points.len() > 3 {
+        let count = points.len();
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = (
+                points[(i + count - 1) % count],
+                points[i],
+                points[(i + 1) % count],
+            );
+            cross(a, b, c) > 0.0
+                && points
+                    .iter()
+                    .filter(|&&point| point != a && point != b && point != c)
+                    .all(|&point| !contains(&[a, b, c], point))
+        });
+
+        // Self-intersecting input has no ear left, keep what was found
+        let i = match ear {
+            Some(i) => i,
+            None => return triangles,
+        };
+        triangles.push([
+            points[(i + count - 1) % count],
+            points[i],
+            points[(i + 1) % count],
+        ]);
+        points.remove(i);
+    }
+
+    triangles.push([points[0], points[1], points[2]]);
+    triangles
+}
+
+/// Whether the point lies inside or on the border of a counterclockwise
+/// triangle
+fn contains(triangle: &[Vec2; 3], point: Vec2) -> bool {
+    let [a, b, c] = *triangle;
+    cross(a, b, point) >= 0.0 && cross(b, c, point) >= 0.0 && cross(c, a, point) >= 0.0
+}
+
+/// Splits a convex polygon minus a convex counterclockwise obstacle into
+/// convex pieces
+fn subtract_convex(mut polygon: Vec<Vec2>, obstacle: &[Vec2; 3]) -> Vec<Vec<Vec2>> {
+    let (min_a, max_a) = bounds(&polygon);
+    let (min_b, max_b) = bounds(obstacle);
+    if min_a.0 >= max_b.0 - EPSILON
+        || min_b.0 >= max_a.0 - EPSILON
+        || min_a.1 >= max_b.1 - EPSILON
+        || min_b.1 >= max_a.1 - EPSILON
+    {
+        return vec![polygon];
+    }
+
+    let mut pieces = Vec::new();
+    for i in 0..obstacle.len() {
+        let (a, b) = (obstacle[i], obstacle[(i + 1) % obstacle.len()]);
+        let outside = clip(&polygon, a, b, -1.0);
+        if area2(&outside) > EPSILON {
+            pieces.push(outside);
+        }
+        polygon = clip(&polygon, a, b, 1.0);
+        if area2(&polygon) <= EPSILON {
+            break;
+        }
+    }
+    pieces
+}
+
+/// Sutherland-Hodgman clipping of a convex polygon against the half-plane on
+/// the `side` of the line through `a` and `b`
+fn clip(polygon: &[Vec2], a: Vec2, b: Vec2, side: f64) -> Vec<Vec2> {
+    let mut result = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let (p, q) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        let (p_side, q_side) = (side * cross(a, b, p), side * cross(a, b, q));
+
+        if p_side >= 0.0 {
+            result.push(p);
+        }
+        if (p_side > 0.0 && q_side < 0.0) || (p_side < 0.0 && q_side > 0.0) {
+            let t = p_side / (p_side - q_side);
+            result.push((p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t));
+        }
+    }
+    result
+}
+
+/// Overlap of two collinear edges of triangles lying on opposite sides
+fn shared_edge(first: &[Vec2; 3], second: &[Vec2; 3]) -> Option<[Vec2; 2]> {
+    for i in 0..3 {
+        let (p, q) = (first[i], first[(i + 1) % 3]);
+        let length = distance(p, q);
+        let direction = ((q.0 - p.0) / length, (q.1 - p.1) / length);
+
+        for j in 0..3 {
+            let (r, s) = (second[j], second[(j + 1) % 3]);
+            if (cross(p, q, r) / length).abs() > EPSILON
+                || (cross(p, q, s) / length).abs() > EPSILON
+            {
+                continue;
+            }
+
+            // Triangles are counterclockwise, so neighbours run the edge in
+            // opposite directions
+            let project =
+                |point: Vec2| (point.0 - p.0) * direction.0 + (point.1 - p.1) * direction.1;
+            let (t_r, t_s) = (project(r), project(s));
+            if t_r <= t_s {
+                continue;
+            }
+
+            let from = t_s.max(0.0);
+            let to = t_r.min(length);
+            if to - from > EPSILON {
+                let at = |t: f64| (p.0 + direction.0 * t, p.1 + direction.1 * t);
+                return Some([at(from), at(to)]);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::models::RenderOrder;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+
+    use serde_json::json;
+
+    fn assert_points(actual: &[Vec2], expected: &[Vec2]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(distance(*a, *e) < 1e-6, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn object(value: serde_json::Value) -> Object {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn l_shape() -> Object {
+        object(json! {
+            {
+                "id":       1,
+                "name":     "floor",
+                "type":     "walkable",
+                "polygon":  [
+                    { "x": 0,   "y": 0 },
+                    { "x": 100, "y": 0 },
+                    { "x": 100, "y": 100 },
+                    { "x": 80,  "y": 100 },
+                    { "x": 80,  "y": 20 },
+                    { "x": 0,   "y": 20 }
+                ],
+                "rotation": 0,
+                "visible":  true,
+                "width":    0,
+                "height":   0,
+                "x":        0,
+                "y":        0
+            }
+        })
+    }
+
+    #[test]
+    fn smooths_path_around_corner() {
+        let mut builder = NavMeshBuilder::new();
+        builder.add_objects(&[l_shape()], (0.0, 0.0), |object| {
+            object.obj_type() == "walkable"
+        });
+        let mesh = builder.build();
+        assert_eq!(mesh.triangles().len(), 4);
+
+        let path = mesh.find_path((10.0, 10.0), (90.0, 90.0)).unwrap();
+        assert_points(&path.points, &[(10.0, 10.0), (80.0, 20.0), (90.0, 90.0)]);
+        assert!((path.length - (70.0f64.hypot(10.0) + 10.0f64.hypot(70.0))).abs() < 1e-6);
+
+        let straight = mesh.find_path((10.0, 10.0), (90.0, 10.0)).unwrap();
+        assert_points(&straight.points, &[(10.0, 10.0), (90.0, 10.0)]);
+
+        assert!(mesh.find_path((10.0, 10.0), (50.0, 50.0)).is_none());
+    }
+
+    #[test]
+    fn avoids_obstacles() {
+        let geometry = MapGeometry {
+            orientation:     Orientation::Orthogonal,
+            render_order:    RenderOrder::RightDown,
+            map_width:       5,
+            map_height:      5,
+            tile_width:      10,
+            tile_height:     10,
+            stagger_axis:    StaggerAxis::Y,
+            stagger_index:   StaggerIndex::Odd,
+            hex_side_length: 0,
+        };
+        let grid = TileGrid {
+            x:      0,
+            y:      0,
+            width:  5,
+            height: 5,
+            tiles:  vec![1; 25],
+        };
+        let wall = object(json! {
+            {
+                "id":       2,
+                "name":     "wall",
+                "type":     "",
+                "rotation": 0,
+                "visible":  true,
+                "width":    10,
+                "height":   40,
+                "x":        20,
+                "y":        0
+            }
+        });
+
+        let mut builder = NavMeshBuilder::new();
+        builder.add_walkable_tiles(&geometry, &grid, |gid| gid.id() == 1);
+        builder.add_objects(&[wall], (0.0, 0.0), |_| false);
+        let mesh = builder.build();
+
+        assert!(mesh.locate((25.0, 20.0)).is_none());
+        let path = mesh.find_path((5.0, 5.0), (45.0, 5.0)).unwrap();
+        assert_points(
+            &path.points,
+            &[(5.0, 5.0), (20.0, 40.0), (30.0, 40.0), (45.0, 5.0)],
+        );
+    }
+
+    #[test]
+    fn links_same_neighbours_as_pairwise_search() {
+        let mut builder = NavMeshBuilder::new();
+        for y in 0..6 {
+            for x in 0..6 {
+                let (left, top) = (x as f64 * 10.0, y as f64 * 10.0);
+                builder.add_walkable(vec![
+                    (left, top),
+                    (left + 10.0, top),
+                    (left + 10.0, top + 10.0),
+                    (left, top + 10.0),
+                ]);
+            }
+        }
+        builder.add_obstacle(vec![(15.0, 15.0), (45.0, 15.0), (45.0, 25.0), (15.0, 25.0)]);
+        let mesh = builder.build();
+
+        let triangles = mesh.triangles();
+        for (i, first) in triangles.iter().enumerate() {
+            let expected: Vec<usize> = (0..triangles.len())
+                .filter(|&j| j != i && shared_edge(first, &triangles[j]).is_some())
+                .collect();
+            let mut actual: Vec<usize> = mesh.links(i).iter().map(|link| link.triangle).collect();
+            actual.sort_unstable();
+            assert_eq!(actual, expected, "triangle {}", i);
+        }
+    }
+
+    #[test]
+    fn subtracts_convex_obstacles() {
+        let square = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let hole = [(4.0, 4.0), (6.0, 4.0), (5.0, 6.0)];
+        let pieces = subtract_convex(square, &hole);
+        let area: f64 = pieces.iter().map(|piece| area2(piece) / 2.0).sum();
+        assert!((area - 98.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,457 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::adjacency::Neighbourhood;
+use super::adjacency::TileAdjacency;
+
+use crate::tme::error::Error;
+use crate::tme::models::find_property;
+use crate::tme::models::Map;
+use crate::tme::models::TileLayer;
+use crate::tme::render::MapGeometry;
+use crate::tme::tile_grid::TileGrid;
+use crate::tme::tileset_lookup::TilesetLookup;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Algorithm {
+    AStar,
+    Dijkstra,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub tiles: Vec<(i32, i32)>,
+    pub cost:  f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PathGridOptions<'a> {
+    pub neighbourhood: Neighbourhood,
+    /// Float or int tile property holding the cost of entering a tile,
+    /// tiles without it cost `1`, negative values make a tile impassable
+    pub cost_property: &'a str,
+    /// Any non-empty tile of this layer blocks movement
+    pub walls:         Option<&'a TileLayer>,
+}
+
+impl<'a> Default for PathGridOptions<'a> {
+    fn default() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::Edges,
+            cost_property: "cost",
+            walls:         None,
+        }
+    }
+}
+
+/// Cost of entering each tile of a rectangular area, `None` for impassable
+/// tiles. Corner moves cost `sqrt(2)` times more and are only allowed when
+/// the tiles they cut through are passable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathGrid {
+    pub adjacency: TileAdjacency,
+    pub x:         i32,
+    pub y:         i32,
+    pub width:     i32,
+    pub height:    i32,
+    costs:         Vec<Option<f64>>,
+}
+
+impl PathGrid {
+    pub fn new(adjacency: TileAdjacency, x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            adjacency,
+            x,
+            y,
+            width,
+            height,
+            costs: vec![Some(1.0); (width.max(0) * height.max(0)) as usize],
+        }
+    }
+
+    /// Builds the grid from the tiles of `ground`, empty ground tiles are
+    /// impassable
+    pub fn from_layers(
+        geometry: &MapGeometry,
+        tilesets: &TilesetLookup,
+        ground: &TileLayer,
+        options: &PathGridOptions,
+    ) -> Result<Self, Error> {
+        let ground = ground.decode_grid()?;
+        let walls = match options.walls {
+            Some(walls) => Some(walls.decode_grid()?),
+            None => None,
+        };
+
+        let adjacency = TileAdjacency::new(geometry, options.neighbourhood);
+        let mut grid = Self::new(adjacency, ground.x, ground.y, ground.width, ground.height);
+        for (x, y, gid) in ground.cells() {
+            let blocked = walls
+                .as_ref()
+                .and_then(|walls| walls.get(x, y))
+                .is_some_and(|wall| !wall.is_empty());
+
+            let cost = if gid.is_empty() || blocked {
+                None
+            } else {
+                let cost = tilesets
+                    .tile(gid)
+                    .and_then(|tile| {
+                        find_property(tile.properties.as_deref(), options.cost_property)
+                    })
+                    .and_then(|property| property.as_f64())
+                    .unwrap_or(1.0);
+                Some(cost).filter(|cost| *cost >= 0.0)
+            };
+            grid.set_cost(x, y, cost);
+        }
+
+        Ok(grid)
+    }
+
+    pub fn from_map(map: &Map, ground: &str, options: &PathGridOptions) -> Result<Self, Error> {
+        let layer = map
+            .find_tile_layer(ground)
+            .ok_or_else(|| Error::LayerNotFound(ground.to_owned()))?;
+        Self::from_layers(
+            &MapGeometry::from_map(map),
+            &TilesetLookup::from_map(map),
+            layer,
+            options,
+        )
+    }
+
+    /// Every non-empty tile of the grid is passable with cost `1`
+    pub fn from_tiles(adjacency: TileAdjacency, tiles: &TileGrid) -> Self {
+        let mut grid = Self::new(adjacency, tiles.x, tiles.y, tiles.width, tiles.height);
+        for (x, y, gid) in tiles.cells() {
+            if gid.is_empty() {
+                grid.set_cost(x, y, None);
+            }
+        }
+        grid
+    }
+
+    pub fn cost(&self, x: i32, y: i32) -> Option<f64> {
+        self.index(x, y).and_then(|index| self.costs[index])
+    }
+
+    pub fn set_cost(&mut self, x: i32, y: i32, cost: Option<f64>) {
+        if let Some(index) = self.index(x, y) {
+            self.costs[index] = cost;
+        }
+    }
+
+    pub fn is_passable(&self, x: i32, y: i32) -> bool {
+        self.cost(x, y).is_some()
+    }
+
+    pub fn find_path(
+        &self,
+        start: (i32, i32),
+        goal: (i32, i32),
+        algorithm: Algorithm,
+    ) -> Option<Path> {
+        let start_index = self.index(start.0, start.1)?;
+        let goal_index = self.index(goal.0, goal.1)?;
+        if !self.is_passable(start.0, start.1) || !self.is_passable(goal.0, goal.1) {
+            return None;
+        }
+
+        // Scaling by the cheapest tile keeps the heuristic admissible
+        let min_cost = self
+            .costs
+            .iter()
+            .flatten()
+            .fold(f64::INFINITY, |min, cost| min.min(*cost));
+        let heuristic = |tile: (i32, i32)| match algorithm {
+            Algorithm::AStar => self.adjacency.distance(tile, goal) * min_cost,
+            Algorithm::Dijkstra => 0.0,
+        };
+
+        let mut costs = vec![f64::INFINITY; self.costs.len()];
+        let mut came_from = vec![usize::MAX; self.costs.len()];
+        let mut queue = BinaryHeap::new();
+
+        costs[start_index] = 0.0;
+        queue.push(QueueItem {
+            priority: heuristic(start),
+            cost:     0.0,
+            index:    start_index,
+        });
+
+        while let Some(QueueItem { cost, index, .. }) = queue.pop() {
+            if index == goal_index {
+                break;
+            }
+            if cost > costs[index] {
+                continue;
+            }
+
+            let tile = self.position(index);
+
+            for neighbour in self.adjacency.neighbours(tile.0, tile.1) {
+                let next = (neighbour.x, neighbour.y);
+                let (next_index, cost) =
+                    match (self.index(next.0, next.1), self.cost(next.0, next.1)) {
+                        (Some(next_index), Some(cost)) => (next_index, cost),
+                        _ => continue,
+                    };
+
+                let step = if neighbour.corner {
+                    let cuts_wall = self
+                        .adjacency
+                        .shared_edge_neighbours(tile, next)
+                        .into_iter()
+                        .any(|(x, y)| !self.is_passable(x, y));
+                    if cuts_wall {
+                        continue;
+                    }
+                    cost * std::f64::consts::SQRT_2
+                } else {
+                    cost
+                };
+
+                let next_cost = costs[index] + step;
+                if next_cost < costs[next_index] {
+                    costs[next_index] = next_cost;
+                    came_from[next_index] = index;
+                    queue.push(QueueItem {
+                        priority: next_cost + heuristic(next),
+                        cost:     next_cost,
+                        index:    next_index,
+                    });
+                }
+            }
+        }
+
+        if !costs[goal_index].is_finite() {
+            return None;
+        }
+
+        let mut tiles = vec![goal];
+        let mut index = goal_index;
+        while index != start_index {
+            index = came_from[index];
+            tiles.push(self.position(index));
+        }
+        tiles.reverse();
+
+        Some(Path {
+            tiles,
+            cost: costs[goal_index],
+        })
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height {
+            Some(((y - self.y) * self.width + (x - self.x)) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn position(&self, index: usize) -> (i32, i32) {
+        let index = index as i32;
+        (self.x + index % self.width, self.y + index / self.width)
+    }
+}
+
+struct QueueItem {
+    priority: f64,
+    cost:     f64,
+    index:    usize,
+}
+
+impl PartialEq for QueueItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueItem {}
+
+impl PartialOrd for QueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueItem {
+    // Reversed to turn the max-heap into a min-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::models::Orientation;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+
+    use serde_json::json;
+
+    fn adjacency(orientation: Orientation, neighbourhood: Neighbourhood) -> TileAdjacency {
+        TileAdjacency {
+            orientation,
+            stagger_axis: StaggerAxis::Y,
+            stagger_index: StaggerIndex::Odd,
+            neighbourhood,
+        }
+    }
+
+    fn make_grid(adjacency: TileAdjacency, rows: &[&str]) -> PathGrid {
+        let mut grid = PathGrid::new(adjacency, 0, 0, rows[0].len() as i32, rows.len() as i32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                let cost = match cell {
+                    '#' => None,
+                    '~' => Some(5.0),
+                    _ => Some(1.0),
+                };
+                grid.set_cost(x as i32, y as i32, cost);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn finds_orthogonal_paths() {
+        let rows = [
+            ".....", //
+            ".###.", ".#...", ".#.#.", "...#.",
+        ];
+        let grid = make_grid(
+            adjacency(Orientation::Orthogonal, Neighbourhood::Edges),
+            &rows,
+        );
+
+        let a_star = grid.find_path((0, 0), (2, 2), Algorithm::AStar).unwrap();
+        let dijkstra = grid.find_path((0, 0), (2, 2), Algorithm::Dijkstra).unwrap();
+        assert_eq!(a_star.cost, 8.0);
+        assert_eq!(dijkstra.cost, 8.0);
+        assert_eq!(a_star.tiles.first(), Some(&(0, 0)));
+        assert_eq!(a_star.tiles.last(), Some(&(2, 2)));
+        assert_eq!(a_star.tiles.len(), 9);
+
+        assert!(grid.find_path((0, 0), (1, 1), Algorithm::AStar).is_none());
+        assert!(grid.find_path((0, 0), (9, 9), Algorithm::AStar).is_none());
+    }
+
+    #[test]
+    fn respects_costs_and_corners() {
+        let rows = [
+            "...", //
+            ".~.", "...",
+        ];
+        let grid = make_grid(
+            adjacency(Orientation::Orthogonal, Neighbourhood::EdgesAndCorners),
+            &rows,
+        );
+        let path = grid.find_path((0, 0), (2, 2), Algorithm::AStar).unwrap();
+        assert_eq!(path.tiles.len(), 4);
+        assert!(!path.tiles.contains(&(1, 1)));
+        assert!((path.cost - (2.0 + std::f64::consts::SQRT_2)).abs() < 1e-9);
+
+        let rows = [
+            ".#", //
+            "..",
+        ];
+        let grid = make_grid(
+            adjacency(Orientation::Orthogonal, Neighbourhood::EdgesAndCorners),
+            &rows,
+        );
+        let path = grid.find_path((0, 0), (1, 1), Algorithm::AStar).unwrap();
+        assert_eq!(path.tiles, vec![(0, 0), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn finds_hexagonal_paths() {
+        let rows = [
+            "....", //
+            ".##.", "....",
+        ];
+        let grid = make_grid(
+            adjacency(Orientation::Hexagonal, Neighbourhood::Edges),
+            &rows,
+        );
+        let a_star = grid.find_path((0, 1), (3, 1), Algorithm::AStar).unwrap();
+        let dijkstra = grid.find_path((0, 1), (3, 1), Algorithm::Dijkstra).unwrap();
+        assert_eq!(a_star.cost, dijkstra.cost);
+        assert_eq!(a_star.cost, 4.0);
+    }
+
+    #[test]
+    fn builds_grid_from_map_layers() {
+        let map: Map = serde_json::from_value(json! {
+            {
+                "compressionlevel": -1,
+                "height": 1,
+                "infinite": false,
+                "layers": [
+                    {
+                        "data": [1, 2, 1, 0],
+                        "height": 1,
+                        "id": 1,
+                        "name": "ground",
+                        "opacity": 1.0,
+                        "type": "tilelayer",
+                        "visible": true,
+                        "width": 4,
+                        "x": 0,
+                        "y": 0
+                    }
+                ],
+                "nextlayerid": 2,
+                "nextobjectid": 1,
+                "orientation": "orthogonal",
+                "renderorder": "right-down",
+                "tiledversion": "1.3.5",
+                "tileheight": 16,
+                "tilesets": [
+                    {
+                        "columns": 2,
+                        "firstgid": 1,
+                        "name": "tiles",
+                        "spacing": 0,
+                        "tilecount": 2,
+                        "tiledversion": "1.3.5",
+                        "tileheight": 16,
+                        "tiles": [
+                            {
+                                "id": 1,
+                                "properties": [
+                                    { "name": "cost", "type": "float", "value": 2.5 }
+                                ]
+                            }
+                        ],
+                        "tilewidth": 16,
+                        "type": "tileset",
+                        "version": 1.2
+                    }
+                ],
+                "tilewidth": 16,
+                "type": "map",
+                "version": 1.2,
+                "width": 4
+            }
+        })
+        .unwrap();
+
+        let grid = PathGrid::from_map(&map, "ground", &PathGridOptions::default()).unwrap();
+        assert_eq!(grid.cost(0, 0), Some(1.0));
+        assert_eq!(grid.cost(1, 0), Some(2.5));
+        assert_eq!(grid.cost(3, 0), None);
+
+        let path = grid.find_path((0, 0), (2, 0), Algorithm::AStar).unwrap();
+        assert_eq!(path.cost, 3.5);
+
+        assert!(PathGrid::from_map(&map, "missing", &PathGridOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::adjacency::Neighbourhood;
+use super::adjacency::TileAdjacency;
+
+use crate::tme::gid::Gid;
+use crate::tme::render::MapGeometry;
+use crate::tme::tile_grid::TileGrid;
+
+/// Connected tiles considered equivalent by the labelling predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub id:       usize,
+    /// Tile the region was grown from, the first one in row-major order
+    pub seed:     (i32, i32),
+    pub gid:      Gid,
+    pub area:     usize,
+    /// Inclusive bounds in tile coordinates
+    pub min:      (i32, i32),
+    pub max:      (i32, i32),
+    /// Tiles with an edge neighbour outside of the region
+    pub boundary: Vec<(i32, i32)>,
+    /// Closed outlines in pixel space, the outer one and those of holes
+    pub contours: Vec<Vec<(f64, f64)>>,
+}
+
+/// Connected-component labelling of a tile grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionMap {
+    pub x:       i32,
+    pub y:       i32,
+    pub width:   i32,
+    pub height:  i32,
+    pub regions: Vec<Region>,
+    labels:      Vec<usize>,
+}
+
+impl RegionMap {
+    /// Labels the grid so that neighbouring tiles share a region whenever
+    /// `same_region` holds for their gids
+    pub fn new<F>(
+        geometry: &MapGeometry,
+        neighbourhood: Neighbourhood,
+        grid: &TileGrid,
+        same_region: F,
+    ) -> Self
+    where
+        F: Fn(Gid, Gid) -> bool,
+    {
+        let adjacency = TileAdjacency::new(geometry, neighbourhood);
+        let mut labels = vec![usize::MAX; grid.tiles.len()];
+        let mut regions = Vec::new();
+
+        for (x, y, gid) in grid.cells() {
+            if labels[index(grid, x, y)] != usize::MAX {
+                continue;
+            }
+
+            let id = regions.len();
+            let tiles = fill(&adjacency, grid, (x, y), &same_region, |tile| {
+                let label = &mut labels[index(grid, tile.0, tile.1)];
+                if *label == usize::MAX {
+                    *label = id;
+                    true
+                } else {
+                    false
+                }
+            });
+
+            let min = tiles
+                .iter()
+                .fold((x, y), |min, tile| (min.0.min(tile.0), min.1.min(tile.1)));
+            let max = tiles
+                .iter()
+                .fold((x, y), |max, tile| (max.0.max(tile.0), max.1.max(tile.1)));
+            regions.push(Region {
+                id,
+                seed: (x, y),
+                gid,
+                area: tiles.len(),
+                min,
+                max,
+                boundary: Vec::new(),
+                contours: Vec::new(),
+            });
+        }
+
+        let mut map = Self {
+            x: grid.x,
+            y: grid.y,
+            width: grid.width,
+            height: grid.height,
+            regions,
+            labels,
+        };
+        map.trace_boundaries(
+            geometry,
+            &TileAdjacency::new(geometry, Neighbourhood::Edges),
+        );
+        map
+    }
+
+    pub fn label(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height {
+            Some(self.labels[((y - self.y) * self.width + (x - self.x)) as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn region_at(&self, x: i32, y: i32) -> Option<&Region> {
+        self.label(x, y).map(|label| &self.regions[label])
+    }
+
+    /// Every region accepted by `is_walkable` except the largest one. Only
+    /// meaningful when walkable and blocked tiles never share a region.
+    pub fn disconnected_areas<F>(&self, is_walkable: F) -> Vec<DisconnectedArea>
+    where
+        F: Fn(Gid) -> bool,
+    {
+        let mut walkable: Vec<&Region> = self
+            .regions
+            .iter()
+            .filter(|region| is_walkable(region.gid))
+            .collect();
+        walkable.sort_by(|a, b| b.area.cmp(&a.area).then(a.id.cmp(&b.id)));
+
+        let main_area = match walkable.first() {
+            Some(main) => main.area,
+            None => return Vec::new(),
+        };
+        walkable[1..]
+            .iter()
+            .map(|region| DisconnectedArea {
+                region: region.id,
+                seed: region.seed,
+                area: region.area,
+                main_area,
+            })
+            .collect()
+    }
+
+    fn trace_boundaries(&mut self, geometry: &MapGeometry, adjacency: &TileAdjacency) {
+        // Directed tile outline edges, the ones shared by two tiles of the
+        // same region cancel out
+        let mut edges: Vec<BTreeMap<PointKey, Vec<PointKey>>> =
+            vec![BTreeMap::new(); self.regions.len()];
+
+        for y in self.y..self.y + self.height {
+            for x in self.x..self.x + self.width {
+                let label = self.labels[((y - self.y) * self.width + (x - self.x)) as usize];
+                let on_boundary = adjacency
+                    .neighbours(x, y)
+                    .iter()
+                    .any(|neighbour| self.label(neighbour.x, neighbour.y) != Some(label));
+                if on_boundary {
+                    self.regions[label].boundary.push((x, y));
+                }
+
+                let polygon: Vec<PointKey> = geometry
+                    .tile_polygon(x, y)
+                    .into_iter()
+                    .map(PointKey::new)
+                    .collect();
+                let region_edges = &mut edges[label];
+                for i in 0..polygon.len() {
+                    let (from, to) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                    let reverse = region_edges
+                        .get(&to)
+                        .and_then(|targets| targets.iter().position(|target| *target == from));
+                    match reverse {
+                        Some(position) => {
+                            let targets = region_edges.get_mut(&to).unwrap();
+                            targets.swap_remove(position);
+                            if targets.is_empty() {
+                                region_edges.remove(&to);
+                            }
+                        }
+                        None => region_edges.entry(from).or_default().push(to),
+                    }
+                }
+            }
+        }
+
+        for (region, mut region_edges) in self.regions.iter_mut().zip(edges) {
+            while let Some((&start, _)) = region_edges.iter().next() {
+                let mut contour = vec![start];
+                let mut current = start;
+                loop {
+                    let targets = region_edges.get_mut(&current).unwrap();
+                    let next = targets.pop().unwrap();
+                    if targets.is_empty() {
+                        region_edges.remove(&current);
+                    }
+                    if next == start || !region_edges.contains_key(&next) {
+                        break;
+                    }
+                    contour.push(next);
+                    current = next;
+                }
+                region.contours.push(simplify(contour));
+            }
+        }
+    }
+}
+
+/// Walkable region unreachable from the largest walkable region
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DisconnectedArea {
+    pub region:    usize,
+    pub seed:      (i32, i32),
+    pub area:      usize,
+    pub main_area: usize,
+}
+
+impl fmt::Display for DisconnectedArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Walkable area of {} tiles at ({}, {}) is disconnected from the main area of {} tiles",
+            self.area, self.seed.0, self.seed.1, self.main_area
+        )
+    }
+}
+
+/// Labels walkable and blocked tiles and reports walkable areas which can't
+/// be reached from the largest one
+pub fn find_disconnected_areas<F>(
+    geometry: &MapGeometry,
+    neighbourhood: Neighbourhood,
+    grid: &TileGrid,
+    is_walkable: F,
+) -> Vec<DisconnectedArea>
+where
+    F: Fn(Gid) -> bool,
+{
+    RegionMap::new(geometry, neighbourhood, grid, |a, b| {
+        is_walkable(a) == is_walkable(b)
+    })
+    .disconnected_areas(&is_walkable)
+}
+
+/// Tiles connected to `start` through neighbours for which `same_region`
+/// holds, in breadth-first order
+pub fn flood_fill<F>(
+    adjacency: &TileAdjacency,
+    grid: &TileGrid,
+    start: (i32, i32),
+    same_region: F,
+) -> Vec<(i32, i32)>
+where
+    F: Fn(Gid, Gid) -> bool,
+{
+    if !grid.contains(start.0, start.1) {
+        return Vec::new();
+    }
+
+    let mut visited = vec![false; grid.tiles.len()];
+    fill(adjacency, grid, start, &same_region, |tile| {
+        !std::mem::replace(&mut visited[index(grid, tile.0, tile.1)], true)
+    })
+}
+
+/// Breadth-first fill, `claim` marks a tile as visited and returns whether
+/// it was unvisited before
+fn fill<F, C>(
+    adjacency: &TileAdjacency,
+    grid: &TileGrid,
+    start: (i32, i32),
+    same_region: &F,
+    mut claim: C,
+) -> Vec<(i32, i32)>
+where
+    F: Fn(Gid, Gid) -> bool,
+    C: FnMut((i32, i32)) -> bool,
+{
+    let mut tiles = Vec::new();
+    let mut queue = VecDeque::new();
+    claim(start);
+    queue.push_back(start);
+
+    while let Some(tile) = queue.pop_front() {
+        tiles.push(tile);
+        let gid = grid.get(tile.0, tile.1).unwrap_or_default();
+        for neighbour in adjacency.neighbours(tile.0, tile.1) {
+            let next = (neighbour.x, neighbour.y);
+            let same = grid
+                .get(next.0, next.1)
+                .is_some_and(|next_gid| same_region(gid, next_gid));
+            if same && claim(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    tiles
+}
+
+fn index(grid: &TileGrid, x: i32, y: i32) -> usize {
+    ((y - grid.y) * grid.width + (x - grid.x)) as usize
+}
+
+/// Pixel position usable as map key, tile outlines only have integer or half
+/// pixel coordinates
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PointKey(i64, i64);
+
+impl PointKey {
+    fn new((x, y): (f64, f64)) -> Self {
+        Self((x * 2.0).round() as i64, (y * 2.0).round() as i64)
+    }
+
+    fn point(self) -> (f64, f64) {
+        (self.0 as f64 / 2.0, self.1 as f64 / 2.0)
+    }
+}
+
+/// Drops points lying on a straight line between their neighbours
+fn simplify(contour: Vec<PointKey>) -> Vec<(f64, f64)> {
+    let count = contour.len();
+    (0..count)
+        .filter(|&i| {
+            let (a, b, c) = (
+                contour[(i + count - 1) % count],
+                contour[i],
+                contour[(i + 1) % count],
+            );
+            (b.0 - a.0) * (c.1 - b.1) != (b.1 - a.1) * (c.0 - b.0)
+        })
+        .map(|i| contour[i].point())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::models::Orientation;
+    use crate::tme::models::RenderOrder;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+
+    fn geometry(orientation: Orientation) -> MapGeometry {
+        MapGeometry {
+            orientation,
+            render_order: RenderOrder::RightDown,
+            map_width: 5,
+            map_height: 4,
+            tile_width: 10,
+            tile_height: 10,
+            stagger_axis: StaggerAxis::Y,
+            stagger_index: StaggerIndex::Odd,
+            hex_side_length: 0,
+        }
+    }
+
+    fn make_grid(rows: &[&str]) -> TileGrid {
+        let mut grid = TileGrid::new(0, 0, rows[0].len() as i32, rows.len() as i32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                let gid = if cell == '#' { Gid(2) } else { Gid(1) };
+                grid.set(x as i32, y as i32, gid);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn labels_regions() {
+        let grid = make_grid(&[
+            "..#..", //
+            "..#..", //
+            "###..", //
+            ".#...", //
+        ]);
+        let regions = RegionMap::new(
+            &geometry(Orientation::Orthogonal),
+            Neighbourhood::Edges,
+            &grid,
+            |a, b| a == b,
+        );
+
+        let summary: Vec<_> = regions
+            .regions
+            .iter()
+            .map(|region| (region.seed, region.area, region.min, region.max))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ((0, 0), 4, (0, 0), (1, 1)),
+                ((2, 0), 6, (0, 0), (2, 3)),
+                ((3, 0), 9, (2, 0), (4, 3)),
+                ((0, 3), 1, (0, 3), (0, 3)),
+            ]
+        );
+        assert_eq!(regions.label(4, 3), Some(2));
+        assert_eq!(regions.label(5, 3), None);
+
+        assert_eq!(
+            regions.regions[0].contours,
+            vec![vec![(0.0, 0.0), (20.0, 0.0), (20.0, 20.0), (0.0, 20.0)]]
+        );
+        assert_eq!(regions.regions[0].boundary.len(), 4);
+        assert_eq!(regions.regions[3].contours[0].len(), 4);
+        assert_eq!(regions.regions[1].contours.len(), 1);
+        assert_eq!(regions.regions[1].contours[0].len(), 10);
+    }
+
+    #[test]
+    fn contours_include_holes() {
+        let grid = make_grid(&[
+            "###", //
+            "#.#", //
+            "###", //
+        ]);
+        let regions = RegionMap::new(
+            &geometry(Orientation::Orthogonal),
+            Neighbourhood::Edges,
+            &grid,
+            |a, b| a == b,
+        );
+        assert_eq!(regions.regions.len(), 2);
+        assert_eq!(regions.regions[0].contours.len(), 2);
+        assert_eq!(regions.regions[0].area, 8);
+        assert_eq!(regions.regions[0].boundary.len(), 8);
+    }
+
+    #[test]
+    fn flood_fills_and_reports_disconnected_areas() {
+        let grid = make_grid(&[
+            "..#..", //
+            "..#..", //
+            "###..", //
+            ".#...", //
+        ]);
+        let geometry = geometry(Orientation::Orthogonal);
+        let adjacency = TileAdjacency::new(&geometry, Neighbourhood::Edges);
+
+        let mut filled = flood_fill(&adjacency, &grid, (4, 0), |a, b| a == b);
+        filled.sort_unstable();
+        assert_eq!(
+            filled,
+            vec![
+                (2, 3),
+                (3, 0),
+                (3, 1),
+                (3, 2),
+                (3, 3),
+                (4, 0),
+                (4, 1),
+                (4, 2),
+                (4, 3)
+            ]
+        );
+
+        let walkable = |gid: Gid| gid.id() == 1;
+        let lints = find_disconnected_areas(&geometry, Neighbourhood::Edges, &grid, walkable);
+        assert_eq!(lints.len(), 2);
+        assert_eq!(lints[0].area, 4);
+        assert_eq!(
+            lints[1].to_string(),
+            "Walkable area of 1 tiles at (0, 3) is disconnected from the main area of 9 tiles"
+        );
+
+        // Diagonal tiles are only connected through corners
+        let grid = make_grid(&[
+            ".#", //
+            "#.", //
+        ]);
+        let lints = find_disconnected_areas(&geometry, Neighbourhood::Edges, &grid, walkable);
+        assert_eq!(lints.len(), 1);
+        let lints =
+            find_disconnected_areas(&geometry, Neighbourhood::EdgesAndCorners, &grid, walkable);
+        assert!(lints.is_empty());
+    }
+
+    #[test]
+    fn staggered_contours() {
+        let grid = make_grid(&[
+            "..", //
+            "..", //
+        ]);
+        let regions = RegionMap::new(
+            &geometry(Orientation::Staggered),
+            Neighbourhood::Edges,
+            &grid,
+            |a, b| a == b,
+        );
+        assert_eq!(regions.regions.len(), 1);
+        assert_eq!(regions.regions[0].boundary.len(), 4);
+        assert_eq!(regions.regions[0].contours.len(), 1);
+        assert_eq!(regions.regions[0].contours[0].len(), 8);
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashSet;
+
+use super::adjacency::Neighbourhood;
+use super::adjacency::TileAdjacency;
+
+use crate::tme::error::Error;
+use crate::tme::models::find_property;
+use crate::tme::models::Map;
+use crate::tme::models::Orientation;
+use crate::tme::models::TileLayer;
+use crate::tme::render::MapGeometry;
+use crate::tme::tile_grid::TileGrid;
+use crate::tme::tileset_lookup::TilesetLookup;
+
+#[derive(Debug, Clone)]
+pub struct OpacityGridOptions<'a> {
+    /// Bool tile property marking tiles that block sight
+    pub opaque_property: &'a str,
+    /// Any non-empty tile of this layer blocks sight
+    pub walls:           Option<&'a TileLayer>,
+}
+
+impl<'a> Default for OpacityGridOptions<'a> {
+    fn default() -> Self {
+        Self {
+            opaque_property: "opaque",
+            walls:           None,
+        }
+    }
+}
+
+/// Tiles blocking sight in a rectangular area, everything outside of it is
+/// opaque
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpacityGrid {
+    pub adjacency: TileAdjacency,
+    pub x:         i32,
+    pub y:         i32,
+    pub width:     i32,
+    pub height:    i32,
+    opaque:        Vec<bool>,
+}
+
+impl OpacityGrid {
+    pub fn new(adjacency: TileAdjacency, x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            adjacency,
+            x,
+            y,
+            width,
+            height,
+            opaque: vec![false; (width.max(0) * height.max(0)) as usize],
+        }
+    }
+
+    pub fn from_layers(
+        geometry: &MapGeometry,
+        tilesets: &TilesetLookup,
+        ground: &TileLayer,
+        options: &OpacityGridOptions,
+    ) -> Result<Self, Error> {
+        let ground = ground.decode_grid()?;
+        let walls = match options.walls {
+            Some(walls) => Some(walls.decode_grid()?),
+            None => None,
+        };
+
+        let adjacency = TileAdjacency::new(geometry, Neighbourhood::Edges);
+        let mut grid = Self::new(adjacency, ground.x, ground.y, ground.width, ground.height);
+        for (x, y, gid) in ground.cells() {
+            let wall = walls
+                .as_ref()
+                .and_then(|walls| walls.get(x, y))
+                .is_some_and(|wall| !wall.is_empty());
+            let opaque = tilesets
+                .tile(gid)
+                .and_then(|tile| find_property(tile.properties.as_deref(), options.opaque_property))
+                .and_then(|property| property.as_bool())
+                .unwrap_or(false);
+            grid.set_opaque(x, y, wall || opaque);
+        }
+
+        Ok(grid)
+    }
+
+    pub fn from_map(map: &Map, ground: &str, options: &OpacityGridOptions) -> Result<Self, Error> {
+        let layer = map
+            .find_tile_layer(ground)
+            .ok_or_else(|| Error::LayerNotFound(ground.to_owned()))?;
+        Self::from_layers(
+            &MapGeometry::from_map(map),
+            &TilesetLookup::from_map(map),
+            layer,
+            options,
+        )
+    }
+
+    /// Every non-empty tile of the grid blocks sight
+    pub fn from_tiles(adjacency: TileAdjacency, tiles: &TileGrid) -> Self {
+        let mut grid = Self::new(adjacency, tiles.x, tiles.y, tiles.width, tiles.height);
+        for (x, y, gid) in tiles.cells() {
+            grid.set_opaque(x, y, !gid.is_empty());
+        }
+        grid
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some()
+    }
+
+    pub fn is_opaque(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_none_or(|index| self.opaque[index])
+    }
+
+    pub fn set_opaque(&mut self, x: i32, y: i32, opaque: bool) {
+        if let Some(index) = self.index(x, y) {
+            self.opaque[index] = opaque;
+        }
+    }
+
+    /// Whether no opaque tile lies strictly between the two tiles, so walls
+    /// themselves can be seen
+    pub fn line_of_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        if !self.contains(from.0, from.1) || !self.contains(to.0, to.1) {
+            return false;
+        }
+
+        let line = self.adjacency.line(from, to);
+        line.len() <= 2
+            || line[1..line.len() - 1]
+                .iter()
+                .all(|&(x, y)| !self.is_opaque(x, y))
+    }
+
+    /// Tiles visible from `origin`, including the origin and the opaque tiles
+    /// bounding the view. `radius` is measured in tiles, on hexagonal maps as
+    /// number of steps.
+    pub fn field_of_view(&self, origin: (i32, i32), radius: Option<i32>) -> HashSet<(i32, i32)> {
+        let mut visible = HashSet::new();
+        if !self.contains(origin.0, origin.1) {
+            return visible;
+        }
+        visible.insert(origin);
+
+        let radius = radius.unwrap_or(self.width + self.height).max(0);
+        match self.adjacency.orientation {
+            Orientation::Orthogonal | Orientation::Isometric => {
+                self.shadow_cast(origin, radius, |tile| tile, &mut visible);
+            }
+            Orientation::Staggered => {
+                let adjacency = self.adjacency;
+                self.shadow_cast(
+                    adjacency.diamond_coords(origin),
+                    radius,
+                    |tile| adjacency.diamond_to_tile(tile),
+                    &mut visible,
+                );
+            }
+            Orientation::Hexagonal => {
+                // Hexagons have no octants, test the lines to every tile in
+                // range instead
+                let (origin_q, origin_r) = self.adjacency.axial_coords(origin);
+                for q in -radius..=radius {
+                    for r in (-radius).max(-q - radius)..=radius.min(radius - q) {
+                        let tile = self.adjacency.axial_to_tile((origin_q + q, origin_r + r));
+                        if self.line_of_sight(origin, tile) {
+                            visible.insert(tile);
+                        }
+                    }
+                }
+            }
+        }
+        visible
+    }
+
+    /// Recursive shadow casting over the eight octants of a square grid,
+    /// `to_tile` maps grid coordinates to tile coordinates
+    fn shadow_cast<F>(
+        &self,
+        origin: (i32, i32),
+        radius: i32,
+        to_tile: F,
+        visible: &mut HashSet<(i32, i32)>,
+    ) where
+        F: Fn((i32, i32)) -> (i32, i32),
+    {
+        const OCTANTS: [(i32, i32, i32, i32); 8] = [
+            (1, 0, 0, 1),
+            (0, 1, 1, 0),
+            (0, -1, 1, 0),
+            (-1, 0, 0, 1),
+            (-1, 0, 0, -1),
+            (0, -1, -1, 0),
+            (0, 1, -1, 0),
+            (1, 0, 0, -1),
+        ];
+
+        for &transform in OCTANTS.iter() {
+            let octant = Octant {
+                origin,
+                radius,
+                transform,
+            };
+            self.cast_light(&octant, 1, 1.0, 0.0, &to_tile, visible);
+        }
+    }
+
+    fn cast_light<F>(
+        &self,
+        octant: &Octant,
+        row: i32,
+        mut start: f64,
+        end: f64,
+        to_tile: &F,
+        visible: &mut HashSet<(i32, i32)>,
+    ) where
+        F: Fn((i32, i32)) -> (i32, i32),
+    {
+        if start < end {
+            return;
+        }
+
+        let (xx, xy, yx, yy) = octant.transform;
+        let mut next_start = start;
+        for distance in row..=octant.radius {
+            let dy = -distance;
+            let mut blocked = false;
+
+            for dx in -distance..=0 {
+                let left_slope = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+                let right_slope = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+                if start < right_slope {
+                    continue;
+                }
+                if end > left_slope {
+                    break;
+                }
+
+                let tile = to_tile((
+                    octant.origin.0 + dx * xx + dy * xy,
+                    octant.origin.1 + dx * yx + dy * yy,
+                ));
+                if self.contains(tile.0, tile.1)
+                    && dx * dx + dy * dy <= octant.radius * octant.radius
+                {
+                    visible.insert(tile);
+                }
+
+                let opaque = self.is_opaque(tile.0, tile.1);
+                if blocked {
+                    if opaque {
+                        next_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if opaque && distance < octant.radius {
+                    blocked = true;
+                    self.cast_light(octant, distance + 1, start, left_slope, to_tile, visible);
+                    next_start = right_slope;
+                }
+            }
+
+            if blocked {
+                break;
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height {
+            Some(((y - self.y) * self.width + (x - self.x)) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+struct Octant {
+    origin:    (i32, i32),
+    radius:    i32,
+    transform: (i32, i32, i32, i32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+
+    fn adjacency(orientation: Orientation) -> TileAdjacency {
+        TileAdjacency {
+            orientation,
+            stagger_axis: StaggerAxis::X,
+            stagger_index: StaggerIndex::Even,
+            neighbourhood: Neighbourhood::Edges,
+        }
+    }
+
+    fn make_grid(orientation: Orientation, rows: &[&str]) -> OpacityGrid {
+        let mut grid = OpacityGrid::new(
+            adjacency(orientation),
+            0,
+            0,
+            rows[0].len() as i32,
+            rows.len() as i32,
+        );
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                grid.set_opaque(x as i32, y as i32, cell == '#');
+            }
+        }
+        grid
+    }
+
+    fn render(grid: &OpacityGrid, visible: &HashSet<(i32, i32)>) -> Vec<String> {
+        (0..grid.height)
+            .map(|y| {
+                (0..grid.width)
+                    .map(|x| if visible.contains(&(x, y)) { 'o' } else { ' ' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn orthogonal_field_of_view() {
+        let grid = make_grid(
+            Orientation::Orthogonal,
+            &[
+                ".......", //
+                ".......", //
+                "...#...", //
+                ".......", //
+                ".......", //
+            ],
+        );
+
+        let visible = grid.field_of_view((3, 4), None);
+        assert_eq!(
+            render(&grid, &visible),
+            vec![
+                "ooo ooo", //
+                "ooo ooo", //
+                "ooooooo", //
+                "ooooooo", //
+                "ooooooo", //
+            ]
+        );
+
+        let visible = grid.field_of_view((3, 4), Some(2));
+        assert_eq!(
+            render(&grid, &visible),
+            vec![
+                "       ", //
+                "       ", //
+                "   o   ", //
+                "  ooo  ", //
+                " ooooo ", //
+            ]
+        );
+    }
+
+    #[test]
+    fn orthogonal_line_of_sight() {
+        let grid = make_grid(
+            Orientation::Orthogonal,
+            &[
+                ".....", //
+                "..#..", //
+                ".....", //
+            ],
+        );
+        assert!(!grid.line_of_sight((0, 1), (4, 1)));
+        assert!(grid.line_of_sight((0, 1), (2, 1)));
+        assert!(grid.line_of_sight((0, 0), (4, 0)));
+        assert!(grid.line_of_sight((0, 2), (4, 0)) == grid.line_of_sight((4, 0), (0, 2)));
+        assert!(!grid.line_of_sight((0, 0), (5, 0)));
+    }
+
+    #[test]
+    fn hexagonal_visibility() {
+        let grid = make_grid(
+            Orientation::Hexagonal,
+            &[
+                ".....", //
+                ".....", //
+                ".....", //
+                "..#..", //
+                ".....", //
+                ".....", //
+            ],
+        );
+
+        // Tiles behind the wall are hidden, the wall itself is not
+        assert!(!grid.line_of_sight((2, 1), (2, 5)));
+        assert!(grid.line_of_sight((2, 1), (2, 3)));
+        assert!(!grid.line_of_sight((2, 1), (1, 5)));
+        assert!(grid.line_of_sight((2, 1), (0, 5)));
+
+        let visible = grid.field_of_view((2, 1), Some(1));
+        let mut expected: Vec<_> = grid
+            .adjacency
+            .neighbours(2, 1)
+            .into_iter()
+            .map(|neighbour| (neighbour.x, neighbour.y))
+            .chain(std::iter::once((2, 1)))
+            .collect();
+        expected.sort_unstable();
+        let mut visible: Vec<_> = visible.into_iter().collect();
+        visible.sort_unstable();
+        assert_eq!(visible, expected);
+
+        let visible = grid.field_of_view((2, 1), None);
+        assert!(visible.contains(&(2, 3)));
+        assert!(!visible.contains(&(2, 4)));
+        assert!(!visible.contains(&(2, 5)));
+        assert!(visible.contains(&(0, 5)));
+    }
+
+    #[test]
+    fn staggered_field_of_view() {
+        let grid = make_grid(
+            Orientation::Staggered,
+            &[
+                "....", //
+                "....", //
+                "....", //
+                "....", //
+            ],
+        );
+        let visible = grid.field_of_view((1, 1), None);
+        assert_eq!(visible.len(), 16);
+
+        let visible = grid.field_of_view((1, 1), Some(1));
+        for neighbour in grid.adjacency.neighbours(1, 1) {
+            assert!(visible.contains(&(neighbour.x, neighbour.y)));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+
+use super::geometry::MapGeometry;
+
+use crate::tme::error::Error;
+use crate::tme::gid::Gid;
+use crate::tme::models::Map;
+use crate::tme::models::Orientation;
+use crate::tme::models::RenderOrder;
+use crate::tme::models::TileLayer;
+use crate::tme::tile_grid::TileGrid;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderCell {
+    pub x:       i32,
+    pub y:       i32,
+    pub gid:     Gid,
+    pub pixel_x: f64,
+    pub pixel_y: f64,
+}
+
+/// Non-empty cells of a tile layer in the order Tiled draws them.
+///
+/// Tiled only honours the render order for orthogonal maps, other
+/// orientations are always drawn back to front by screen position.
+pub struct RenderCells {
+    cells: std::vec::IntoIter<RenderCell>,
+}
+
+impl RenderCells {
+    pub fn new(geometry: &MapGeometry, grid: &TileGrid) -> Self {
+        let mut cells: Vec<RenderCell> = grid
+            .cells()
+            .filter(|(_, _, gid)| !gid.is_empty())
+            .map(|(x, y, gid)| {
+                let (pixel_x, pixel_y) = geometry.tile_to_pixel(x, y);
+                RenderCell {
+                    x,
+                    y,
+                    gid,
+                    pixel_x,
+                    pixel_y,
+                }
+            })
+            .collect();
+
+        match geometry.orientation {
+            Orientation::Orthogonal => {
+                let order = geometry.render_order;
+                cells.sort_by(|a, b| compare_orthogonal(order, a, b));
+            }
+            _ => cells.sort_by(|a, b| {
+                a.pixel_y
+                    .total_cmp(&b.pixel_y)
+                    .then(a.pixel_x.total_cmp(&b.pixel_x))
+            }),
+        }
+
+        Self {
+            cells: cells.into_iter(),
+        }
+    }
+}
+
+impl Iterator for RenderCells {
+    type Item = RenderCell;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cells.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.cells.size_hint()
+    }
+}
+
+impl ExactSizeIterator for RenderCells {}
+
+fn compare_orthogonal(order: RenderOrder, a: &RenderCell, b: &RenderCell) -> Ordering {
+    let (rows, columns) = match order {
+        RenderOrder::RightDown => (a.y.cmp(&b.y), a.x.cmp(&b.x)),
+        RenderOrder::RightUp => (b.y.cmp(&a.y), a.x.cmp(&b.x)),
+        RenderOrder::LeftDown => (a.y.cmp(&b.y), b.x.cmp(&a.x)),
+        RenderOrder::LeftUp => (b.y.cmp(&a.y), b.x.cmp(&a.x)),
+    };
+    rows.then(columns)
+}
+
+impl Map {
+    pub fn render_cells(&self, layer: &TileLayer) -> Result<RenderCells, Error> {
+        let grid = layer.decode_grid()?;
+        Ok(RenderCells::new(&MapGeometry::from_map(self), &grid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+
+    fn geometry(orientation: Orientation, render_order: RenderOrder) -> MapGeometry {
+        MapGeometry {
+            orientation,
+            render_order,
+            map_width: 2,
+            map_height: 2,
+            tile_width: 32,
+            tile_height: 16,
+            stagger_axis: StaggerAxis::X,
+            stagger_index: StaggerIndex::Odd,
+            hex_side_length: 0,
+        }
+    }
+
+    fn grid() -> TileGrid {
+        TileGrid {
+            x:      0,
+            y:      0,
+            width:  2,
+            height: 2,
+            tiles:  vec![1, 2, 3, 4],
+        }
+    }
+
+    fn order(geometry: MapGeometry) -> Vec<(i32, i32)> {
+        RenderCells::new(&geometry, &grid())
+            .map(|cell| (cell.x, cell.y))
+            .collect()
+    }
+
+    #[test]
+    fn orthogonal_follows_render_order() {
+        let expecteds = vec![
+            (RenderOrder::RightDown, vec![(0, 0), (1, 0), (0, 1), (1, 1)]),
+            (RenderOrder::RightUp, vec![(0, 1), (1, 1), (0, 0), (1, 0)]),
+            (RenderOrder::LeftDown, vec![(1, 0), (0, 0), (1, 1), (0, 1)]),
+            (RenderOrder::LeftUp, vec![(1, 1), (0, 1), (1, 0), (0, 0)]),
+        ];
+
+        for (render_order, expected) in expecteds {
+            assert_eq!(
+                order(geometry(Orientation::Orthogonal, render_order)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn isometric_draws_back_to_front() {
+        for render_order in [RenderOrder::RightDown, RenderOrder::LeftUp] {
+            assert_eq!(
+                order(geometry(Orientation::Isometric, render_order)),
+                vec![(0, 0), (0, 1), (1, 0), (1, 1)]
+            );
+        }
+    }
+
+    #[test]
+    fn staggered_draws_upper_columns_first() {
+        assert_eq!(
+            order(geometry(Orientation::Staggered, RenderOrder::RightDown)),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn skips_empty_cells_and_reports_positions() {
+        let grid = TileGrid {
+            x:      -1,
+            y:      0,
+            width:  2,
+            height: 1,
+            tiles:  vec![0, 7],
+        };
+        let cells: Vec<RenderCell> = RenderCells::new(
+            &geometry(Orientation::Orthogonal, RenderOrder::RightDown),
+            &grid,
+        )
+        .collect();
+
+        assert_eq!(
+            cells,
+            vec![RenderCell {
+                x:       0,
+                y:       0,
+                gid:     Gid(7),
+                pixel_x: 0.0,
+                pixel_y: 0.0,
+            }]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use crate::tme::models::Map;
+use crate::tme::models::Orientation;
+use crate::tme::models::RenderOrder;
+use crate::tme::models::StaggerAxis;
+use crate::tme::models::StaggerIndex;
+
+/// Projection parameters needed to place tiles of a map in pixel space,
+/// mirroring the renderers of Tiled
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MapGeometry {
+    pub orientation:     Orientation,
+    pub render_order:    RenderOrder,
+    pub map_width:       i32,
+    pub map_height:      i32,
+    pub tile_width:      i32,
+    pub tile_height:     i32,
+    pub stagger_axis:    StaggerAxis,
+    pub stagger_index:   StaggerIndex,
+    pub hex_side_length: i32,
+}
+
+impl MapGeometry {
+    pub fn from_map(map: &Map) -> Self {
+        Self {
+            orientation:     map.orientation(),
+            render_order:    map.render_order(),
+            map_width:       map.width(),
+            map_height:      map.height(),
+            tile_width:      map.tile_width(),
+            tile_height:     map.tile_height(),
+            stagger_axis:    map.stagger_axis().unwrap_or(StaggerAxis::Y),
+            stagger_index:   map.stagger_index().unwrap_or(StaggerIndex::Odd),
+            hex_side_length: map.hex_side_length(),
+        }
+    }
+
+    /// Top-left corner of the bounding box of the tile at `(x, y)`
+    pub fn tile_to_pixel(&self, x: i32, y: i32) -> (f64, f64) {
+        let tile_width = self.tile_width as f64;
+        let tile_height = self.tile_height as f64;
+
+        match self.orientation {
+            Orientation::Orthogonal => (x as f64 * tile_width, y as f64 * tile_height),
+            Orientation::Isometric => (
+                (x - y + self.map_height - 1) as f64 * tile_width / 2.0,
+                (x + y) as f64 * tile_height / 2.0,
+            ),
+            Orientation::Staggered | Orientation::Hexagonal => {
+                let params = HexParams::new(self);
+                match self.stagger_axis {
+                    StaggerAxis::X => {
+                        let mut pixel_y = y * (params.tile_height + params.side_length_y);
+                        if self.is_staggered(x) {
+                            pixel_y += params.row_height;
+                        }
+                        ((x * params.column_width) as f64, pixel_y as f64)
+                    }
+                    StaggerAxis::Y => {
+                        let mut pixel_x = x * (params.tile_width + params.side_length_x);
+                        if self.is_staggered(y) {
+                            pixel_x += params.column_width;
+                        }
+                        (pixel_x as f64, (y * params.row_height) as f64)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tile whose shape contains the pixel, the inverse of `tile_to_pixel`
+    pub fn pixel_to_tile(&self, x: f64, y: f64) -> (i32, i32) {
+        let tile_width = self.tile_width as f64;
+        let tile_height = self.tile_height as f64;
+
+        match self.orientation {
+            Orientation::Orthogonal => (
+                (x / tile_width).floor() as i32,
+                (y / tile_height).floor() as i32,
+            ),
+            Orientation::Isometric => {
+                let column = (x - self.map_height as f64 * tile_width / 2.0) / tile_width;
+                let row = y / tile_height;
+                ((row + column).floor() as i32, (row - column).floor() as i32)
+            }
+            Orientation::Staggered | Orientation::Hexagonal => {
+                // Rows or columns overlap along the stagger axis, so look for
+                // the closest tile around a rough estimate
+                let params = HexParams::new(self);
+                let (column, row) = match self.stagger_axis {
+                    StaggerAxis::X => (
+                        x / params.column_width.max(1) as f64,
+                        y / (params.tile_height + params.side_length_y).max(1) as f64,
+                    ),
+                    StaggerAxis::Y => (
+                        x / (params.tile_width + params.side_length_x).max(1) as f64,
+                        y / params.row_height.max(1) as f64,
+                    ),
+                };
+                let (column, row) = (column.floor() as i32, row.floor() as i32);
+
+                let distance = |(tile_x, tile_y): (i32, i32)| {
+                    let (center_x, center_y) = self.tile_center(tile_x, tile_y);
+                    let (dx, dy) = ((x - center_x).abs(), (y - center_y).abs());
+                    match self.orientation {
+                        Orientation::Staggered => dx / tile_width + dy / tile_height,
+                        _ => dx * dx + dy * dy,
+                    }
+                };
+                (row - 1..=row + 1)
+                    .flat_map(|tile_y| {
+                        (column - 1..=column + 1).map(move |tile_x| (tile_x, tile_y))
+                    })
+                    .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+                    .unwrap_or((column, row))
+            }
+        }
+    }
+
+    /// Size of the whole map in pixels
+    pub fn pixel_size(&self) -> (i32, i32) {
+        match self.orientation {
+            Orientation::Orthogonal => (
+                self.map_width * self.tile_width,
+                self.map_height * self.tile_height,
+            ),
+            Orientation::Isometric => {
+                let side = self.map_width + self.map_height;
+                (side * self.tile_width / 2, side * self.tile_height / 2)
+            }
+            Orientation::Staggered | Orientation::Hexagonal => {
+                let params = HexParams::new(self);
+                match self.stagger_axis {
+                    StaggerAxis::X => {
+                        let mut height =
+                            self.map_height * (params.tile_height + params.side_length_y);
+                        if self.map_width > 1 {
+                            height += params.row_height;
+                        }
+                        (
+                            self.map_width * params.column_width + params.tile_width
+                                - params.column_width,
+                            height,
+                        )
+                    }
+                    StaggerAxis::Y => {
+                        let mut width = self.map_width * (params.tile_width + params.side_length_x);
+                        if self.map_height > 1 {
+                            width += params.column_width;
+                        }
+                        (
+                            width,
+                            self.map_height * params.row_height + params.tile_height
+                                - params.row_height,
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn tile_center(&self, x: i32, y: i32) -> (f64, f64) {
+        let (pixel_x, pixel_y) = self.tile_to_pixel(x, y);
+        (
+            pixel_x + self.tile_width as f64 / 2.0,
+            pixel_y + self.tile_height as f64 / 2.0,
+        )
+    }
+
+    /// Outline of the tile at `(x, y)` in pixel space, clockwise on screen
+    /// starting at the top
+    pub fn tile_polygon(&self, x: i32, y: i32) -> Vec<(f64, f64)> {
+        let (left, top) = self.tile_to_pixel(x, y);
+        let width = self.tile_width as f64;
+        let height = self.tile_height as f64;
+
+        let local = match self.orientation {
+            Orientation::Orthogonal => {
+                vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            }
+            Orientation::Isometric => vec![
+                (width / 2.0, 0.0),
+                (width, height / 2.0),
+                (width / 2.0, height),
+                (0.0, height / 2.0),
+            ],
+            Orientation::Staggered | Orientation::Hexagonal => {
+                let params = HexParams::new(self);
+                let width = params.tile_width as f64;
+                let height = params.tile_height as f64;
+                let side_offset_x = (params.column_width - params.side_length_x) as f64;
+                let side_offset_y = (params.row_height - params.side_length_y) as f64;
+                vec![
+                    (side_offset_x, 0.0),
+                    (width - side_offset_x, 0.0),
+                    (width, side_offset_y),
+                    (width, height - side_offset_y),
+                    (width - side_offset_x, height),
+                    (side_offset_x, height),
+                    (0.0, height - side_offset_y),
+                    (0.0, side_offset_y),
+                ]
+            }
+        };
+
+        let mut polygon: Vec<(f64, f64)> = Vec::with_capacity(local.len());
+        for (dx, dy) in local {
+            let point = (left + dx, top + dy);
+            if polygon.last() != Some(&point) && polygon.first() != Some(&point) {
+                polygon.push(point);
+            }
+        }
+        polygon
+    }
+
+    /// Whether the row or column with the given index along the stagger axis
+    /// is shifted
+    pub fn is_staggered(&self, index: i32) -> bool {
+        ((index & 1) != 0) ^ (self.stagger_index == StaggerIndex::Even)
+    }
+}
+
+pub(crate) struct HexParams {
+    pub tile_width:    i32,
+    pub tile_height:   i32,
+    pub side_length_x: i32,
+    pub side_length_y: i32,
+    pub column_width:  i32,
+    pub row_height:    i32,
+}
+
+impl HexParams {
+    pub fn new(geometry: &MapGeometry) -> Self {
+        let tile_width = geometry.tile_width & !1;
+        let tile_height = geometry.tile_height & !1;
+
+        let side_length = match geometry.orientation {
+            Orientation::Hexagonal => geometry.hex_side_length,
+            _ => 0,
+        };
+        let (side_length_x, side_length_y) = match geometry.stagger_axis {
+            StaggerAxis::X => (side_length, 0),
+            StaggerAxis::Y => (0, side_length),
+        };
+
+        let side_offset_x = (tile_width - side_length_x) / 2;
+        let side_offset_y = (tile_height - side_length_y) / 2;
+
+        Self {
+            tile_width,
+            tile_height,
+            side_length_x,
+            side_length_y,
+            column_width: side_offset_x + side_length_x,
+            row_height: side_offset_y + side_length_y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(orientation: Orientation) -> MapGeometry {
+        MapGeometry {
+            orientation,
+            render_order: RenderOrder::RightDown,
+            map_width: 4,
+            map_height: 4,
+            tile_width: 32,
+            tile_height: 16,
+            stagger_axis: StaggerAxis::Y,
+            stagger_index: StaggerIndex::Odd,
+            hex_side_length: 0,
+        }
+    }
+
+    #[test]
+    fn projects_tiles() {
+        let orthogonal = geometry(Orientation::Orthogonal);
+        assert_eq!(orthogonal.tile_to_pixel(2, 3), (64.0, 48.0));
+
+        let isometric = geometry(Orientation::Isometric);
+        assert_eq!(isometric.tile_to_pixel(0, 0), (48.0, 0.0));
+        assert_eq!(isometric.tile_to_pixel(1, 0), (64.0, 8.0));
+        assert_eq!(isometric.tile_to_pixel(0, 1), (32.0, 8.0));
+
+        let staggered = geometry(Orientation::Staggered);
+        assert_eq!(staggered.tile_to_pixel(0, 0), (0.0, 0.0));
+        assert_eq!(staggered.tile_to_pixel(0, 1), (16.0, 8.0));
+        assert_eq!(staggered.tile_to_pixel(1, 2), (32.0, 16.0));
+
+        let hexagonal = MapGeometry {
+            stagger_axis: StaggerAxis::X,
+            stagger_index: StaggerIndex::Even,
+            hex_side_length: 16,
+            tile_height: 32,
+            ..geometry(Orientation::Hexagonal)
+        };
+        assert_eq!(hexagonal.tile_to_pixel(0, 0), (0.0, 16.0));
+        assert_eq!(hexagonal.tile_to_pixel(1, 0), (24.0, 0.0));
+        assert_eq!(hexagonal.tile_to_pixel(2, 1), (48.0, 48.0));
+    }
+
+    #[test]
+    fn tile_polygons() {
+        let orthogonal = geometry(Orientation::Orthogonal);
+        assert_eq!(
+            orthogonal.tile_polygon(1, 0),
+            vec![(32.0, 0.0), (64.0, 0.0), (64.0, 16.0), (32.0, 16.0)]
+        );
+
+        let staggered = geometry(Orientation::Staggered);
+        assert_eq!(
+            staggered.tile_polygon(0, 1),
+            vec![(32.0, 8.0), (48.0, 16.0), (32.0, 24.0), (16.0, 16.0)]
+        );
+
+        let hexagonal = MapGeometry {
+            hex_side_length: 8,
+            ..geometry(Orientation::Hexagonal)
+        };
+        assert_eq!(
+            hexagonal.tile_polygon(0, 0),
+            vec![
+                (16.0, 0.0),
+                (32.0, 4.0),
+                (32.0, 12.0),
+                (16.0, 16.0),
+                (0.0, 12.0),
+                (0.0, 4.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_tiles_at_pixels() {
+        let hexagonal = MapGeometry {
+            stagger_axis: StaggerAxis::X,
+            stagger_index: StaggerIndex::Even,
+            hex_side_length: 16,
+            tile_height: 32,
+            ..geometry(Orientation::Hexagonal)
+        };
+        let geometries = [
+            geometry(Orientation::Orthogonal),
+            geometry(Orientation::Isometric),
+            geometry(Orientation::Staggered),
+            hexagonal,
+        ];
+        for geometry in &geometries {
+            for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+                let (center_x, center_y) = geometry.tile_center(x, y);
+                assert_eq!(geometry.pixel_to_tile(center_x, center_y), (x, y));
+            }
+        }
+
+        let staggered = geometry(Orientation::Staggered);
+        assert_eq!(staggered.pixel_to_tile(1.0, 1.0), (-1, -1));
+        assert_eq!(staggered.pixel_to_tile(32.0, 9.0), (0, 1));
+        let isometric = geometry(Orientation::Isometric);
+        assert_eq!(isometric.pixel_to_tile(63.0, 1.0), (0, 0));
+        assert_eq!(isometric.pixel_to_tile(79.0, 1.0), (0, -1));
+    }
+
+    #[test]
+    fn measures_maps() {
+        assert_eq!(geometry(Orientation::Orthogonal).pixel_size(), (128, 64));
+        assert_eq!(geometry(Orientation::Isometric).pixel_size(), (128, 64));
+        assert_eq!(geometry(Orientation::Staggered).pixel_size(), (144, 40));
+    }
+}
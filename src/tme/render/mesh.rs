@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+
+use super::cells::RenderCells;
+use super::geometry::MapGeometry;
+
+use crate::tme::error::Error;
+use crate::tme::gid::Gid;
+use crate::tme::models::Orientation;
+use crate::tme::models::TileLayer;
+use crate::tme::tile_grid::TileGrid;
+use crate::tme::tileset_lookup::TilesetLookup;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct TileVertex {
+    pub position: [f32; 2],
+    pub uv:       [f32; 2],
+    pub opacity:  f32,
+}
+
+/// Quads sharing one texture, optionally restricted to one spatial chunk.
+/// Each quad is four vertices (top-left, top-right, bottom-right,
+/// bottom-left) and six indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileBatch {
+    pub image:    String,
+    pub chunk:    Option<(i32, i32)>,
+    pub min:      [f32; 2],
+    pub max:      [f32; 2],
+    pub vertices: Vec<TileVertex>,
+    pub indices:  Vec<u32>,
+}
+
+impl TileBatch {
+    fn new(image: String, chunk: Option<(i32, i32)>) -> Self {
+        Self {
+            image,
+            chunk,
+            min: [f32::MAX; 2],
+            max: [f32::MIN; 2],
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn push_quad(&mut self, corners: [[f32; 2]; 4], uvs: [[f32; 2]; 4], opacity: f32) {
+        let base = self.vertices.len() as u32;
+        for (position, uv) in corners.iter().zip(uvs.iter()) {
+            self.min = [self.min[0].min(position[0]), self.min[1].min(position[1])];
+            self.max = [self.max[0].max(position[0]), self.max[1].max(position[1])];
+            self.vertices.push(TileVertex {
+                position: *position,
+                uv: *uv,
+                opacity,
+            });
+        }
+        self.indices
+            .extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+}
+
+pub struct TileMeshBuilder<'a> {
+    geometry:   MapGeometry,
+    tilesets:   &'a TilesetLookup<'a>,
+    chunk_size: Option<i32>,
+}
+
+impl<'a> TileMeshBuilder<'a> {
+    pub fn new(geometry: MapGeometry, tilesets: &'a TilesetLookup<'a>) -> Self {
+        Self {
+            geometry,
+            tilesets,
+            chunk_size: None,
+        }
+    }
+
+    /// Splits the output into square chunks of `chunk_size` tiles
+    pub fn with_chunk_size(mut self, chunk_size: i32) -> Self {
+        self.chunk_size = Some(chunk_size.max(1));
+        self
+    }
+
+    pub fn build(&self, layer: &TileLayer) -> Result<Vec<TileBatch>, Error> {
+        let grid = layer.decode_grid()?;
+        let offset = (
+            layer.offset_x.unwrap_or_default(),
+            layer.offset_y.unwrap_or_default(),
+        );
+        Ok(self.build_grid(&grid, offset, layer.opacity as f32))
+    }
+
+    pub fn build_grid(&self, grid: &TileGrid, offset: (f64, f64), opacity: f32) -> Vec<TileBatch> {
+        let mut batches: BTreeMap<(Option<(i32, i32)>, String), TileBatch> = BTreeMap::new();
+
+        for cell in RenderCells::new(&self.geometry, grid) {
+            let (_, resolved, id) = match self.tilesets.resolve(cell.gid) {
+                Some(resolved) => resolved,
+                None => continue,
+            };
+            let tileset = resolved.tileset;
+            let image = match tileset.tile_image(id) {
+                Some(image) => image,
+                None => continue,
+            };
+
+            let (offset_x, offset_y) = tileset
+                .tile_offset
+                .as_ref()
+                .map(|tile_offset| (tile_offset.x as f64, tile_offset.y as f64))
+                .unwrap_or_default();
+
+            // Tiles are aligned to the bottom-left corner of their cell
+            let left = cell.pixel_x + offset.0 + offset_x;
+            let bottom = cell.pixel_y + self.geometry.tile_height as f64 + offset.1 + offset_y;
+            let right = left + image.width as f64;
+            let top = bottom - image.height as f64;
+
+            let mut corners = [[left, top], [right, top], [right, bottom], [left, bottom]];
+
+            let hexagonal = self.geometry.orientation == Orientation::Hexagonal;
+            if hexagonal {
+                // Hexagonal maps reuse the diagonal flag as a 60 degree rotation
+                let mut degrees: f64 = 0.0;
+                if cell.gid.flipped_diagonally() {
+                    degrees += 60.0;
+                }
+                if cell.gid.rotated_hexagonal_120() {
+                    degrees += 120.0;
+                }
+                rotate(&mut corners, degrees.to_radians());
+            }
+
+            let image_width = image.image_width.max(1) as f64;
+            let image_height = image.image_height.max(1) as f64;
+            let uvs = flip_corners(cell.gid, !hexagonal).map(|[a, b]| {
+                [
+                    ((image.x as f64 + a * image.width as f64) / image_width) as f32,
+                    ((image.y as f64 + b * image.height as f64) / image_height) as f32,
+                ]
+            });
+
+            let chunk = self
+                .chunk_size
+                .map(|size| (cell.x.div_euclid(size), cell.y.div_euclid(size)));
+
+            batches
+                .entry((chunk, image.image.to_owned()))
+                .or_insert_with(|| TileBatch::new(image.image.to_owned(), chunk))
+                .push_quad(corners.map(|[x, y]| [x as f32, y as f32]), uvs, opacity);
+        }
+
+        batches.into_values().collect()
+    }
+}
+
+/// Normalized texture coordinates for the quad corners. Tiled applies the
+/// diagonal flip first, then the horizontal and vertical ones.
+fn flip_corners(gid: Gid, diagonal: bool) -> [[f64; 2]; 4] {
+    [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]].map(|[mut a, mut b]| {
+        if gid.flipped_vertically() {
+            b = 1.0 - b;
+        }
+        if gid.flipped_horizontally() {
+            a = 1.0 - a;
+        }
+        if diagonal && gid.flipped_diagonally() {
+            std::mem::swap(&mut a, &mut b);
+        }
+        [a, b]
+    })
+}
+
+fn rotate(corners: &mut [[f64; 2]; 4], angle: f64) {
+    if angle == 0.0 {
+        return;
+    }
+
+    let center_x = (corners[0][0] + corners[2][0]) / 2.0;
+    let center_y = (corners[0][1] + corners[2][1]) / 2.0;
+    let (sin, cos) = angle.sin_cos();
+    for corner in corners.iter_mut() {
+        let (dx, dy) = (corner[0] - center_x, corner[1] - center_y);
+        *corner = [
+            center_x + dx * cos - dy * sin,
+            center_y + dx * sin + dy * cos,
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::gid::FLIPPED_DIAGONALLY_FLAG;
+    use crate::tme::gid::FLIPPED_HORIZONTALLY_FLAG;
+    use crate::tme::models::RenderOrder;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+    use crate::tme::models::Tileset;
+    use crate::tme::tileset_lookup::ResolvedTileset;
+
+    use serde_json::json;
+
+    fn geometry() -> MapGeometry {
+        MapGeometry {
+            orientation:     Orientation::Orthogonal,
+            render_order:    RenderOrder::RightDown,
+            map_width:       4,
+            map_height:      4,
+            tile_width:      16,
+            tile_height:     16,
+            stagger_axis:    StaggerAxis::Y,
+            stagger_index:   StaggerIndex::Odd,
+            hex_side_length: 0,
+        }
+    }
+
+    fn tileset() -> Tileset {
+        serde_json::from_value(json! {
+            {
+                "columns":      2,
+                "image":        "atlas.png",
+                "imageheight":  32,
+                "imagewidth":   32,
+                "name":         "atlas",
+                "spacing":      0,
+                "tilecount":    4,
+                "tiledversion": "1.3.5",
+                "tileheight":   16,
+                "tileoffset":   { "x": 0, "y": 0 },
+                "tilewidth":    16,
+                "type":         "tileset",
+                "version":      1.2
+            }
+        })
+        .unwrap()
+    }
+
+    fn grid() -> TileGrid {
+        TileGrid {
+            x:      0,
+            y:      0,
+            width:  4,
+            height: 1,
+            tiles:  vec![
+                1,
+                (4 | FLIPPED_HORIZONTALLY_FLAG) as i32,
+                (2 | FLIPPED_HORIZONTALLY_FLAG | FLIPPED_DIAGONALLY_FLAG) as i32,
+                0,
+            ],
+        }
+    }
+
+    #[test]
+    fn builds_quads_with_flips() {
+        let tileset = tileset();
+        let lookup = TilesetLookup::new(vec![ResolvedTileset {
+            first_gid: 1,
+            tileset:   &tileset,
+        }]);
+
+        let batches =
+            TileMeshBuilder::new(geometry(), &lookup).build_grid(&grid(), (0.0, 0.0), 0.5);
+        assert_eq!(batches.len(), 1);
+
+        let batch = &batches[0];
+        assert_eq!(batch.image, "atlas.png");
+        assert_eq!(batch.vertices.len(), 12);
+        assert_eq!(batch.indices.len(), 18);
+        assert_eq!(&batch.indices[6..12], &[4, 5, 6, 6, 7, 4]);
+        assert_eq!((batch.min, batch.max), ([0.0, 0.0], [48.0, 16.0]));
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&batch.vertices).len(),
+            12 * 20
+        );
+
+        let uvs: Vec<[f32; 2]> = batch.vertices.iter().map(|vertex| vertex.uv).collect();
+        assert_eq!(
+            &uvs[0..4],
+            &[[0.0, 0.0], [0.5, 0.0], [0.5, 0.5], [0.0, 0.5]]
+        );
+        assert_eq!(
+            &uvs[4..8],
+            &[[1.0, 0.5], [0.5, 0.5], [0.5, 1.0], [1.0, 1.0]]
+        );
+        // Rotated clockwise by 90 degrees
+        assert_eq!(
+            &uvs[8..12],
+            &[[0.5, 0.5], [0.5, 0.0], [1.0, 0.0], [1.0, 0.5]]
+        );
+
+        assert_eq!(batch.vertices[5].position, [32.0, 0.0]);
+        assert!(batch.vertices.iter().all(|vertex| vertex.opacity == 0.5));
+    }
+
+    #[test]
+    fn splits_into_chunks() {
+        let tileset = tileset();
+        let lookup = TilesetLookup::new(vec![ResolvedTileset {
+            first_gid: 1,
+            tileset:   &tileset,
+        }]);
+
+        let batches = TileMeshBuilder::new(geometry(), &lookup)
+            .with_chunk_size(2)
+            .build_grid(&grid(), (8.0, 0.0), 1.0);
+
+        let chunks: Vec<_> = batches
+            .iter()
+            .map(|batch| (batch.chunk, batch.vertices.len()))
+            .collect();
+        assert_eq!(chunks, vec![(Some((0, 0)), 8), (Some((1, 0)), 4)]);
+        assert_eq!(batches[1].min, [40.0, 0.0]);
+    }
+}
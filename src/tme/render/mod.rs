@@ -0,0 +1,9 @@
+pub mod cells;
+pub mod geometry;
+pub mod mesh;
+pub mod text_layout;
+
+pub use cells::*;
+pub use geometry::*;
+pub use mesh::*;
+pub use text_layout::*;
@@ -0,0 +1,479 @@
+use std::ops::Range;
+
+use crate::tme::models::HorizontalAlign;
+use crate::tme::models::Text;
+use crate::tme::models::TextObject;
+use crate::tme::models::VerticalAlign;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FontStyle<'a> {
+    pub family:     &'a str,
+    pub pixel_size: i32,
+    pub bold:       bool,
+    pub italic:     bool,
+}
+
+impl<'a> FontStyle<'a> {
+    pub fn from_text(text: &'a Text) -> Self {
+        Self {
+            family:     &text.font_family,
+            pixel_size: text.pixel_size,
+            bold:       text.bold,
+            italic:     text.italic,
+        }
+    }
+}
+
+/// Glyph measurements supplied by the font backend of the consumer.
+/// All values are in pixels.
+pub trait FontMetrics {
+    fn advance(&self, font: &FontStyle, character: char) -> f64;
+
+    fn ascent(&self, font: &FontStyle) -> f64;
+
+    fn line_height(&self, font: &FontStyle) -> f64;
+
+    fn kerning(&self, _font: &FontStyle, _left: char, _right: char) -> f64 {
+        0.0
+    }
+
+    /// Distance from the baseline down to the underline
+    fn underline_offset(&self, font: &FontStyle) -> f64 {
+        font.pixel_size as f64 / 10.0
+    }
+
+    /// Distance from the baseline up to the strike-out line
+    fn strike_out_offset(&self, font: &FontStyle) -> f64 {
+        self.ascent(font) * 0.3
+    }
+
+    fn decoration_thickness(&self, font: &FontStyle) -> f64 {
+        (font.pixel_size as f64 / 16.0).max(1.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PositionedGlyph {
+    pub character: char,
+    pub x:         f64,
+    pub y:         f64,
+    pub advance:   f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecorationKind {
+    Underline,
+    StrikeOut,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextDecoration {
+    pub kind:      DecorationKind,
+    pub x:         f64,
+    pub y:         f64,
+    pub width:     f64,
+    pub thickness: f64,
+}
+
+/// Single laid out line, glyph `y` is the baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    pub text:        String,
+    pub x:           f64,
+    pub y:           f64,
+    pub baseline:    f64,
+    pub width:       f64,
+    pub height:      f64,
+    pub glyphs:      Vec<PositionedGlyph>,
+    pub decorations: Vec<TextDecoration>,
+}
+
+/// Lines positioned relative to the top-left corner of the text box
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub lines:  Vec<TextLine>,
+    pub width:  f64,
+    pub height: f64,
+}
+
+pub fn layout_text<M: FontMetrics>(
+    text: &Text,
+    width: f64,
+    height: f64,
+    metrics: &M,
+) -> TextLayout {
+    let font = FontStyle::from_text(text);
+    let measurer = Measurer {
+        metrics,
+        font: &font,
+        kerning: text.kerning,
+    };
+
+    let mut ranges = Vec::new();
+    for paragraph in text.text.split('\n') {
+        let chars: Vec<char> = paragraph.trim_end_matches('\r').chars().collect();
+        let lines = if text.wrap {
+            wrap_paragraph(&chars, width, &measurer)
+        } else {
+            std::iter::once(0..chars.len()).collect()
+        };
+
+        let last = lines.len() - 1;
+        for (index, range) in lines.into_iter().enumerate() {
+            ranges.push((chars[range].to_vec(), index == last));
+        }
+    }
+
+    let line_height = metrics.line_height(&font);
+    let ascent = metrics.ascent(&font);
+    let total_height = line_height * ranges.len() as f64;
+    let top = match text.v_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Center => (height - total_height) / 2.0,
+        VerticalAlign::Bottom => height - total_height,
+    };
+
+    let mut lines = Vec::with_capacity(ranges.len());
+    for (index, (chars, last_in_paragraph)) in ranges.into_iter().enumerate() {
+        let line_width = measurer.measure(&chars);
+        let spaces = chars.iter().filter(|c| c.is_whitespace()).count();
+
+        let (x, extra_space) = match text.h_align {
+            HorizontalAlign::Left => (0.0, 0.0),
+            HorizontalAlign::Center => ((width - line_width) / 2.0, 0.0),
+            HorizontalAlign::Right => (width - line_width, 0.0),
+            HorizontalAlign::Justify if !last_in_paragraph && spaces > 0 && line_width < width => {
+                (0.0, (width - line_width) / spaces as f64)
+            }
+            HorizontalAlign::Justify => (0.0, 0.0),
+        };
+
+        let y = top + line_height * index as f64;
+        let baseline = y + ascent;
+
+        let mut glyphs = Vec::with_capacity(chars.len());
+        let mut cursor = x;
+        let mut previous = None;
+        for &character in &chars {
+            if let Some(previous) = previous {
+                cursor += measurer.kerning(previous, character);
+            }
+
+            let mut advance = metrics.advance(&font, character);
+            if character.is_whitespace() {
+                advance += extra_space;
+            }
+
+            glyphs.push(PositionedGlyph {
+                character,
+                x: cursor,
+                y: baseline,
+                advance,
+            });
+            cursor += advance;
+            previous = Some(character);
+        }
+
+        let width = cursor - x;
+        let thickness = metrics.decoration_thickness(&font);
+        let mut decorations = Vec::new();
+        if text.underline {
+            decorations.push(TextDecoration {
+                kind: DecorationKind::Underline,
+                x,
+                y: baseline + metrics.underline_offset(&font),
+                width,
+                thickness,
+            });
+        }
+        if text.strike_out {
+            decorations.push(TextDecoration {
+                kind: DecorationKind::StrikeOut,
+                x,
+                y: baseline - metrics.strike_out_offset(&font),
+                width,
+                thickness,
+            });
+        }
+
+        lines.push(TextLine {
+            text: chars.iter().collect(),
+            x,
+            y,
+            baseline,
+            width,
+            height: line_height,
+            glyphs,
+            decorations,
+        });
+    }
+
+    TextLayout {
+        lines,
+        width,
+        height,
+    }
+}
+
+impl TextObject {
+    pub fn layout<M: FontMetrics>(&self, metrics: &M) -> TextLayout {
+        layout_text(&self.text, self.width, self.height, metrics)
+    }
+}
+
+struct Measurer<'a, M> {
+    metrics: &'a M,
+    font:    &'a FontStyle<'a>,
+    kerning: bool,
+}
+
+impl<'a, M: FontMetrics> Measurer<'a, M> {
+    fn kerning(&self, left: char, right: char) -> f64 {
+        if self.kerning {
+            self.metrics.kerning(self.font, left, right)
+        } else {
+            0.0
+        }
+    }
+
+    fn measure(&self, chars: &[char]) -> f64 {
+        let mut width = 0.0;
+        let mut previous = None;
+        for &character in chars {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, character);
+            }
+            width += self.metrics.advance(self.font, character);
+            previous = Some(character);
+        }
+        width
+    }
+}
+
+/// Greedy line breaking at whitespace, falling back to breaking inside words
+/// which do not fit on a line of their own. Trailing whitespace is dropped.
+fn wrap_paragraph<M: FontMetrics>(
+    chars: &[char],
+    max_width: f64,
+    measurer: &Measurer<M>,
+) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let start = index;
+        while index < chars.len() && chars[index].is_whitespace() {
+            index += 1;
+        }
+        while index < chars.len() && !chars[index].is_whitespace() {
+            index += 1;
+        }
+        words.push(start..index);
+    }
+
+    let mut lines = Vec::new();
+    let mut line_start: Option<usize> = None;
+    let mut line_end = 0;
+    for word in words {
+        if let Some(start) = line_start {
+            // The current line absorbs trailing whitespace, even past its width
+            if chars[word.clone()].iter().all(|c| c.is_whitespace()) {
+                continue;
+            }
+            if measurer.measure(&chars[start..word.end]) <= max_width {
+                line_end = word.end;
+                continue;
+            }
+            lines.push(start..line_end);
+        }
+
+        // Whitespace which caused the break does not start the next line
+        let mut start = if line_start.is_some() {
+            (word.start..word.end)
+                .find(|&i| !chars[i].is_whitespace())
+                .unwrap_or(word.end)
+        } else {
+            word.start
+        };
+
+        while start < word.end && measurer.measure(&chars[start..word.end]) > max_width {
+            let mut end = start + 1;
+            while end < word.end && measurer.measure(&chars[start..end + 1]) <= max_width {
+                end += 1;
+            }
+            lines.push(start..end);
+            start = end;
+        }
+
+        line_start = Some(start);
+        line_end = word.end;
+    }
+
+    match line_start {
+        Some(start) => lines.push(start..line_end),
+        None => lines.push(0..0),
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::color::Color;
+
+    struct Monospace;
+
+    impl FontMetrics for Monospace {
+        fn advance(&self, _font: &FontStyle, _character: char) -> f64 {
+            10.0
+        }
+
+        fn ascent(&self, _font: &FontStyle) -> f64 {
+            16.0
+        }
+
+        fn line_height(&self, _font: &FontStyle) -> f64 {
+            20.0
+        }
+
+        fn kerning(&self, _font: &FontStyle, left: char, right: char) -> f64 {
+            if left == 'A' && right == 'V' {
+                -2.0
+            } else {
+                0.0
+            }
+        }
+    }
+
+    fn make_text(
+        value: &str,
+        wrap: bool,
+        h_align: HorizontalAlign,
+        v_align: VerticalAlign,
+    ) -> Text {
+        Text {
+            bold: false,
+            color: Color::new_black(),
+            font_family: "sans-serif".to_owned(),
+            h_align,
+            italic: false,
+            kerning: true,
+            pixel_size: 16,
+            strike_out: false,
+            text: value.to_owned(),
+            underline: false,
+            v_align,
+            wrap,
+        }
+    }
+
+    fn lines(layout: &TextLayout) -> Vec<(&str, f64, f64)> {
+        layout
+            .lines
+            .iter()
+            .map(|line| (line.text.as_str(), line.x, line.y))
+            .collect()
+    }
+
+    #[test]
+    fn wraps_words_and_breaks_long_ones() {
+        let text = make_text(
+            "hello big world\nabcdefghijkl",
+            true,
+            HorizontalAlign::Left,
+            VerticalAlign::Top,
+        );
+        let layout = layout_text(&text, 80.0, 100.0, &Monospace);
+
+        assert_eq!(
+            lines(&layout),
+            vec![
+                ("hello", 0.0, 0.0),
+                ("big", 0.0, 20.0),
+                ("world", 0.0, 40.0),
+                ("abcdefgh", 0.0, 60.0),
+                ("ijkl", 0.0, 80.0),
+            ]
+        );
+        assert_eq!(layout.lines[1].baseline, 36.0);
+    }
+
+    #[test]
+    fn drops_trailing_whitespace_when_wrapping() {
+        let text = make_text(
+            "ab  \ncd ef ",
+            true,
+            HorizontalAlign::Left,
+            VerticalAlign::Top,
+        );
+        let layout = layout_text(&text, 20.0, 100.0, &Monospace);
+
+        assert_eq!(
+            lines(&layout),
+            vec![("ab", 0.0, 0.0), ("cd", 0.0, 20.0), ("ef", 0.0, 40.0)]
+        );
+    }
+
+    #[test]
+    fn aligns_lines_in_box() {
+        let text = make_text(
+            "ab\nabcd",
+            false,
+            HorizontalAlign::Right,
+            VerticalAlign::Bottom,
+        );
+        let layout = layout_text(&text, 100.0, 100.0, &Monospace);
+        assert_eq!(
+            lines(&layout),
+            vec![("ab", 80.0, 60.0), ("abcd", 60.0, 80.0)]
+        );
+
+        let text = make_text("ab", false, HorizontalAlign::Center, VerticalAlign::Center);
+        let layout = layout_text(&text, 100.0, 100.0, &Monospace);
+        assert_eq!(lines(&layout), vec![("ab", 40.0, 40.0)]);
+    }
+
+    #[test]
+    fn justifies_all_but_last_line() {
+        let text = make_text(
+            "aa bb cc dd",
+            true,
+            HorizontalAlign::Justify,
+            VerticalAlign::Top,
+        );
+        let layout = layout_text(&text, 90.0, 100.0, &Monospace);
+
+        assert_eq!(
+            lines(&layout),
+            vec![("aa bb cc", 0.0, 0.0), ("dd", 0.0, 20.0)]
+        );
+        assert_eq!(layout.lines[0].width, 90.0);
+        let positions: Vec<f64> = layout.lines[0].glyphs.iter().map(|glyph| glyph.x).collect();
+        assert_eq!(
+            positions,
+            vec![0.0, 10.0, 20.0, 35.0, 45.0, 55.0, 70.0, 80.0]
+        );
+        assert_eq!(layout.lines[1].width, 20.0);
+    }
+
+    #[test]
+    fn applies_kerning_and_decorations() {
+        let mut text = make_text("AV", false, HorizontalAlign::Left, VerticalAlign::Top);
+        text.underline = true;
+        text.strike_out = true;
+        let layout = layout_text(&text, 100.0, 100.0, &Monospace);
+
+        let line = &layout.lines[0];
+        assert_eq!(line.glyphs[1].x, 8.0);
+        assert_eq!(line.width, 18.0);
+        assert_eq!(line.decorations.len(), 2);
+        assert_eq!(line.decorations[0].kind, DecorationKind::Underline);
+        assert_eq!(line.decorations[0].y, 17.6);
+        assert_eq!(line.decorations[1].kind, DecorationKind::StrikeOut);
+
+        text.kerning = false;
+        let layout = layout_text(&text, 100.0, 100.0, &Monospace);
+        assert_eq!(layout.lines[0].width, 20.0);
+    }
+}
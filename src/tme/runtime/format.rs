@@ -0,0 +1,215 @@
+use crate::tme::error::Error;
+
+/// First bytes of every runtime map
+pub const RUNTIME_MAGIC: [u8; 4] = *b"EMBR";
+/// Version written by this crate, older ones are still readable
+pub const RUNTIME_VERSION: u32 = 1;
+
+/// Magic, version, payload length and CRC32 of the payload
+pub(super) const HEADER_LENGTH: usize = 16;
+/// Marks absent strings and lists where an index or count is expected
+pub(super) const NONE: u32 = u32::MAX;
+
+/// Checks the header and returns the payload it guards
+pub(super) fn payload(bytes: &[u8]) -> Result<&[u8], Error> {
+    if bytes.len() < RUNTIME_MAGIC.len() || bytes[..RUNTIME_MAGIC.len()] != RUNTIME_MAGIC {
+        return Error::InvalidRuntimeMagic.fail();
+    }
+    let mut header = Cursor::new(
+        bytes
+            .get(..HEADER_LENGTH)
+            .ok_or(Error::TruncatedRuntimeMap)?,
+    );
+    header.skip(RUNTIME_MAGIC.len())?;
+    let version = header.u32()?;
+    if version == 0 || version > RUNTIME_VERSION {
+        return Error::UnsupportedRuntimeVersion(version).fail();
+    }
+    let length = header.u32()? as usize;
+    let expected = header.u32()?;
+
+    let payload = bytes
+        .get(HEADER_LENGTH..HEADER_LENGTH + length)
+        .ok_or(Error::TruncatedRuntimeMap)?;
+    let actual = crc32fast::hash(payload);
+    if actual != expected {
+        return Error::RuntimeChecksumMismatch { expected, actual }.fail();
+    }
+    Ok(payload)
+}
+
+/// Prepends the header to `payload`
+pub(super) fn seal(payload: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    bytes.extend_from_slice(&RUNTIME_MAGIC);
+    bytes.put_u32(RUNTIME_VERSION);
+    bytes.put_u32(payload.len() as u32);
+    bytes.put_u32(crc32fast::hash(&payload));
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// Little endian primitives appended to a buffer
+pub(super) trait Put {
+    fn put_u8(&mut self, value: u8);
+    fn put_u32(&mut self, value: u32);
+    fn put_i32(&mut self, value: i32);
+    fn put_i64(&mut self, value: i64);
+    fn put_f64(&mut self, value: f64);
+    fn put_bool(&mut self, value: bool);
+    fn pad(&mut self, alignment: usize);
+}
+
+impl Put for Vec<u8> {
+    fn put_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_i32(&mut self, value: i32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_i64(&mut self, value: i64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_f64(&mut self, value: f64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_bool(&mut self, value: bool) {
+        self.push(value as u8);
+    }
+
+    fn pad(&mut self, alignment: usize) {
+        while !self.len().is_multiple_of(alignment) {
+            self.push(0);
+        }
+    }
+}
+
+/// Reads little endian primitives, failing on truncated input
+pub(super) struct Cursor<'a> {
+    bytes:    &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub(super) fn bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(Error::TruncatedRuntimeMap)?;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(Error::TruncatedRuntimeMap)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub(super) fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    pub(super) fn skip(&mut self, length: usize) -> Result<(), Error> {
+        self.bytes(length).map(|_| ())
+    }
+
+    pub(super) fn align(&mut self, alignment: usize) -> Result<(), Error> {
+        let padding = (alignment - self.position % alignment) % alignment;
+        self.skip(padding)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, Error> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub(super) fn i32(&mut self) -> Result<i32, Error> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub(super) fn i64(&mut self) -> Result<i64, Error> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    pub(super) fn f64(&mut self) -> Result<f64, Error> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    pub(super) fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Error::InvalidRuntimeValue("flag".to_owned()).fail(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_header() {
+        let bytes = seal(vec![1, 2, 3]);
+        assert_eq!(bytes.len(), HEADER_LENGTH + 3);
+        assert_eq!(payload(&bytes).unwrap(), &[1, 2, 3]);
+
+        assert!(matches!(payload(b"{}"), Err(Error::InvalidRuntimeMagic)));
+        assert!(matches!(
+            payload(&bytes[..bytes.len() - 1]),
+            Err(Error::TruncatedRuntimeMap)
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[HEADER_LENGTH] = 7;
+        assert!(matches!(
+            payload(&corrupt),
+            Err(Error::RuntimeChecksumMismatch { .. })
+        ));
+
+        let mut future = bytes;
+        future[4] = RUNTIME_VERSION as u8 + 1;
+        assert!(matches!(
+            payload(&future),
+            Err(Error::UnsupportedRuntimeVersion(version)) if version == RUNTIME_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn reads_primitives() {
+        let mut bytes = Vec::new();
+        bytes.put_u8(7);
+        bytes.pad(4);
+        bytes.put_i32(-2);
+        bytes.put_f64(1.5);
+        bytes.put_bool(true);
+
+        let mut cursor = Cursor::new(&bytes);
+        assert_eq!(cursor.u8().unwrap(), 7);
+        cursor.align(4).unwrap();
+        assert_eq!(cursor.i32().unwrap(), -2);
+        assert_eq!(cursor.f64().unwrap(), 1.5);
+        assert!(cursor.bool().unwrap());
+        assert!(matches!(cursor.u8(), Err(Error::TruncatedRuntimeMap)));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod format;
+
+mod reader;
+mod writer;
+
+pub use format::RUNTIME_MAGIC;
+pub use format::RUNTIME_VERSION;
+pub use reader::*;
+pub use writer::*;
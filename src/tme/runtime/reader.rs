@@ -0,0 +1,688 @@
+use std::path::PathBuf;
+
+use super::format;
+use super::format::Cursor;
+use super::format::NONE;
+
+use crate::tme::color::Color;
+use crate::tme::error::Error;
+use crate::tme::models::wang_color::WangColor;
+use crate::tme::models::wang_set::WangSet;
+use crate::tme::models::wang_tile::WangTile;
+use crate::tme::models::BoolProperty;
+use crate::tme::models::Chunk;
+use crate::tme::models::ColorProperty;
+use crate::tme::models::DataSource;
+use crate::tme::models::DrawOrder;
+use crate::tme::models::EllipseObject;
+use crate::tme::models::Encoding;
+use crate::tme::models::FileProperty;
+use crate::tme::models::FloatProperty;
+use crate::tme::models::Frame;
+use crate::tme::models::GeneralObject;
+use crate::tme::models::Grid;
+use crate::tme::models::GroupLayer;
+use crate::tme::models::HexagonalMap;
+use crate::tme::models::HorizontalAlign;
+use crate::tme::models::ImageLayer;
+use crate::tme::models::IntProperty;
+use crate::tme::models::IsometricMap;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::MapType;
+use crate::tme::models::Object;
+use crate::tme::models::ObjectGroupLayer;
+use crate::tme::models::Orientation;
+use crate::tme::models::OrthogonalMap;
+use crate::tme::models::Point;
+use crate::tme::models::PointObject;
+use crate::tme::models::PolygonObject;
+use crate::tme::models::PolylineObject;
+use crate::tme::models::Property;
+use crate::tme::models::RectangleObject;
+use crate::tme::models::RenderOrder;
+use crate::tme::models::StaggerAxis;
+use crate::tme::models::StaggerIndex;
+use crate::tme::models::StaggeredMap;
+use crate::tme::models::StringProperty;
+use crate::tme::models::Terrain;
+use crate::tme::models::Text;
+use crate::tme::models::TextObject;
+use crate::tme::models::Tile;
+use crate::tme::models::TileLayer;
+use crate::tme::models::TileOffset;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+use crate::tme::models::TilesetRef;
+use crate::tme::models::VerticalAlign;
+
+/// Reads a map written by `write_runtime_map`.
+///
+/// Tile data comes back decoded, so tile layers hold plain arrays with CSV
+/// encoding whatever encoding and compression they were written with.
+pub fn read_runtime_map(bytes: &[u8]) -> Result<Map, Error> {
+    let mut cursor = Cursor::new(format::payload(bytes)?);
+
+    let string_count = cursor.u32()? as usize;
+    let mut strings = Vec::with_capacity(string_count.min(bytes.len()));
+    for _ in 0..string_count {
+        let length = cursor.u32()? as usize;
+        let string = std::str::from_utf8(cursor.bytes(length)?)
+            .map_err(|_| Error::InvalidRuntimeValue("string".to_owned()))?;
+        strings.push(string);
+    }
+    cursor.align(4)?;
+    let tile_count = cursor.u32()? as usize;
+    let tiles = cursor.bytes(tile_count.saturating_mul(4))?;
+
+    let mut decoder = Decoder {
+        strings,
+        tiles,
+        objects: Vec::new().into_iter(),
+        object_offset: 0,
+    };
+    let object_count = cursor.u32()? as usize;
+    let mut objects = Vec::with_capacity(object_count.min(bytes.len()));
+    for _ in 0..object_count {
+        objects.push(decoder.object(&mut cursor)?);
+    }
+    decoder.objects = objects.into_iter();
+
+    decoder.map(&mut cursor)
+}
+
+struct Decoder<'a> {
+    strings:       Vec<&'a str>,
+    /// Little endian words, they are copied out so alignment does not matter
+    tiles:         &'a [u8],
+    /// Objects are taken in the order the object groups refer to them
+    objects:       std::vec::IntoIter<Object>,
+    object_offset: u32,
+}
+
+impl<'a> Decoder<'a> {
+    fn map(&mut self, cursor: &mut Cursor) -> Result<Map, Error> {
+        let orientation = match cursor.u8()? {
+            0 => Orientation::Orthogonal,
+            1 => Orientation::Isometric,
+            2 => Orientation::Staggered,
+            3 => Orientation::Hexagonal,
+            _ => return invalid("orientation"),
+        };
+        let background_color = color(cursor)?;
+        let compression_level = cursor.i32()?;
+        let width = cursor.i32()?;
+        let height = cursor.i32()?;
+        let tile_width = cursor.i32()?;
+        let tile_height = cursor.i32()?;
+        let infinite = cursor.bool()?;
+        let next_layer_id = cursor.i32()?;
+        let next_object_id = cursor.i32()?;
+        let render_order = match cursor.u8()? {
+            0 => RenderOrder::RightDown,
+            1 => RenderOrder::RightUp,
+            2 => RenderOrder::LeftDown,
+            3 => RenderOrder::LeftUp,
+            _ => return invalid("render order"),
+        };
+        let tiled_version = self.string(cursor)?;
+        let version = self.string(cursor)?;
+        let properties = self.properties(cursor)?;
+        let stagger = match orientation {
+            Orientation::Staggered | Orientation::Hexagonal => {
+                Some((stagger_axis(cursor)?, stagger_index(cursor)?))
+            }
+            _ => None,
+        };
+        let hex_side_length = match orientation {
+            Orientation::Hexagonal => cursor.i32()?,
+            _ => 0,
+        };
+
+        let tile_sets = (0..cursor.u32()?)
+            .map(|_| self.tileset_container(cursor))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let layers = self.layers(cursor)?;
+
+        macro_rules! build {
+            ($variant:ident, $map:ident { $($field:ident = $value:expr),* }) => {
+                Map::$variant($map {
+                    background_color,
+                    compression_level,
+                    height,
+                    infinite,
+                    layers,
+                    next_layer_id,
+                    next_object_id,
+                    properties,
+                    render_order,
+                    tiled_version,
+                    tile_height,
+                    tile_sets,
+                    tile_width,
+                    map_type: MapType::Map,
+                    version,
+                    width,
+                    $($field: $value,)*
+                })
+            };
+        }
+
+        Ok(match (orientation, stagger) {
+            (Orientation::Orthogonal, _) => build!(Orthogonal, OrthogonalMap {}),
+            (Orientation::Isometric, _) => build!(Isometric, IsometricMap {}),
+            (Orientation::Staggered, Some((stagger_axis, stagger_index))) => {
+                build!(Staggered, StaggeredMap {
+                    stagger_axis = stagger_axis,
+                    stagger_index = stagger_index
+                })
+            }
+            (Orientation::Hexagonal, Some((stagger_axis, stagger_index))) => {
+                build!(Hexagonal, HexagonalMap {
+                    hex_side_length = hex_side_length,
+                    stagger_axis = stagger_axis,
+                    stagger_index = stagger_index
+                })
+            }
+            _ => return invalid("orientation"),
+        })
+    }
+
+    fn tileset_container(&mut self, cursor: &mut Cursor) -> Result<TilesetContainer, Error> {
+        match cursor.u8()? {
+            0 => Ok(TilesetContainer::Tileset(self.tileset(cursor)?)),
+            1 => Ok(TilesetContainer::TilesetRef(TilesetRef {
+                first_gid: cursor.i32()?,
+                source:    PathBuf::from(self.string(cursor)?),
+            })),
+            _ => invalid("tileset"),
+        }
+    }
+
+    fn tileset(&mut self, cursor: &mut Cursor) -> Result<Tileset, Error> {
+        let first_gid = optional_i32(cursor)?;
+        let name = self.string(cursor)?;
+        let tileset_type = self.string(cursor)?;
+        let tiled_version = self.string(cursor)?;
+        let version = self.string(cursor)?;
+        let tile_width = cursor.i32()?;
+        let tile_height = cursor.i32()?;
+        let tile_count = cursor.u32()? as usize;
+        let columns = cursor.u32()? as usize;
+        let margin = cursor.i32()?;
+        let spacing = cursor.i32()?;
+        let background_color = color(cursor)?;
+        let transparent_color = optional_color(cursor)?;
+        let image = self.optional_string(cursor)?;
+        let image_width = optional_i32(cursor)?;
+        let image_height = optional_i32(cursor)?;
+        let tile_offset = match cursor.bool()? {
+            true => Some(TileOffset {
+                x: cursor.i32()?,
+                y: cursor.i32()?,
+            }),
+            false => None,
+        };
+        let grid = match cursor.bool()? {
+            true => Some(grid(cursor)?),
+            false => None,
+        };
+        let properties = self.properties(cursor)?;
+        let terrains = self.list(cursor, |decoder, cursor| {
+            Ok(Terrain {
+                name:       decoder.string(cursor)?,
+                tile:       cursor.i32()?,
+                properties: decoder.properties(cursor)?,
+            })
+        })?;
+        let tiles = self.list(cursor, Self::tile)?;
+        let wang_sets = self.list(cursor, Self::wang_set)?;
+
+        Ok(Tileset {
+            background_color,
+            columns,
+            first_gid,
+            grid,
+            image,
+            image_height,
+            image_width,
+            margin,
+            name,
+            properties,
+            spacing,
+            terrains,
+            tile_count,
+            tiled_version,
+            tile_height,
+            tile_offset,
+            tiles,
+            tile_width,
+            transparent_color,
+            tileset_type,
+            version,
+            wang_sets,
+        })
+    }
+
+    fn tile(&mut self, cursor: &mut Cursor) -> Result<Tile, Error> {
+        let id = cursor.i32()?;
+        let tile_type = self.optional_string(cursor)?;
+        let image = self.optional_string(cursor)?;
+        let image_width = optional_i32(cursor)?;
+        let image_height = optional_i32(cursor)?;
+        let probability = optional_f64(cursor)?;
+        let terrain = self.list(cursor, |_, cursor| cursor.i32())?;
+        let animation = self.list(cursor, |_, cursor| {
+            Ok(Frame {
+                tiled_id: cursor.i32()?,
+                duration: cursor.i64()?,
+            })
+        })?;
+        let properties = self.properties(cursor)?;
+        let object_group = match cursor.bool()? {
+            true => Some(self.layer(cursor)?),
+            false => None,
+        };
+
+        Ok(Tile {
+            animation,
+            id,
+            image,
+            image_height,
+            image_width,
+            object_group,
+            probability,
+            properties,
+            terrain,
+            tile_type,
+        })
+    }
+
+    fn wang_set(&mut self, cursor: &mut Cursor) -> Result<WangSet, Error> {
+        let name = self.string(cursor)?;
+        let tile = cursor.i32()?;
+        let properties = self.properties(cursor)?;
+        let corner_colors = self.vec(cursor, Self::wang_color)?;
+        let edge_colors = self.vec(cursor, Self::wang_color)?;
+        let wang_tiles = self.vec(cursor, |decoder, cursor| {
+            Ok(WangTile {
+                tile_id: cursor.i32()?,
+                h_flip:  cursor.bool()?,
+                v_flip:  cursor.bool()?,
+                d_flip:  cursor.bool()?,
+                wang_id: decoder.vec(cursor, |_, cursor| cursor.i64())?,
+            })
+        })?;
+
+        Ok(WangSet {
+            corner_colors,
+            edge_colors,
+            name,
+            properties,
+            tile,
+            wang_tiles,
+        })
+    }
+
+    fn wang_color(&mut self, cursor: &mut Cursor) -> Result<WangColor, Error> {
+        Ok(WangColor {
+            name:        self.string(cursor)?,
+            color:       color(cursor)?,
+            probability: cursor.f64()?,
+            tile:        cursor.i64()?,
+        })
+    }
+
+    fn layers(&mut self, cursor: &mut Cursor) -> Result<Vec<Layer>, Error> {
+        self.vec(cursor, Self::layer)
+    }
+
+    fn layer(&mut self, cursor: &mut Cursor) -> Result<Layer, Error> {
+        let kind = cursor.u8()?;
+        let id = cursor.i32()?;
+        let name = self.string(cursor)?;
+        let x = cursor.i32()?;
+        let y = cursor.i32()?;
+        let offset_x = optional_f64(cursor)?;
+        let offset_y = optional_f64(cursor)?;
+        let start_x = optional_i32(cursor)?;
+        let start_y = optional_i32(cursor)?;
+        let opacity = cursor.f64()?;
+        let visible = cursor.bool()?;
+        let properties = self.properties(cursor)?;
+
+        macro_rules! build {
+            ($variant:ident, $layer:ident { $($field:ident = $value:expr),* }) => {
+                Layer::$variant($layer {
+                    id,
+                    name,
+                    offset_x,
+                    offset_y,
+                    opacity,
+                    properties,
+                    start_x,
+                    start_y,
+                    visible,
+                    x,
+                    y,
+                    $($field: $value,)*
+                })
+            };
+        }
+
+        Ok(match kind {
+            0 => {
+                let width = cursor.i32()?;
+                let height = cursor.i32()?;
+                let data = self.tiles(cursor)?;
+                let chunks = self.list(cursor, |decoder, cursor| {
+                    Ok(Chunk {
+                        x:      cursor.i32()?,
+                        y:      cursor.i32()?,
+                        width:  cursor.i32()?,
+                        height: cursor.i32()?,
+                        data:   decoder.tiles(cursor)?,
+                    })
+                })?;
+                build!(TileLayer, TileLayer {
+                    chunks = chunks,
+                    compression = None,
+                    data = data,
+                    encoding = Some(Encoding::Csv),
+                    height = height,
+                    width = width
+                })
+            }
+            1 => {
+                let draw_order = match cursor.u8()? {
+                    0 => DrawOrder::TopDown,
+                    1 => DrawOrder::Index,
+                    _ => return invalid("draw order"),
+                };
+                build!(ObjectGroupLayer, ObjectGroupLayer {
+                    draw_order = draw_order,
+                    objects = self.objects(cursor)?
+                })
+            }
+            2 => build!(ImageLayer, ImageLayer {
+                image = self.string(cursor)?,
+                transparent_color = optional_color(cursor)?
+            }),
+            3 => build!(GroupLayer, GroupLayer {
+                layers = self.layers(cursor)?
+            }),
+            _ => return invalid("layer type"),
+        })
+    }
+
+    fn tiles(&mut self, cursor: &mut Cursor) -> Result<DataSource, Error> {
+        let offset = cursor.u32()? as usize;
+        let length = cursor.u32()? as usize;
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| self.tiles.get(offset * 4..end * 4))
+            .ok_or_else(|| Error::InvalidRuntimeValue("tile range".to_owned()))?;
+        Ok(DataSource::Raw(
+            bytes
+                .chunks_exact(4)
+                .map(|word| i32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+                .collect(),
+        ))
+    }
+
+    fn objects(&mut self, cursor: &mut Cursor) -> Result<Vec<Object>, Error> {
+        let offset = cursor.u32()?;
+        let count = cursor.u32()?;
+        if offset != self.object_offset || count as usize > self.objects.len() {
+            return invalid("object range");
+        }
+        self.object_offset += count;
+        Ok(self.objects.by_ref().take(count as usize).collect())
+    }
+
+    fn object(&mut self, cursor: &mut Cursor) -> Result<Object, Error> {
+        let kind = cursor.u8()?;
+        let id = cursor.i64()?;
+        let name = self.string(cursor)?;
+        let obj_type = self.string(cursor)?;
+        let template = self.optional_string(cursor)?;
+        let x = cursor.f64()?;
+        let y = cursor.f64()?;
+        let width = cursor.f64()?;
+        let height = cursor.f64()?;
+        let rotation = cursor.f64()?;
+        let visible = cursor.bool()?;
+        let properties = self.properties(cursor)?;
+
+        macro_rules! build {
+            ($variant:ident, $object:ident { $($field:ident = $value:expr),* }) => {
+                Object::$variant($object {
+                    height,
+                    id,
+                    name,
+                    properties,
+                    rotation,
+                    template,
+                    obj_type,
+                    visible,
+                    width,
+                    x,
+                    y,
+                    $($field: $value,)*
+                })
+            };
+        }
+
+        Ok(match kind {
+            0 => build!(General, GeneralObject {
+                gid = cursor.i64()?
+            }),
+            1 => build!(Ellipse, EllipseObject {
+                ellipse = cursor.bool()?
+            }),
+            2 => build!(Rectangle, RectangleObject {}),
+            3 => build!(Point, PointObject {
+                point = cursor.bool()?
+            }),
+            4 => build!(Polygon, PolygonObject {
+                polygon = self.vec(cursor, point)?
+            }),
+            5 => build!(Polyline, PolylineObject {
+                polyline = self.vec(cursor, point)?
+            }),
+            6 => build!(Text, TextObject {
+                text = self.text(cursor)?
+            }),
+            _ => return invalid("object type"),
+        })
+    }
+
+    fn text(&mut self, cursor: &mut Cursor) -> Result<Text, Error> {
+        let text = self.string(cursor)?;
+        let font_family = self.string(cursor)?;
+        let pixel_size = cursor.i32()?;
+        let color = color(cursor)?;
+        let h_align = match cursor.u8()? {
+            0 => HorizontalAlign::Center,
+            1 => HorizontalAlign::Right,
+            2 => HorizontalAlign::Justify,
+            3 => HorizontalAlign::Left,
+            _ => return invalid("horizontal align"),
+        };
+        let v_align = match cursor.u8()? {
+            0 => VerticalAlign::Center,
+            1 => VerticalAlign::Bottom,
+            2 => VerticalAlign::Top,
+            _ => return invalid("vertical align"),
+        };
+
+        Ok(Text {
+            bold: cursor.bool()?,
+            italic: cursor.bool()?,
+            underline: cursor.bool()?,
+            strike_out: cursor.bool()?,
+            kerning: cursor.bool()?,
+            wrap: cursor.bool()?,
+            color,
+            font_family,
+            h_align,
+            pixel_size,
+            text,
+            v_align,
+        })
+    }
+
+    fn properties(&mut self, cursor: &mut Cursor) -> Result<Option<Vec<Property>>, Error> {
+        self.list(cursor, Self::property)
+    }
+
+    fn property(&mut self, cursor: &mut Cursor) -> Result<Property, Error> {
+        let kind = cursor.u8()?;
+        let name = self.string(cursor)?;
+        Ok(match kind {
+            0 => Property::Int(IntProperty {
+                name,
+                value: cursor.i32()?,
+            }),
+            1 => Property::Bool(BoolProperty {
+                name,
+                value: cursor.bool()?,
+            }),
+            2 => Property::File(FileProperty {
+                name,
+                value: PathBuf::from(self.string(cursor)?),
+            }),
+            3 => Property::Color(ColorProperty {
+                name,
+                value: color(cursor)?,
+            }),
+            4 => Property::Float(FloatProperty {
+                name,
+                value: cursor.f64()?,
+            }),
+            5 => Property::String(StringProperty {
+                name,
+                value: self.string(cursor)?,
+            }),
+            _ => return invalid("property type"),
+        })
+    }
+
+    /// List that is absent when its count is `NONE`
+    fn list<T, F>(&mut self, cursor: &mut Cursor, item: F) -> Result<Option<Vec<T>>, Error>
+    where
+        F: FnMut(&mut Self, &mut Cursor) -> Result<T, Error>,
+    {
+        match cursor.u32()? {
+            NONE => Ok(None),
+            count => self.items(cursor, count, item).map(Some),
+        }
+    }
+
+    fn vec<T, F>(&mut self, cursor: &mut Cursor, item: F) -> Result<Vec<T>, Error>
+    where
+        F: FnMut(&mut Self, &mut Cursor) -> Result<T, Error>,
+    {
+        let count = cursor.u32()?;
+        self.items(cursor, count, item)
+    }
+
+    fn items<T, F>(&mut self, cursor: &mut Cursor, count: u32, mut item: F) -> Result<Vec<T>, Error>
+    where
+        F: FnMut(&mut Self, &mut Cursor) -> Result<T, Error>,
+    {
+        // Every item takes at least a byte, which bounds bogus counts
+        let mut items = Vec::with_capacity((count as usize).min(cursor.remaining()));
+        for _ in 0..count {
+            items.push(item(self, cursor)?);
+        }
+        Ok(items)
+    }
+
+    fn string(&self, cursor: &mut Cursor) -> Result<String, Error> {
+        let index = cursor.u32()? as usize;
+        match self.strings.get(index) {
+            Some(string) => Ok((*string).to_owned()),
+            None => invalid("string index"),
+        }
+    }
+
+    fn optional_string(&self, cursor: &mut Cursor) -> Result<Option<String>, Error> {
+        let index = cursor.u32()?;
+        match index {
+            NONE => Ok(None),
+            index => match self.strings.get(index as usize) {
+                Some(string) => Ok(Some((*string).to_owned())),
+                None => invalid("string index"),
+            },
+        }
+    }
+}
+
+fn invalid<T>(what: &str) -> Result<T, Error> {
+    Error::InvalidRuntimeValue(what.to_owned()).fail()
+}
+
+fn optional_i32(cursor: &mut Cursor) -> Result<Option<i32>, Error> {
+    match cursor.bool()? {
+        true => cursor.i32().map(Some),
+        false => Ok(None),
+    }
+}
+
+fn optional_f64(cursor: &mut Cursor) -> Result<Option<f64>, Error> {
+    match cursor.bool()? {
+        true => cursor.f64().map(Some),
+        false => Ok(None),
+    }
+}
+
+fn color(cursor: &mut Cursor) -> Result<Color, Error> {
+    let bytes = cursor.bytes(4)?;
+    Ok(Color::with_alpha(bytes[0], bytes[1], bytes[2], bytes[3]))
+}
+
+fn optional_color(cursor: &mut Cursor) -> Result<Option<Color>, Error> {
+    match cursor.bool()? {
+        true => color(cursor).map(Some),
+        false => Ok(None),
+    }
+}
+
+fn grid(cursor: &mut Cursor) -> Result<Grid, Error> {
+    let orientation = match cursor.u8()? {
+        0 => Orientation::Orthogonal,
+        1 => Orientation::Isometric,
+        2 => Orientation::Staggered,
+        3 => Orientation::Hexagonal,
+        _ => return invalid("grid orientation"),
+    };
+    Ok(Grid {
+        orientation,
+        width: cursor.i32()?,
+        height: cursor.i32()?,
+    })
+}
+
+fn stagger_axis(cursor: &mut Cursor) -> Result<StaggerAxis, Error> {
+    match cursor.u8()? {
+        0 => Ok(StaggerAxis::X),
+        1 => Ok(StaggerAxis::Y),
+        _ => invalid("stagger axis"),
+    }
+}
+
+fn stagger_index(cursor: &mut Cursor) -> Result<StaggerIndex, Error> {
+    match cursor.u8()? {
+        0 => Ok(StaggerIndex::Odd),
+        1 => Ok(StaggerIndex::Even),
+        _ => invalid("stagger index"),
+    }
+}
+
+fn point(_: &mut Decoder, cursor: &mut Cursor) -> Result<Point, Error> {
+    Ok(Point {
+        x: cursor.f64()?,
+        y: cursor.f64()?,
+    })
+}
@@ -0,0 +1,711 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::format;
+use super::format::Put;
+use super::format::NONE;
+
+use crate::tme::color::Color;
+use crate::tme::error::Error;
+use crate::tme::models::map::with_map;
+use crate::tme::models::wang_color::WangColor;
+use crate::tme::models::wang_set::WangSet;
+use crate::tme::models::DataSource;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::Point;
+use crate::tme::models::Property;
+use crate::tme::models::Text;
+use crate::tme::models::Tile;
+use crate::tme::models::TileLayer;
+use crate::tme::models::TileOrigin;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+
+/// Serializes a map into the binary runtime format.
+///
+/// The payload starts with a table of all distinct strings, followed by the
+/// decoded tile data of every layer and chunk as one packed `u32` array and
+/// the objects of every object group as one flat array. The tree of map,
+/// tilesets and layers comes last and refers to those by index.
+pub fn write_runtime_map(map: &Map) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder::default();
+    let mut body = Vec::new();
+    encoder.map(&mut body, map)?;
+
+    let mut payload = Vec::new();
+    payload.put_u32(encoder.strings.len() as u32);
+    for string in &encoder.strings {
+        payload.put_u32(string.len() as u32);
+        payload.extend_from_slice(string.as_bytes());
+    }
+    payload.pad(4);
+    payload.put_u32(encoder.tiles.len() as u32);
+    for &tile in &encoder.tiles {
+        payload.put_u32(tile);
+    }
+    payload.put_u32(encoder.object_count);
+    payload.extend_from_slice(&encoder.objects);
+    payload.extend_from_slice(&body);
+    Ok(format::seal(payload))
+}
+
+#[derive(Default)]
+struct Encoder<'a> {
+    strings:      Vec<Cow<'a, str>>,
+    indices:      HashMap<Cow<'a, str>, u32>,
+    tiles:        Vec<u32>,
+    objects:      Vec<u8>,
+    object_count: u32,
+}
+
+impl<'a> Encoder<'a> {
+    fn map(&mut self, out: &mut Vec<u8>, map: &'a Map) -> Result<(), Error> {
+        out.put_u8(map.orientation() as u8);
+        with_map!(map, inner => {
+            color(out, inner.background_color);
+            out.put_i32(inner.compression_level);
+            out.put_i32(inner.width);
+            out.put_i32(inner.height);
+            out.put_i32(inner.tile_width);
+            out.put_i32(inner.tile_height);
+            out.put_bool(inner.infinite);
+            out.put_i32(inner.next_layer_id);
+            out.put_i32(inner.next_object_id);
+            out.put_u8(inner.render_order as u8);
+            self.string(out, &inner.tiled_version);
+            self.string(out, &inner.version);
+            self.properties(out, inner.properties.as_deref());
+        });
+        match map {
+            Map::Staggered(staggered) => {
+                out.put_u8(staggered.stagger_axis as u8);
+                out.put_u8(staggered.stagger_index as u8);
+            }
+            Map::Hexagonal(hexagonal) => {
+                out.put_u8(hexagonal.stagger_axis as u8);
+                out.put_u8(hexagonal.stagger_index as u8);
+                out.put_i32(hexagonal.hex_side_length);
+            }
+            Map::Orthogonal(_) | Map::Isometric(_) => {}
+        }
+
+        out.put_u32(map.tile_sets().len() as u32);
+        for container in map.tile_sets() {
+            match container {
+                TilesetContainer::Tileset(tileset) => {
+                    out.put_u8(0);
+                    self.tileset(out, tileset)?;
+                }
+                TilesetContainer::TilesetRef(reference) => {
+                    out.put_u8(1);
+                    out.put_i32(reference.first_gid);
+                    self.string(out, reference.source.to_string_lossy());
+                }
+            }
+        }
+        self.layers(out, map.layers())
+    }
+
+    fn tileset(&mut self, out: &mut Vec<u8>, tileset: &'a Tileset) -> Result<(), Error> {
+        optional_i32(out, tileset.first_gid);
+        self.string(out, &tileset.name);
+        self.string(out, &tileset.tileset_type);
+        self.string(out, &tileset.tiled_version);
+        self.string(out, &tileset.version);
+        out.put_i32(tileset.tile_width);
+        out.put_i32(tileset.tile_height);
+        out.put_u32(tileset.tile_count as u32);
+        out.put_u32(tileset.columns as u32);
+        out.put_i32(tileset.margin);
+        out.put_i32(tileset.spacing);
+        color(out, tileset.background_color);
+        optional_color(out, tileset.transparent_color);
+        self.optional_string(out, tileset.image.as_deref());
+        optional_i32(out, tileset.image_width);
+        optional_i32(out, tileset.image_height);
+
+        out.put_bool(tileset.tile_offset.is_some());
+        if let Some(offset) = &tileset.tile_offset {
+            out.put_i32(offset.x);
+            out.put_i32(offset.y);
+        }
+        out.put_bool(tileset.grid.is_some());
+        if let Some(grid) = &tileset.grid {
+            out.put_u8(grid.orientation as u8);
+            out.put_i32(grid.width);
+            out.put_i32(grid.height);
+        }
+        self.properties(out, tileset.properties.as_deref());
+
+        count(out, tileset.terrains.as_ref().map(Vec::len));
+        for terrain in tileset.terrains.iter().flatten() {
+            self.string(out, &terrain.name);
+            out.put_i32(terrain.tile);
+            self.properties(out, terrain.properties.as_deref());
+        }
+        count(out, tileset.tiles.as_ref().map(Vec::len));
+        for tile in tileset.tiles.iter().flatten() {
+            self.tile(out, tile)?;
+        }
+        count(out, tileset.wang_sets.as_ref().map(Vec::len));
+        for wang_set in tileset.wang_sets.iter().flatten() {
+            self.wang_set(out, wang_set);
+        }
+        Ok(())
+    }
+
+    fn tile(&mut self, out: &mut Vec<u8>, tile: &'a Tile) -> Result<(), Error> {
+        out.put_i32(tile.id);
+        self.optional_string(out, tile.tile_type.as_deref());
+        self.optional_string(out, tile.image.as_deref());
+        optional_i32(out, tile.image_width);
+        optional_i32(out, tile.image_height);
+        optional_f64(out, tile.probability);
+        count(out, tile.terrain.as_ref().map(Vec::len));
+        for &terrain in tile.terrain.iter().flatten() {
+            out.put_i32(terrain);
+        }
+        count(out, tile.animation.as_ref().map(Vec::len));
+        for frame in tile.animation.iter().flatten() {
+            out.put_i32(frame.tiled_id);
+            out.put_i64(frame.duration);
+        }
+        self.properties(out, tile.properties.as_deref());
+
+        out.put_bool(tile.object_group.is_some());
+        match &tile.object_group {
+            Some(layer) => self.layer(out, layer),
+            None => Ok(()),
+        }
+    }
+
+    fn wang_set(&mut self, out: &mut Vec<u8>, wang_set: &'a WangSet) {
+        self.string(out, &wang_set.name);
+        out.put_i32(wang_set.tile);
+        self.properties(out, wang_set.properties.as_deref());
+        for colors in &[&wang_set.corner_colors, &wang_set.edge_colors] {
+            out.put_u32(colors.len() as u32);
+            for wang_color in colors.iter() {
+                self.wang_color(out, wang_color);
+            }
+        }
+        out.put_u32(wang_set.wang_tiles.len() as u32);
+        for tile in &wang_set.wang_tiles {
+            out.put_i32(tile.tile_id);
+            out.put_bool(tile.h_flip);
+            out.put_bool(tile.v_flip);
+            out.put_bool(tile.d_flip);
+            out.put_u32(tile.wang_id.len() as u32);
+            for &id in &tile.wang_id {
+                out.put_i64(id);
+            }
+        }
+    }
+
+    fn wang_color(&mut self, out: &mut Vec<u8>, wang_color: &'a WangColor) {
+        self.string(out, &wang_color.name);
+        color(out, wang_color.color);
+        out.put_f64(wang_color.probability);
+        out.put_i64(wang_color.tile);
+    }
+
+    fn layers(&mut self, out: &mut Vec<u8>, layers: &'a [Layer]) -> Result<(), Error> {
+        out.put_u32(layers.len() as u32);
+        for layer in layers {
+            self.layer(out, layer)?;
+        }
+        Ok(())
+    }
+
+    fn layer(&mut self, out: &mut Vec<u8>, layer: &'a Layer) -> Result<(), Error> {
+        macro_rules! common {
+            ($kind:expr, $layer:expr) => {{
+                out.put_u8($kind);
+                out.put_i32($layer.id);
+                self.string(out, &$layer.name);
+                out.put_i32($layer.x);
+                out.put_i32($layer.y);
+                optional_f64(out, $layer.offset_x);
+                optional_f64(out, $layer.offset_y);
+                optional_i32(out, $layer.start_x);
+                optional_i32(out, $layer.start_y);
+                out.put_f64($layer.opacity);
+                out.put_bool($layer.visible);
+                self.properties(out, $layer.properties.as_deref());
+            }};
+        }
+
+        match layer {
+            Layer::TileLayer(tile_layer) => {
+                common!(0, tile_layer);
+                out.put_i32(tile_layer.width);
+                out.put_i32(tile_layer.height);
+                let origin = TileOrigin::Layer(tile_layer.id);
+                self.tiles(out, tile_layer, &tile_layer.data, origin)?;
+                count(out, tile_layer.chunks.as_ref().map(Vec::len));
+                for chunk in tile_layer.chunks.iter().flatten() {
+                    out.put_i32(chunk.x);
+                    out.put_i32(chunk.y);
+                    out.put_i32(chunk.width);
+                    out.put_i32(chunk.height);
+                    let origin = TileOrigin::Chunk {
+                        layer: tile_layer.id,
+                        x:     chunk.x,
+                        y:     chunk.y,
+                    };
+                    self.tiles(out, tile_layer, &chunk.data, origin)?;
+                }
+            }
+            Layer::ObjectGroupLayer(group) => {
+                common!(1, group);
+                out.put_u8(group.draw_order as u8);
+                out.put_u32(self.object_count);
+                out.put_u32(group.objects.len() as u32);
+                let mut objects = std::mem::take(&mut self.objects);
+                for object in &group.objects {
+                    self.object(&mut objects, object);
+                }
+                self.objects = objects;
+                self.object_count += group.objects.len() as u32;
+            }
+            Layer::ImageLayer(image_layer) => {
+                common!(2, image_layer);
+                self.string(out, &image_layer.image);
+                optional_color(out, image_layer.transparent_color);
+            }
+            Layer::GroupLayer(group) => {
+                common!(3, group);
+                self.layers(out, &group.layers)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the tiles and refers to them by offset and length
+    fn tiles(
+        &mut self,
+        out: &mut Vec<u8>,
+        layer: &TileLayer,
+        data: &DataSource,
+        origin: TileOrigin,
+    ) -> Result<(), Error> {
+        let tiles = data.extract_tiles_of(layer.encoding, layer.compression, origin)?;
+        out.put_u32(self.tiles.len() as u32);
+        out.put_u32(tiles.len() as u32);
+        self.tiles.extend(tiles.into_iter().map(|tile| tile as u32));
+        Ok(())
+    }
+
+    fn object(&mut self, out: &mut Vec<u8>, object: &'a Object) {
+        let kind = match object {
+            Object::General(_) => 0,
+            Object::Ellipse(_) => 1,
+            Object::Rectangle(_) => 2,
+            Object::Point(_) => 3,
+            Object::Polygon(_) => 4,
+            Object::Polyline(_) => 5,
+            Object::Text(_) => 6,
+        };
+        out.put_u8(kind);
+        out.put_i64(object.id());
+        self.string(out, object.name());
+        self.string(out, object.obj_type());
+        self.optional_string(out, object.template());
+        out.put_f64(object.x());
+        out.put_f64(object.y());
+        out.put_f64(object.width());
+        out.put_f64(object.height());
+        out.put_f64(object.rotation());
+        out.put_bool(object.visible());
+        self.properties(out, object.properties());
+
+        match object {
+            Object::General(general) => out.put_i64(general.gid),
+            Object::Ellipse(ellipse) => out.put_bool(ellipse.ellipse),
+            Object::Rectangle(_) => {}
+            Object::Point(point) => out.put_bool(point.point),
+            Object::Polygon(polygon) => points(out, &polygon.polygon),
+            Object::Polyline(polyline) => points(out, &polyline.polyline),
+            Object::Text(text_object) => self.text(out, &text_object.text),
+        }
+    }
+
+    fn text(&mut self, out: &mut Vec<u8>, text: &'a Text) {
+        self.string(out, &text.text);
+        self.string(out, &text.font_family);
+        out.put_i32(text.pixel_size);
+        color(out, text.color);
+        out.put_u8(text.h_align as u8);
+        out.put_u8(text.v_align as u8);
+        for &flag in &[
+            text.bold,
+            text.italic,
+            text.underline,
+            text.strike_out,
+            text.kerning,
+            text.wrap,
+        ] {
+            out.put_bool(flag);
+        }
+    }
+
+    fn properties(&mut self, out: &mut Vec<u8>, properties: Option<&'a [Property]>) {
+        count(out, properties.map(<[Property]>::len));
+        for property in properties.into_iter().flatten() {
+            self.property(out, property);
+        }
+    }
+
+    fn property(&mut self, out: &mut Vec<u8>, property: &'a Property) {
+        match property {
+            Property::Int(int) => {
+                out.put_u8(0);
+                self.string(out, &int.name);
+                out.put_i32(int.value);
+            }
+            Property::Bool(bool) => {
+                out.put_u8(1);
+                self.string(out, &bool.name);
+                out.put_bool(bool.value);
+            }
+            Property::File(file) => {
+                out.put_u8(2);
+                self.string(out, &file.name);
+                self.string(out, file.value.to_string_lossy());
+            }
+            Property::Color(property) => {
+                out.put_u8(3);
+                self.string(out, &property.name);
+                color(out, property.value);
+            }
+            Property::Float(float) => {
+                out.put_u8(4);
+                self.string(out, &float.name);
+                out.put_f64(float.value);
+            }
+            Property::String(string) => {
+                out.put_u8(5);
+                self.string(out, &string.name);
+                self.string(out, &string.value);
+            }
+        }
+    }
+
+    /// Writes the index of `string` in the string table, adding it if new
+    fn string<S: Into<Cow<'a, str>>>(&mut self, out: &mut Vec<u8>, string: S) {
+        let string = string.into();
+        let index = match self.indices.get(&string) {
+            Some(&index) => index,
+            None => {
+                let index = self.strings.len() as u32;
+                self.strings.push(string.clone());
+                self.indices.insert(string, index);
+                index
+            }
+        };
+        out.put_u32(index);
+    }
+
+    fn optional_string(&mut self, out: &mut Vec<u8>, string: Option<&'a str>) {
+        match string {
+            Some(string) => self.string(out, string),
+            None => out.put_u32(NONE),
+        }
+    }
+}
+
+/// Lengths of optional lists, `NONE` when absent
+fn count(out: &mut Vec<u8>, count: Option<usize>) {
+    out.put_u32(count.map_or(NONE, |count| count as u32));
+}
+
+fn optional_i32(out: &mut Vec<u8>, value: Option<i32>) {
+    out.put_bool(value.is_some());
+    if let Some(value) = value {
+        out.put_i32(value);
+    }
+}
+
+fn optional_f64(out: &mut Vec<u8>, value: Option<f64>) {
+    out.put_bool(value.is_some());
+    if let Some(value) = value {
+        out.put_f64(value);
+    }
+}
+
+fn color(out: &mut Vec<u8>, color: Color) {
+    out.extend_from_slice(&[color.a, color.r, color.g, color.b]);
+}
+
+fn optional_color(out: &mut Vec<u8>, value: Option<Color>) {
+    out.put_bool(value.is_some());
+    if let Some(value) = value {
+        color(out, value);
+    }
+}
+
+fn points(out: &mut Vec<u8>, points: &[Point]) {
+    out.put_u32(points.len() as u32);
+    for point in points {
+        out.put_f64(point.x);
+        out.put_f64(point.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::tme::builder::GroupLayerBuilder;
+    use crate::tme::builder::ImageLayerBuilder;
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::gid::FLIPPED_HORIZONTALLY_FLAG;
+    use crate::tme::models::wang_tile::WangTile;
+    use crate::tme::models::BoolProperty;
+    use crate::tme::models::ColorProperty;
+    use crate::tme::models::Compression;
+    use crate::tme::models::DrawOrder;
+    use crate::tme::models::Encoding;
+    use crate::tme::models::FileProperty;
+    use crate::tme::models::FloatProperty;
+    use crate::tme::models::Frame;
+    use crate::tme::models::Grid;
+    use crate::tme::models::IntProperty;
+    use crate::tme::models::Orientation;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+    use crate::tme::models::StringProperty;
+    use crate::tme::models::Terrain;
+    use crate::tme::models::TileOffset;
+    use crate::tme::runtime::format::HEADER_LENGTH;
+    use crate::tme::runtime::reader::read_runtime_map;
+
+    fn sample_properties() -> Vec<Property> {
+        vec![
+            Property::Int(IntProperty {
+                name:  "depth".to_owned(),
+                value: -3,
+            }),
+            Property::Bool(BoolProperty {
+                name:  "solid".to_owned(),
+                value: true,
+            }),
+            Property::File(FileProperty {
+                name:  "script".to_owned(),
+                value: "scripts/door.lua".into(),
+            }),
+            Property::Color(ColorProperty {
+                name:  "tint".to_owned(),
+                value: Color::with_alpha(128, 1, 2, 3),
+            }),
+            Property::Float(FloatProperty {
+                name:  "speed".to_owned(),
+                value: 0.75,
+            }),
+            Property::String(StringProperty {
+                name:  "note".to_owned(),
+                value: "first\nsecond".to_owned(),
+            }),
+        ]
+    }
+
+    fn sample_map() -> Map {
+        let mut builder = sample_properties().into_iter().fold(
+            MapBuilder::hexagonal(2, 2, 16, 14, 7, StaggerAxis::Y, StaggerIndex::Even)
+                .with_background_color(Color::new(16, 32, 48)),
+            |builder, property| builder.with_property(property),
+        );
+
+        let mut tileset = TilesetBuilder::new("terrain", 16, 16)
+            .with_image("terrain.png", 32, 32)
+            .with_spacing(1)
+            .build();
+        tileset.transparent_color = Some(Color::new(255, 0, 255));
+        tileset.tile_offset = Some(TileOffset { x: 2, y: -2 });
+        tileset.grid = Some(Grid {
+            height:      8,
+            orientation: Orientation::Isometric,
+            width:       16,
+        });
+        tileset.terrains = Some(vec![Terrain {
+            name:       "grass".to_owned(),
+            properties: None,
+            tile:       0,
+        }]);
+        tileset.wang_sets = Some(vec![WangSet {
+            corner_colors: vec![],
+            edge_colors:   vec![WangColor {
+                color:       Color::new(255, 0, 0),
+                name:        "road".to_owned(),
+                probability: 1.0,
+                tile:        -1,
+            }],
+            name:          "roads".to_owned(),
+            properties:    Some(vec![]),
+            tile:          -1,
+            wang_tiles:    vec![WangTile {
+                d_flip:  false,
+                h_flip:  true,
+                tile_id: 0,
+                v_flip:  false,
+                wang_id: vec![1, 0, 1, 0, 0, 0, 1, 0],
+            }],
+        }]);
+        tileset.tiles = Some(vec![Tile {
+            animation:    Some(vec![Frame {
+                duration: 100,
+                tiled_id: 1,
+            }]),
+            id:           0,
+            image:        None,
+            image_height: None,
+            image_width:  None,
+            object_group: Some(
+                ObjectLayerBuilder::new("collision")
+                    .with_object(ObjectBuilder::rectangle(0.0, 0.0, 16.0, 8.0).build())
+                    .build(),
+            ),
+            probability:  Some(0.5),
+            properties:   Some(sample_properties()),
+            terrain:      Some(vec![0, -1, -1, 0]),
+            tile_type:    Some("grass".to_owned()),
+        }]);
+        builder.add_tileset(tileset);
+        builder.add_tileset_ref("props.tsx", 3);
+
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(0, 0, Gid(1))
+            .with_tile(1, 1, Gid::new(2, FLIPPED_HORIZONTALLY_FLAG))
+            .build();
+        builder.add_layer(ground);
+        let mut packed = builder
+            .tile_layer("packed")
+            .with_opacity(0.25)
+            .with_tile(1, 0, Gid(3))
+            .build();
+        if let Layer::TileLayer(layer) = &mut packed {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Gzip))
+                .unwrap();
+        }
+        builder.add_layer(packed);
+
+        let text: Text = serde_json::from_value(json!({
+            "text": "Hello",
+            "bold": true,
+            "halign": "justify",
+            "valign": "bottom",
+            "color": "#FF00FF00"
+        }))
+        .unwrap();
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_draw_order(DrawOrder::Index)
+            .with_object(
+                ObjectBuilder::rectangle(1.0, 2.0, 3.0, 4.0)
+                    .with_name("box")
+                    .with_type("crate")
+                    .with_rotation(90.0)
+                    .build(),
+            )
+            .with_object(ObjectBuilder::ellipse(0.5, 0.5, 8.0, 8.0).build())
+            .with_object(ObjectBuilder::point(3.0, 3.0).with_visible(false).build())
+            .with_object(
+                ObjectBuilder::polygon(0.0, 0.0, &[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]).build(),
+            )
+            .with_object(ObjectBuilder::polyline(1.0, 1.0, &[(0.0, 0.0), (-2.5, 3.0)]).build())
+            .with_object(ObjectBuilder::tile(Gid(5), 0.0, 16.0, 16.0, 16.0).build())
+            .with_object(ObjectBuilder::text(text, 0.0, 0.0, 64.0, 16.0).build())
+            .build();
+        let group = GroupLayerBuilder::new("group")
+            .with_offset(2.0, -3.5)
+            .with_visible(false)
+            .with_layer(objects)
+            .build();
+        builder.add_layer(group);
+        builder.add_layer(
+            ImageLayerBuilder::new("sky", "sky.png")
+                .with_transparent_color(Color::new(0, 0, 0))
+                .build(),
+        );
+        builder.build()
+    }
+
+    /// The runtime format keeps tile data decoded
+    fn decoded(mut map: Map) -> serde_json::Value {
+        let ids: Vec<i32> = map.layers().iter().map(Layer::id).collect();
+        for id in ids {
+            if let Some(Layer::TileLayer(layer)) = map.layer_mut(id) {
+                layer.encode(Encoding::Csv, None).unwrap();
+            }
+        }
+        serde_json::to_value(&map).unwrap()
+    }
+
+    #[test]
+    fn writes_and_reads_back_map() {
+        let map = sample_map();
+        let bytes = write_runtime_map(&map).unwrap();
+        assert_eq!(bytes[..4], *b"EMBR");
+
+        let read = read_runtime_map(&bytes).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), decoded(map));
+    }
+
+    #[test]
+    fn writes_and_reads_back_infinite_map() {
+        let mut map = sample_map();
+        map.set_infinite(true).unwrap();
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(2) {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Zstd))
+                .unwrap();
+        }
+
+        let read = read_runtime_map(&write_runtime_map(&map).unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), decoded(map));
+    }
+
+    #[test]
+    fn shares_strings() {
+        let map = sample_map();
+        let bytes = write_runtime_map(&map).unwrap();
+        let payload = &bytes[HEADER_LENGTH..];
+        let count = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+
+        let mut encoder = Encoder::default();
+        encoder.map(&mut Vec::new(), &map).unwrap();
+        assert_eq!(count as usize, encoder.strings.len());
+        assert_eq!(
+            encoder
+                .strings
+                .iter()
+                .filter(|string| *string == "note")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_damaged_data() {
+        let mut bytes = write_runtime_map(&sample_map()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(
+            read_runtime_map(&bytes),
+            Err(Error::RuntimeChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            read_runtime_map(&bytes[..last]),
+            Err(Error::TruncatedRuntimeMap)
+        ));
+    }
+}
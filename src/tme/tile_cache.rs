@@ -0,0 +1,447 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use memmap2::Mmap;
+
+use super::error::Error;
+use super::gid::Gid;
+use super::models::data_source;
+use super::models::flatten_layers;
+use super::models::Compression;
+use super::models::DataSource;
+use super::models::Encoding;
+use super::models::Layer;
+use super::models::Map;
+use super::models::TileOrigin;
+use super::models::MAX_DECOMPRESSED_SIZE;
+use super::tile_grid::TileGridView;
+
+const MAGIC: [u8; 4] = *b"EMBT";
+const VERSION: u32 = 1;
+const HEADER_LENGTH: usize = 16;
+const ENTRY_LENGTH: usize = 40;
+
+/// Tile data of one finite layer or one chunk of an infinite layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub layer:       i32,
+    pub chunk:       bool,
+    pub x:           i32,
+    pub y:           i32,
+    pub width:       i32,
+    pub height:      i32,
+    /// Chunks stay compressed in the cache when the map had them compressed
+    /// and are decompressed on first access
+    pub compression: Option<Compression>,
+    offset:          usize,
+    length:          usize,
+}
+
+impl CacheEntry {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    pub fn origin(&self) -> TileOrigin {
+        if self.chunk {
+            TileOrigin::Chunk {
+                layer: self.layer,
+                x:     self.x,
+                y:     self.y,
+            }
+        } else {
+            TileOrigin::Layer(self.layer)
+        }
+    }
+
+    fn tile_count(&self) -> usize {
+        (self.width.max(0) as usize) * (self.height.max(0) as usize)
+    }
+}
+
+/// Decoded tile data of every tile layer of a map, stored in a file and
+/// accessed through a memory map without copying.
+///
+/// The file starts with a 16 byte header and a table of 40 byte entries,
+/// followed by the little endian tile data of each entry aligned to four
+/// bytes, so that uncompressed entries can be cast to `Gid` slices.
+pub struct TileCache {
+    mmap:    Mmap,
+    entries: Vec<CacheEntry>,
+    decoded: Vec<OnceLock<Vec<Gid>>>,
+}
+
+impl TileCache {
+    /// Decodes the tile layers of `map` one at a time into a cache file
+    pub fn write<P: AsRef<Path>>(map: &Map, path: P) -> Result<(), Error> {
+        let mut entries = Vec::new();
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let tile_layers: Vec<_> = flatten_layers(map.layers())
+            .into_iter()
+            .filter_map(|layer| match layer {
+                Layer::TileLayer(tile_layer) => Some(tile_layer),
+                _ => None,
+            })
+            .collect();
+        let count: usize = tile_layers
+            .iter()
+            .map(|layer| match &layer.chunks {
+                Some(chunks) if !chunks.is_empty() => chunks.len(),
+                _ => 1,
+            })
+            .sum();
+        let mut offset = HEADER_LENGTH + count * ENTRY_LENGTH;
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        for layer in tile_layers {
+            let compression = layer
+                .compression
+                .filter(|&compression| compression != Compression::None)
+                .filter(|_| layer.encoding != Some(Encoding::Csv));
+            let blocks: Vec<_> = match &layer.chunks {
+                Some(chunks) if !chunks.is_empty() => chunks
+                    .iter()
+                    .map(|chunk| {
+                        (
+                            true,
+                            chunk.x,
+                            chunk.y,
+                            chunk.width,
+                            chunk.height,
+                            &chunk.data,
+                        )
+                    })
+                    .collect(),
+                _ => vec![(false, 0, 0, layer.width, layer.height, &layer.data)],
+            };
+
+            for (chunk, x, y, width, height, data) in blocks {
+                let mut entry = CacheEntry {
+                    layer: layer.id,
+                    chunk,
+                    x,
+                    y,
+                    width,
+                    height,
+                    compression: None,
+                    offset,
+                    length: 0,
+                };
+                let bytes = match (chunk, data, compression) {
+                    (true, DataSource::Encoded(encoded), Some(compression)) => {
+                        entry.compression = Some(compression);
+                        data_source::decode_base64(encoded)?
+                    }
+                    _ => {
+                        let tiles = data.decode_tiles(
+                            layer.encoding,
+                            layer.compression,
+                            entry.origin(),
+                            entry.tile_count(),
+                        )?;
+                        tiles.iter().flat_map(|tile| tile.to_le_bytes()).collect()
+                    }
+                };
+
+                file.write_all(&bytes)?;
+                let padding = (4 - bytes.len() % 4) % 4;
+                file.write_all(&[0; 4][..padding])?;
+                entry.length = bytes.len();
+                offset += bytes.len() + padding;
+                entries.push(entry);
+            }
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        file.write_all(&(entries.len() as u32).to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        for entry in &entries {
+            for value in &[entry.layer, entry.x, entry.y, entry.width, entry.height] {
+                file.write_all(&value.to_le_bytes())?;
+            }
+            let compression = match entry.compression {
+                None | Some(Compression::None) => 0u8,
+                Some(Compression::Zlib) => 1,
+                Some(Compression::Gzip) => 2,
+                Some(Compression::Zstd) => 3,
+            };
+            file.write_all(&[entry.chunk as u8, compression, 0, 0])?;
+            file.write_all(&(entry.offset as u64).to_le_bytes())?;
+            file.write_all(&(entry.length as u64).to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read only, like every memory map it relies on
+        // the file not being truncated by someone else while it is open
+        let mmap = unsafe { Mmap::map(&file)? };
+        let entries = entries(&mmap)?;
+        let decoded = entries.iter().map(|_| OnceLock::new()).collect();
+        Ok(Self {
+            mmap,
+            entries,
+            decoded,
+        })
+    }
+
+    pub fn entries(&self) -> &[CacheEntry] {
+        &self.entries
+    }
+
+    /// Tiles of the entry at `index`, decompressing a compressed chunk the
+    /// first time it is requested
+    pub fn grid(&self, index: usize) -> Result<TileGridView<'_>, Error> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| Error::InvalidTileCache(format!("no entry {}", index)))?;
+        let bytes = &self.mmap[entry.offset..entry.offset + entry.length];
+
+        let tiles = match entry.compression {
+            None => bytemuck::try_cast_slice(bytes)
+                .map_err(|_| Error::MisalignedTileData(entry.origin(), bytes.len()))?,
+            Some(compression) => match self.decoded[index].get() {
+                Some(tiles) => tiles.as_slice(),
+                None => {
+                    let expected = entry.tile_count();
+                    let limit = (expected * std::mem::size_of::<Gid>()).min(MAX_DECOMPRESSED_SIZE);
+                    let tiles: Vec<Gid> =
+                        data_source::decompress(bytes, compression, entry.origin(), limit)?
+                            .into_iter()
+                            .map(Gid::from)
+                            .collect();
+                    if tiles.len() != expected {
+                        return Error::TileCountMismatch {
+                            origin: entry.origin(),
+                            expected,
+                            actual: tiles.len(),
+                        }
+                        .fail();
+                    }
+                    self.decoded[index].get_or_init(|| tiles).as_slice()
+                }
+            },
+        };
+
+        Ok(TileGridView {
+            x: entry.x,
+            y: entry.y,
+            width: entry.width,
+            height: entry.height,
+            tiles,
+        })
+    }
+
+    /// Tiles of a finite layer
+    pub fn layer(&self, id: i32) -> Result<Option<TileGridView<'_>>, Error> {
+        self.entries
+            .iter()
+            .position(|entry| entry.layer == id && !entry.chunk)
+            .map(|index| self.grid(index))
+            .transpose()
+    }
+
+    /// The chunk of an infinite layer holding the tile at `(x, y)`
+    pub fn chunk_at(&self, id: i32, x: i32, y: i32) -> Result<Option<TileGridView<'_>>, Error> {
+        self.entries
+            .iter()
+            .position(|entry| entry.layer == id && entry.chunk && entry.contains(x, y))
+            .map(|index| self.grid(index))
+            .transpose()
+    }
+
+    pub fn tile(&self, id: i32, x: i32, y: i32) -> Result<Option<Gid>, Error> {
+        let grid = match self.layer(id)? {
+            Some(grid) => Some(grid),
+            None => self.chunk_at(id, x, y)?,
+        };
+        Ok(grid.and_then(|grid| grid.get(x, y)))
+    }
+}
+
+fn entries(bytes: &[u8]) -> Result<Vec<CacheEntry>, Error> {
+    let invalid = |reason: &str| Error::InvalidTileCache(reason.to_owned());
+    let word = |offset: usize| -> Result<[u8; 4], Error> {
+        let mut word = [0; 4];
+        word.copy_from_slice(
+            bytes
+                .get(offset..offset + 4)
+                .ok_or_else(|| invalid("truncated"))?,
+        );
+        Ok(word)
+    };
+    let long = |offset: usize| -> Result<usize, Error> {
+        let (low, high) = (word(offset)?, word(offset + 4)?);
+        let value = u32::from_le_bytes(low) as u64 | (u32::from_le_bytes(high) as u64) << 32;
+        Ok(value as usize)
+    };
+
+    if bytes.get(..4) != Some(&MAGIC[..]) {
+        return invalid("missing magic").fail();
+    }
+    let version = u32::from_le_bytes(word(4)?);
+    if version != VERSION {
+        return Error::InvalidTileCache(format!("unsupported version {}", version)).fail();
+    }
+    let count = u32::from_le_bytes(word(8)?) as usize;
+
+    let mut entries = Vec::with_capacity(count.min(bytes.len() / ENTRY_LENGTH));
+    for index in 0..count {
+        let start = HEADER_LENGTH + index * ENTRY_LENGTH;
+        let value = |field: usize| word(start + field * 4).map(i32::from_le_bytes);
+        let flags = word(start + 20)?;
+        let compression = match flags[1] {
+            0 => None,
+            1 => Some(Compression::Zlib),
+            2 => Some(Compression::Gzip),
+            3 => Some(Compression::Zstd),
+            _ => return invalid("unknown compression").fail(),
+        };
+        let entry = CacheEntry {
+            layer: value(0)?,
+            chunk: flags[0] != 0,
+            x: value(1)?,
+            y: value(2)?,
+            width: value(3)?,
+            height: value(4)?,
+            compression,
+            offset: long(start + 24)?,
+            length: long(start + 32)?,
+        };
+
+        let in_bounds = matches!(
+            entry.offset.checked_add(entry.length),
+            Some(end) if end <= bytes.len()
+        );
+        if !in_bounds || !entry.offset.is_multiple_of(4) {
+            return invalid("entry out of bounds").fail();
+        }
+        if entry.compression.is_none() && entry.length != entry.tile_count() * 4 {
+            return invalid("entry length does not match its size").fail();
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::models::Encoding;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("embercore-{}-{}.cache", name, std::process::id()))
+    }
+
+    fn sample_map() -> Map {
+        let mut builder = MapBuilder::orthogonal(3, 2, 16, 16);
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(0, 0, Gid(1))
+            .with_tile(2, 1, Gid(7))
+            .build();
+        builder.add_layer(ground);
+        let mut packed = builder.tile_layer("packed").with_tile(1, 0, Gid(3)).build();
+        if let Layer::TileLayer(layer) = &mut packed {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Zlib))
+                .unwrap();
+        }
+        builder.add_layer(packed);
+        builder.build()
+    }
+
+    #[test]
+    fn reads_finite_layers() {
+        let path = temp_path("finite");
+        TileCache::write(&sample_map(), &path).unwrap();
+        let cache = TileCache::open(&path).unwrap();
+
+        assert_eq!(cache.entries().len(), 2);
+        let ground = cache.layer(1).unwrap().unwrap();
+        assert_eq!((ground.width, ground.height), (3, 2));
+        assert_eq!(ground.get(2, 1), Some(Gid(7)));
+        assert_eq!(ground.tiles.as_ptr() as usize % 4, 0);
+        assert_eq!(cache.tile(2, 1, 0).unwrap(), Some(Gid(3)));
+        assert_eq!(cache.tile(2, 5, 5).unwrap(), None);
+        assert!(cache.layer(3).unwrap().is_none());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn decompresses_chunks_lazily() {
+        let mut map = sample_map();
+        map.set_infinite(true).unwrap();
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(2) {
+            layer.set_tile(40, -3, Gid(9)).unwrap();
+            layer
+                .encode(Encoding::Base64, Some(Compression::Zstd))
+                .unwrap();
+        }
+
+        let path = temp_path("infinite");
+        TileCache::write(&map, &path).unwrap();
+        let cache = TileCache::open(&path).unwrap();
+
+        let compressed: Vec<_> = cache
+            .entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.compression == Some(Compression::Zstd))
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(compressed.len(), 2);
+        assert!(compressed
+            .iter()
+            .all(|&index| cache.decoded[index].get().is_none()));
+
+        let chunk = cache.chunk_at(2, 40, -3).unwrap().unwrap();
+        assert_eq!((chunk.x, chunk.y, chunk.width), (32, -16, 16));
+        assert_eq!(chunk.get(40, -3), Some(Gid(9)));
+        assert_eq!(
+            compressed
+                .iter()
+                .filter(|&&index| cache.decoded[index].get().is_some())
+                .count(),
+            1
+        );
+        assert_eq!(cache.tile(1, 2, 1).unwrap(), Some(Gid(7)));
+
+        let layer = match map.layer(2) {
+            Some(Layer::TileLayer(layer)) => layer.decode_grid().unwrap(),
+            _ => unreachable!(),
+        };
+        for index in compressed {
+            for (x, y, gid) in cache.grid(index).unwrap().cells() {
+                assert_eq!(layer.get(x, y), Some(gid));
+            }
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = temp_path("invalid");
+        std::fs::write(&path, b"{\"layers\": []}").unwrap();
+        assert!(matches!(
+            TileCache::open(&path),
+            Err(Error::InvalidTileCache(_))
+        ));
+        let _ = std::fs::remove_file(path);
+    }
+}
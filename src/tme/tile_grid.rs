@@ -0,0 +1,714 @@
+use super::error::Error;
+use super::gid::Gid;
+use super::models::Chunk;
+use super::models::Compression;
+use super::models::DataSource;
+use super::models::Encoding;
+use super::models::TileLayer;
+use super::models::TileOrigin;
+
+/// Size of the chunks Tiled creates for infinite layers
+pub const CHUNK_SIZE: i32 = 16;
+
+/// Decoded tile layer data covering the rectangle `x..x + width, y..y + height`
+/// in tile coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileGrid {
+    pub x:      i32,
+    pub y:      i32,
+    pub width:  i32,
+    pub height: i32,
+    pub tiles:  Vec<i32>,
+}
+
+impl TileGrid {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            tiles: vec![0; (width.max(0) * height.max(0)) as usize],
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Gid> {
+        self.index(x, y).map(|index| Gid::from(self.tiles[index]))
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, gid: Gid) -> bool {
+        match self.index(x, y) {
+            Some(index) => {
+                self.tiles[index] = gid.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, Gid)> + '_ {
+        let (x, y, width) = (self.x, self.y, self.width.max(1));
+        self.tiles.iter().enumerate().map(move |(index, &raw)| {
+            let index = index as i32;
+            (x + index % width, y + index / width, Gid::from(raw))
+        })
+    }
+
+    pub fn view(&self) -> TileGridView<'_> {
+        TileGridView {
+            x:      self.x,
+            y:      self.y,
+            width:  self.width,
+            height: self.height,
+            tiles:  bytemuck::cast_slice(&self.tiles),
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if self.contains(x, y) {
+            Some(((y - self.y) * self.width + (x - self.x)) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// Borrowed tile data covering the same kind of rectangle as `TileGrid`,
+/// for example straight from a memory mapped `TileCache`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TileGridView<'a> {
+    pub x:      i32,
+    pub y:      i32,
+    pub width:  i32,
+    pub height: i32,
+    pub tiles:  &'a [Gid],
+}
+
+impl<'a> TileGridView<'a> {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Gid> {
+        if self.contains(x, y) {
+            let index = ((y - self.y) * self.width + (x - self.x)) as usize;
+            self.tiles.get(index).copied()
+        } else {
+            None
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, Gid)> + 'a {
+        let (x, y, width) = (self.x, self.y, self.width.max(1));
+        self.tiles.iter().enumerate().map(move |(index, &gid)| {
+            let index = index as i32;
+            (x + index % width, y + index / width, gid)
+        })
+    }
+
+    pub fn to_grid(&self) -> TileGrid {
+        TileGrid {
+            x:      self.x,
+            y:      self.y,
+            width:  self.width,
+            height: self.height,
+            tiles:  bytemuck::cast_slice(self.tiles).to_vec(),
+        }
+    }
+}
+
+fn chunk_contains(chunk: &Chunk, x: i32, y: i32) -> bool {
+    x >= chunk.x && y >= chunk.y && x < chunk.x + chunk.width && y < chunk.y + chunk.height
+}
+
+/// Left, top, right and bottom edge of the area spanned by `chunks`
+fn chunk_bounds(chunks: &[Chunk]) -> (i32, i32, i32, i32) {
+    let left = chunks.iter().map(|chunk| chunk.x).min().unwrap_or(0);
+    let top = chunks.iter().map(|chunk| chunk.y).min().unwrap_or(0);
+    let right = chunks
+        .iter()
+        .map(|chunk| chunk.x + chunk.width)
+        .max()
+        .unwrap_or(0);
+    let bottom = chunks
+        .iter()
+        .map(|chunk| chunk.y + chunk.height)
+        .max()
+        .unwrap_or(0);
+    (left, top, right, bottom)
+}
+
+impl TileLayer {
+    /// Tiles of a finite layer, failing unless there are `width * height`
+    pub fn decode_data(&self) -> Result<Vec<i32>, Error> {
+        self.data.decode_tiles(
+            self.encoding,
+            self.compression,
+            TileOrigin::Layer(self.id),
+            (self.width.max(0) as usize) * (self.height.max(0) as usize),
+        )
+    }
+
+    /// Tiles of one of the chunks of the layer, failing unless there are
+    /// `width * height`
+    pub fn decode_chunk(&self, chunk: &Chunk) -> Result<Vec<i32>, Error> {
+        chunk.data.decode_tiles(
+            self.encoding,
+            self.compression,
+            TileOrigin::Chunk {
+                layer: self.id,
+                x:     chunk.x,
+                y:     chunk.y,
+            },
+            (chunk.width.max(0) as usize) * (chunk.height.max(0) as usize),
+        )
+    }
+
+    pub fn decode_grid(&self) -> Result<TileGrid, Error> {
+        let chunks = match &self.chunks {
+            Some(chunks) => chunks,
+            None => {
+                let mut grid = TileGrid::new(0, 0, self.width, self.height);
+                grid.tiles = self.decode_data()?;
+                return Ok(grid);
+            }
+        };
+
+        let (left, top, right, bottom) = chunk_bounds(chunks);
+        let mut grid = TileGrid::new(left, top, right - left, bottom - top);
+        for chunk in chunks {
+            let tiles = self.decode_chunk(chunk)?;
+            let width = chunk.width.max(1);
+            for (index, &raw) in tiles.iter().enumerate() {
+                let index = index as i32;
+                grid.set(
+                    chunk.x + index % width,
+                    chunk.y + index / width,
+                    Gid::from(raw),
+                );
+            }
+        }
+
+        Ok(grid)
+    }
+
+    /// Tile at `(x, y)`, `None` outside of the layer or, for infinite
+    /// layers, outside of the area spanned by the chunks. Only the data or
+    /// chunk holding the tile is decoded.
+    pub fn tile(&self, x: i32, y: i32) -> Result<Option<Gid>, Error> {
+        let chunks = match &self.chunks {
+            Some(chunks) => chunks,
+            None => {
+                if x < 0 || y < 0 || x >= self.width || y >= self.height {
+                    return Ok(None);
+                }
+                let tiles = self.decode_data()?;
+                return Ok(Some(Gid::from(tiles[(y * self.width + x) as usize])));
+            }
+        };
+
+        match chunks.iter().find(|chunk| chunk_contains(chunk, x, y)) {
+            Some(chunk) => {
+                let tiles = self.decode_chunk(chunk)?;
+                Ok(Some(Gid::from(
+                    tiles[((y - chunk.y) * chunk.width + (x - chunk.x)) as usize],
+                )))
+            }
+            None => {
+                let (left, top, right, bottom) = chunk_bounds(chunks);
+                let spanned = x >= left && y >= top && x < right && y < bottom;
+                Ok(if spanned { Some(Gid::EMPTY) } else { None })
+            }
+        }
+    }
+
+    /// Replaces the tile at `(x, y)` and returns the previous one, see
+    /// `set_tiles`
+    pub fn set_tile(&mut self, x: i32, y: i32, gid: Gid) -> Result<Option<Gid>, Error> {
+        Ok(self.set_tiles(vec![(x, y, gid)])?.pop().flatten())
+    }
+
+    /// Replaces the given tiles in order and returns the previous tile of
+    /// each, `None` when the position lies outside of a finite layer. The
+    /// data and every touched chunk are decoded once and written back with
+    /// the encoding and compression of the layer. Infinite layers get new
+    /// chunks as needed and lose touched chunks left without tiles, so
+    /// painting the previous tiles back restores the chunks and bounds.
+    pub fn set_tiles<I>(&mut self, tiles: I) -> Result<Vec<Option<Gid>>, Error>
+    where
+        I: IntoIterator<Item = (i32, i32, Gid)>,
+    {
+        let (encoding, compression) = (self.encoding, self.compression);
+        let tiles = tiles.into_iter();
+
+        if self.chunks.is_none() {
+            let mut data = self.decode_data()?;
+            let previous = tiles
+                .map(|(x, y, gid)| {
+                    if x < 0 || y < 0 || x >= self.width || y >= self.height {
+                        return None;
+                    }
+                    let index = (y * self.width + x) as usize;
+                    Some(Gid::from(std::mem::replace(&mut data[index], gid.into())))
+                })
+                .collect();
+            self.data = DataSource::encode(&data, encoding.unwrap_or(Encoding::Csv), compression)?;
+            return Ok(previous);
+        }
+
+        // Decoded tiles of the chunks touched so far, by chunk index
+        let mut decoded: Vec<Option<Vec<i32>>> =
+            vec![None; self.chunks.as_ref().map_or(0, Vec::len)];
+        let mut previous = Vec::new();
+        for (x, y, gid) in tiles {
+            let chunks = self.chunks.as_mut().unwrap();
+            let index = match chunks.iter().position(|chunk| chunk_contains(chunk, x, y)) {
+                Some(index) => index,
+                None if gid.is_empty() => {
+                    previous.push(Some(Gid::EMPTY));
+                    continue;
+                }
+                None => {
+                    let chunk = Chunk {
+                        data:   DataSource::Raw(Vec::new()),
+                        height: CHUNK_SIZE,
+                        width:  CHUNK_SIZE,
+                        x:      x.div_euclid(CHUNK_SIZE) * CHUNK_SIZE,
+                        y:      y.div_euclid(CHUNK_SIZE) * CHUNK_SIZE,
+                    };
+                    let index = chunks
+                        .iter()
+                        .position(|other| (other.y, other.x) > (chunk.y, chunk.x))
+                        .unwrap_or(chunks.len());
+                    chunks.insert(index, chunk);
+                    decoded.insert(index, Some(vec![0; (CHUNK_SIZE * CHUNK_SIZE) as usize]));
+                    index
+                }
+            };
+
+            if decoded[index].is_none() {
+                decoded[index] = Some(self.decode_chunk(&self.chunks.as_ref().unwrap()[index])?);
+            }
+            let chunk = &self.chunks.as_ref().unwrap()[index];
+            let tile = ((y - chunk.y) * chunk.width + (x - chunk.x)) as usize;
+            let data = decoded[index].as_mut().unwrap();
+            previous.push(Some(Gid::from(std::mem::replace(
+                &mut data[tile],
+                gid.into(),
+            ))));
+        }
+
+        let mut kept = Vec::with_capacity(decoded.len());
+        for (mut chunk, tiles) in self.chunks.take().unwrap().into_iter().zip(decoded) {
+            if let Some(tiles) = tiles {
+                if tiles.iter().all(|&raw| Gid::from(raw).is_empty()) {
+                    continue;
+                }
+                chunk.data =
+                    DataSource::encode(&tiles, encoding.unwrap_or(Encoding::Csv), compression)?;
+            }
+            kept.push(chunk);
+        }
+        self.chunks = Some(kept);
+        self.update_chunk_bounds();
+        Ok(previous)
+    }
+
+    /// Replaces base64 and compressed data of the layer and its chunks with
+    /// plain tile arrays
+    pub fn decode_to_csv(&mut self) -> Result<(), Error> {
+        let encoded = matches!(self.data, DataSource::Encoded(_))
+            || self
+                .chunks
+                .iter()
+                .flatten()
+                .any(|chunk| matches!(chunk.data, DataSource::Encoded(_)));
+        if !encoded {
+            return Ok(());
+        }
+
+        if let DataSource::Encoded(_) = self.data {
+            self.data = DataSource::Raw(self.decode_data()?);
+        }
+        let chunks = self
+            .chunks
+            .iter()
+            .flatten()
+            .map(|chunk| match chunk.data {
+                DataSource::Encoded(_) => self.decode_chunk(chunk).map(Some),
+                DataSource::Raw(_) => Ok(None),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        for (chunk, tiles) in self.chunks.iter_mut().flatten().zip(chunks) {
+            if let Some(tiles) = tiles {
+                chunk.data = DataSource::Raw(tiles);
+            }
+        }
+        self.encoding = Some(Encoding::Csv);
+        self.compression = None;
+        Ok(())
+    }
+
+    /// Re-encodes the data of the layer and its chunks, `compression` only
+    /// applies to base64
+    pub fn encode(
+        &mut self,
+        encoding: Encoding,
+        compression: Option<Compression>,
+    ) -> Result<(), Error> {
+        let compression = match encoding {
+            Encoding::Csv => None,
+            Encoding::Base64 => compression.filter(|&compression| compression != Compression::None),
+        };
+        let chunks = self
+            .chunks
+            .iter()
+            .flatten()
+            .map(|chunk| self.decode_chunk(chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Infinite layers keep their tiles in chunks only
+        if self.chunks.is_none() {
+            self.data = DataSource::encode(&self.decode_data()?, encoding, compression)?;
+        }
+        for (chunk, tiles) in self.chunks.iter_mut().flatten().zip(chunks) {
+            chunk.data = DataSource::encode(&tiles, encoding, compression)?;
+        }
+        self.encoding = Some(encoding);
+        self.compression = compression;
+        Ok(())
+    }
+
+    /// Replaces the tiles of the layer with `grid`, keeping its encoding and
+    /// compression. Finite layers take the grid as starting at the origin,
+    /// infinite ones get a chunk for every aligned block holding tiles.
+    pub fn store_grid(&mut self, grid: &TileGrid, infinite: bool) -> Result<(), Error> {
+        let (encoding, compression) = (self.encoding, self.compression);
+
+        if infinite {
+            let mut chunks: Vec<Chunk> = Vec::new();
+            for (x, y, gid) in grid.cells().filter(|(_, _, gid)| !gid.is_empty()) {
+                let (chunk_x, chunk_y) = (
+                    x.div_euclid(CHUNK_SIZE) * CHUNK_SIZE,
+                    y.div_euclid(CHUNK_SIZE) * CHUNK_SIZE,
+                );
+                let index = match chunks
+                    .iter()
+                    .position(|chunk| chunk.x == chunk_x && chunk.y == chunk_y)
+                {
+                    Some(index) => index,
+                    None => {
+                        chunks.push(Chunk {
+                            data:   DataSource::Raw(vec![0; (CHUNK_SIZE * CHUNK_SIZE) as usize]),
+                            height: CHUNK_SIZE,
+                            width:  CHUNK_SIZE,
+                            x:      chunk_x,
+                            y:      chunk_y,
+                        });
+                        chunks.len() - 1
+                    }
+                };
+                if let DataSource::Raw(tiles) = &mut chunks[index].data {
+                    tiles[((y - chunk_y) * CHUNK_SIZE + x - chunk_x) as usize] = gid.into();
+                }
+            }
+            chunks.sort_by_key(|chunk| (chunk.y, chunk.x));
+
+            self.data = DataSource::Raw(Vec::new());
+            self.start_x = Some(0);
+            self.start_y = Some(0);
+            self.width = 0;
+            self.height = 0;
+            self.chunks = Some(chunks);
+            self.update_chunk_bounds();
+        } else {
+            self.chunks = None;
+            self.data = DataSource::Raw(grid.tiles.clone());
+            self.width = grid.width;
+            self.height = grid.height;
+            self.start_x = None;
+            self.start_y = None;
+        }
+
+        self.restore_encoding(encoding, compression)
+    }
+
+    /// Brings plain tile arrays back to the `encoding` and `compression` the
+    /// layer had before they were decoded
+    fn restore_encoding(
+        &mut self,
+        encoding: Option<Encoding>,
+        compression: Option<Compression>,
+    ) -> Result<(), Error> {
+        if encoding == Some(Encoding::Base64) {
+            self.encode(Encoding::Base64, compression)?;
+        }
+        self.encoding = encoding;
+        self.compression = compression;
+        Ok(())
+    }
+
+    /// Makes `start_x`, `start_y`, `width` and `height` of an infinite layer
+    /// span all of its chunks, the way Tiled writes them
+    pub fn update_chunk_bounds(&mut self) {
+        let chunks = match &self.chunks {
+            Some(chunks) => chunks,
+            None => return,
+        };
+        let (left, top, right, bottom) = chunk_bounds(chunks);
+        self.start_x = Some(left);
+        self.start_y = Some(top);
+        self.width = right - left;
+        self.height = bottom - top;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn decodes_finite_layer() {
+        let layer: TileLayer = serde_json::from_value(json! {
+            {
+                "data": [1, 2, 3, 4, 5, 6],
+                "height": 2,
+                "id": 1,
+                "name": "L1",
+                "opacity": 1.0,
+                "visible": true,
+                "width": 3,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+
+        let grid = layer.decode_grid().unwrap();
+        assert_eq!((grid.x, grid.y, grid.width, grid.height), (0, 0, 3, 2));
+        assert_eq!(grid.get(2, 0), Some(Gid(3)));
+        assert_eq!(grid.get(0, 1), Some(Gid(4)));
+        assert_eq!(grid.get(3, 0), None);
+    }
+
+    #[test]
+    fn decodes_chunked_layer() {
+        let layer: TileLayer = serde_json::from_value(json! {
+            {
+                "chunks": [
+                    {
+                        "data": [1, 2, 3, 4],
+                        "height": 2,
+                        "width": 2,
+                        "x": -2,
+                        "y": 0
+                    },
+                    {
+                        "data": [5, 6, 7, 8],
+                        "height": 2,
+                        "width": 2,
+                        "x": 2,
+                        "y": 2
+                    }
+                ],
+                "data": [],
+                "height": 4,
+                "id": 1,
+                "name": "L1",
+                "opacity": 1.0,
+                "startx": -2,
+                "starty": 0,
+                "visible": true,
+                "width": 6,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+
+        let grid = layer.decode_grid().unwrap();
+        assert_eq!((grid.x, grid.y, grid.width, grid.height), (-2, 0, 6, 4));
+        assert_eq!(grid.get(-1, 1), Some(Gid(4)));
+        assert_eq!(grid.get(3, 3), Some(Gid(8)));
+        assert_eq!(grid.get(0, 0), Some(Gid::EMPTY));
+        assert_eq!(
+            grid.cells().filter(|(_, _, gid)| !gid.is_empty()).count(),
+            8
+        );
+    }
+
+    #[test]
+    fn decodes_csv_string() {
+        let layer: TileLayer = serde_json::from_value(json! {
+            {
+                "data": "1,2,3,\n4,5,6",
+                "encoding": "csv",
+                "height": 2,
+                "id": 1,
+                "name": "L1",
+                "opacity": 1.0,
+                "visible": true,
+                "width": 3,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+
+        let grid = layer.decode_grid().unwrap();
+        assert_eq!(grid.tiles, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn rejects_chunks_of_wrong_size() {
+        let layer: TileLayer = serde_json::from_value(json! {
+            {
+                "chunks": [
+                    {
+                        "data": [1, 2, 3],
+                        "height": 2,
+                        "width": 2,
+                        "x": 2,
+                        "y": 0
+                    }
+                ],
+                "data": [],
+                "height": 2,
+                "id": 4,
+                "name": "L1",
+                "opacity": 1.0,
+                "visible": true,
+                "width": 2,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+
+        let error = layer.decode_grid().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Tile data of chunk 2,0 of layer 4 has 3 tiles instead of 4"
+        );
+    }
+
+    #[test]
+    fn stores_grid_as_chunks() {
+        let mut layer: TileLayer = serde_json::from_value(json! {
+            {
+                "data": [],
+                "height": 0,
+                "id": 1,
+                "name": "L1",
+                "opacity": 1.0,
+                "visible": true,
+                "width": 0,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+
+        let mut grid = TileGrid::new(-3, 0, 40, 2);
+        grid.set(-3, 1, Gid(1));
+        grid.set(20, 0, Gid(2));
+        layer.store_grid(&grid, true).unwrap();
+
+        let chunks = layer.chunks.as_ref().unwrap();
+        let origins: Vec<_> = chunks.iter().map(|chunk| (chunk.x, chunk.y)).collect();
+        assert_eq!(origins, vec![(-16, 0), (16, 0)]);
+        assert_eq!((layer.start_x, layer.start_y), (Some(-16), Some(0)));
+        assert_eq!((layer.width, layer.height), (48, 16));
+        assert_eq!(layer.tile(-3, 1).unwrap(), Some(Gid(1)));
+        assert_eq!(layer.tile(20, 0).unwrap(), Some(Gid(2)));
+
+        let grid = TileGrid::new(0, 0, 2, 1);
+        layer.store_grid(&grid, false).unwrap();
+        assert_eq!(layer.chunks, None);
+        assert_eq!(layer.data, DataSource::Raw(vec![0, 0]));
+        assert_eq!((layer.width, layer.height), (2, 1));
+    }
+
+    #[test]
+    fn paints_into_empty_infinite_layer() {
+        let mut layer: TileLayer = serde_json::from_value(json! {
+            {
+                "chunks": [],
+                "compression": "gzip",
+                "data": [],
+                "encoding": "base64",
+                "height": 0,
+                "id": 1,
+                "name": "L1",
+                "opacity": 1.0,
+                "startx": 0,
+                "starty": 0,
+                "visible": true,
+                "width": 0,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+
+        assert_eq!(layer.set_tile(-1, 17, Gid(5)).unwrap(), Some(Gid::EMPTY));
+        assert_eq!(layer.set_tile(3, 2, Gid::EMPTY).unwrap(), Some(Gid::EMPTY));
+        let chunks = layer.chunks.as_ref().unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert!(matches!(chunks[0].data, DataSource::Encoded(_)));
+        assert_eq!((layer.start_x, layer.start_y), (Some(-16), Some(16)));
+        assert_eq!((layer.width, layer.height), (16, 16));
+        assert_eq!(layer.tile(-1, 17).unwrap(), Some(Gid(5)));
+        assert_eq!(layer.compression, Some(Compression::Gzip));
+
+        layer.set_tile(-1, 17, Gid::EMPTY).unwrap();
+        assert_eq!(layer.chunks, Some(vec![]));
+        assert_eq!((layer.width, layer.height), (0, 0));
+    }
+
+    #[test]
+    fn encodes_layer_and_chunks() {
+        let mut layer: TileLayer = serde_json::from_value(json! {
+            {
+                "chunks": [
+                    {"data": [1, 2], "height": 1, "width": 2, "x": 0, "y": 0},
+                    {"data": [3, 0], "height": 1, "width": 2, "x": 2, "y": 0}
+                ],
+                "data": [],
+                "height": 1,
+                "id": 1,
+                "name": "L1",
+                "opacity": 1.0,
+                "visible": true,
+                "width": 4,
+                "x": 0,
+                "y": 0
+            }
+        })
+        .unwrap();
+        let grid = layer.decode_grid().unwrap();
+
+        layer
+            .encode(Encoding::Base64, Some(Compression::Gzip))
+            .unwrap();
+        assert_eq!(layer.encoding, Some(Encoding::Base64));
+        assert_eq!(layer.compression, Some(Compression::Gzip));
+        assert!(matches!(
+            layer.chunks.as_ref().unwrap()[1].data,
+            DataSource::Encoded(_)
+        ));
+        assert_eq!(layer.decode_grid().unwrap(), grid);
+
+        layer
+            .encode(Encoding::Csv, Some(Compression::Zlib))
+            .unwrap();
+        assert_eq!(layer.compression, None);
+        assert_eq!(layer.chunks.unwrap()[1].data, DataSource::Raw(vec![3, 0]));
+    }
+}
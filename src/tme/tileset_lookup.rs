@@ -0,0 +1,134 @@
+use super::gid::Gid;
+use super::models::Map;
+use super::models::Tile;
+use super::models::Tileset;
+use super::models::TilesetContainer;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ResolvedTileset<'a> {
+    pub first_gid: u32,
+    pub tileset:   &'a Tileset,
+}
+
+/// Maps global tile ids to tilesets the same way Tiled does: a gid belongs to
+/// the tileset with the largest `first_gid` not exceeding it
+#[derive(Debug, Clone, Default)]
+pub struct TilesetLookup<'a> {
+    tilesets: Vec<ResolvedTileset<'a>>,
+}
+
+impl<'a> TilesetLookup<'a> {
+    pub fn new(mut tilesets: Vec<ResolvedTileset<'a>>) -> Self {
+        tilesets.sort_by_key(|resolved| resolved.first_gid);
+        Self { tilesets }
+    }
+
+    /// Collects the embedded tilesets of the map, external references have to
+    /// be loaded by the caller and passed to `new`
+    pub fn from_map(map: &'a Map) -> Self {
+        Self::new(
+            map.tile_sets()
+                .iter()
+                .filter_map(|container| match container {
+                    TilesetContainer::Tileset(tileset) => Some(ResolvedTileset {
+                        first_gid: tileset.first_gid? as u32,
+                        tileset,
+                    }),
+                    TilesetContainer::TilesetRef(_) => None,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn tilesets(&self) -> &[ResolvedTileset<'a>] {
+        &self.tilesets
+    }
+
+    /// Returns the position of the tileset in the lookup together with the
+    /// local tile id
+    pub fn resolve(&self, gid: Gid) -> Option<(usize, ResolvedTileset<'a>, i32)> {
+        if gid.is_empty() {
+            return None;
+        }
+
+        let index = match self
+            .tilesets
+            .binary_search_by_key(&gid.id(), |resolved| resolved.first_gid)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+
+        let resolved = self.tilesets[index];
+        Some((index, resolved, (gid.id() - resolved.first_gid) as i32))
+    }
+
+    pub fn tile(&self, gid: Gid) -> Option<&'a Tile> {
+        let (_, resolved, id) = self.resolve(gid)?;
+        resolved.tileset.tile(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn tileset(name: &str) -> Tileset {
+        serde_json::from_value(json! {
+            {
+                "columns":      2,
+                "image":        format!("{}.png", name),
+                "imageheight":  64,
+                "imagewidth":   64,
+                "name":         name,
+                "spacing":      0,
+                "tilecount":    4,
+                "tiledversion": "1.3.5",
+                "tileheight":   32,
+                "tiles":        [{ "id": 1, "type": "wall" }],
+                "tilewidth":    32,
+                "type":         "tileset",
+                "version":      1.2
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_gids() {
+        let first = tileset("first");
+        let second = tileset("second");
+        let lookup = TilesetLookup::new(vec![
+            ResolvedTileset {
+                first_gid: 5,
+                tileset:   &second,
+            },
+            ResolvedTileset {
+                first_gid: 1,
+                tileset:   &first,
+            },
+        ]);
+
+        assert!(lookup.resolve(Gid(0)).is_none());
+
+        let (index, resolved, id) = lookup.resolve(Gid(2)).unwrap();
+        assert_eq!((index, resolved.tileset.name.as_str(), id), (0, "first", 1));
+
+        let (index, resolved, id) = lookup.resolve(Gid::new(6, 0x8000_0000)).unwrap();
+        assert_eq!(
+            (index, resolved.tileset.name.as_str(), id),
+            (1, "second", 1)
+        );
+
+        assert_eq!(
+            lookup
+                .tile(Gid(6))
+                .and_then(|tile| tile.tile_type.as_deref()),
+            Some("wall")
+        );
+        assert!(lookup.tile(Gid(5)).is_none());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod reader;
+pub mod writer;
+
+pub use reader::*;
+pub use writer::*;
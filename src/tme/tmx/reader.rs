@@ -0,0 +1,849 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use roxmltree::Document;
+use roxmltree::Node;
+
+use crate::tme::color::Color;
+use crate::tme::error::Error;
+use crate::tme::models::data_source::parse_csv;
+use crate::tme::models::text;
+use crate::tme::models::wang_color::WangColor;
+use crate::tme::models::wang_set::WangSet;
+use crate::tme::models::wang_tile::WangTile;
+use crate::tme::models::BoolProperty;
+use crate::tme::models::Chunk;
+use crate::tme::models::ColorProperty;
+use crate::tme::models::Compression;
+use crate::tme::models::DataSource;
+use crate::tme::models::DrawOrder;
+use crate::tme::models::EllipseObject;
+use crate::tme::models::Encoding;
+use crate::tme::models::FileProperty;
+use crate::tme::models::FloatProperty;
+use crate::tme::models::Frame;
+use crate::tme::models::GeneralObject;
+use crate::tme::models::Grid;
+use crate::tme::models::GroupLayer;
+use crate::tme::models::HexagonalMap;
+use crate::tme::models::ImageLayer;
+use crate::tme::models::IntProperty;
+use crate::tme::models::IsometricMap;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::MapType;
+use crate::tme::models::Object;
+use crate::tme::models::ObjectGroupLayer;
+use crate::tme::models::Orientation;
+use crate::tme::models::OrthogonalMap;
+use crate::tme::models::Point;
+use crate::tme::models::PointObject;
+use crate::tme::models::PolygonObject;
+use crate::tme::models::PolylineObject;
+use crate::tme::models::Property;
+use crate::tme::models::RectangleObject;
+use crate::tme::models::RenderOrder;
+use crate::tme::models::StaggeredMap;
+use crate::tme::models::StringProperty;
+use crate::tme::models::Terrain;
+use crate::tme::models::Text;
+use crate::tme::models::TextObject;
+use crate::tme::models::Tile;
+use crate::tme::models::TileLayer;
+use crate::tme::models::TileOffset;
+use crate::tme::models::TileOrigin;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+use crate::tme::models::TilesetRef;
+
+/// Reads a map saved in the XML format of Tiled
+pub fn read_tmx(xml: &str) -> Result<Map, Error> {
+    let document = Document::parse(xml)?;
+    let root = document.root_element();
+    if !root.has_tag_name("map") {
+        return Error::MissingTmxValue("map".to_owned()).fail();
+    }
+    map(root)
+}
+
+/// Reads an external tileset saved in the XML format of Tiled
+pub fn read_tsx(xml: &str) -> Result<Tileset, Error> {
+    let document = Document::parse(xml)?;
+    let root = document.root_element();
+    if !root.has_tag_name("tileset") {
+        return Error::MissingTmxValue("tileset".to_owned()).fail();
+    }
+    tileset(root, None, ("", ""))
+}
+
+fn map(node: Node) -> Result<Map, Error> {
+    let orientation: Orientation = required(node, "orientation")?;
+    let background_color =
+        attribute(node, "backgroundcolor")?.unwrap_or_else(Color::new_transparent);
+    let compression_level = attribute(node, "compressionlevel")?.unwrap_or(-1);
+    let height = required(node, "height")?;
+    let infinite = flag(node, "infinite", false)?;
+    let layers = layers(node)?;
+    let next_layer_id = attribute(node, "nextlayerid")?.unwrap_or(1);
+    let next_object_id = attribute(node, "nextobjectid")?.unwrap_or(1);
+    let properties = properties(node)?;
+    let render_order = attribute(node, "renderorder")?.unwrap_or(RenderOrder::RightDown);
+    let tiled_version: String = attribute(node, "tiledversion")?.unwrap_or_default();
+    let tile_height = required(node, "tileheight")?;
+    let tile_width = required(node, "tilewidth")?;
+    let version: String = attribute(node, "version")?.unwrap_or_default();
+    let width = required(node, "width")?;
+    let tile_sets = children(node, "tileset")
+        .map(|child| {
+            let first_gid = required(child, "firstgid")?;
+            Ok(match child.attribute("source") {
+                Some(source) => TilesetContainer::TilesetRef(TilesetRef {
+                    first_gid,
+                    source: PathBuf::from(source),
+                }),
+                None => TilesetContainer::Tileset(tileset(
+                    child,
+                    Some(first_gid),
+                    (&tiled_version, &version),
+                )?),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    macro_rules! build {
+        ($variant:ident, $map:ident { $($field:ident = $value:expr),* }) => {
+            Map::$variant($map {
+                background_color,
+                compression_level,
+                height,
+                infinite,
+                layers,
+                next_layer_id,
+                next_object_id,
+                properties,
+                render_order,
+                tiled_version,
+                tile_height,
+                tile_sets,
+                tile_width,
+                map_type: MapType::Map,
+                version,
+                width,
+                $($field: $value,)*
+            })
+        };
+    }
+
+    Ok(match orientation {
+        Orientation::Orthogonal => build!(Orthogonal, OrthogonalMap {}),
+        Orientation::Isometric => build!(Isometric, IsometricMap {}),
+        Orientation::Staggered => build!(Staggered, StaggeredMap {
+            stagger_axis = required(node, "staggeraxis")?,
+            stagger_index = required(node, "staggerindex")?
+        }),
+        Orientation::Hexagonal => build!(Hexagonal, HexagonalMap {
+            hex_side_length = required(node, "hexsidelength")?,
+            stagger_axis = required(node, "staggeraxis")?,
+            stagger_index = required(node, "staggerindex")?
+        }),
+    })
+}
+
+/// Embedded tilesets carry no versions of their own and take `versions`,
+/// the tiled and format version of the map
+fn tileset(node: Node, first_gid: Option<i32>, versions: (&str, &str)) -> Result<Tileset, Error> {
+    let image = child(node, "image");
+    let tiles = children(node, "tile")
+        .map(tile)
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(Tileset {
+        background_color: attribute(node, "backgroundcolor")?
+            .unwrap_or_else(Color::new_transparent),
+        columns: attribute(node, "columns")?.unwrap_or(0),
+        first_gid,
+        grid: child(node, "grid").map(grid).transpose()?,
+        image: image.map(|image| required(image, "source")).transpose()?,
+        image_height: image
+            .map(|image| attribute(image, "height"))
+            .transpose()?
+            .flatten(),
+        image_width: image
+            .map(|image| attribute(image, "width"))
+            .transpose()?
+            .flatten(),
+        margin: attribute(node, "margin")?.unwrap_or(0),
+        name: attribute(node, "name")?.unwrap_or_default(),
+        properties: properties(node)?,
+        spacing: attribute(node, "spacing")?.unwrap_or(0),
+        terrains: child(node, "terraintypes")
+            .map(|terrains| children(terrains, "terrain").map(terrain).collect())
+            .transpose()?,
+        tile_count: attribute(node, "tilecount")?.unwrap_or(0),
+        tiled_version: attribute(node, "tiledversion")?.unwrap_or_else(|| versions.0.to_owned()),
+        tile_height: required(node, "tileheight")?,
+        tile_offset: child(node, "tileoffset").map(tile_offset).transpose()?,
+        tiles: Some(tiles).filter(|tiles| !tiles.is_empty()),
+        tile_width: required(node, "tilewidth")?,
+        transparent_color: image
+            .map(|image| attribute(image, "trans"))
+            .transpose()?
+            .flatten(),
+        tileset_type: "tileset".to_owned(),
+        version: attribute(node, "version")?.unwrap_or_else(|| versions.1.to_owned()),
+        wang_sets: child(node, "wangsets")
+            .map(|wang_sets| children(wang_sets, "wangset").map(wang_set).collect())
+            .transpose()?,
+    })
+}
+
+fn grid(node: Node) -> Result<Grid, Error> {
+    Ok(Grid {
+        height:      required(node, "height")?,
+        orientation: attribute(node, "orientation")?.unwrap_or(Orientation::Orthogonal),
+        width:       required(node, "width")?,
+    })
+}
+
+fn tile_offset(node: Node) -> Result<TileOffset, Error> {
+    Ok(TileOffset {
+        x: attribute(node, "x")?.unwrap_or(0),
+        y: attribute(node, "y")?.unwrap_or(0),
+    })
+}
+
+fn tile(node: Node) -> Result<Tile, Error> {
+    let image = child(node, "image");
+    Ok(Tile {
+        animation:    child(node, "animation")
+            .map(|animation| {
+                children(animation, "frame")
+                    .map(|frame| {
+                        Ok(Frame {
+                            duration: required(frame, "duration")?,
+                            tiled_id: required(frame, "tileid")?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .transpose()?,
+        id:           required(node, "id")?,
+        image:        image.map(|image| required(image, "source")).transpose()?,
+        image_height: image
+            .map(|image| attribute(image, "height"))
+            .transpose()?
+            .flatten(),
+        image_width:  image
+            .map(|image| attribute(image, "width"))
+            .transpose()?
+            .flatten(),
+        object_group: child(node, "objectgroup")
+            .map(object_group_layer)
+            .transpose()?,
+        probability:  attribute(node, "probability")?,
+        properties:   properties(node)?,
+        // Corners without terrain are left empty in TMX and are -1 in JSON
+        terrain:      node
+            .attribute("terrain")
+            .map(|terrain| {
+                terrain
+                    .split(',')
+                    .map(|id| match id.trim() {
+                        "" => Ok(-1),
+                        id => parse(node, "terrain", id),
+                    })
+                    .collect()
+            })
+            .transpose()?,
+        tile_type:    attribute(node, "type")?,
+    })
+}
+
+fn terrain(node: Node) -> Result<Terrain, Error> {
+    Ok(Terrain {
+        name:       attribute(node, "name")?.unwrap_or_default(),
+        properties: properties(node)?,
+        tile:       attribute(node, "tile")?.unwrap_or(-1),
+    })
+}
+
+fn wang_set(node: Node) -> Result<WangSet, Error> {
+    let colors = |name| {
+        children(node, name)
+            .map(|color| {
+                Ok(WangColor {
+                    color:       required(color, "color")?,
+                    name:        attribute(color, "name")?.unwrap_or_default(),
+                    probability: attribute(color, "probability")?.unwrap_or(1.0),
+                    tile:        attribute(color, "tile")?.unwrap_or(-1),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    };
+
+    Ok(WangSet {
+        corner_colors: colors("wangcornercolor")?,
+        edge_colors:   colors("wangedgecolor")?,
+        name:          attribute(node, "name")?.unwrap_or_default(),
+        properties:    properties(node)?,
+        tile:          attribute(node, "tile")?.unwrap_or(-1),
+        wang_tiles:    children(node, "wangtile")
+            .map(|tile| {
+                Ok(WangTile {
+                    d_flip:  flag(tile, "dflip", false)?,
+                    h_flip:  flag(tile, "hflip", false)?,
+                    tile_id: required(tile, "tileid")?,
+                    v_flip:  flag(tile, "vflip", false)?,
+                    wang_id: wang_id(tile)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+    })
+}
+
+/// Tiled before 1.5 packs the eight colors of a wang id into the nibbles of
+/// a hex number, later versions write them as a comma separated list
+fn wang_id(node: Node) -> Result<Vec<i64>, Error> {
+    let value: String = required(node, "wangid")?;
+    match value.strip_prefix("0x") {
+        Some(hex) => {
+            let packed = u32::from_str_radix(hex, 16)
+                .map_err(|_| Error::ParseTmxValue(path(node, "wangid"), value.clone()))?;
+            Ok((0..8).map(|i| ((packed >> (i * 4)) & 0xF) as i64).collect())
+        }
+        None => value
+            .split(',')
+            .map(|color| parse(node, "wangid", color.trim()))
+            .collect(),
+    }
+}
+
+fn layers(node: Node) -> Result<Vec<Layer>, Error> {
+    node.children()
+        .filter_map(|child| match child.tag_name().name() {
+            "layer" => Some(tile_layer(child)),
+            "objectgroup" => Some(object_group_layer(child)),
+            "imagelayer" => Some(image_layer(child)),
+            "group" => Some(group_layer(child)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Attributes shared by all kinds of layers
+struct LayerAttributes {
+    id:         i32,
+    name:       String,
+    offset_x:   Option<f64>,
+    offset_y:   Option<f64>,
+    opacity:    f64,
+    properties: Option<Vec<Property>>,
+    visible:    bool,
+    x:          i32,
+    y:          i32,
+}
+
+impl LayerAttributes {
+    fn new(node: Node) -> Result<Self, Error> {
+        Ok(Self {
+            id:         attribute(node, "id")?.unwrap_or(0),
+            name:       attribute(node, "name")?.unwrap_or_default(),
+            offset_x:   attribute(node, "offsetx")?,
+            offset_y:   attribute(node, "offsety")?,
+            opacity:    attribute(node, "opacity")?.unwrap_or(1.0),
+            properties: properties(node)?,
+            visible:    flag(node, "visible", true)?,
+            x:          attribute(node, "x")?.unwrap_or(0),
+            y:          attribute(node, "y")?.unwrap_or(0),
+        })
+    }
+}
+
+fn tile_layer(node: Node) -> Result<Layer, Error> {
+    let common = LayerAttributes::new(node)?;
+    let data = child(node, "data").ok_or_else(|| Error::MissingTmxValue(path(node, "data")))?;
+    let encoding = attribute(data, "encoding")?;
+    let chunks = children(data, "chunk")
+        .map(|chunk| {
+            let (x, y) = (required(chunk, "x")?, required(chunk, "y")?);
+            let origin = TileOrigin::Chunk {
+                layer: common.id,
+                x,
+                y,
+            };
+            Ok(Chunk {
+                data: data_source(chunk, encoding, origin)?,
+                height: required(chunk, "height")?,
+                width: required(chunk, "width")?,
+                x,
+                y,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut layer = TileLayer {
+        data: if chunks.is_empty() {
+            data_source(data, encoding, TileOrigin::Layer(common.id))?
+        } else {
+            DataSource::Raw(Vec::new())
+        },
+        chunks: Some(chunks).filter(|chunks| !chunks.is_empty()),
+        compression: attribute(data, "compression")?
+            .filter(|&compression| compression != Compression::None),
+        encoding,
+        height: required(node, "height")?,
+        id: common.id,
+        name: common.name,
+        offset_x: common.offset_x,
+        offset_y: common.offset_y,
+        opacity: common.opacity,
+        properties: common.properties,
+        start_x: None,
+        start_y: None,
+        visible: common.visible,
+        width: required(node, "width")?,
+        x: common.x,
+        y: common.y,
+    };
+    layer.update_chunk_bounds();
+    Ok(Layer::TileLayer(layer))
+}
+
+/// Tiles of a `<data>` or `<chunk>` element, gids above `i32::MAX` carry
+/// flip flags and are stored with their bits unchanged
+fn data_source(
+    node: Node,
+    encoding: Option<Encoding>,
+    origin: TileOrigin,
+) -> Result<DataSource, Error> {
+    let text = node.text().unwrap_or_default();
+    match encoding {
+        Some(Encoding::Base64) => Ok(DataSource::Encoded(text.trim().to_owned())),
+        Some(Encoding::Csv) => parse_csv(text, origin).map(DataSource::Raw),
+        None => children(node, "tile")
+            .map(|tile| Ok(attribute::<u32>(tile, "gid")?.unwrap_or(0) as i32))
+            .collect::<Result<_, Error>>()
+            .map(DataSource::Raw),
+    }
+}
+
+fn object_group_layer(node: Node) -> Result<Layer, Error> {
+    let common = LayerAttributes::new(node)?;
+    Ok(Layer::ObjectGroupLayer(ObjectGroupLayer {
+        draw_order: attribute(node, "draworder")?.unwrap_or(DrawOrder::TopDown),
+        id:         common.id,
+        name:       common.name,
+        objects:    children(node, "object")
+            .map(object)
+            .collect::<Result<Vec<_>, Error>>()?,
+        offset_x:   common.offset_x,
+        offset_y:   common.offset_y,
+        opacity:    common.opacity,
+        properties: common.properties,
+        start_x:    None,
+        start_y:    None,
+        visible:    common.visible,
+        x:          common.x,
+        y:          common.y,
+    }))
+}
+
+fn image_layer(node: Node) -> Result<Layer, Error> {
+    let common = LayerAttributes::new(node)?;
+    let image = child(node, "image");
+    Ok(Layer::ImageLayer(ImageLayer {
+        id:                common.id,
+        image:             image
+            .map(|image| attribute(image, "source"))
+            .transpose()?
+            .flatten()
+            .unwrap_or_default(),
+        name:              common.name,
+        offset_x:          common.offset_x,
+        offset_y:          common.offset_y,
+        opacity:           common.opacity,
+        properties:        common.properties,
+        start_x:           None,
+        start_y:           None,
+        transparent_color: image
+            .map(|image| attribute(image, "trans"))
+            .transpose()?
+            .flatten(),
+        visible:           common.visible,
+        x:                 common.x,
+        y:                 common.y,
+    }))
+}
+
+fn group_layer(node: Node) -> Result<Layer, Error> {
+    let common = LayerAttributes::new(node)?;
+    Ok(Layer::GroupLayer(GroupLayer {
+        id:         common.id,
+        layers:     layers(node)?,
+        name:       common.name,
+        offset_x:   common.offset_x,
+        offset_y:   common.offset_y,
+        opacity:    common.opacity,
+        properties: common.properties,
+        start_x:    None,
+        start_y:    None,
+        visible:    common.visible,
+        x:          common.x,
+        y:          common.y,
+    }))
+}
+
+fn object(node: Node) -> Result<Object, Error> {
+    let height = attribute(node, "height")?.unwrap_or(0.0);
+    let id = attribute(node, "id")?.unwrap_or(0);
+    let name = attribute(node, "name")?.unwrap_or_default();
+    let properties = properties(node)?;
+    let rotation = attribute(node, "rotation")?.unwrap_or(0.0);
+    let template = attribute(node, "template")?;
+    let obj_type = attribute(node, "type")?.unwrap_or_default();
+    let visible = flag(node, "visible", true)?;
+    let width = attribute(node, "width")?.unwrap_or(0.0);
+    let x = attribute(node, "x")?.unwrap_or(0.0);
+    let y = attribute(node, "y")?.unwrap_or(0.0);
+
+    macro_rules! build {
+        ($variant:ident, $object:ident { $($field:ident = $value:expr),* }) => {
+            Object::$variant($object {
+                height,
+                id,
+                name,
+                properties,
+                rotation,
+                template,
+                obj_type,
+                visible,
+                width,
+                x,
+                y,
+                $($field: $value,)*
+            })
+        };
+    }
+
+    if let Some(gid) = attribute::<u32>(node, "gid")? {
+        return Ok(build!(General, GeneralObject { gid = gid as i64 }));
+    }
+    Ok(if child(node, "ellipse").is_some() {
+        build!(Ellipse, EllipseObject { ellipse = true })
+    } else if child(node, "point").is_some() {
+        build!(Point, PointObject { point = true })
+    } else if let Some(polygon) = child(node, "polygon") {
+        build!(Polygon, PolygonObject {
+            polygon = points(polygon)?
+        })
+    } else if let Some(polyline) = child(node, "polyline") {
+        build!(Polyline, PolylineObject {
+            polyline = points(polyline)?
+        })
+    } else if let Some(text_node) = child(node, "text") {
+        build!(Text, TextObject {
+            text = text(text_node)?
+        })
+    } else {
+        build!(Rectangle, RectangleObject {})
+    })
+}
+
+fn points(node: Node) -> Result<Vec<Point>, Error> {
+    let points: String = required(node, "points")?;
+    points
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point
+                .split_once(',')
+                .ok_or_else(|| Error::ParseTmxValue(path(node, "points"), point.to_owned()))?;
+            Ok(Point {
+                x: parse(node, "points", x)?,
+                y: parse(node, "points", y)?,
+            })
+        })
+        .collect()
+}
+
+fn text(node: Node) -> Result<Text, Error> {
+    Ok(Text {
+        bold:        flag(node, "bold", false)?,
+        color:       attribute(node, "color")?.unwrap_or_else(Color::new_black),
+        font_family: attribute(node, "fontfamily")?.unwrap_or_else(text::default_font_family),
+        h_align:     attribute(node, "halign")?.unwrap_or_default(),
+        italic:      flag(node, "italic", false)?,
+        kerning:     flag(node, "kerning", true)?,
+        pixel_size:  attribute(node, "pixelsize")?.unwrap_or_else(text::default_pixel_size),
+        strike_out:  flag(node, "strikeout", false)?,
+        text:        node.text().unwrap_or_default().to_owned(),
+        underline:   flag(node, "underline", false)?,
+        v_align:     attribute(node, "valign")?.unwrap_or_default(),
+        wrap:        flag(node, "wrap", false)?,
+    })
+}
+
+fn properties(node: Node) -> Result<Option<Vec<Property>>, Error> {
+    child(node, "properties")
+        .map(|properties| children(properties, "property").map(property).collect())
+        .transpose()
+}
+
+/// Multiline string values are stored as the text of the element
+fn property(node: Node) -> Result<Property, Error> {
+    let name = required(node, "name")?;
+    let value = node
+        .attribute("value")
+        .or_else(|| node.text())
+        .unwrap_or_default();
+
+    Ok(match node.attribute("type").unwrap_or("string") {
+        "int" => Property::Int(IntProperty {
+            name,
+            value: parse(node, "value", value)?,
+        }),
+        "bool" => Property::Bool(BoolProperty {
+            name,
+            value: parse(node, "value", value)?,
+        }),
+        "float" => Property::Float(FloatProperty {
+            name,
+            value: parse(node, "value", value)?,
+        }),
+        "color" => Property::Color(ColorProperty {
+            name,
+            value: match value {
+                "" => Color::new_transparent(),
+                value => parse(node, "value", value)?,
+            },
+        }),
+        "file" => Property::File(FileProperty {
+            name,
+            value: PathBuf::from(value),
+        }),
+        "string" => Property::String(StringProperty {
+            name,
+            value: value.to_owned(),
+        }),
+        other => return Error::ParseTmxValue(path(node, "type"), other.to_owned()).fail(),
+    })
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.has_tag_name(name))
+}
+
+/// Location of a value in error messages like `layer.width`
+fn path(node: Node, name: &str) -> String {
+    format!("{}.{}", node.tag_name().name(), name)
+}
+
+fn parse<T: FromStr>(node: Node, name: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::ParseTmxValue(path(node, name), value.to_owned()))
+}
+
+fn attribute<T: FromStr>(node: Node, name: &str) -> Result<Option<T>, Error> {
+    node.attribute(name)
+        .map(|value| parse(node, name, value))
+        .transpose()
+}
+
+fn required<T: FromStr>(node: Node, name: &str) -> Result<T, Error> {
+    attribute(node, name)?.ok_or_else(|| Error::MissingTmxValue(path(node, name)))
+}
+
+/// Booleans are written as `0` and `1` in TMX
+fn flag(node: Node, name: &str, default: bool) -> Result<bool, Error> {
+    match node.attribute(name) {
+        None => Ok(default),
+        Some("1") | Some("true") => Ok(true),
+        Some("0") | Some("false") => Ok(false),
+        Some(value) => Error::ParseTmxValue(path(node, name), value.to_owned()).fail(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::gid::Gid;
+    use crate::tme::gid::FLIPPED_HORIZONTALLY_FLAG;
+
+    const TMX: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.2" tiledversion="1.3.5" orientation="staggered" renderorder="right-down" width="2" height="2" tilewidth="32" tileheight="16" staggeraxis="y" staggerindex="odd" backgroundcolor="#ff102030" infinite="0" nextlayerid="6" nextobjectid="4">
+ <properties>
+  <property name="music" value="calm"/>
+  <property name="speed" type="float" value="1.5"/>
+  <property name="notes">first
+second</property>
+ </properties>
+ <tileset firstgid="1" name="terrain" tilewidth="32" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" trans="ff00ff" width="64" height="32"/>
+  <tile id="1" type="water" terrain="0,,0,1">
+   <animation>
+    <frame tileid="1" duration="100"/>
+    <frame tileid="2" duration="100"/>
+   </animation>
+  </tile>
+ </tileset>
+ <tileset firstgid="5" source="props.tsx"/>
+ <layer id="1" name="ground" width="2" height="2" opacity="0.5">
+  <data encoding="csv">
+1,2,
+3,2147483649
+</data>
+ </layer>
+ <layer id="2" name="packed" width="2" height="2" visible="0">
+  <data encoding="base64" compression="zlib">
+   eJxjZGBgYAJiZiBmAWIAAGAACw==
+  </data>
+ </layer>
+ <group id="3" name="group" offsetx="4" offsety="-2">
+  <objectgroup id="4" name="objects" draworder="index">
+   <object id="1" name="spawn" type="npc" x="8" y="4">
+    <point/>
+   </object>
+   <object id="2" x="0" y="0" rotation="45">
+    <polygon points="0,0 16,0 16,8"/>
+   </object>
+   <object id="3" gid="5" x="1.5" y="2" width="32" height="16" visible="0">
+    <properties>
+     <property name="locked" type="bool" value="true"/>
+    </properties>
+   </object>
+  </objectgroup>
+ </group>
+ <imagelayer id="5" name="sky">
+  <image source="sky.png"/>
+ </imagelayer>
+</map>
+"##;
+
+    #[test]
+    fn reads_map() {
+        let map = read_tmx(TMX).unwrap();
+        assert_eq!(map.orientation(), Orientation::Staggered);
+        assert_eq!((map.width(), map.height()), (2, 2));
+        assert_eq!((map.next_layer_id(), map.next_object_id()), (6, 4));
+        assert_eq!(
+            map.properties().unwrap(),
+            &[
+                Property::String(StringProperty {
+                    name:  "music".to_owned(),
+                    value: "calm".to_owned(),
+                }),
+                Property::Float(FloatProperty {
+                    name:  "speed".to_owned(),
+                    value: 1.5,
+                }),
+                Property::String(StringProperty {
+                    name:  "notes".to_owned(),
+                    value: "first\nsecond".to_owned(),
+                }),
+            ][..]
+        );
+
+        let tileset = match &map.tile_sets()[0] {
+            TilesetContainer::Tileset(tileset) => tileset,
+            _ => unreachable!(),
+        };
+        assert_eq!(tileset.first_gid, Some(1));
+        assert_eq!(tileset.tiled_version, "1.3.5");
+        assert_eq!(tileset.image.as_deref(), Some("terrain.png"));
+        assert_eq!(tileset.transparent_color, Some(Color::new(255, 0, 255)));
+        let tile = tileset.tile(1).unwrap();
+        assert_eq!(tile.terrain, Some(vec![0, -1, 0, 1]));
+        assert_eq!(tile.animation.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            map.tile_sets()[1],
+            TilesetContainer::TilesetRef(TilesetRef {
+                first_gid: 5,
+                source:    PathBuf::from("props.tsx"),
+            })
+        );
+
+        let ground = match map.layer(1) {
+            Some(Layer::TileLayer(layer)) => layer,
+            _ => unreachable!(),
+        };
+        assert_eq!(ground.opacity, 0.5);
+        assert_eq!(
+            ground.tile(1, 1).unwrap(),
+            Some(Gid::new(1, FLIPPED_HORIZONTALLY_FLAG))
+        );
+        let packed = match map.layer(2) {
+            Some(Layer::TileLayer(layer)) => layer,
+            _ => unreachable!(),
+        };
+        assert!(!packed.visible);
+        assert_eq!(packed.compression, Some(Compression::Zlib));
+        assert_eq!(packed.decode_grid().unwrap().tiles, vec![1, 2, 3, 4]);
+
+        assert!(matches!(map.object(1), Some((4, Object::Point(_)))));
+        assert!(
+            matches!(map.object(2), Some((4, Object::Polygon(polygon))) if polygon.polygon.len() == 3)
+        );
+        match map.object(3) {
+            Some((_, Object::General(object))) => {
+                assert_eq!(object.gid, 5);
+                assert_eq!((object.x, object.y, object.visible), (1.5, 2.0, false));
+                assert_eq!(object.properties.as_ref().unwrap().len(), 1);
+            }
+            other => panic!("unexpected object {:?}", other),
+        }
+        match map.layer(3) {
+            Some(Layer::GroupLayer(group)) => {
+                assert_eq!((group.offset_x, group.offset_y), (Some(4.0), Some(-2.0)));
+            }
+            other => panic!("unexpected layer {:?}", other),
+        }
+        assert!(matches!(map.layer(5), Some(Layer::ImageLayer(layer)) if layer.image == "sky.png"));
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        assert!(matches!(
+            read_tmx("<tileset/>"),
+            Err(Error::MissingTmxValue(name)) if name == "map"
+        ));
+        assert!(matches!(
+            read_tmx(r#"<map orientation="orthogonal" width="x" height="1" tilewidth="8" tileheight="8"/>"#),
+            Err(Error::ParseTmxValue(name, value)) if name == "map.width" && value == "x"
+        ));
+        assert!(matches!(
+            read_tmx(r#"<map orientation="orthogonal" width="1" height="1" tilewidth="8"/>"#),
+            Err(Error::MissingTmxValue(name)) if name == "map.tileheight"
+        ));
+        assert!(matches!(read_tmx("<map"), Err(Error::ParseXml(_))));
+    }
+
+    #[test]
+    fn reports_invalid_csv_tiles() {
+        let map = |data: &str| {
+            format!(
+                r#"<map orientation="orthogonal" width="3" height="1" tilewidth="8" tileheight="8">
+ <layer id="7" width="3" height="1"><data encoding="csv">{}</data></layer>
+</map>"#,
+                data
+            )
+        };
+        assert!(matches!(
+            read_tmx(&map("1,,2")),
+            Err(Error::ParseCsvTile(TileOrigin::Layer(7), gid)) if gid.is_empty()
+        ));
+        assert!(matches!(
+            read_tmx(&map("1,x,2")),
+            Err(Error::ParseCsvTile(TileOrigin::Layer(7), gid)) if gid == "x"
+        ));
+        assert!(read_tmx(&map("1,2,3,")).is_ok());
+    }
+}
@@ -0,0 +1,784 @@
+use serde::Serialize;
+
+use crate::tme::color::Color;
+use crate::tme::models::data_source;
+use crate::tme::models::map::with_map;
+use crate::tme::models::text;
+use crate::tme::models::wang_color::WangColor;
+use crate::tme::models::wang_set::WangSet;
+use crate::tme::models::Chunk;
+use crate::tme::models::Compression;
+use crate::tme::models::DataSource;
+use crate::tme::models::DrawOrder;
+use crate::tme::models::Encoding;
+use crate::tme::models::HorizontalAlign;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::Point;
+use crate::tme::models::Property;
+use crate::tme::models::Text;
+use crate::tme::models::Tile;
+use crate::tme::models::TileLayer;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+use crate::tme::models::VerticalAlign;
+
+const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+/// Writes a map in the XML format of Tiled
+pub fn write_tmx(map: &Map) -> String {
+    let element = with_map!(map, inner => Element::new("map")
+        .attribute("version", &inner.version)
+        .attribute("tiledversion", &inner.tiled_version)
+        .attribute("orientation", serde_name(&map.orientation()))
+        .attribute("renderorder", serde_name(&inner.render_order))
+        .attribute("compressionlevel", inner.compression_level)
+        .attribute("width", inner.width)
+        .attribute("height", inner.height)
+        .attribute("tilewidth", inner.tile_width)
+        .attribute("tileheight", inner.tile_height)
+        .optional("hexsidelength", match map {
+            Map::Hexagonal(hexagonal) => Some(hexagonal.hex_side_length),
+            _ => None,
+        })
+        .optional("staggeraxis", map.stagger_axis().map(|axis| serde_name(&axis)))
+        .optional("staggerindex", map.stagger_index().map(|index| serde_name(&index)))
+        .optional("backgroundcolor", color(inner.background_color, Color::new_transparent()))
+        .attribute("infinite", inner.infinite as i32)
+        .attribute("nextlayerid", inner.next_layer_id)
+        .attribute("nextobjectid", inner.next_object_id)
+        .children(properties(inner.properties.as_deref()))
+        .children(inner.tile_sets.iter().map(|container| match container {
+            TilesetContainer::Tileset(tileset) => tileset_element(tileset),
+            TilesetContainer::TilesetRef(reference) => Element::new("tileset")
+                .attribute("firstgid", reference.first_gid)
+                .attribute("source", reference.source.display()),
+        }))
+        .children(inner.layers.iter().map(layer_element)));
+
+    let mut xml = XML_HEADER.to_owned();
+    element.write(&mut xml, 0);
+    xml
+}
+
+/// Writes an external tileset in the XML format of Tiled
+pub fn write_tsx(tileset: &Tileset) -> String {
+    let mut xml = XML_HEADER.to_owned();
+    tileset_element(tileset).write(&mut xml, 0);
+    xml
+}
+
+fn tileset_element(tileset: &Tileset) -> Element {
+    Element::new("tileset")
+        .optional("firstgid", tileset.first_gid)
+        .attribute("version", &tileset.version)
+        .attribute("tiledversion", &tileset.tiled_version)
+        .attribute("name", &tileset.name)
+        .attribute("tilewidth", tileset.tile_width)
+        .attribute("tileheight", tileset.tile_height)
+        .optional(
+            "spacing",
+            Some(tileset.spacing).filter(|&spacing| spacing != 0),
+        )
+        .optional("margin", Some(tileset.margin).filter(|&margin| margin != 0))
+        .attribute("tilecount", tileset.tile_count)
+        .attribute("columns", tileset.columns)
+        .optional(
+            "backgroundcolor",
+            color(tileset.background_color, Color::new_transparent()),
+        )
+        .children(tileset.tile_offset.as_ref().map(|offset| {
+            Element::new("tileoffset")
+                .attribute("x", offset.x)
+                .attribute("y", offset.y)
+        }))
+        .children(tileset.grid.as_ref().map(|grid| {
+            Element::new("grid")
+                .attribute("orientation", serde_name(&grid.orientation))
+                .attribute("width", grid.width)
+                .attribute("height", grid.height)
+        }))
+        .children(properties(tileset.properties.as_deref()))
+        .children(tileset.image.as_ref().map(|image| {
+            Element::new("image")
+                .attribute("source", image)
+                .optional("trans", tileset.transparent_color.map(transparent_color))
+                .optional("width", tileset.image_width)
+                .optional("height", tileset.image_height)
+        }))
+        .children(tileset.terrains.as_ref().map(|terrains| {
+            Element::new("terraintypes").children(terrains.iter().map(|terrain| {
+                Element::new("terrain")
+                    .attribute("name", &terrain.name)
+                    .attribute("tile", terrain.tile)
+                    .children(properties(terrain.properties.as_deref()))
+            }))
+        }))
+        .children(tileset.tiles.iter().flatten().map(tile))
+        .children(
+            tileset
+                .wang_sets
+                .as_ref()
+                .map(|wang_sets| Element::new("wangsets").children(wang_sets.iter().map(wang_set))),
+        )
+}
+
+fn tile(tile: &Tile) -> Element {
+    let terrain = tile.terrain.as_ref().map(|terrain| {
+        terrain
+            .iter()
+            .map(|&id| {
+                if id < 0 {
+                    String::new()
+                } else {
+                    id.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+
+    Element::new("tile")
+        .attribute("id", tile.id)
+        .optional("type", tile.tile_type.as_ref())
+        .optional("terrain", terrain)
+        .optional("probability", tile.probability)
+        .children(properties(tile.properties.as_deref()))
+        .children(tile.image.as_ref().map(|image| {
+            Element::new("image")
+                .optional("width", tile.image_width)
+                .optional("height", tile.image_height)
+                .attribute("source", image)
+        }))
+        .children(tile.object_group.as_ref().map(layer_element))
+        .children(tile.animation.as_ref().map(|animation| {
+            Element::new("animation").children(animation.iter().map(|frame| {
+                Element::new("frame")
+                    .attribute("tileid", frame.tiled_id)
+                    .attribute("duration", frame.duration)
+            }))
+        }))
+}
+
+fn wang_set(wang_set: &WangSet) -> Element {
+    let color = |name, color: &WangColor| {
+        Element::new(name)
+            .attribute("name", &color.name)
+            .attribute("color", color.color)
+            .attribute("tile", color.tile)
+            .attribute("probability", color.probability)
+    };
+
+    Element::new("wangset")
+        .attribute("name", &wang_set.name)
+        .attribute("tile", wang_set.tile)
+        .children(properties(wang_set.properties.as_deref()))
+        .children(
+            wang_set
+                .corner_colors
+                .iter()
+                .map(|corner| color("wangcornercolor", corner)),
+        )
+        .children(
+            wang_set
+                .edge_colors
+                .iter()
+                .map(|edge| color("wangedgecolor", edge)),
+        )
+        .children(wang_set.wang_tiles.iter().map(|tile| {
+            // Packed into nibbles the way Tiled 1.3 does whenever possible
+            let wang_id = if tile.wang_id.len() <= 8
+                && tile.wang_id.iter().all(|&id| (0..16).contains(&id))
+            {
+                let packed = tile
+                    .wang_id
+                    .iter()
+                    .enumerate()
+                    .fold(0u32, |packed, (i, &id)| packed | (id as u32) << (i * 4));
+                format!("{:#x}", packed)
+            } else {
+                join(&tile.wang_id)
+            };
+            Element::new("wangtile")
+                .attribute("tileid", tile.tile_id)
+                .attribute("wangid", wang_id)
+                .optional("hflip", flag(tile.h_flip))
+                .optional("vflip", flag(tile.v_flip))
+                .optional("dflip", flag(tile.d_flip))
+        }))
+}
+
+fn layer_element(layer: &Layer) -> Element {
+    match layer {
+        Layer::TileLayer(tile_layer) => common(
+            Element::new("layer")
+                .attribute("id", tile_layer.id)
+                .attribute("name", &tile_layer.name)
+                .attribute("width", tile_layer.width)
+                .attribute("height", tile_layer.height),
+            tile_layer.opacity,
+            tile_layer.visible,
+            (tile_layer.offset_x, tile_layer.offset_y),
+            (tile_layer.x, tile_layer.y),
+        )
+        .children(properties(tile_layer.properties.as_deref()))
+        .child(data(tile_layer)),
+        Layer::ObjectGroupLayer(group) => common(
+            Element::new("objectgroup")
+                .attribute("id", group.id)
+                .attribute("name", &group.name)
+                .optional(
+                    "draworder",
+                    Some(group.draw_order)
+                        .filter(|&order| order != DrawOrder::TopDown)
+                        .map(|order| serde_name(&order)),
+                ),
+            group.opacity,
+            group.visible,
+            (group.offset_x, group.offset_y),
+            (group.x, group.y),
+        )
+        .children(properties(group.properties.as_deref()))
+        .children(group.objects.iter().map(object)),
+        Layer::ImageLayer(image_layer) => common(
+            Element::new("imagelayer")
+                .attribute("id", image_layer.id)
+                .attribute("name", &image_layer.name),
+            image_layer.opacity,
+            image_layer.visible,
+            (image_layer.offset_x, image_layer.offset_y),
+            (image_layer.x, image_layer.y),
+        )
+        .children(properties(image_layer.properties.as_deref()))
+        .children(
+            Some(&image_layer.image)
+                .filter(|image| !image.is_empty())
+                .map(|image| {
+                    Element::new("image").attribute("source", image).optional(
+                        "trans",
+                        image_layer.transparent_color.map(transparent_color),
+                    )
+                }),
+        ),
+        Layer::GroupLayer(group) => common(
+            Element::new("group")
+                .attribute("id", group.id)
+                .attribute("name", &group.name),
+            group.opacity,
+            group.visible,
+            (group.offset_x, group.offset_y),
+            (group.x, group.y),
+        )
+        .children(properties(group.properties.as_deref()))
+        .children(group.layers.iter().map(layer_element)),
+    }
+}
+
+fn common(
+    element: Element,
+    opacity: f64,
+    visible: bool,
+    offset: (Option<f64>, Option<f64>),
+    position: (i32, i32),
+) -> Element {
+    element
+        .optional("opacity", Some(opacity).filter(|&opacity| opacity != 1.0))
+        .optional("visible", (!visible).then_some(0))
+        .optional("offsetx", offset.0)
+        .optional("offsety", offset.1)
+        .optional("x", Some(position.0).filter(|&x| x != 0))
+        .optional("y", Some(position.1).filter(|&y| y != 0))
+}
+
+/// Plain tile arrays are written as CSV unless the layer uses the deprecated
+/// `<tile>` elements, encoded ones as base64
+fn data(layer: &TileLayer) -> Element {
+    let encoding = match (&layer.data, layer.encoding) {
+        (DataSource::Raw(_), encoding) => encoding,
+        (DataSource::Encoded(_), Some(Encoding::Csv)) => Some(Encoding::Csv),
+        (DataSource::Encoded(_), _) => Some(Encoding::Base64),
+    };
+    let element = Element::new("data")
+        .optional("encoding", encoding.map(|encoding| serde_name(&encoding)))
+        .optional(
+            "compression",
+            layer
+                .compression
+                .filter(|&compression| compression != Compression::None)
+                .filter(|_| encoding == Some(Encoding::Base64))
+                .map(|compression| serde_name(&compression)),
+        );
+
+    match &layer.chunks {
+        Some(chunks) if !chunks.is_empty() => {
+            element.children(chunks.iter().map(|chunk: &Chunk| {
+                tiles(
+                    Element::new("chunk")
+                        .attribute("x", chunk.x)
+                        .attribute("y", chunk.y)
+                        .attribute("width", chunk.width)
+                        .attribute("height", chunk.height),
+                    &chunk.data,
+                    encoding,
+                    chunk.width,
+                )
+            }))
+        }
+        _ => tiles(element, &layer.data, encoding, layer.width),
+    }
+}
+
+fn tiles(element: Element, data: &DataSource, encoding: Option<Encoding>, width: i32) -> Element {
+    match (data, encoding) {
+        (DataSource::Encoded(encoded), _) => element.text(format!("\n{}\n", encoded.trim())),
+        (DataSource::Raw(tiles), None) => element.children(tiles.iter().map(|&gid| {
+            Element::new("tile").optional("gid", Some(gid as u32).filter(|&gid| gid != 0))
+        })),
+        (DataSource::Raw(tiles), Some(_)) => {
+            element.text(format!("\n{}\n", data_source::write_csv(tiles, width)))
+        }
+    }
+}
+
+fn object(object: &Object) -> Element {
+    let element = Element::new("object")
+        .attribute("id", object.id())
+        .optional("template", object.template())
+        .optional("name", Some(object.name()).filter(|name| !name.is_empty()))
+        .optional(
+            "type",
+            Some(object.obj_type()).filter(|obj_type| !obj_type.is_empty()),
+        )
+        .optional(
+            "gid",
+            match object {
+                Object::General(general) => Some(general.gid),
+                _ => None,
+            },
+        )
+        .attribute("x", object.x())
+        .attribute("y", object.y())
+        .optional("width", Some(object.width()).filter(|&width| width != 0.0))
+        .optional(
+            "height",
+            Some(object.height()).filter(|&height| height != 0.0),
+        )
+        .optional(
+            "rotation",
+            Some(object.rotation()).filter(|&rotation| rotation != 0.0),
+        )
+        .optional("visible", (!object.visible()).then_some(0))
+        .children(properties(object.properties()));
+
+    match object {
+        Object::General(_) | Object::Rectangle(_) => element,
+        Object::Ellipse(_) => element.child(Element::new("ellipse")),
+        Object::Point(_) => element.child(Element::new("point")),
+        Object::Polygon(polygon) => {
+            element.child(Element::new("polygon").attribute("points", points(&polygon.polygon)))
+        }
+        Object::Polyline(polyline) => {
+            element.child(Element::new("polyline").attribute("points", points(&polyline.polyline)))
+        }
+        Object::Text(text_object) => element.child(text(&text_object.text)),
+    }
+}
+
+fn points(points: &[Point]) -> String {
+    points
+        .iter()
+        .map(|point| format!("{},{}", point.x, point.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn text(text: &Text) -> Element {
+    Element::new("text")
+        .optional(
+            "fontfamily",
+            Some(&text.font_family).filter(|&family| *family != text::default_font_family()),
+        )
+        .optional(
+            "pixelsize",
+            Some(text.pixel_size).filter(|&size| size != text::default_pixel_size()),
+        )
+        .optional("wrap", flag(text.wrap))
+        .optional("color", color(text.color, Color::new_black()))
+        .optional("bold", flag(text.bold))
+        .optional("italic", flag(text.italic))
+        .optional("underline", flag(text.underline))
+        .optional("strikeout", flag(text.strike_out))
+        .optional("kerning", (!text.kerning).then_some(0))
+        .optional(
+            "halign",
+            Some(text.h_align)
+                .filter(|&align| align != HorizontalAlign::Left)
+                .map(|align| serde_name(&align)),
+        )
+        .optional(
+            "valign",
+            Some(text.v_align)
+                .filter(|&align| align != VerticalAlign::Top)
+                .map(|align| serde_name(&align)),
+        )
+        .text(text.text.clone())
+}
+
+fn properties(properties: Option<&[Property]>) -> Option<Element> {
+    let properties = properties.filter(|properties| !properties.is_empty())?;
+    Some(Element::new("properties").children(properties.iter().map(property)))
+}
+
+/// Strings spanning multiple lines are written as the text of the element
+fn property(property: &Property) -> Element {
+    let element = Element::new("property").attribute("name", property.name());
+    match property {
+        Property::Int(int) => element
+            .attribute("type", "int")
+            .attribute("value", int.value),
+        Property::Bool(bool) => element
+            .attribute("type", "bool")
+            .attribute("value", bool.value),
+        Property::Float(float) => element
+            .attribute("type", "float")
+            .attribute("value", float.value),
+        Property::Color(color) => element
+            .attribute("type", "color")
+            .attribute("value", color.value),
+        Property::File(file) => element
+            .attribute("type", "file")
+            .attribute("value", file.value.display()),
+        Property::String(string) if string.value.contains('\n') => {
+            element.text(string.value.clone())
+        }
+        Property::String(string) => element.attribute("value", &string.value),
+    }
+}
+
+/// Name of a unit variant as it appears in JSON
+fn serde_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn color(color: Color, default: Color) -> Option<Color> {
+    Some(color).filter(|&color| color != default)
+}
+
+/// Transparent colors of images are written as `RRGGBB` without a hash
+fn transparent_color(color: Color) -> String {
+    format!("{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn flag(value: bool) -> Option<i32> {
+    value.then_some(1)
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// XML element that is built first and written as a whole, indented with
+/// a single space per level like Tiled does
+#[derive(Debug)]
+struct Element {
+    name:       &'static str,
+    attributes: Vec<(&'static str, String)>,
+    children:   Vec<Element>,
+    text:       Option<String>,
+}
+
+impl Element {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: None,
+        }
+    }
+
+    fn attribute<T: ToString>(mut self, name: &'static str, value: T) -> Self {
+        self.attributes.push((name, value.to_string()));
+        self
+    }
+
+    fn optional<T: ToString>(self, name: &'static str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.attribute(name, value),
+            None => self,
+        }
+    }
+
+    fn child(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    fn children<I: IntoIterator<Item = Element>>(mut self, children: I) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    fn text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    fn write(&self, xml: &mut String, depth: usize) {
+        let indent = " ".repeat(depth);
+        xml.push_str(&indent);
+        xml.push('<');
+        xml.push_str(self.name);
+        for (name, value) in &self.attributes {
+            xml.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+
+        if let Some(text) = &self.text {
+            xml.push_str(&format!(">{}</{}>\n", escape(text), self.name));
+        } else if self.children.is_empty() {
+            xml.push_str("/>\n");
+        } else {
+            xml.push_str(">\n");
+            for child in &self.children {
+                child.write(xml, depth + 1);
+            }
+            xml.push_str(&format!("{}</{}>\n", indent, self.name));
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::tme::builder::GroupLayerBuilder;
+    use crate::tme::builder::ImageLayerBuilder;
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::builder::ObjectBuilder;
+    use crate::tme::builder::ObjectLayerBuilder;
+    use crate::tme::builder::TilesetBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::models::wang_tile::WangTile;
+    use crate::tme::models::ColorProperty;
+    use crate::tme::models::Frame;
+    use crate::tme::models::StaggerAxis;
+    use crate::tme::models::StaggerIndex;
+    use crate::tme::models::StringProperty;
+    use crate::tme::tmx::reader::read_tmx;
+    use crate::tme::tmx::reader::read_tsx;
+
+    fn note(value: &str) -> Property {
+        Property::String(StringProperty {
+            name:  "note".to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    fn sample_tileset() -> Tileset {
+        let mut tileset = TilesetBuilder::new("terrain", 16, 16)
+            .with_image("terrain & co.png", 32, 32)
+            .with_spacing(1)
+            .with_property(Property::Color(ColorProperty {
+                name:  "tint".to_owned(),
+                value: Color::with_alpha(128, 1, 2, 3),
+            }))
+            .build();
+        tileset.transparent_color = Some(Color::new(255, 0, 255));
+        tileset.wang_sets = Some(vec![WangSet {
+            corner_colors: vec![],
+            edge_colors:   vec![WangColor {
+                color:       Color::new(255, 0, 0),
+                name:        "road".to_owned(),
+                probability: 1.0,
+                tile:        -1,
+            }],
+            name:          "roads".to_owned(),
+            properties:    None,
+            tile:          -1,
+            wang_tiles:    vec![WangTile {
+                d_flip:  false,
+                h_flip:  true,
+                tile_id: 0,
+                v_flip:  false,
+                wang_id: vec![1, 0, 1, 0, 0, 0, 1, 0],
+            }],
+        }]);
+        tileset.tiles = Some(vec![Tile {
+            animation:    Some(vec![Frame {
+                duration: 100,
+                tiled_id: 1,
+            }]),
+            id:           0,
+            image:        None,
+            image_height: None,
+            image_width:  None,
+            object_group: None,
+            probability:  Some(0.5),
+            properties:   Some(vec![note("first\nsecond")]),
+            terrain:      Some(vec![0, -1, -1, 0]),
+            tile_type:    Some("grass".to_owned()),
+        }]);
+        tileset
+    }
+
+    fn sample_map() -> Map {
+        let mut builder = MapBuilder::hexagonal(2, 2, 16, 14, 7, StaggerAxis::Y, StaggerIndex::Odd)
+            .with_background_color(Color::new(16, 32, 48))
+            .with_property(note("<\"quoted\">"));
+        builder.add_tileset(sample_tileset());
+        builder.add_tileset_ref("props.tsx", 3);
+
+        let ground = builder
+            .tile_layer("ground")
+            .with_tile(0, 0, Gid(1))
+            .with_tile(1, 1, Gid::new(2, crate::tme::gid::FLIPPED_VERTICALLY_FLAG))
+            .build();
+        builder.add_layer(ground);
+        let mut packed = builder
+            .tile_layer("packed")
+            .with_opacity(0.25)
+            .with_tile(1, 0, Gid(3))
+            .build();
+        if let Layer::TileLayer(layer) = &mut packed {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Gzip))
+                .unwrap();
+        }
+        builder.add_layer(packed);
+
+        let text: Text = serde_json::from_value(json!({
+            "text": "Hello & bye",
+            "bold": true,
+            "halign": "center",
+            "color": "#FF00FF00"
+        }))
+        .unwrap();
+        let objects = ObjectLayerBuilder::new("objects")
+            .with_draw_order(DrawOrder::Index)
+            .with_object(
+                ObjectBuilder::rectangle(1.0, 2.0, 3.0, 4.0)
+                    .with_name("box")
+                    .with_type("crate")
+                    .with_rotation(90.0)
+                    .build(),
+            )
+            .with_object(ObjectBuilder::ellipse(0.5, 0.5, 8.0, 8.0).build())
+            .with_object(ObjectBuilder::point(3.0, 3.0).with_visible(false).build())
+            .with_object(
+                ObjectBuilder::polygon(0.0, 0.0, &[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]).build(),
+            )
+            .with_object(ObjectBuilder::polyline(1.0, 1.0, &[(0.0, 0.0), (-2.5, 3.0)]).build())
+            .with_object(ObjectBuilder::tile(Gid(5), 0.0, 16.0, 16.0, 16.0).build())
+            .with_object(ObjectBuilder::text(text, 0.0, 0.0, 64.0, 16.0).build())
+            .build();
+        let group = GroupLayerBuilder::new("group")
+            .with_offset(2.0, -3.5)
+            .with_visible(false)
+            .with_layer(objects)
+            .build();
+        builder.add_layer(group);
+        builder.add_layer(
+            ImageLayerBuilder::new("sky", "sky.png")
+                .with_transparent_color(Color::new(0, 0, 0))
+                .build(),
+        );
+        builder.build()
+    }
+
+    #[test]
+    fn writes_and_reads_back_map() {
+        let map = sample_map();
+        let xml = write_tmx(&map);
+
+        assert!(xml.starts_with(XML_HEADER));
+        assert!(xml.contains(
+            " <layer id=\"1\" name=\"ground\" width=\"2\" height=\"2\">\n  <data \
+             encoding=\"csv\">\n1,0,\n0,1073741826\n</data>\n"
+        ));
+        assert!(xml.contains("<data encoding=\"base64\" compression=\"gzip\">"));
+        assert!(xml.contains("<property name=\"note\" value=\"&lt;&quot;quoted&quot;&gt;\"/>"));
+        assert!(xml.contains("<property name=\"note\">first\nsecond</property>"));
+        assert!(xml.contains("<image source=\"terrain &amp; co.png\" trans=\"ff00ff\""));
+        assert!(xml.contains("<wangtile tileid=\"0\" wangid=\"0x1000101\" hflip=\"1\"/>"));
+        assert!(
+            xml.contains("<tile id=\"0\" type=\"grass\" terrain=\"0,,,0\" probability=\"0.5\">")
+        );
+        assert!(xml.contains(
+            "<text color=\"#FF00FF00\" bold=\"1\" halign=\"center\">Hello &amp; bye</text>"
+        ));
+
+        let read = read_tmx(&xml).unwrap();
+        assert_eq!(
+            serde_json::to_value(&read).unwrap(),
+            serde_json::to_value(&map).unwrap()
+        );
+    }
+
+    #[test]
+    fn writes_and_reads_back_infinite_layers() {
+        let mut map = sample_map();
+        map.set_infinite(true).unwrap();
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(2) {
+            layer
+                .encode(Encoding::Base64, Some(Compression::Zstd))
+                .unwrap();
+        }
+
+        let xml = write_tmx(&map);
+        assert!(xml.contains("<chunk x=\"0\" y=\"0\" width=\"16\" height=\"16\">"));
+        let read = read_tmx(&xml).unwrap();
+        assert_eq!(
+            serde_json::to_value(&read).unwrap(),
+            serde_json::to_value(&map).unwrap()
+        );
+    }
+
+    #[test]
+    fn writes_csv_strings_as_they_are() {
+        let mut map = sample_map();
+        if let Some(Layer::TileLayer(layer)) = map.layer_mut(1) {
+            layer.data = DataSource::Encoded("1,0,\n0,3\n".to_owned());
+        }
+
+        let xml = write_tmx(&map);
+        assert!(xml.contains("<data encoding=\"csv\">\n1,0,\n0,3\n</data>"));
+        match read_tmx(&xml).unwrap().layer(1) {
+            Some(Layer::TileLayer(layer)) => {
+                assert_eq!(layer.data, DataSource::Raw(vec![1, 0, 0, 3]))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn writes_and_reads_back_tileset() {
+        let tileset = sample_tileset();
+        let tsx = write_tsx(&tileset);
+        assert!(tsx.contains("<tileset version=\"1.2\" tiledversion=\"1.3.5\" name=\"terrain\""));
+        assert_eq!(read_tsx(&tsx).unwrap(), tileset);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    /// Tile data that can not be decoded
+    InvalidData(String),
+    DataLength {
+        expected: usize,
+        actual:   usize,
+    },
+    /// Finite tile layer with a size different from the map
+    LayerSize {
+        width:  i32,
+        height: i32,
+    },
+    GidOutOfRange(u32),
+    DuplicateLayerId(i32),
+    DuplicateObjectId(i64),
+    LayerIdAboveNext {
+        id:   i32,
+        next: i32,
+    },
+    ObjectIdAboveNext {
+        id:   i64,
+        next: i32,
+    },
+    MissingImage(PathBuf),
+    FrameOutOfRange {
+        tile_id:    i32,
+        tile_count: usize,
+    },
+    TooFewPoints {
+        points:   usize,
+        required: usize,
+    },
+    NegativeSize {
+        width:  f64,
+        height: f64,
+    },
+}
+
+impl DiagnosticKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticKind::LayerSize { .. } | DiagnosticKind::TooFewPoints { .. } => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::InvalidData(reason) => {
+                write!(f, "Tile data can not be decoded: {}", reason)
+            }
+            DiagnosticKind::DataLength { expected, actual } => {
+                write!(f, "Expected {} tiles but found {}", expected, actual)
+            }
+            DiagnosticKind::LayerSize { width, height } => {
+                write!(
+                    f,
+                    "Layer size {}x{} differs from the map size",
+                    width, height
+                )
+            }
+            DiagnosticKind::GidOutOfRange(gid) => {
+                write!(f, "Gid {} is not part of any tileset", gid)
+            }
+            DiagnosticKind::DuplicateLayerId(id) => {
+                write!(f, "Layer id {} is used more than once", id)
+            }
+            DiagnosticKind::DuplicateObjectId(id) => {
+                write!(f, "Object id {} is used more than once", id)
+            }
+            DiagnosticKind::LayerIdAboveNext { id, next } => {
+                write!(f, "Layer id {} is not below the next layer id {}", id, next)
+            }
+            DiagnosticKind::ObjectIdAboveNext { id, next } => {
+                write!(
+                    f,
+                    "Object id {} is not below the next object id {}",
+                    id, next
+                )
+            }
+            DiagnosticKind::MissingImage(path) => {
+                write!(f, "Image {} does not exist", path.display())
+            }
+            DiagnosticKind::FrameOutOfRange {
+                tile_id,
+                tile_count,
+            } => write!(
+                f,
+                "Animation frame shows tile {} of a tileset with {} tiles",
+                tile_id, tile_count
+            ),
+            DiagnosticKind::TooFewPoints { points, required } => write!(
+                f,
+                "Shape has {} points but needs at least {}",
+                points, required
+            ),
+            DiagnosticKind::NegativeSize { width, height } => {
+                write!(f, "Size {}x{} is negative", width, height)
+            }
+        }
+    }
+}
+
+/// Problem found by the validator, `path` points at the offending value in
+/// the JSON of the map like `$.layers[2].objects[0].polygon`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub path: String,
+    pub kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}: {}", self.severity(), self.path, self.kind)
+    }
+}
@@ -0,0 +1,5 @@
+pub mod diagnostic;
+pub mod validator;
+
+pub use diagnostic::*;
+pub use validator::*;
@@ -0,0 +1,518 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::diagnostic::Diagnostic;
+use super::diagnostic::DiagnosticKind;
+
+use crate::tme::gid::Gid;
+use crate::tme::models::Layer;
+use crate::tme::models::Map;
+use crate::tme::models::Object;
+use crate::tme::models::TileLayer;
+use crate::tme::models::Tileset;
+use crate::tme::models::TilesetContainer;
+
+/// Checks maps and tilesets for data Tiled itself would never write
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    base_dir: Option<PathBuf>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory image paths are relative to, usually the one of the map
+    /// file. Images are only checked when it is set.
+    pub fn with_base_dir<P: Into<PathBuf>>(mut self, base_dir: P) -> Self {
+        self.base_dir = Some(base_dir.into());
+        self
+    }
+
+    pub fn validate_map(&self, map: &Map) -> Vec<Diagnostic> {
+        let mut context = Context {
+            validator:   self,
+            diagnostics: Vec::new(),
+            gid_ranges:  gid_ranges(map.tile_sets()),
+            layer_ids:   HashSet::new(),
+            object_ids:  HashSet::new(),
+        };
+
+        if map.width() < 0 || map.height() < 0 {
+            context.report(
+                "$",
+                DiagnosticKind::NegativeSize {
+                    width:  map.width() as f64,
+                    height: map.height() as f64,
+                },
+            );
+        }
+        for (index, container) in map.tile_sets().iter().enumerate() {
+            if let TilesetContainer::Tileset(tileset) = container {
+                context.tileset(&format!("$.tilesets[{}]", index), tileset);
+            }
+        }
+        context.layers("$", map.layers(), map);
+
+        if let Some(&id) = context
+            .layer_ids
+            .iter()
+            .max()
+            .filter(|&&id| id >= map.next_layer_id())
+        {
+            let next = map.next_layer_id();
+            context.report(
+                "$.nextlayerid",
+                DiagnosticKind::LayerIdAboveNext { id, next },
+            );
+        }
+        if let Some(&id) = context.object_ids.iter().max() {
+            if id >= map.next_object_id() as i64 {
+                let next = map.next_object_id();
+                context.report(
+                    "$.nextobjectid",
+                    DiagnosticKind::ObjectIdAboveNext { id, next },
+                );
+            }
+        }
+
+        context.diagnostics
+    }
+
+    pub fn validate_tileset(&self, tileset: &Tileset) -> Vec<Diagnostic> {
+        let mut context = Context {
+            validator:   self,
+            diagnostics: Vec::new(),
+            gid_ranges:  Vec::new(),
+            layer_ids:   HashSet::new(),
+            object_ids:  HashSet::new(),
+        };
+        context.tileset("$", tileset);
+        context.diagnostics
+    }
+}
+
+struct Context<'a> {
+    validator:   &'a Validator,
+    diagnostics: Vec<Diagnostic>,
+    /// First gid and tile count of every tileset, `None` for external ones
+    gid_ranges:  Vec<(u32, Option<u32>)>,
+    layer_ids:   HashSet<i32>,
+    object_ids:  HashSet<i64>,
+}
+
+impl<'a> Context<'a> {
+    fn report(&mut self, path: &str, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            path: path.to_owned(),
+            kind,
+        });
+    }
+
+    fn tileset(&mut self, path: &str, tileset: &Tileset) {
+        if tileset.tile_width < 0 || tileset.tile_height < 0 {
+            self.report(
+                path,
+                DiagnosticKind::NegativeSize {
+                    width:  tileset.tile_width as f64,
+                    height: tileset.tile_height as f64,
+                },
+            );
+        }
+        if let Some(image) = &tileset.image {
+            self.image(&format!("{}.image", path), image);
+        }
+
+        for (index, tile) in tileset.tiles.iter().flatten().enumerate() {
+            let tile_path = format!("{}.tiles[{}]", path, index);
+            if let Some(image) = &tile.image {
+                self.image(&format!("{}.image", tile_path), image);
+            }
+            for (frame_index, frame) in tile.animation.iter().flatten().enumerate() {
+                if frame.tiled_id < 0 || frame.tiled_id as usize >= tileset.tile_count {
+                    self.report(
+                        &format!("{}.animation[{}].tiledid", tile_path, frame_index),
+                        DiagnosticKind::FrameOutOfRange {
+                            tile_id:    frame.tiled_id,
+                            tile_count: tileset.tile_count,
+                        },
+                    );
+                }
+            }
+            if let Some(Layer::ObjectGroupLayer(group)) = &tile.object_group {
+                for (object_index, object) in group.objects.iter().enumerate() {
+                    let object_path =
+                        format!("{}.objectgroup.objects[{}]", tile_path, object_index);
+                    self.shape(&object_path, object);
+                }
+            }
+        }
+    }
+
+    fn layers(&mut self, path: &str, layers: &[Layer], map: &Map) {
+        for (index, layer) in layers.iter().enumerate() {
+            let path = format!("{}.layers[{}]", path, index);
+            if !self.layer_ids.insert(layer.id()) {
+                self.report(
+                    &format!("{}.id", path),
+                    DiagnosticKind::DuplicateLayerId(layer.id()),
+                );
+            }
+
+            match layer {
+                Layer::TileLayer(layer) => self.tile_layer(&path, layer, map),
+                Layer::ObjectGroupLayer(group) => {
+                    for (index, object) in group.objects.iter().enumerate() {
+                        self.object(&format!("{}.objects[{}]", path, index), object);
+                    }
+                }
+                Layer::ImageLayer(layer) => self.image(&format!("{}.image", path), &layer.image),
+                Layer::GroupLayer(group) => self.layers(&path, &group.layers, map),
+            }
+        }
+    }
+
+    fn tile_layer(&mut self, path: &str, layer: &TileLayer, map: &Map) {
+        if layer.width < 0 || layer.height < 0 {
+            self.report(
+                path,
+                DiagnosticKind::NegativeSize {
+                    width:  layer.width as f64,
+                    height: layer.height as f64,
+                },
+            );
+            return;
+        }
+
+        match &layer.chunks {
+            Some(chunks) if !chunks.is_empty() => {
+                for (index, chunk) in chunks.iter().enumerate() {
+                    let path = format!("{}.chunks[{}].data", path, index);
+                    let expected = (chunk.width.max(0) * chunk.height.max(0)) as usize;
+                    match chunk.data.extract_tiles(layer.encoding, layer.compression) {
+                        Ok(tiles) => self.tiles(&path, &tiles, expected),
+                        Err(error) => {
+                            self.report(&path, DiagnosticKind::InvalidData(error.to_string()))
+                        }
+                    }
+                }
+            }
+            _ => {
+                if !map.infinite() && (layer.width, layer.height) != (map.width(), map.height()) {
+                    self.report(
+                        path,
+                        DiagnosticKind::LayerSize {
+                            width:  layer.width,
+                            height: layer.height,
+                        },
+                    );
+                }
+                let path = format!("{}.data", path);
+                let expected = (layer.width * layer.height) as usize;
+                match layer.data.extract_tiles(layer.encoding, layer.compression) {
+                    Ok(tiles) => self.tiles(&path, &tiles, expected),
+                    Err(error) => {
+                        self.report(&path, DiagnosticKind::InvalidData(error.to_string()))
+                    }
+                }
+            }
+        }
+    }
+
+    fn tiles(&mut self, path: &str, tiles: &[i32], expected: usize) {
+        if tiles.len() != expected {
+            self.report(
+                path,
+                DiagnosticKind::DataLength {
+                    expected,
+                    actual: tiles.len(),
+                },
+            );
+        }
+        for (index, &raw) in tiles.iter().enumerate() {
+            self.gid(&format!("{}[{}]", path, index), Gid::from(raw));
+        }
+    }
+
+    fn gid(&mut self, path: &str, gid: Gid) {
+        if gid.is_empty() {
+            return;
+        }
+        let tileset = self
+            .gid_ranges
+            .iter()
+            .filter(|(first_gid, _)| *first_gid <= gid.id())
+            .max_by_key(|(first_gid, _)| *first_gid);
+        let valid = match tileset {
+            Some((first_gid, Some(tile_count))) => gid.id() < first_gid + tile_count,
+            Some((_, None)) => true,
+            None => false,
+        };
+        if !valid {
+            self.report(path, DiagnosticKind::GidOutOfRange(gid.id()));
+        }
+    }
+
+    fn object(&mut self, path: &str, object: &Object) {
+        if !self.object_ids.insert(object.id()) {
+            self.report(
+                &format!("{}.id", path),
+                DiagnosticKind::DuplicateObjectId(object.id()),
+            );
+        }
+        if let Object::General(tile_object) = object {
+            self.gid(&format!("{}.gid", path), Gid(tile_object.gid as u32));
+        }
+        self.shape(path, object);
+    }
+
+    fn shape(&mut self, path: &str, object: &Object) {
+        if object.width() < 0.0 || object.height() < 0.0 {
+            self.report(
+                path,
+                DiagnosticKind::NegativeSize {
+                    width:  object.width(),
+                    height: object.height(),
+                },
+            );
+        }
+
+        let (field, points, required) = match object {
+            Object::Polygon(polygon) => ("polygon", polygon.polygon.len(), 3),
+            Object::Polyline(polyline) => ("polyline", polyline.polyline.len(), 2),
+            _ => return,
+        };
+        if points < required {
+            self.report(
+                &format!("{}.{}", path, field),
+                DiagnosticKind::TooFewPoints { points, required },
+            );
+        }
+    }
+
+    fn image(&mut self, path: &str, image: &str) {
+        let base_dir = match &self.validator.base_dir {
+            Some(base_dir) => base_dir,
+            None => return,
+        };
+        if image.is_empty() {
+            return;
+        }
+        let image = base_dir.join(Path::new(image));
+        if !image.exists() {
+            self.report(path, DiagnosticKind::MissingImage(image));
+        }
+    }
+}
+
+fn gid_ranges(tile_sets: &[TilesetContainer]) -> Vec<(u32, Option<u32>)> {
+    tile_sets
+        .iter()
+        .filter_map(|container| {
+            let first_gid = container.first_gid()? as u32;
+            Some(match container {
+                TilesetContainer::Tileset(tileset) => (first_gid, Some(tileset.tile_count as u32)),
+                TilesetContainer::TilesetRef(_) => (first_gid, None),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tme::validation::Severity;
+    use serde_json::json;
+
+    fn test_map() -> Map {
+        serde_json::from_value(json! {
+            {
+                "compressionlevel": -1,
+                "height": 2,
+                "infinite": false,
+                "layers": [
+                    {
+                        "data": [1, 2, 9, 0, 0],
+                        "height": 2,
+                        "id": 1,
+                        "name": "ground",
+                        "opacity": 1.0,
+                        "type": "tilelayer",
+                        "visible": true,
+                        "width": 2,
+                        "x": 0,
+                        "y": 0
+                    },
+                    {
+                        "draworder": "topdown",
+                        "id": 1,
+                        "name": "objects",
+                        "objects": [
+                            {
+                                "height": 0.0,
+                                "id": 3,
+                                "name": "",
+                                "polygon": [{ "x": 0.0, "y": 0.0 }, { "x": 1.0, "y": 0.0 }],
+                                "rotation": 0.0,
+                                "type": "",
+                                "visible": true,
+                                "width": 0.0,
+                                "x": 0.0,
+                                "y": 0.0
+                            },
+                            {
+                                "height": -4.0,
+                                "id": 3,
+                                "name": "",
+                                "rotation": 0.0,
+                                "type": "",
+                                "visible": true,
+                                "width": 4.0,
+                                "x": 0.0,
+                                "y": 0.0
+                            }
+                        ],
+                        "opacity": 1.0,
+                        "type": "objectgroup",
+                        "visible": true,
+                        "x": 0,
+                        "y": 0
+                    },
+                    {
+                        "id": 2,
+                        "image": "missing.png",
+                        "name": "background",
+                        "opacity": 1.0,
+                        "transparentcolor": null,
+                        "type": "imagelayer",
+                        "visible": true,
+                        "x": 0,
+                        "y": 0
+                    }
+                ],
+                "nextlayerid": 2,
+                "nextobjectid": 3,
+                "orientation": "orthogonal",
+                "renderorder": "right-down",
+                "tiledversion": "1.3.5",
+                "tileheight": 16,
+                "tilesets": [
+                    {
+                        "columns": 2,
+                        "firstgid": 1,
+                        "image": "Cargo.toml",
+                        "imageheight": 16,
+                        "imagewidth": 32,
+                        "margin": 0,
+                        "name": "tiles",
+                        "spacing": 0,
+                        "tilecount": 2,
+                        "tiledversion": "1.3.5",
+                        "tileheight": 16,
+                        "tiles": [
+                            {
+                                "animation": [
+                                    { "duration": 100, "tiledid": 1 },
+                                    { "duration": 100, "tiledid": 2 }
+                                ],
+                                "id": 0
+                            }
+                        ],
+                        "tilewidth": 16,
+                        "type": "tileset",
+                        "version": 1.2
+                    }
+                ],
+                "tilewidth": 16,
+                "type": "map",
+                "version": 1.2,
+                "width": 2
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_broken_maps() {
+        let map = test_map();
+        let diagnostics = Validator::new()
+            .with_base_dir(env!("CARGO_MANIFEST_DIR"))
+            .validate_map(&map);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.path.as_str(), &diagnostic.kind))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "$.tilesets[0].tiles[0].animation[1].tiledid",
+                    &DiagnosticKind::FrameOutOfRange {
+                        tile_id:    2,
+                        tile_count: 2,
+                    }
+                ),
+                (
+                    "$.layers[0].data",
+                    &DiagnosticKind::DataLength {
+                        expected: 4,
+                        actual:   5,
+                    }
+                ),
+                ("$.layers[0].data[2]", &DiagnosticKind::GidOutOfRange(9)),
+                ("$.layers[1].id", &DiagnosticKind::DuplicateLayerId(1)),
+                (
+                    "$.layers[1].objects[0].polygon",
+                    &DiagnosticKind::TooFewPoints {
+                        points:   2,
+                        required: 3,
+                    }
+                ),
+                (
+                    "$.layers[1].objects[1].id",
+                    &DiagnosticKind::DuplicateObjectId(3)
+                ),
+                (
+                    "$.layers[1].objects[1]",
+                    &DiagnosticKind::NegativeSize {
+                        width:  4.0,
+                        height: -4.0,
+                    }
+                ),
+                (
+                    "$.layers[2].image",
+                    &DiagnosticKind::MissingImage(
+                        Path::new(env!("CARGO_MANIFEST_DIR")).join("missing.png")
+                    )
+                ),
+                (
+                    "$.nextlayerid",
+                    &DiagnosticKind::LayerIdAboveNext { id: 2, next: 2 }
+                ),
+                (
+                    "$.nextobjectid",
+                    &DiagnosticKind::ObjectIdAboveNext { id: 3, next: 3 }
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[3].severity(), Severity::Error);
+        assert_eq!(diagnostics[4].severity(), Severity::Warning);
+        assert_eq!(
+            diagnostics[2].to_string(),
+            "error at $.layers[0].data[2]: Gid 9 is not part of any tileset"
+        );
+    }
+
+    #[test]
+    fn skips_images_without_base_dir() {
+        let map = test_map();
+        let diagnostics = Validator::new().validate_map(&map);
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| !matches!(diagnostic.kind, DiagnosticKind::MissingImage(_))));
+    }
+}
@@ -0,0 +1,428 @@
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::error::Error;
+use super::models::Map;
+use super::render::MapGeometry;
+use super::runtime::read_runtime_map;
+use super::tmx::read_tmx;
+
+/// Contents of a Tiled `.world` file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldFile {
+    #[serde(default)]
+    pub maps:                    Vec<WorldMapEntry>,
+    #[serde(default)]
+    pub patterns:                Vec<WorldPattern>,
+    #[serde(default)]
+    pub only_show_adjacent_maps: bool,
+    #[serde(rename = "type")]
+    pub world_type:              Option<String>,
+}
+
+/// Map placed at a position in world pixels, the size is optional since
+/// Tiled takes it from the map itself
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldMapEntry {
+    pub file_name: String,
+    pub x:         i32,
+    pub y:         i32,
+    pub width:     Option<i32>,
+    pub height:    Option<i32>,
+}
+
+/// Places every map in the directory of the world whose file name matches
+/// `regexp`, the first two captures being multiplied to get its position
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldPattern {
+    pub regexp:       String,
+    #[serde(default)]
+    pub multiplier_x: i32,
+    #[serde(default)]
+    pub multiplier_y: i32,
+    #[serde(default)]
+    pub offset_x:     i32,
+    #[serde(default)]
+    pub offset_y:     i32,
+    pub map_width:    Option<i32>,
+    pub map_height:   Option<i32>,
+}
+
+impl WorldFile {
+    /// Explicitly listed maps followed by the files among `file_names` that
+    /// match a pattern, in the order given. Files whose position does not fit
+    /// into an `i32` are skipped.
+    pub fn resolve<S: AsRef<str>>(&self, file_names: &[S]) -> Result<Vec<WorldMapEntry>, Error> {
+        let mut entries = self.maps.clone();
+        for pattern in &self.patterns {
+            let regex = Regex::new(&pattern.regexp)
+                .map_err(|error| Error::InvalidWorldPattern(pattern.regexp.clone(), error))?;
+            for file_name in file_names {
+                let file_name = file_name.as_ref();
+                if entries.iter().any(|entry| entry.file_name == file_name) {
+                    continue;
+                }
+                let (x, y) = match regex
+                    .captures(file_name)
+                    .and_then(|captures| Some((captures.get(1)?, captures.get(2)?)))
+                {
+                    Some((x, y)) => (x.as_str(), y.as_str()),
+                    None => continue,
+                };
+                let position = |capture: &str, multiplier: i32, offset: i32| {
+                    capture
+                        .parse::<i32>()
+                        .ok()?
+                        .checked_mul(multiplier)?
+                        .checked_add(offset)
+                };
+                let (x, y) = match (
+                    position(x, pattern.multiplier_x, pattern.offset_x),
+                    position(y, pattern.multiplier_y, pattern.offset_y),
+                ) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => continue,
+                };
+                entries.push(WorldMapEntry {
+                    file_name: file_name.to_owned(),
+                    x,
+                    y,
+                    width: pattern.map_width,
+                    height: pattern.map_height,
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Rectangle in world pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WorldRect {
+    pub x:      i32,
+    pub y:      i32,
+    pub width:  i32,
+    pub height: i32,
+}
+
+impl WorldRect {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x as f64
+            && y >= self.y as f64
+            && x < (self.x + self.width) as f64
+            && y < (self.y + self.height) as f64
+    }
+
+    pub fn intersects(&self, other: &WorldRect) -> bool {
+        other.x < self.x + self.width
+            && other.y < self.y + self.height
+            && other.x + other.width > self.x
+            && other.y + other.height > self.y
+    }
+}
+
+/// Loaded map of a world and the rectangle it covers
+#[derive(Debug, Clone)]
+pub struct WorldMap {
+    pub file_name: String,
+    pub rect:      WorldRect,
+    pub map:       Map,
+}
+
+impl WorldMap {
+    pub fn geometry(&self) -> MapGeometry {
+        MapGeometry::from_map(&self.map)
+    }
+
+    /// Tile of the map at a position in world pixels, which may lie outside
+    /// of the map
+    pub fn world_to_tile(&self, x: f64, y: f64) -> (i32, i32) {
+        self.geometry()
+            .pixel_to_tile(x - self.rect.x as f64, y - self.rect.y as f64)
+    }
+
+    /// Top-left corner of the bounding box of a tile in world pixels
+    pub fn tile_to_world(&self, x: i32, y: i32) -> (f64, f64) {
+        let (pixel_x, pixel_y) = self.geometry().tile_to_pixel(x, y);
+        (pixel_x + self.rect.x as f64, pixel_y + self.rect.y as f64)
+    }
+}
+
+/// Maps of a `.world` file placed in a common pixel space. Like Tiled, the
+/// size of each map comes from the map itself.
+#[derive(Debug, Clone)]
+pub struct World {
+    pub maps:                    Vec<WorldMap>,
+    pub only_show_adjacent_maps: bool,
+}
+
+impl World {
+    /// Reads the world file and every map it refers to, patterns are matched
+    /// against the files next to it
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file: WorldFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::load_from(&file, directory)
+    }
+
+    /// Loads the maps of `file` relative to `directory`
+    pub fn load_from<P: AsRef<Path>>(file: &WorldFile, directory: P) -> Result<Self, Error> {
+        let directory = directory.as_ref();
+        let mut file_names = Vec::new();
+        if !file.patterns.is_empty() {
+            for entry in fs::read_dir(directory)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    if let Some(file_name) = entry.file_name().to_str() {
+                        file_names.push(file_name.to_owned());
+                    }
+                }
+            }
+            file_names.sort();
+        }
+
+        let maps = file
+            .resolve(&file_names)?
+            .into_iter()
+            .map(|entry| {
+                let map = read_map_file(directory.join(&entry.file_name))?;
+                let (width, height) = MapGeometry::from_map(&map).pixel_size();
+                Ok(WorldMap {
+                    file_name: entry.file_name,
+                    rect: WorldRect {
+                        x: entry.x,
+                        y: entry.y,
+                        width,
+                        height,
+                    },
+                    map,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            maps,
+            only_show_adjacent_maps: file.only_show_adjacent_maps,
+        })
+    }
+
+    pub fn map(&self, file_name: &str) -> Option<&WorldMap> {
+        self.maps.iter().find(|map| map.file_name == file_name)
+    }
+
+    /// Maps covering a position in world pixels
+    pub fn maps_at(&self, x: f64, y: f64) -> Vec<&WorldMap> {
+        self.maps
+            .iter()
+            .filter(|map| map.rect.contains(x, y))
+            .collect()
+    }
+
+    /// Maps overlapping a rectangle in world pixels
+    pub fn maps_in(&self, rect: &WorldRect) -> Vec<&WorldMap> {
+        self.maps
+            .iter()
+            .filter(|map| map.rect.intersects(rect))
+            .collect()
+    }
+
+    /// First map covering a position in world pixels and its tile there
+    pub fn tile_at(&self, x: f64, y: f64) -> Option<(&WorldMap, (i32, i32))> {
+        self.maps_at(x, y)
+            .into_iter()
+            .next()
+            .map(|map| (map, map.world_to_tile(x, y)))
+    }
+}
+
+/// Reads a map as TMX when the file ends with `.tmx`, in the runtime format
+/// when it ends with `.embr` and as JSON otherwise
+pub fn read_map_file<P: AsRef<Path>>(path: P) -> Result<Map, Error> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("embr") => read_runtime_map(&fs::read(path)?),
+        Some("tmx") => read_tmx(&fs::read_to_string(path)?),
+        _ => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::tme::builder::MapBuilder;
+    use crate::tme::gid::Gid;
+    use crate::tme::tmx::write_tmx;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("embercore-world-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn sample_map(gid: u32) -> Map {
+        let mut builder = MapBuilder::orthogonal(4, 2, 16, 16);
+        builder.add_layer(
+            builder
+                .tile_layer("ground")
+                .with_tile(1, 1, Gid(gid))
+                .build(),
+        );
+        builder.build()
+    }
+
+    #[test]
+    fn resolves_patterns() {
+        let file: WorldFile = serde_json::from_value(json! {
+            {
+                "maps": [
+                    { "fileName": "town.json", "x": -64, "y": 0, "width": 64, "height": 32 }
+                ],
+                "patterns": [
+                    {
+                        "regexp": "area-(-?\\d+)-(-?\\d+)\\.tmx",
+                        "multiplierX": 64,
+                        "multiplierY": 32,
+                        "offsetX": 0,
+                        "offsetY": 100
+                    }
+                ],
+                "onlyShowAdjacentMaps": false,
+                "type": "world"
+            }
+        })
+        .unwrap();
+
+        let entries = file
+            .resolve(&[
+                "area-0-1.tmx",
+                "area-2--1.tmx",
+                "area-x-1.tmx",
+                "area-40000000-0.tmx",
+                "town.json",
+            ])
+            .unwrap();
+        let positions: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.file_name.as_str(), entry.x, entry.y))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("town.json", -64, 0),
+                ("area-0-1.tmx", 0, 132),
+                ("area-2--1.tmx", 128, 68)
+            ]
+        );
+
+        let invalid = WorldFile {
+            patterns: vec![WorldPattern {
+                regexp:       "(".to_owned(),
+                multiplier_x: 1,
+                multiplier_y: 1,
+                offset_x:     0,
+                offset_y:     0,
+                map_width:    None,
+                map_height:   None,
+            }],
+            ..file
+        };
+        assert!(matches!(
+            invalid.resolve(&["a.tmx"]),
+            Err(Error::InvalidWorldPattern(..))
+        ));
+
+        let optional = WorldFile {
+            maps: Vec::new(),
+            patterns: vec![WorldPattern {
+                regexp: "area(?:-(\\d+))?-(\\d+)\\.tmx".to_owned(),
+                ..invalid.patterns[0].clone()
+            }],
+            ..invalid
+        };
+        let entries = optional.resolve(&["area-3.tmx", "area-1-2.tmx"]).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].x, entries[0].y), (1, 2));
+    }
+
+    #[test]
+    fn loads_and_queries_maps() {
+        let directory = temp_dir("load");
+        fs::write(
+            directory.join("town.json"),
+            serde_json::to_string(&sample_map(1)).unwrap(),
+        )
+        .unwrap();
+        fs::write(directory.join("area-0-0.tmx"), write_tmx(&sample_map(2))).unwrap();
+        fs::write(directory.join("area-1-0.tmx"), write_tmx(&sample_map(3))).unwrap();
+        fs::write(
+            directory.join("overworld.world"),
+            serde_json::to_string(&json! {
+                {
+                    "maps": [{ "fileName": "town.json", "x": -64, "y": 0 }],
+                    "patterns": [
+                        { "regexp": "area-(\\d+)-(\\d+)\\.tmx", "multiplierX": 64, "multiplierY": 32 }
+                    ],
+                    "type": "world"
+                }
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let world = World::load(directory.join("overworld.world")).unwrap();
+        assert_eq!(world.maps.len(), 3);
+        assert_eq!(
+            world.map("area-1-0.tmx").unwrap().rect,
+            WorldRect {
+                x:      64,
+                y:      0,
+                width:  64,
+                height: 32,
+            }
+        );
+
+        let (map, tile) = world.tile_at(-40.0, 20.0).unwrap();
+        assert_eq!((map.file_name.as_str(), tile), ("town.json", (1, 1)));
+        let (map, tile) = world.tile_at(80.0, 16.0).unwrap();
+        assert_eq!(map.map.tile_width(), 16);
+        let layer = match map.map.layer(1) {
+            Some(crate::tme::models::Layer::TileLayer(layer)) => layer,
+            _ => unreachable!(),
+        };
+        assert_eq!(layer.tile(tile.0, tile.1).unwrap(), Some(Gid(3)));
+        assert_eq!(map.tile_to_world(1, 1), (80.0, 16.0));
+        assert!(world.tile_at(0.0, 40.0).is_none());
+
+        let names = |maps: Vec<&WorldMap>| -> Vec<String> {
+            maps.into_iter().map(|map| map.file_name.clone()).collect()
+        };
+        assert_eq!(names(world.maps_at(64.0, 0.0)), vec!["area-1-0.tmx"]);
+        assert_eq!(
+            names(world.maps_in(&WorldRect {
+                x:      -1,
+                y:      10,
+                width:  66,
+                height: 1,
+            })),
+            vec!["town.json", "area-0-0.tmx", "area-1-0.tmx"]
+        );
+
+        let _ = fs::remove_dir_all(directory);
+    }
+}